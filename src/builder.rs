@@ -0,0 +1,828 @@
+//! Composition of complete MIME messages
+//!
+//! [`MessageBuilder`] assembles typed headers, text bodies and
+//! attachments into a message that can be sent as is. A
+//! Content-Transfer-Encoding is chosen for each part, boundaries and
+//! Message-ID are generated and long header fields are folded. The
+//! output is parsed back identically by [`crate::mime`] and
+//! [`crate::rfc5322`].
+
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::{self, Display};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::dsn::{DeliveryStatus, DsnError};
+use crate::esmtp::BodyType;
+use crate::headersection::{header_section, write_header_section, HeaderField, HeaderOrder};
+use crate::mdn::DispositionNotification;
+use crate::mediatype::{
+    MESSAGE_DELIVERY_STATUS, MESSAGE_DISPOSITION_NOTIFICATION, MESSAGE_GLOBAL, MESSAGE_GLOBAL_DELIVERY_STATUS,
+    MESSAGE_GLOBAL_DISPOSITION_NOTIFICATION, MESSAGE_GLOBAL_HEADERS, MESSAGE_RFC822, TEXT_RFC822_HEADERS,
+};
+use crate::mime::{body_stats, encode_base64, encode_quoted_printable};
+use crate::rfc2047::{encode, EncodeContext};
+use crate::rfc2231::encode_parameter;
+use crate::rfc3461::DSNRet;
+use crate::subject::{split_prefix, PrefixKind, SubjectPrefix};
+use crate::util::normalize_crlf;
+use crate::behaviour::Intl;
+use crate::rfc5322::{from, merge_address_lists, references, reply_to, unstructured, Address, AddressList, Mailbox};
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Maximum length of a folded header line, excluding the CRLF.
+const FOLD_LEN: usize = 78;
+
+/// Builder for a complete MIME message.
+///
+/// Header fields are written in the order they are first set, with
+/// the exact name casing given to [`header`](Self::header). Typed
+/// setters replace the previous value of their field in place.
+///
+/// The message structure depends on the content:
+///  * a single text part,
+///  * `multipart/alternative` when both text and HTML are set,
+///  * `multipart/mixed` when there are attachments,
+///  * `multipart/report` for a [delivery status
+///    notification](Self::delivery_status) or a [disposition
+///    notification](Self::disposition_notification).
+/// # Examples
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::builder::MessageBuilder;
+/// use rustyknife::mime::parse_message;
+/// use rustyknife::rfc5322::from;
+///
+/// let (_, sender) = from::<Intl>(b"Ren\xc3\xa9 <rene@example.org>").unwrap();
+/// let (_, to) = from::<Intl>(b"bob@example.org").unwrap();
+///
+/// let raw = MessageBuilder::new()
+///     .from(&sender)
+///     .to(&to)
+///     .subject("Café")
+///     .date(UNIX_EPOCH + Duration::from_secs(1_000_000_000))
+///     .message_id("1@example.org")
+///     .text("Hello\n")
+///     .attach("notes.txt", "text/plain", b"a note\r\n")
+///     .build();
+///
+/// assert!(raw.starts_with(b"From: =?utf-8?q?Ren=C3=A9?= <rene@example.org>\r\nTo: bob@example.org\r\n\
+///                           Subject: =?utf-8?q?Caf=C3=A9?=\r\nDate: Sun, 09 Sep 2001 01:46:40 +0000\r\n\
+///                           Message-ID: <1@example.org>\r\nMIME-Version: 1.0\r\n"));
+///
+/// let message = parse_message(&raw);
+/// assert_eq!(message.parts[0].decoded_text(), "Hello\r\n");
+/// assert_eq!(message.attachments().next().unwrap().filename.as_deref(), Some("notes.txt"));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MessageBuilder {
+    headers: Vec<(String, String)>,
+    text: Option<String>,
+    html: Option<String>,
+    attachments: Vec<(String, String, Vec<u8>)>,
+    order: Option<HeaderOrder>,
+    id_domain: Option<String>,
+    report: Option<(&'static str, &'static str, Vec<u8>)>,
+    returned: Option<(&'static str, Vec<u8>)>,
+    global: bool,
+}
+
+/// Error returned by [`MessageBuilder::try_build`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuildError {
+    /// The named header field contains raw UTF-8 but the builder was
+    /// not created with [`MessageBuilder::global`].
+    Utf8Header(String),
+    /// The named header field has an invalid name, or a value with a
+    /// line break that does not fold it or another control character.
+    InvalidHeader(String),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::Utf8Header(name) => write!(f, "raw UTF-8 in {} header outside of a global message", name),
+            BuildError::InvalidHeader(name) => write!(f, "invalid {} header", name),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+// A body part ready to be serialized.
+struct Part {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Part {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        for (name, value) in &self.headers {
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(b": ");
+            out.extend_from_slice(sanitize_value(value).as_bytes());
+            out.extend_from_slice(b"\r\n");
+        }
+        out.extend_from_slice(b"\r\n");
+        out.extend_from_slice(&self.body);
+
+        out
+    }
+}
+
+fn is_seven_bit(data: &[u8]) -> bool {
+    body_stats(data).required_body_type() == BodyType::SevenBit
+}
+
+fn text_part(subtype: &str, text: &str) -> Part {
+    let data = normalize_crlf(text.as_bytes());
+    let charset = if text.is_ascii() { "us-ascii" } else { "utf-8" };
+    let (cte, body) = if is_seven_bit(&data) {
+        ("7bit", data)
+    } else {
+        ("quoted-printable", encode_quoted_printable(&data).into_bytes())
+    };
+
+    Part {
+        headers: vec![("Content-Type".into(), format!("text/{}; charset={}", subtype, charset)),
+                      ("Content-Transfer-Encoding".into(), cte.into())],
+        body,
+    }
+}
+
+// Only identity encodings are allowed for message types.
+fn identity_cte(data: &[u8]) -> &'static str {
+    match body_stats(data).required_body_type() {
+        BodyType::SevenBit => "7bit",
+        BodyType::EightBitMime => "8bit",
+        BodyType::BinaryMime => "binary",
+    }
+}
+
+fn identity_part(content_type: &str, data: &[u8]) -> Part {
+    Part {
+        headers: vec![("Content-Type".into(), content_type.into()),
+                      ("Content-Transfer-Encoding".into(), identity_cte(data).into())],
+        body: data.to_vec(),
+    }
+}
+
+fn attachment_part(filename: &str, content_type: &str, data: &[u8]) -> Part {
+    let (cte, body) = if content_type.starts_with("text/") && is_seven_bit(data) {
+        ("7bit", data.to_vec())
+    } else if content_type.starts_with("message/") {
+        (identity_cte(data), data.to_vec())
+    } else {
+        ("base64", encode_base64(data).into_bytes())
+    };
+
+    Part {
+        headers: vec![("Content-Type".into(), format!("{}{}", content_type, encode_parameter("name", filename, FOLD_LEN - 1))),
+                      ("Content-Disposition".into(), format!("attachment{}", encode_parameter("filename", filename, FOLD_LEN - 1))),
+                      ("Content-Transfer-Encoding".into(), cte.into())],
+        body,
+    }
+}
+
+// Pick a boundary that does not occur in any of `parts`.
+fn boundary(parts: &[Vec<u8>]) -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+
+    loop {
+        let mut hasher = DefaultHasher::new();
+        (nanos, COUNTER.fetch_add(1, Ordering::Relaxed), parts.len()).hash(&mut hasher);
+        let boundary = format!("=_{:016x}", hasher.finish());
+
+        if !parts.iter().any(|p| p.windows(boundary.len()).any(|w| w == boundary.as_bytes())) {
+            return boundary;
+        }
+    }
+}
+
+fn multipart(subtype: &str, parts: Vec<Part>) -> Part {
+    let parts: Vec<_> = parts.iter().map(Part::to_bytes).collect();
+    let boundary = boundary(&parts);
+    let mut body = Vec::new();
+
+    for part in parts {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        body.extend_from_slice(&part);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    Part {
+        headers: vec![("Content-Type".into(), format!("multipart/{}{}", subtype, encode_parameter("boundary", &boundary, FOLD_LEN)))],
+        body,
+    }
+}
+
+fn is_field_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|c| (33..=126).contains(&c) && c != b':')
+}
+
+// Whether `value` has line breaks only to fold it and no other control
+// characters than tab.
+fn is_clean_value(value: &str) -> bool {
+    let value = value.as_bytes();
+    let folds = |i: usize| value.get(i) == Some(&b'\r') && value.get(i + 1) == Some(&b'\n')
+        && matches!(value.get(i + 2), Some(b' ') | Some(b'\t'));
+
+    value.iter().enumerate().all(|(i, c)| match c {
+        b'\r' => folds(i),
+        b'\n' => i > 0 && folds(i - 1),
+        b'\t' => true,
+        c => !c.is_ascii_control(),
+    })
+}
+
+// Replace the control characters of `value` that would end the field
+// with spaces, keeping folding line breaks.
+fn sanitize_value(value: &str) -> String {
+    if is_clean_value(value) {
+        return value.into();
+    }
+
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("\r\n ") || rest.starts_with("\r\n\t") {
+            out.push_str(&rest[..3]);
+            rest = &rest[3..];
+            continue;
+        }
+        out.push(if c != '\t' && c.is_ascii_control() { ' ' } else { c });
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+// Fold `value` at spaces so that lines stay within `FOLD_LEN` when
+// possible. Values already containing line breaks are kept as is,
+// after removing the line breaks that do not fold them.
+fn fold(name: &str, value: &str) -> String {
+    let value = sanitize_value(value);
+    if value.contains("\r\n") {
+        return format!(" {}", value);
+    }
+
+    let mut out = String::with_capacity(value.len() + 1);
+    let mut line_len = name.len() + 1;
+
+    for word in value.split(' ') {
+        if !word.is_empty() && line_len + 1 + word.len() > FOLD_LEN {
+            out.push_str("\r\n");
+            line_len = 0;
+        }
+        out.push(' ');
+        out.push_str(word);
+        line_len += 1 + word.len();
+    }
+
+    out
+}
+
+/// Format `time` as an [RFC 5322 date-time] in UTC.
+///
+/// [RFC 5322 date-time]: https://tools.ietf.org/html/rfc5322#section-3.3
+/// # Examples
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use rustyknife::builder::format_date;
+///
+/// assert_eq!(format_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 +0000");
+/// assert_eq!(format_date(UNIX_EPOCH + Duration::from_secs(951_782_400)), "Tue, 29 Feb 2000 00:00:00 +0000");
+/// ```
+pub fn format_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // Civil date from days since the epoch, in eras of 400 years
+    // starting on March 1st.
+    let z = days + 719_468;
+    let (era, doe) = (z / 146_097, z % 146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!("{}, {:02} {} {} {:02}:{:02}:{:02} +0000", WEEKDAYS[(days % 7) as usize], day,
+            MONTHS[(month - 1) as usize], year, rem / 3600, rem / 60 % 60, rem % 60)
+}
+
+// Message identifiers cannot contain whitespace or control characters,
+// they are removed.
+fn msg_id(id: &str) -> String {
+    let id: String = id.chars().filter(|c| !c.is_whitespace() && !c.is_control()).collect();
+    format!("<{}>", id.trim_start_matches('<').trim_end_matches('>'))
+}
+
+impl MessageBuilder {
+    /// Create an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a builder for an internationalized message ([RFC 6532]).
+    ///
+    /// Header fields are written in raw UTF-8 instead of encoded
+    /// words. Reports use the `message/global-*` media types of
+    /// [RFC 6533] and the returned content is `message/global`.
+    ///
+    /// Such a message may only be relayed to servers supporting
+    /// SMTPUTF8.
+    ///
+    /// [RFC 6532]: https://tools.ietf.org/html/rfc6532
+    /// [RFC 6533]: https://tools.ietf.org/html/rfc6533
+    /// # Examples
+    /// ```
+    /// use rustyknife::behaviour::Intl;
+    /// use rustyknife::builder::MessageBuilder;
+    /// use rustyknife::rfc5322::from;
+    ///
+    /// let (_, to) = from::<Intl>("René <rené@example.org>".as_bytes()).unwrap();
+    ///
+    /// let raw = MessageBuilder::global().to(&to).subject("Café").build();
+    /// assert!(raw.starts_with("To: René <rené@example.org>\r\nSubject: Café\r\n".as_bytes()));
+    ///
+    /// assert!(MessageBuilder::new().to(&to).try_build().is_err());
+    /// assert!(MessageBuilder::global().to(&to).try_build().is_ok());
+    /// ```
+    pub fn global() -> Self {
+        MessageBuilder { global: true, ..Self::default() }
+    }
+
+    fn set(mut self, name: &str, value: String) -> Self {
+        match self.headers.iter_mut().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+            Some(field) => field.1 = value,
+            None => self.headers.push((name.into(), value)),
+        }
+        self
+    }
+
+    fn addresses(self, name: &str, addresses: &[Address]) -> Self {
+        let list = AddressList(addresses);
+        let value = match self.global {
+            true => list.to_string(),
+            false => list.to_ascii_string().unwrap_or_else(|_| list.to_string()),
+        };
+
+        self.set(name, value)
+    }
+
+    /// Set the From field.
+    ///
+    /// The domain of the first mailbox is used in the generated
+    /// Message-ID. Display names are encoded as needed, addresses
+    /// with a UTF-8 local part are kept as is and rejected by
+    /// [`try_build`](Self::try_build) unless the builder is
+    /// [`global`](Self::global).
+    pub fn from(mut self, addresses: &[Address]) -> Self {
+        let global = self.global;
+        self.id_domain = addresses.iter().flat_map(Address::mailboxes).next()
+            .map(|m| m.address.domain_part())
+            .map(|d| if global { d.to_unicode_string() } else { d.to_ascii_string().unwrap_or_else(|_| d.to_string()) });
+        self.addresses("From", addresses)
+    }
+
+    /// Set the Sender field.
+    pub fn sender(self, mailbox: &Mailbox) -> Self {
+        self.addresses("Sender", &[Address::Mailbox(mailbox.clone())])
+    }
+
+    /// Set the Reply-To field.
+    pub fn reply_to(self, addresses: &[Address]) -> Self {
+        self.addresses("Reply-To", addresses)
+    }
+
+    /// Set the To field.
+    pub fn to(self, addresses: &[Address]) -> Self {
+        self.addresses("To", addresses)
+    }
+
+    /// Set the Cc field.
+    pub fn cc(self, addresses: &[Address]) -> Self {
+        self.addresses("Cc", addresses)
+    }
+
+    /// Set the Subject field, using encoded words if it is not ASCII
+    /// and the builder is not [`global`](Self::global), or if it
+    /// contains control characters other than tab.
+    /// # Examples
+    /// ```
+    /// use rustyknife::builder::MessageBuilder;
+    ///
+    /// let raw = MessageBuilder::new().subject("hi\r\nBcc: victim@example.org").build();
+    /// assert!(raw.starts_with(b"Subject: =?utf-8?q?hi=0D=0ABcc:_victim@example.org?=\r\n"));
+    /// ```
+    pub fn subject(self, subject: &str) -> Self {
+        let control = subject.chars().any(|c| c != '\t' && c.is_control());
+        let value = if (self.global || subject.is_ascii()) && !control {
+            subject.into()
+        } else {
+            encode(subject, EncodeContext::Text)
+        };
+
+        self.set("Subject", value)
+    }
+
+    /// Set the Date field. The time of [`build`](Self::build) is used
+    /// by default.
+    pub fn date(self, time: SystemTime) -> Self {
+        self.set("Date", format_date(time))
+    }
+
+    /// Set the Message-ID field, with or without angle brackets. A
+    /// unique identifier is generated by default.
+    pub fn message_id(self, id: &str) -> Self {
+        self.set("Message-ID", msg_id(id))
+    }
+
+    /// Set the In-Reply-To field from message identifiers.
+    pub fn in_reply_to<S: AsRef<str>>(self, ids: &[S]) -> Self {
+        self.set("In-Reply-To", ids.iter().map(|id| msg_id(id.as_ref())).collect::<Vec<_>>().join(" "))
+    }
+
+    /// Set the References field from message identifiers.
+    pub fn references<S: AsRef<str>>(self, ids: &[S]) -> Self {
+        self.set("References", ids.iter().map(|id| msg_id(id.as_ref())).collect::<Vec<_>>().join(" "))
+    }
+
+    /// Append a header field with the exact `name` given.
+    ///
+    /// `value` must already be encoded, it is only folded. Line breaks
+    /// in `value` must be followed by a space or tab. Other line breaks
+    /// and control characters are replaced with spaces, and fields with
+    /// an invalid `name` are left out, unless [`try_build`](Self::try_build)
+    /// is used to reject them.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the text body. Line endings are converted to CRLF.
+    pub fn text(mut self, text: &str) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    /// Set the HTML body. Line endings are converted to CRLF.
+    pub fn html(mut self, html: &str) -> Self {
+        self.html = Some(html.into());
+        self
+    }
+
+    /// Add an attachment of type `content_type`.
+    pub fn attach(mut self, filename: &str, content_type: &str, data: &[u8]) -> Self {
+        self.attachments.push((filename.into(), content_type.into(), data.to_vec()));
+        self
+    }
+
+    /// Make the message a `multipart/report` delivery status
+    /// notification, with the fields of `status` as its second part.
+    /// That part is `message/global-delivery-status` in a
+    /// [`global`](Self::global) message.
+    ///
+    /// The text and HTML bodies form the human-readable first part.
+    /// Attachments are left out of reports. Fails when `status` is
+    /// incomplete, see [`DeliveryStatus::to_bytes`].
+    /// # Examples
+    /// ```
+    /// use rustyknife::builder::MessageBuilder;
+    /// use rustyknife::dsn::{delivery_status, Action, DeliveryStatus, RecipientReport};
+    /// use rustyknife::mime::parse_message;
+    /// use rustyknife::rfc3461::DSNRet;
+    ///
+    /// let original = b"From: alice@example.org\r\nSubject: Hi\r\n\r\nHello\r\n";
+    /// let status = DeliveryStatus {
+    ///     reporting_mta: Some(("dns".into(), "mx.example.org".into())),
+    ///     recipients: vec![RecipientReport {
+    ///         final_recipient: Some(("rfc822".into(), "bob@example.net".into())),
+    ///         action: Some(Action::Failed),
+    ///         status: Some("5.1.1".into()),
+    ///         diagnostic_code: Some(("smtp".into(), "550 5.1.1 No such user".into())),
+    ///         ..RecipientReport::default()
+    ///     }],
+    ///     ..DeliveryStatus::default()
+    /// };
+    ///
+    /// let raw = MessageBuilder::new()
+    ///     .subject("Undelivered Mail Returned to Sender")
+    ///     .text("Your message could not be delivered.\n")
+    ///     .delivery_status(&status).unwrap()
+    ///     .returned_content(original, DSNRet::Hdrs)
+    ///     .build();
+    ///
+    /// let message = parse_message(&raw);
+    /// assert_eq!(message.content_type, "multipart/report");
+    /// assert_eq!(message.param("report-type"), Some("delivery-status"));
+    /// assert_eq!(delivery_status(message.parts[1].body), status);
+    /// assert_eq!(message.parts[2].content_type, "text/rfc822-headers");
+    /// ```
+    pub fn delivery_status(mut self, status: &DeliveryStatus) -> Result<Self, DsnError> {
+        let content_type = if self.global { MESSAGE_GLOBAL_DELIVERY_STATUS } else { MESSAGE_DELIVERY_STATUS };
+        self.report = Some(("delivery-status", content_type, status.to_bytes()?));
+        Ok(self)
+    }
+
+    /// Make the message a `multipart/report` message disposition
+    /// notification, with the fields of `mdn` as its second part.
+    ///
+    /// See [`delivery_status`](Self::delivery_status) for the other
+    /// parts. Fails when a field of `mdn` is invalid, see
+    /// [`DispositionNotification::to_bytes`].
+    pub fn disposition_notification(mut self, mdn: &DispositionNotification) -> Result<Self, DsnError> {
+        let content_type = match self.global {
+            true => MESSAGE_GLOBAL_DISPOSITION_NOTIFICATION,
+            false => MESSAGE_DISPOSITION_NOTIFICATION,
+        };
+        self.report = Some(("disposition-notification", content_type, mdn.to_bytes()?));
+        Ok(self)
+    }
+
+    /// Return the original `message` as the third part of a report.
+    ///
+    /// With [`DSNRet::Hdrs`] only its header section is returned, as
+    /// `text/rfc822-headers`, or `message/global-headers` in a
+    /// [`global`](Self::global) message.
+    pub fn returned_content(mut self, message: &[u8], ret: DSNRet) -> Self {
+        let global = self.global;
+        self.returned = Some(match ret {
+            DSNRet::Full => (if global { MESSAGE_GLOBAL } else { MESSAGE_RFC822 }, message.to_vec()),
+            DSNRet::Hdrs => {
+                let end = header_section(message).map(|(body, _)| message.len() - body.len()).unwrap_or(message.len());
+                (if global { MESSAGE_GLOBAL_HEADERS } else { TEXT_RFC822_HEADERS }, message[..end].to_vec())
+            }
+        });
+        self
+    }
+
+    /// Choose the order of the header fields, [`HeaderOrder::Preserve`]
+    /// by default.
+    pub fn order(mut self, order: HeaderOrder) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    fn root(&self) -> Part {
+        let mut body = Vec::new();
+        if let Some(text) = &self.text {
+            body.push(text_part("plain", text));
+        }
+        if let Some(html) = &self.html {
+            body.push(text_part("html", html));
+        }
+
+        let body = match body.len() {
+            0 if self.attachments.is_empty() || self.report.is_some() => Some(text_part("plain", "")),
+            0 => None,
+            1 => body.pop(),
+            _ => Some(multipart("alternative", body)),
+        };
+
+        if let Some((report_type, content_type, data)) = &self.report {
+            let parts = body.into_iter().chain(Some(identity_part(content_type, data)))
+                .chain(self.returned.iter().map(|(ct, data)| identity_part(ct, data)))
+                .collect();
+            multipart(&format!("report; report-type={}", report_type), parts)
+        } else if self.attachments.is_empty() {
+            body.unwrap()
+        } else {
+            let parts = body.into_iter()
+                .chain(self.attachments.iter().map(|(name, ct, data)| attachment_part(name, ct, data)))
+                .collect();
+            multipart("mixed", parts)
+        }
+    }
+
+    /// Assemble the message, checking that raw UTF-8 only appears in
+    /// the header fields of a [`global`](Self::global) message and that
+    /// no header field contains a line break that does not fold it or
+    /// another control character.
+    ///
+    /// Fields given to [`header`](Self::header) and the content types
+    /// given to [`attach`](Self::attach) are checked too.
+    /// # Examples
+    /// ```
+    /// use rustyknife::builder::{BuildError, MessageBuilder};
+    ///
+    /// let injected = MessageBuilder::new().header("X-Note", "hi\r\nBcc: victim@example.org");
+    /// assert_eq!(injected.try_build(), Err(BuildError::InvalidHeader("X-Note".into())));
+    /// assert!(MessageBuilder::new().header("X-Note", "folded\r\n value").try_build().is_ok());
+    /// ```
+    pub fn try_build(self) -> Result<Vec<u8>, BuildError> {
+        if let Some((name, _)) = self.headers.iter().find(|(n, v)| !is_field_name(n) || !is_clean_value(v)) {
+            return Err(BuildError::InvalidHeader(name.clone()));
+        }
+        if self.attachments.iter().any(|(_, content_type, _)| !is_clean_value(content_type)) {
+            return Err(BuildError::InvalidHeader("Content-Type".into()));
+        }
+        if !self.global {
+            if let Some((name, _)) = self.headers.iter().find(|(n, v)| !n.is_ascii() || !v.is_ascii()) {
+                return Err(BuildError::Utf8Header(name.clone()));
+            }
+        }
+
+        Ok(self.build())
+    }
+
+    /// Assemble the message.
+    ///
+    /// Header fields are not checked, see [`try_build`](Self::try_build).
+    /// Control characters are still kept from ending a field early.
+    pub fn build(self) -> Vec<u8> {
+        let root = self.root();
+        let mut builder = self;
+
+        if !builder.headers.iter().any(|(n, _)| n.eq_ignore_ascii_case("Date")) {
+            builder = builder.date(SystemTime::now());
+        }
+        if !builder.headers.iter().any(|(n, _)| n.eq_ignore_ascii_case("Message-ID")) {
+            let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+            let id = format!("{:x}.{:x}.{:x}@{}", nanos, std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed),
+                             builder.id_domain.as_deref().unwrap_or("localhost"));
+            builder = builder.message_id(&id);
+        }
+        builder = builder.set("MIME-Version", "1.0".into());
+
+        let fields: Vec<_> = builder.headers.iter().chain(root.headers.iter())
+            .filter(|(name, _)| is_field_name(name))
+            .map(|(name, value)| (name.as_str(), fold(name, value)))
+            .collect();
+        let fields: Vec<HeaderField> = fields.iter().map(|(name, value)| Ok((name.as_bytes(), value.as_bytes()))).collect();
+
+        let mut out = write_header_section(&fields, builder.order.unwrap_or(HeaderOrder::Preserve));
+        out.extend_from_slice(&root.body);
+        out
+    }
+}
+
+/// Compute the subject of a reply to a message with `subject`.
+///
+/// Any existing reply prefixes, such as `"Re:"`, `"RE[2]:"` or the
+/// German `"AW:"`, are collapsed into a single `"Re: "`. See
+/// [`crate::subject`] for the recognized prefixes.
+/// # Examples
+/// ```
+/// use rustyknife::builder::reply_subject;
+///
+/// assert_eq!(reply_subject("Lunch"), "Re: Lunch");
+/// assert_eq!(reply_subject("RE: Re[3]: AW: Lunch"), "Re: Lunch");
+/// assert_eq!(reply_subject("Fwd: Lunch"), "Re: Fwd: Lunch");
+/// ```
+pub fn reply_subject(subject: &str) -> String {
+    let mut subject = subject.trim();
+
+    while let Some((PrefixKind::Reply, rest)) = split_prefix(subject).map(|(p, rest)| (p.kind, rest)) {
+        subject = rest;
+    }
+
+    format!("Re: {}", subject)
+}
+
+/// Compute the subject when forwarding a message with `subject`.
+///
+/// Nothing is added if it already starts with a forward prefix.
+/// # Examples
+/// ```
+/// use rustyknife::builder::forward_subject;
+///
+/// assert_eq!(forward_subject("Lunch"), "Fwd: Lunch");
+/// assert_eq!(forward_subject("FW: Lunch"), "FW: Lunch");
+/// ```
+pub fn forward_subject(subject: &str) -> String {
+    let subject = subject.trim();
+
+    if matches!(split_prefix(subject), Some((SubjectPrefix { kind: PrefixKind::Forward, .. }, _))) {
+        subject.into()
+    } else {
+        format!("Fwd: {}", subject)
+    }
+}
+
+// Extract the message identifiers of a header value, in `<id>` form.
+fn msg_ids(value: &[u8]) -> Vec<String> {
+    references::<Intl>(value).map(|(_, ids)| ids.iter().map(|id| format!("<{}>", id)).collect()).unwrap_or_default()
+}
+
+/// Header values for a reply, see [`ReplyHeaders::new`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplyHeaders {
+    /// The recipients: the Reply-To addresses of the original message
+    /// if present, its From addresses otherwise.
+    pub to: Vec<Address>,
+    /// The other recipients of the original message when replying to
+    /// all, without the addresses already in [`to`](Self::to).
+    pub cc: Vec<Address>,
+    /// The subject with a single reply prefix.
+    pub subject: String,
+    /// The Message-ID of the original message.
+    pub in_reply_to: Vec<String>,
+    /// The thread identifiers, ending with the Message-ID of the
+    /// original message.
+    pub references: Vec<String>,
+}
+
+impl ReplyHeaders {
+    /// Compute the reply headers for a message with `headers`.
+    ///
+    /// The References are chained as specified in [RFC 5322 section
+    /// 3.6.4]: those of the original message, or its In-Reply-To when
+    /// it holds a single identifier, followed by its Message-ID.
+    ///
+    /// Headers that fail to parse are ignored. The mailboxes of `me`,
+    /// the replying user, are removed from both recipient lists,
+    /// comparing addresses case insensitively. Control characters of
+    /// the decoded subject are replaced with spaces.
+    ///
+    /// [RFC 5322 section 3.6.4]: https://tools.ietf.org/html/rfc5322#section-3.6.4
+    /// # Examples
+    /// ```
+    /// use rustyknife::builder::ReplyHeaders;
+    /// use rustyknife::headersection::header_section;
+    /// use rustyknife::behaviour::Intl;
+    /// use rustyknife::rfc5322::{from, AddressList};
+    ///
+    /// let (_, headers) = header_section(b"From: bob@example.org\r\nReply-To: list@example.org\r\n\
+    ///                                      To: list@example.org, alice@example.org\r\nSubject: Re: Lunch\r\n\
+    ///                                      Message-ID: <2@example.org>\r\nIn-Reply-To: <1@example.org>\r\n\r\n").unwrap();
+    /// let reply = ReplyHeaders::new(&headers, true, &[]);
+    ///
+    /// assert_eq!(AddressList(&reply.to).to_string(), "list@example.org");
+    /// assert_eq!(AddressList(&reply.cc).to_string(), "alice@example.org");
+    /// assert_eq!(reply.subject, "Re: Lunch");
+    /// assert_eq!(reply.in_reply_to, ["<2@example.org>"]);
+    /// assert_eq!(reply.references, ["<1@example.org>", "<2@example.org>"]);
+    ///
+    /// let (_, me) = from::<Intl>(b"Alice <ALICE@example.org>").unwrap();
+    /// let reply = ReplyHeaders::new(&headers, true, me[0].mailboxes());
+    /// assert!(reply.cc.is_empty());
+    /// ```
+    pub fn new(headers: &[HeaderField], reply_all: bool, me: &[Mailbox]) -> Self {
+        let get = |name: &str| headers.iter().flatten().find(|(n, _)| n.eq_ignore_ascii_case(name.as_bytes())).map(|(_, v)| *v);
+        let addresses = |name: &str, parser: fn(&[u8]) -> crate::NomResult<Vec<Address>>| {
+            get(name).and_then(|v| parser(v).ok()).map(|(_, a)| a).unwrap_or_default()
+        };
+
+        let to = match addresses("Reply-To", reply_to::<Intl>) {
+            reply_to if !reply_to.is_empty() => reply_to,
+            _ => addresses("From", from::<Intl>),
+        };
+        let to = merge_address_lists(&[&to], false);
+        let cc = if reply_all {
+            let (original_to, original_cc) = (addresses("To", from::<Intl>), addresses("Cc", from::<Intl>));
+            merge_address_lists(&[&to, &original_to, &original_cc], false).split_off(to.len())
+        } else {
+            Vec::new()
+        };
+
+        let subject = get("Subject").and_then(|v| unstructured::<Intl>(v).ok()).map(|(_, s)| s).unwrap_or_default();
+        let in_reply_to = get("Message-ID").map(msg_ids).unwrap_or_default();
+        let mut references = match get("References").map(msg_ids) {
+            Some(ids) if !ids.is_empty() => ids,
+            _ => get("In-Reply-To").map(msg_ids).filter(|ids| ids.len() == 1).unwrap_or_default(),
+        };
+        references.extend(in_reply_to.iter().cloned());
+
+        let is_me = |m: &Mailbox| me.iter().any(|me| me.address.eq_ignore_case(&m.address));
+        let not_me = |list: Vec<Address>| list.into_iter().filter_map(|address| match address {
+            Address::Mailbox(m) if is_me(&m) => None,
+            Address::Group(mut g) => {
+                g.members.retain(|m| !is_me(m));
+                Some(Address::Group(g))
+            }
+            address => Some(address),
+        }).collect();
+        let subject: String = subject.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+
+        ReplyHeaders { to: not_me(to), cc: not_me(cc), subject: reply_subject(&subject), in_reply_to, references }
+    }
+
+    /// Set these headers on `builder`, omitting empty ones.
+    pub fn apply(&self, mut builder: MessageBuilder) -> MessageBuilder {
+        if !self.to.is_empty() {
+            builder = builder.to(&self.to);
+        }
+        if !self.cc.is_empty() {
+            builder = builder.cc(&self.cc);
+        }
+        builder = builder.subject(&self.subject);
+        if !self.in_reply_to.is_empty() {
+            builder = builder.in_reply_to(&self.in_reply_to);
+        }
+        if !self.references.is_empty() {
+            builder = builder.references(&self.references);
+        }
+        builder
+    }
+}
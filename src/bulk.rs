@@ -0,0 +1,162 @@
+//! Bulk and automated mail indicators
+//!
+//! Collects the headers that mark a message as sent by a mailing list,
+//! a bulk sender or an automated process into a single summary, for
+//! routing and suppression decisions such as not sending vacation
+//! replies.
+
+use crate::behaviour::Intl;
+use crate::headersection::HeaderField;
+use crate::rfc5322::unstructured;
+
+/// Value of the `"Precedence:"` header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Precedence {
+    /// `"bulk"`
+    Bulk,
+    /// `"list"`
+    List,
+    /// `"junk"`
+    Junk,
+    /// Any other value, lowercased.
+    Other(String),
+}
+
+/// Value of the [`"Auto-Submitted:"`] header.
+///
+/// [`"Auto-Submitted:"`]: https://tools.ietf.org/html/rfc3834#section-5
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AutoSubmitted {
+    /// `"no"`, the message was written by a person.
+    No,
+    /// `"auto-generated"`
+    AutoGenerated,
+    /// `"auto-replied"`
+    AutoReplied,
+    /// `"auto-notified"`
+    AutoNotified,
+    /// Any other value, lowercased.
+    Other(String),
+}
+
+/// Summary of the bulk mail indicators of a header section.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BulkIndicators {
+    /// The `"Precedence:"` header.
+    pub precedence: Option<Precedence>,
+    /// The identifier of the [`"List-Id:"`] header, without its angle
+    /// brackets.
+    ///
+    /// [`"List-Id:"`]: https://tools.ietf.org/html/rfc2919
+    pub list_id: Option<String>,
+    /// The `"Feedback-ID:"` header used by feedback loops.
+    pub feedback_id: Option<String>,
+    /// The `"X-Campaign:"` or `"X-Campaign-Id:"` header.
+    pub campaign: Option<String>,
+    /// The `"Auto-Submitted:"` header.
+    pub auto_submitted: Option<AutoSubmitted>,
+    /// The URIs of the [`"List-Unsubscribe:"`] header.
+    ///
+    /// [`"List-Unsubscribe:"`]: https://tools.ietf.org/html/rfc2369#section-3.2
+    pub unsubscribe: Vec<String>,
+    /// Whether [one-click unsubscription] is offered.
+    ///
+    /// [one-click unsubscription]: https://tools.ietf.org/html/rfc8058
+    pub one_click_unsubscribe: bool,
+}
+
+impl BulkIndicators {
+    /// Returns `true` if the message comes from a list or a bulk
+    /// sender.
+    pub fn is_bulk(&self) -> bool {
+        matches!(self.precedence, Some(Precedence::Bulk) | Some(Precedence::List) | Some(Precedence::Junk))
+            || self.list_id.is_some()
+            || self.feedback_id.is_some()
+            || self.campaign.is_some()
+            || !self.unsubscribe.is_empty()
+    }
+
+    /// Returns `true` if the message was sent by an automated process.
+    pub fn is_automated(&self) -> bool {
+        matches!(&self.auto_submitted, Some(a) if *a != AutoSubmitted::No)
+    }
+}
+
+fn text(value: &[u8]) -> String {
+    unstructured::<Intl>(value).map(|(_, s)| s).unwrap_or_else(|_| String::from_utf8_lossy(value).into()).trim().into()
+}
+
+// Lowercased value up to the first parameter or comment.
+fn keyword(value: &[u8]) -> String {
+    text(value).split([';', '(']).next().unwrap_or_default().trim().to_lowercase()
+}
+
+// The contents of each `<...>` in `value`.
+fn bracketed(value: &[u8]) -> Vec<String> {
+    let value = String::from_utf8_lossy(value);
+
+    value.split('<').skip(1)
+        .filter_map(|s| s.split_once('>'))
+        .map(|(inner, _)| inner.split_whitespace().collect())
+        .filter(|inner: &String| !inner.is_empty())
+        .collect()
+}
+
+/// Extract the bulk mail indicators of a header section.
+///
+/// Header names are matched case-insensitively and the first
+/// occurrence of each header is used. One-click unsubscription
+/// requires both an HTTPS URI in `"List-Unsubscribe:"` and
+/// `"List-Unsubscribe-Post: List-Unsubscribe=One-Click"`.
+/// # Examples
+/// ```
+/// use rustyknife::bulk::{bulk_indicators, Precedence};
+/// use rustyknife::headersection::header_section;
+///
+/// let (_, headers) = header_section(b"Precedence: bulk\r\n\
+///                                     List-Id: Announcements <announce.example.org>\r\n\
+///                                     List-Unsubscribe: <mailto:leave@example.org>,\r\n <https://example.org/u/1>\r\n\
+///                                     List-Unsubscribe-Post: List-Unsubscribe=One-Click\r\n\r\n").unwrap();
+/// let bulk = bulk_indicators(&headers);
+///
+/// assert_eq!(bulk.precedence, Some(Precedence::Bulk));
+/// assert_eq!(bulk.list_id.as_deref(), Some("announce.example.org"));
+/// assert_eq!(bulk.unsubscribe, ["mailto:leave@example.org", "https://example.org/u/1"]);
+/// assert!(bulk.one_click_unsubscribe);
+/// assert!(bulk.is_bulk() && !bulk.is_automated());
+/// ```
+pub fn bulk_indicators(headers: &[HeaderField]) -> BulkIndicators {
+    let get = |name: &str| headers.iter().filter_map(|h| h.as_ref().ok())
+        .find(|(n, _)| n.eq_ignore_ascii_case(name.as_bytes()))
+        .map(|(_, v)| *v);
+
+    let precedence = get("Precedence").map(keyword).map(|p| match p.as_str() {
+        "bulk" => Precedence::Bulk,
+        "list" => Precedence::List,
+        "junk" => Precedence::Junk,
+        _ => Precedence::Other(p),
+    });
+    let auto_submitted = get("Auto-Submitted").map(keyword).map(|a| match a.as_str() {
+        "no" => AutoSubmitted::No,
+        "auto-generated" => AutoSubmitted::AutoGenerated,
+        "auto-replied" => AutoSubmitted::AutoReplied,
+        "auto-notified" => AutoSubmitted::AutoNotified,
+        _ => AutoSubmitted::Other(a),
+    });
+
+    let unsubscribe = get("List-Unsubscribe").map(bracketed).unwrap_or_default();
+    let one_click_unsubscribe = unsubscribe.iter().any(|u| matches!(u.get(..8), Some(s) if s.eq_ignore_ascii_case("https://")))
+        && matches!(get("List-Unsubscribe-Post"), Some(v) if text(v).eq_ignore_ascii_case("List-Unsubscribe=One-Click"));
+
+    let non_empty = |s: String| Some(s).filter(|s| !s.is_empty());
+
+    BulkIndicators {
+        precedence,
+        list_id: get("List-Id").and_then(|v| bracketed(v).pop()),
+        feedback_id: get("Feedback-ID").map(text).and_then(non_empty),
+        campaign: get("X-Campaign").or_else(|| get("X-Campaign-Id")).map(text).and_then(non_empty),
+        auto_submitted,
+        unsubscribe,
+        one_click_unsubscribe,
+    }
+}
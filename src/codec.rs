@@ -0,0 +1,175 @@
+//! [tokio] codec for SMTP replies
+//!
+//! [`SmtpReplyCodec`] turns the byte stream received by an SMTP client
+//! into [`Reply`] values, for use with
+//! [`FramedRead`](tokio_util::codec::FramedRead). Only available with
+//! the `tokio` feature.
+//!
+//! [tokio]: https://tokio.rs
+
+use std::fmt::{self, Display};
+use std::io;
+use std::marker::PhantomData;
+
+use bytes::BytesMut;
+use tokio_util::codec::Decoder;
+
+use crate::reply::{reply, Reply};
+use crate::rfc5321::UTF8Policy;
+
+/// Error returned by the [`SmtpReplyCodec`].
+///
+/// The connection should be dropped after any of these, since the
+/// position of the next reply is unknown.
+#[derive(Debug)]
+pub enum ReplyCodecError {
+    /// Reading from the stream failed.
+    Io(io::Error),
+    /// A line is longer than the configured limit, including CRLF.
+    LineTooLong,
+    /// A reply has more lines than the configured limit.
+    TooManyLines,
+    /// The reply is not valid.
+    Syntax,
+    /// The stream ended in the middle of a reply.
+    Truncated,
+}
+
+impl Display for ReplyCodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplyCodecError::Io(e) => write!(f, "I/O error: {}", e),
+            ReplyCodecError::LineTooLong => write!(f, "reply line too long"),
+            ReplyCodecError::TooManyLines => write!(f, "too many reply lines"),
+            ReplyCodecError::Syntax => write!(f, "invalid reply"),
+            ReplyCodecError::Truncated => write!(f, "connection closed in the middle of a reply"),
+        }
+    }
+}
+
+impl std::error::Error for ReplyCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReplyCodecError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReplyCodecError {
+    fn from(e: io::Error) -> Self {
+        ReplyCodecError::Io(e)
+    }
+}
+
+/// Decoder assembling multiline replies.
+///
+/// Lines are checked for length as they arrive, so that a misbehaving
+/// server cannot make the client buffer without bound. Complete lines
+/// of an unfinished reply are not scanned again on the next call,
+/// and [`is_partial`](Self::is_partial) tells whether a reply was cut
+/// short when a read times out.
+/// # Examples
+/// ```
+/// use bytes::BytesMut;
+/// use rustyknife::behaviour::Legacy;
+/// use rustyknife::codec::SmtpReplyCodec;
+/// use tokio_util::codec::Decoder;
+///
+/// let mut codec = SmtpReplyCodec::<Legacy>::new();
+/// let mut buf = BytesMut::from(&b"250-mx.example.org\r\n250-PIPE"[..]);
+///
+/// assert!(codec.decode(&mut buf).unwrap().is_none());
+/// assert!(codec.is_partial());
+///
+/// buf.extend_from_slice(b"LINING\r\n250 SIZE\r\n354 ");
+/// let reply = codec.decode(&mut buf).unwrap().unwrap();
+/// assert_eq!(reply.lines, ["mx.example.org", "PIPELINING", "SIZE"]);
+/// assert_eq!(&buf[..], b"354 ");
+/// ```
+#[derive(Debug)]
+pub struct SmtpReplyCodec<P> {
+    max_line: usize,
+    max_lines: usize,
+    // Length of the complete lines of the current reply.
+    scanned: usize,
+    lines: usize,
+    partial: bool,
+    policy: PhantomData<P>,
+}
+
+impl<P: UTF8Policy> SmtpReplyCodec<P> {
+    /// Create a codec allowing lines of 512 bytes, as specified in
+    /// RFC 5321, and up to 100 lines per reply.
+    pub fn new() -> Self {
+        Self::with_limits(512, 100)
+    }
+
+    /// Create a codec with the given line length, including CRLF, and
+    /// line count limits.
+    pub fn with_limits(max_line: usize, max_lines: usize) -> Self {
+        SmtpReplyCodec { max_line, max_lines, scanned: 0, lines: 0, partial: false, policy: PhantomData }
+    }
+
+    /// Whether data of an incomplete reply was left in the buffer by
+    /// the last call to [`decode`](Decoder::decode).
+    pub fn is_partial(&self) -> bool {
+        self.partial
+    }
+
+    fn reset(&mut self) {
+        self.scanned = 0;
+        self.lines = 0;
+    }
+}
+
+impl<P: UTF8Policy> Default for SmtpReplyCodec<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: UTF8Policy> Decoder for SmtpReplyCodec<P> {
+    type Item = Reply;
+    type Error = ReplyCodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Reply>, ReplyCodecError> {
+        while let Some(nl) = src[self.scanned..].iter().position(|c| *c == b'\n') {
+            let start = self.scanned;
+            let end = start + nl + 1;
+            if end - start > self.max_line {
+                return Err(ReplyCodecError::LineTooLong);
+            }
+            self.lines += 1;
+            if self.lines > self.max_lines {
+                return Err(ReplyCodecError::TooManyLines);
+            }
+            self.scanned = end;
+
+            // Only the last line of a reply has no hyphen after the code.
+            if src[start..end].get(3) != Some(&b'-') {
+                let data = src.split_to(end);
+                self.reset();
+                self.partial = !src.is_empty();
+                return match reply::<P>(&data) {
+                    Ok((b"", reply)) => Ok(Some(reply)),
+                    _ => Err(ReplyCodecError::Syntax),
+                };
+            }
+        }
+
+        if src.len() - self.scanned > self.max_line {
+            return Err(ReplyCodecError::LineTooLong);
+        }
+        self.partial = !src.is_empty();
+        Ok(None)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Reply>, ReplyCodecError> {
+        match self.decode(src)? {
+            Some(reply) => Ok(Some(reply)),
+            None if src.is_empty() => Ok(None),
+            None => Err(ReplyCodecError::Truncated),
+        }
+    }
+}
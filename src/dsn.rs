@@ -0,0 +1,446 @@
+//! [Delivery status notification] reports
+//!
+//! Parses the `message/delivery-status` part of a bounce and matches
+//! its per-recipient fields back to the recipients of the original
+//! mail transaction, so that a failure can be attributed to the
+//! address it was sent to even after forwarding or aliasing.
+//!
+//! Addresses of the `utf-8` type may contain the `"\x{HEX}"` escapes
+//! of [RFC 6533].
+//!
+//! [Delivery status notification]: https://tools.ietf.org/html/rfc3464
+//! [RFC 6533]: https://tools.ietf.org/html/rfc6533
+
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use crate::behaviour::Intl;
+use crate::headersection::{header_section, HeaderField};
+use crate::rfc3461::xtext;
+use crate::rfc3463::{enhanced_status_code, EnhancedStatusCode};
+use crate::rfc5321::ForwardPath;
+use crate::rfc5322::unstructured;
+use crate::types::Mailbox;
+use crate::util::normalize_crlf;
+
+/// Error returned by [`DeliveryStatus::to_bytes`] and
+/// [`DispositionNotification::to_bytes`].
+///
+/// [`DispositionNotification::to_bytes`]: crate::mdn::DispositionNotification::to_bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DsnError {
+    /// The report has no recipients.
+    NoRecipients,
+    /// A required field is missing.
+    MissingField(&'static str),
+    /// A field value contains a line break or another control
+    /// character.
+    InvalidValue(&'static str),
+}
+
+impl Display for DsnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DsnError::NoRecipients => write!(f, "no recipient reports"),
+            DsnError::MissingField(name) => write!(f, "missing {} field", name),
+            DsnError::InvalidValue(name) => write!(f, "invalid {} value", name),
+        }
+    }
+}
+
+impl std::error::Error for DsnError {}
+
+/// Value of the `"Action:"` field of a recipient report.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Action {
+    /// `"failed"`
+    Failed,
+    /// `"delayed"`
+    Delayed,
+    /// `"delivered"`
+    Delivered,
+    /// `"relayed"`
+    Relayed,
+    /// `"expanded"`
+    Expanded,
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            Action::Failed => "failed",
+            Action::Delayed => "delayed",
+            Action::Delivered => "delivered",
+            Action::Relayed => "relayed",
+            Action::Expanded => "expanded",
+        })
+    }
+}
+
+/// The per-recipient fields of a delivery status report.
+///
+/// Typed fields are split into their lowercased type and their value
+/// as written, without decoding.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RecipientReport {
+    /// The `"Original-Recipient:"` field, copied from the ORCPT
+    /// parameter.
+    pub original_recipient: Option<(String, String)>,
+    /// The `"Final-Recipient:"` field.
+    pub final_recipient: Option<(String, String)>,
+    /// The `"Action:"` field, `None` if missing or unknown.
+    pub action: Option<Action>,
+    /// The `"Status:"` field, such as `"5.1.1"`.
+    pub status: Option<String>,
+    /// The `"Diagnostic-Code:"` field.
+    pub diagnostic_code: Option<(String, String)>,
+}
+
+impl RecipientReport {
+    /// The `"Status:"` field as an [`EnhancedStatusCode`], ignoring
+    /// any text following the code.
+    /// # Examples
+    /// ```
+    /// use rustyknife::dsn::RecipientReport;
+    ///
+    /// let report = RecipientReport { status: Some("5.1.1 (unknown user)".into()), ..RecipientReport::default() };
+    /// assert_eq!(report.status_code().unwrap().to_string(), "5.1.1");
+    /// ```
+    pub fn status_code(&self) -> Option<EnhancedStatusCode> {
+        let (_, code) = enhanced_status_code(self.status.as_deref()?.trim_start().as_bytes()).ok()?;
+        Some(code)
+    }
+}
+
+/// A parsed `message/delivery-status` body.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeliveryStatus {
+    /// The `"Original-Envelope-Id:"` field.
+    pub envelope_id: Option<String>,
+    /// The `"Reporting-MTA:"` field.
+    pub reporting_mta: Option<(String, String)>,
+    /// The per-recipient reports in order of appearance.
+    pub recipients: Vec<RecipientReport>,
+}
+
+impl DeliveryStatus {
+    /// Serialize the per-message fields followed by a block of fields
+    /// for each recipient.
+    ///
+    /// Fails when `"Reporting-MTA:"` is missing, when there are no
+    /// recipients, when a recipient report lacks `"Final-Recipient:"`,
+    /// `"Action:"` or `"Status:"`, or when a value contains a control
+    /// character. See [`MessageBuilder::delivery_status`] to build the
+    /// complete report message.
+    ///
+    /// [`MessageBuilder::delivery_status`]: crate::builder::MessageBuilder::delivery_status
+    /// # Examples
+    /// ```
+    /// use rustyknife::dsn::{Action, DeliveryStatus, DsnError, RecipientReport};
+    ///
+    /// let status = DeliveryStatus {
+    ///     reporting_mta: Some(("dns".into(), "mx.example.org".into())),
+    ///     recipients: vec![RecipientReport {
+    ///         final_recipient: Some(("rfc822".into(), "bob@example.org".into())),
+    ///         action: Some(Action::Delayed),
+    ///         status: Some("4.4.1".into()),
+    ///         ..RecipientReport::default()
+    ///     }],
+    ///     ..DeliveryStatus::default()
+    /// };
+    ///
+    /// assert_eq!(status.to_bytes().unwrap(), b"Reporting-MTA: dns; mx.example.org\r\n\
+    ///                                          \r\n\
+    ///                                          Final-Recipient: rfc822; bob@example.org\r\n\
+    ///                                          Action: delayed\r\n\
+    ///                                          Status: 4.4.1\r\n");
+    ///
+    /// let incomplete = DeliveryStatus { reporting_mta: None, ..status };
+    /// assert_eq!(incomplete.to_bytes(), Err(DsnError::MissingField("Reporting-MTA")));
+    /// ```
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DsnError> {
+        let mut out = String::new();
+
+        if let Some(id) = &self.envelope_id {
+            field(&mut out, "Original-Envelope-Id", id)?;
+        }
+        let mta = self.reporting_mta.as_ref().ok_or(DsnError::MissingField("Reporting-MTA"))?;
+        typed_field(&mut out, "Reporting-MTA", mta)?;
+        if self.recipients.is_empty() {
+            return Err(DsnError::NoRecipients);
+        }
+
+        for rcpt in &self.recipients {
+            out.push_str("\r\n");
+            if let Some(original) = &rcpt.original_recipient {
+                typed_field(&mut out, "Original-Recipient", original)?;
+            }
+            let recipient = rcpt.final_recipient.as_ref().ok_or(DsnError::MissingField("Final-Recipient"))?;
+            typed_field(&mut out, "Final-Recipient", recipient)?;
+            let action = rcpt.action.ok_or(DsnError::MissingField("Action"))?;
+            field(&mut out, "Action", &action.to_string())?;
+            field(&mut out, "Status", rcpt.status.as_deref().ok_or(DsnError::MissingField("Status"))?)?;
+            if let Some(code) = &rcpt.diagnostic_code {
+                typed_field(&mut out, "Diagnostic-Code", code)?;
+            }
+        }
+
+        Ok(out.into_bytes())
+    }
+}
+
+pub(crate) fn field(out: &mut String, name: &'static str, value: &str) -> Result<(), DsnError> {
+    if value.chars().any(char::is_control) {
+        return Err(DsnError::InvalidValue(name));
+    }
+    out.push_str(&format!("{}: {}\r\n", name, value));
+    Ok(())
+}
+
+pub(crate) fn typed_field(out: &mut String, name: &'static str, (kind, value): &(String, String)) -> Result<(), DsnError> {
+    if kind.contains(';') {
+        return Err(DsnError::InvalidValue(name));
+    }
+    field(out, name, &format!("{}; {}", kind, value))
+}
+
+fn text(value: &[u8]) -> String {
+    unstructured::<Intl>(value).map(|(_, s)| s).unwrap_or_else(|_| String::from_utf8_lossy(value).into()).trim().into()
+}
+
+fn typed(value: &[u8]) -> Option<(String, String)> {
+    let value = text(value);
+    let (kind, rest) = value.split_once(';')?;
+
+    Some((kind.trim().to_lowercase(), rest.trim().into()))
+}
+
+// First word of the value, before any comment.
+fn word(value: &[u8]) -> String {
+    text(value).split(|c: char| c.is_whitespace() || c == '(').next().unwrap_or_default().into()
+}
+
+fn recipient_report(block: &[HeaderField]) -> RecipientReport {
+    let get = |name: &str| block.iter().filter_map(|h| h.as_ref().ok())
+        .find(|(n, _)| n.eq_ignore_ascii_case(name.as_bytes()))
+        .map(|(_, v)| *v);
+
+    let action = get("Action").map(word).and_then(|a| match a.to_lowercase().as_str() {
+        "failed" => Some(Action::Failed),
+        "delayed" => Some(Action::Delayed),
+        "delivered" => Some(Action::Delivered),
+        "relayed" => Some(Action::Relayed),
+        "expanded" => Some(Action::Expanded),
+        _ => None,
+    });
+
+    RecipientReport {
+        original_recipient: get("Original-Recipient").and_then(typed),
+        final_recipient: get("Final-Recipient").and_then(typed),
+        action,
+        status: get("Status").map(word).filter(|s| !s.is_empty()),
+        diagnostic_code: get("Diagnostic-Code").and_then(typed),
+    }
+}
+
+/// Parse a `message/delivery-status` body.
+///
+/// Parsing is lenient: bare LF line endings are accepted and a report
+/// missing its per-message fields is not an error.
+/// # Examples
+/// ```
+/// use rustyknife::dsn::{delivery_status, Action};
+///
+/// let report = delivery_status(b"Reporting-MTA: dns; mx.example.net\r\n\
+///                                \r\n\
+///                                Final-Recipient: rfc822; bob@example.net\r\n\
+///                                Original-Recipient: rfc822;bob@example.org\r\n\
+///                                Action: failed\r\n\
+///                                Status: 5.1.1\r\n\
+///                                Diagnostic-Code: smtp; 550 5.1.1 No such user\r\n");
+///
+/// assert_eq!(report.reporting_mta, Some(("dns".into(), "mx.example.net".into())));
+/// let rcpt = &report.recipients[0];
+/// assert_eq!(rcpt.action, Some(Action::Failed));
+/// assert_eq!(rcpt.status.as_deref(), Some("5.1.1"));
+/// assert_eq!(rcpt.original_recipient, Some(("rfc822".into(), "bob@example.org".into())));
+/// ```
+pub fn delivery_status(input: &[u8]) -> DeliveryStatus {
+    // The header parser needs a blank line to end the last block.
+    let mut input = normalize_crlf(input);
+    input.extend_from_slice(b"\r\n\r\n");
+
+    let mut blocks = Vec::new();
+    let mut rem = &input[..];
+    while let Ok((r, block)) = header_section(rem) {
+        if r.len() == rem.len() {
+            break;
+        }
+        if !block.is_empty() {
+            blocks.push(block);
+        }
+        rem = r;
+    }
+
+    let is_recipient = |block: &[HeaderField]| block.iter().filter_map(|h| h.as_ref().ok())
+        .any(|(n, _)| n.eq_ignore_ascii_case(b"Final-Recipient") || n.eq_ignore_ascii_case(b"Original-Recipient"));
+
+    let mut out = DeliveryStatus::default();
+    let mut blocks = blocks.iter().peekable();
+
+    if let Some(first) = blocks.next_if(|b| !is_recipient(b)) {
+        let get = |name: &str| first.iter().filter_map(|h| h.as_ref().ok())
+            .find(|(n, _)| n.eq_ignore_ascii_case(name.as_bytes()))
+            .map(|(_, v)| *v);
+
+        out.envelope_id = get("Original-Envelope-Id").map(text).filter(|s| !s.is_empty());
+        out.reporting_mta = get("Reporting-MTA").and_then(typed);
+    }
+    out.recipients = blocks.filter(|b| is_recipient(b)).map(|b| recipient_report(b)).collect();
+
+    out
+}
+
+/// Decode the `"\x{HEX}"` escapes of an RFC 6533 `utf-8` address.
+///
+/// Malformed escapes are kept as is.
+/// # Examples
+/// ```
+/// use rustyknife::dsn::unitext_decode;
+///
+/// assert_eq!(unitext_decode(r"ren\x{E9}@example.org"), "rené@example.org");
+/// assert_eq!(unitext_decode(r"a\x{ZZ}@example.org"), r"a\x{ZZ}@example.org");
+/// ```
+pub fn unitext_decode(input: &str) -> Cow<str> {
+    if !input.contains("\\x{") {
+        return Cow::Borrowed(input);
+    }
+
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(i) = rest.find("\\x{") {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 3..];
+        let decoded = after.find('}').and_then(|end| {
+            let hex = &after[..end];
+            Some(hex).filter(|h| (1..=6).contains(&h.len()) && h.bytes().all(|c| c.is_ascii_hexdigit()))
+                .and_then(|h| char::from_u32(u32::from_str_radix(h, 16).ok()?))
+                .map(|c| (c, end))
+        });
+
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push_str("\\x{");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+
+    Cow::Owned(out)
+}
+
+/// A recipient of the original mail transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TransactionRecipient {
+    /// The path of the `"RCPT TO:"` command.
+    pub path: ForwardPath,
+    /// The ORCPT parameter of the command, as in
+    /// [`RcptParams::orcpt`](crate::esmtp::RcptParams::orcpt).
+    pub orcpt: Option<(String, String)>,
+}
+
+fn address_key(address: &str) -> String {
+    match Mailbox::from_smtp(address.as_bytes()) {
+        Ok(mailbox) => mailbox.caseless_key(),
+        Err(_) => address.to_lowercase(),
+    }
+}
+
+fn path_key(path: &ForwardPath) -> String {
+    match path {
+        ForwardPath::Path(p) => p.0.caseless_key(),
+        ForwardPath::PostMaster(None) => "postmaster".into(),
+        ForwardPath::PostMaster(Some(domain)) => address_key(&format!("postmaster@{}", domain)),
+    }
+}
+
+// Comparison keys of a typed address. Report fields are also tried
+// xtext decoded since some MTAs copy the ORCPT value verbatim.
+fn typed_keys((kind, address): &(String, String), try_xtext: bool) -> Vec<String> {
+    let address = address.trim().trim_start_matches('<').trim_end_matches('>');
+    let mut forms = vec![address.to_string()];
+
+    if try_xtext {
+        if let Ok((b"", decoded)) = xtext(address.as_bytes()) {
+            match String::from_utf8(decoded) {
+                Ok(decoded) if decoded != address => forms.push(decoded),
+                _ => (),
+            }
+        }
+    }
+
+    forms.iter().map(|f| match kind.eq_ignore_ascii_case("utf-8") {
+        true => address_key(&unitext_decode(f)),
+        false => address_key(f),
+    }).collect()
+}
+
+/// Match recipient reports to the recipients of the original
+/// transaction.
+///
+/// Returns the index in `recipients` of the recipient each report
+/// refers to, or `None`. The `"Original-Recipient:"` field is matched
+/// against the ORCPT parameters first, then against the `"RCPT TO:"`
+/// paths. Reports without a match are tried again with their
+/// `"Final-Recipient:"` field. Addresses are compared ignoring case.
+/// # Examples
+/// ```
+/// use rustyknife::dsn::{delivery_status, reconcile, TransactionRecipient};
+///
+/// let recipients = [
+///     TransactionRecipient { path: "<alice@example.net>".parse().unwrap(), orcpt: None },
+///     TransactionRecipient { path: "<list-1234@example.net>".parse().unwrap(),
+///                            orcpt: Some(("rfc822".into(), "Bob@example.org".into())) },
+/// ];
+/// let report = delivery_status(b"Reporting-MTA: dns; mx.example.net\n\
+///                                \n\
+///                                Final-Recipient: rfc822; bob@mailbox.example.net\n\
+///                                Original-Recipient: rfc822; bob@example.org\n\
+///                                Action: failed\n\
+///                                \n\
+///                                Final-Recipient: rfc822; ALICE@example.net\n\
+///                                Action: delayed\n");
+///
+/// assert_eq!(reconcile(&report.recipients, &recipients), [Some(1), Some(0)]);
+/// ```
+pub fn reconcile(reports: &[RecipientReport], recipients: &[TransactionRecipient]) -> Vec<Option<usize>> {
+    let paths: Vec<String> = recipients.iter().map(|r| path_key(&r.path)).collect();
+    let orcpts: Vec<Vec<String>> = recipients.iter()
+        .map(|r| r.orcpt.as_ref().map(|o| typed_keys(o, false)).unwrap_or_default())
+        .collect();
+
+    let by_orcpt = |keys: &[String]| orcpts.iter().position(|o| o.iter().any(|k| keys.contains(k)));
+    let by_path = |keys: &[String]| paths.iter().position(|p| keys.contains(p));
+
+    reports.iter().map(|report| {
+        let original = report.original_recipient.as_ref().map(|o| typed_keys(o, true)).unwrap_or_default();
+        let last = report.final_recipient.as_ref().map(|f| typed_keys(f, true)).unwrap_or_default();
+
+        by_orcpt(&original).or_else(|| by_path(&original))
+            .or_else(|| by_path(&last))
+            .or_else(|| by_orcpt(&last))
+    }).collect()
+}
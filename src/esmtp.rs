@@ -0,0 +1,1010 @@
+//! ESMTP parameter lists
+//!
+//! Wraps the parameters of a MAIL or RCPT command for keyword lookup
+//! and conversion into typed option blocks for the common extensions.
+//! [`MailCommand`] and [`RcptCommand`] build commands from typed
+//! parameters.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use nom::branch::alt;
+use nom::bytes::complete::{tag, tag_no_case, take_while_m_n};
+use nom::combinator::{map, map_res, opt, verify};
+use nom::sequence::{pair, preceded, tuple};
+
+use crate::options::DuplicatePolicy;
+use crate::reply::Reply;
+use crate::rfc3461::{dsn_mail_params, dsn_notify, orcpt_address, xtext, xtext_encode, DSNMailParams, DSNRet, Notify};
+use crate::rfc5321::{Command, ForwardPath, Keyword, Param, ReversePath, Value};
+use crate::types::Mailbox;
+use crate::util::*;
+
+/// Error returned when building or converting [`EsmtpParams`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParamError {
+    /// A keyword was repeated and duplicates are rejected.
+    Duplicate(Keyword),
+    /// A known parameter has an invalid or missing value.
+    Invalid(&'static str),
+}
+
+impl Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParamError::Duplicate(keyword) => write!(f, "duplicate parameter {}", keyword),
+            ParamError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl Error for ParamError {}
+
+/// The message body type from the BODY parameter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyType {
+    /// `7BIT`
+    SevenBit,
+    /// `8BITMIME` from RFC 6152
+    EightBitMime,
+    /// `BINARYMIME` from RFC 3030
+    BinaryMime,
+}
+
+impl BodyType {
+    /// Whether the body must be transferred with BDAT, as specified
+    /// by the CHUNKING extension.
+    pub fn requires_chunking(self) -> bool {
+        self == BodyType::BinaryMime
+    }
+}
+
+impl FromStr for BodyType {
+    type Err = ParamError;
+
+    /// Values are matched case insensitively.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_uppercase().as_str() {
+            "7BIT" => Ok(BodyType::SevenBit),
+            "8BITMIME" => Ok(BodyType::EightBitMime),
+            "BINARYMIME" => Ok(BodyType::BinaryMime),
+            _ => Err(ParamError::Invalid("Invalid BODY")),
+        }
+    }
+}
+
+impl Display for BodyType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            BodyType::SevenBit => "7BIT",
+            BodyType::EightBitMime => "8BITMIME",
+            BodyType::BinaryMime => "BINARYMIME",
+        })
+    }
+}
+
+/// The AUTH parameter from RFC 4954.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuthParam {
+    /// `AUTH=<>`, the submitter is unknown or not trusted.
+    Unknown,
+    /// The decoded address of the authenticated submitter.
+    Mailbox(Mailbox),
+}
+
+/// What to do when a message cannot be delivered in time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByMode {
+    /// `N`, notify the sender and continue delivery.
+    Notify,
+    /// `R`, return the message to the sender.
+    Return,
+}
+
+/// The BY parameter from the DELIVERBY extension, [RFC 2852].
+///
+/// [RFC 2852]: https://tools.ietf.org/html/rfc2852
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeliverBy {
+    /// Seconds left before the deadline, negative when it has passed.
+    pub deadline: i64,
+    /// The action taken when the deadline is missed.
+    pub mode: ByMode,
+    /// The `T` flag, requesting trace information in DSNs.
+    pub trace: bool,
+}
+
+/// Requested release time from the FUTURERELEASE extension, [RFC 4865].
+///
+/// [RFC 4865]: https://tools.ietf.org/html/rfc4865
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Hold {
+    /// `HOLDFOR`, the number of seconds to hold the message.
+    For(u64),
+    /// `HOLDUNTIL`, the release time as an RFC 3339 date-time.
+    Until(String),
+}
+
+/// Parameters of a MAIL FROM command.
+#[derive(Debug, PartialEq)]
+pub struct MailParams {
+    /// The BODY parameter.
+    pub body: Option<BodyType>,
+    /// The SIZE parameter, in octets.
+    pub size: Option<u64>,
+    /// The AUTH parameter.
+    pub auth: Option<AuthParam>,
+    /// The SMTPUTF8 parameter is present.
+    pub smtputf8: bool,
+    /// The REQUIRETLS parameter from RFC 8689 is present.
+    pub requiretls: bool,
+    /// The BY parameter.
+    pub by: Option<DeliverBy>,
+    /// The HOLDFOR or HOLDUNTIL parameter.
+    pub hold: Option<Hold>,
+    /// The MT-PRIORITY parameter from RFC 6710, between -9 and 9.
+    pub priority: Option<i8>,
+    /// The DSN parameters, RET and ENVID.
+    pub dsn: DSNMailParams,
+    /// Parameters not listed above.
+    pub other: Vec<Param>,
+}
+
+/// What to do when the RRVS check cannot be performed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RrvsAction {
+    /// `C`, continue delivery.
+    Continue,
+    /// `R`, reject the recipient.
+    Reject,
+}
+
+/// The RRVS parameter from [RFC 7293].
+///
+/// [RFC 7293]: https://tools.ietf.org/html/rfc7293#section-3.1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rrvs {
+    /// The time since which the sender knows the mailbox to be owned
+    /// by the same user, as an RFC 3339 date-time.
+    pub time: String,
+    /// The action requested when the check is not supported.
+    pub action: Option<RrvsAction>,
+}
+
+/// Parameters of a RCPT TO command.
+#[derive(Debug, PartialEq)]
+pub struct RcptParams {
+    /// The DSN NOTIFY parameter.
+    pub notify: Option<Notify>,
+    /// The DSN ORCPT parameter, as the address type and the decoded
+    /// address.
+    pub orcpt: Option<(String, String)>,
+    /// The RRVS parameter.
+    pub rrvs: Option<Rrvs>,
+    /// Parameters not listed above.
+    pub other: Vec<Param>,
+}
+
+/// Parse the value of the RRVS parameter that may be present on a
+/// RCPT TO command.
+/// # Examples
+/// ```
+/// use rustyknife::esmtp::{rrvs, Rrvs, RrvsAction};
+///
+/// assert_eq!(rrvs(b"2014-04-03T23:01:00Z;C").unwrap().1,
+///            Rrvs { time: "2014-04-03T23:01:00Z".into(), action: Some(RrvsAction::Continue) });
+/// assert_eq!(rrvs(b"2014-04-03T23:01:00+02:00").unwrap().1.action, None);
+/// ```
+pub fn rrvs(input: &[u8]) -> NomResult<Rrvs> {
+    map(tuple((rfc3339_date_time, opt(preceded(tag(";"), alt((
+        map(tag_no_case("C"), |_| RrvsAction::Continue),
+        map(tag_no_case("R"), |_| RrvsAction::Reject),
+    )))))),
+        |(time, action)| Rrvs { time: String::from_utf8(time.to_vec()).unwrap(), action })(input)
+}
+
+/// Parse the value of the BY parameter that may be present on a MAIL
+/// FROM command.
+///
+/// A deadline that is not positive is only valid in notify mode.
+/// # Examples
+/// ```
+/// use rustyknife::esmtp::{deliver_by, ByMode, DeliverBy};
+///
+/// assert_eq!(deliver_by(b"120;RT").unwrap().1, DeliverBy { deadline: 120, mode: ByMode::Return, trace: true });
+/// assert_eq!(deliver_by(b"-60;N").unwrap().1, DeliverBy { deadline: -60, mode: ByMode::Notify, trace: false });
+/// assert!(deliver_by(b"0;R").is_err());
+/// ```
+pub fn deliver_by(input: &[u8]) -> NomResult<DeliverBy> {
+    verify(map(tuple((
+        signed_seconds(9),
+        tag(";"),
+        alt((map(tag_no_case("N"), |_| ByMode::Notify), map(tag_no_case("R"), |_| ByMode::Return))),
+        opt(tag_no_case("T")),
+    )), |(deadline, _, mode, trace)| DeliverBy { deadline, mode, trace: trace.is_some() }),
+           |by| by.mode == ByMode::Notify || by.deadline > 0)(input)
+}
+
+/// Parse the value of the MT-PRIORITY parameter that may be present on
+/// a MAIL FROM command, as specified in [RFC 6710 section 3].
+///
+/// [RFC 6710 section 3]: https://tools.ietf.org/html/rfc6710#section-3
+/// # Examples
+/// ```
+/// use rustyknife::esmtp::mt_priority;
+///
+/// assert_eq!(mt_priority(b"-9").unwrap().1, -9);
+/// assert_eq!(mt_priority(b"+4").unwrap().1, 4);
+/// assert_eq!(mt_priority(b"10").unwrap(), (&b"0"[..], 1));
+/// ```
+pub fn mt_priority(input: &[u8]) -> NomResult<i8> {
+    map(pair(opt(alt((tag("-"), tag("+")))), take_while_m_n(1, 1, |c: u8| c.is_ascii_digit())),
+        |(sign, digit): (_, &[u8])| {
+            let n = (digit[0] - b'0') as i8;
+            if sign == Some(&b"-"[..]) { -n } else { n }
+        })(input)
+}
+
+/// Parse the value of the AUTH parameter that may be present on a
+/// MAIL FROM command, as specified in [RFC 4954 section 5].
+///
+/// Returns the decoded mailbox or `None` for `"<>"`, meaning that the
+/// submitter is unknown.
+/// # Examples
+/// ```
+/// use rustyknife::esmtp::auth_mailbox;
+///
+/// let (_, mailbox) = auth_mailbox(b"bob+2Bx@example.org").unwrap();
+/// assert_eq!(mailbox.unwrap().to_string(), "bob+x@example.org");
+///
+/// assert_eq!(auth_mailbox(b"<>").unwrap().1, None);
+/// ```
+///
+/// [RFC 4954 section 5]: https://tools.ietf.org/html/rfc4954#section-5
+pub fn auth_mailbox(input: &[u8]) -> NomResult<Option<Mailbox>> {
+    alt((map(tag("<>"), |_| None),
+         map_res(xtext, |decoded| Mailbox::from_smtp(&decoded).map(Some))))(input)
+}
+
+/// ESMTP parameter list with unique keywords.
+///
+/// Keywords are compared case insensitively.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::esmtp::{BodyType, EsmtpParams};
+/// use rustyknife::options::DuplicatePolicy;
+/// use rustyknife::rfc5321::mail_command;
+///
+/// let (_, (_, params)) = mail_command::<Intl>(b"MAIL FROM:<bob@example.org> body=8BITMIME SIZE=1024 X-TAG=1\r\n").unwrap();
+/// let params = EsmtpParams::new(params, DuplicatePolicy::Reject).unwrap();
+///
+/// assert!(params.contains("BODY"));
+/// assert_eq!(params.value("x-tag"), Some(Some("1")));
+///
+/// let mail = params.mail_params().unwrap();
+/// assert_eq!(mail.body, Some(BodyType::EightBitMime));
+/// assert_eq!(mail.size, Some(1024));
+/// assert_eq!(mail.other.len(), 1);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EsmtpParams(Vec<Param>);
+
+/// Rejects repeated keywords, which RFC 5321 forbids.
+/// # Examples
+/// ```
+/// use std::convert::TryFrom;
+/// use rustyknife::esmtp::{EsmtpParams, ParamError};
+/// use rustyknife::rfc5321::Param;
+///
+/// let params = vec![Param::new("BODY", Some("8BITMIME")).unwrap(), Param::new("body", Some("7BIT")).unwrap()];
+///
+/// match EsmtpParams::try_from(params) {
+///     Err(ParamError::Duplicate(keyword)) => assert_eq!(keyword.to_string(), "body"),
+///     _ => unreachable!(),
+/// }
+/// ```
+impl TryFrom<Vec<Param>> for EsmtpParams {
+    type Error = ParamError;
+
+    fn try_from(params: Vec<Param>) -> Result<Self, ParamError> {
+        EsmtpParams::new(params, DuplicatePolicy::Reject)
+    }
+}
+
+impl EsmtpParams {
+    /// Build a parameter list, handling repeated keywords according
+    /// to `policy`.
+    pub fn new(params: Vec<Param>, policy: DuplicatePolicy) -> Result<Self, ParamError> {
+        let mut out: Vec<Param> = Vec::with_capacity(params.len());
+
+        for param in params {
+            match out.iter().position(|p| p.0 == param.0) {
+                None => out.push(param),
+                Some(i) => match policy {
+                    DuplicatePolicy::KeepFirst => (),
+                    DuplicatePolicy::KeepLast => out[i] = param,
+                    DuplicatePolicy::Reject => return Err(ParamError::Duplicate(param.0)),
+                },
+            }
+        }
+
+        Ok(EsmtpParams(out))
+    }
+
+    /// Get the parameter with `keyword`.
+    pub fn get(&self, keyword: &str) -> Option<&Param> {
+        self.0.iter().find(|p| p.0 == keyword)
+    }
+
+    /// Whether a parameter with `keyword` is present.
+    pub fn contains(&self, keyword: &str) -> bool {
+        self.get(keyword).is_some()
+    }
+
+    /// Get the value of the parameter with `keyword`.
+    ///
+    /// Returns `Some(None)` for a parameter without a value.
+    pub fn value(&self, keyword: &str) -> Option<Option<&str>> {
+        self.get(keyword).map(|p| p.1.as_deref())
+    }
+
+    /// Iterate over the parameters in their original order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Param> {
+        self.0.iter()
+    }
+
+    /// Return the underlying parameters.
+    pub fn into_inner(self) -> Vec<Param> {
+        self.0
+    }
+
+    /// Return the parameters as a map from keyword to value.
+    ///
+    /// Lookups in the map are case insensitive, see [`Keyword`].
+    /// # Examples
+    /// ```
+    /// use std::convert::TryFrom;
+    /// use rustyknife::behaviour::Intl;
+    /// use rustyknife::esmtp::EsmtpParams;
+    /// use rustyknife::rfc5321::{rcpt_command, Keyword};
+    ///
+    /// let (_, (_, params)) = rcpt_command::<Intl>(b"RCPT TO:<bob@example.org> notify=NEVER X-TAG\r\n").unwrap();
+    /// let map = EsmtpParams::try_from(params).unwrap().into_map();
+    ///
+    /// assert_eq!(map[&"NOTIFY".parse::<Keyword>().unwrap()].as_deref(), Some("NEVER"));
+    /// assert_eq!(map[&"x-tag".parse::<Keyword>().unwrap()], None);
+    /// ```
+    pub fn into_map(self) -> HashMap<Keyword, Option<Value>> {
+        self.0.into_iter().map(|Param(keyword, value)| (keyword, value)).collect()
+    }
+
+    /// Interpret the parameters of a MAIL FROM command.
+    pub fn mail_params(&self) -> Result<MailParams, ParamError> {
+        let mut body = None;
+        let mut size = None;
+        let mut auth = None;
+        let mut smtputf8 = false;
+        let mut requiretls = false;
+        let mut by = None;
+        let mut hold = None;
+        let mut priority = None;
+        let mut rest = Vec::new();
+
+        for param in &self.0 {
+            let is = |keyword: Keyword| param.0 == keyword;
+            match param.1.as_deref() {
+                Some(value) if is(Keyword::BODY) => body = Some(value.parse()?),
+                None if is(Keyword::BODY) => return Err(ParamError::Invalid("BODY without value")),
+                Some(value) if is(Keyword::SIZE) => match exact!(value.as_bytes(), bounded_u64(20, u64::MAX)) {
+                    Ok((_, s)) => size = Some(s),
+                    _ => return Err(ParamError::Invalid("Invalid SIZE")),
+                },
+                None if is(Keyword::SIZE) => return Err(ParamError::Invalid("SIZE without value")),
+                Some(value) if is(Keyword::HOLDFOR) => match exact!(value.as_bytes(), bounded_u64(9, 999_999_999)) {
+                    Ok((_, h)) => hold = Some(Hold::For(h)),
+                    _ => return Err(ParamError::Invalid("Invalid HOLDFOR")),
+                },
+                Some(value) if is(Keyword::HOLDUNTIL) => match exact!(value.as_bytes(), rfc3339_date_time) {
+                    Ok((_, h)) => hold = Some(Hold::Until(String::from_utf8(h.to_vec()).unwrap())),
+                    _ => return Err(ParamError::Invalid("Invalid HOLDUNTIL")),
+                },
+                None if is(Keyword::HOLDFOR) || is(Keyword::HOLDUNTIL) => return Err(ParamError::Invalid("HOLDFOR or HOLDUNTIL without value")),
+                Some(value) if is(Keyword::MT_PRIORITY) => match exact!(value.as_bytes(), mt_priority) {
+                    Ok((_, p)) => priority = Some(p),
+                    _ => return Err(ParamError::Invalid("Invalid MT-PRIORITY")),
+                },
+                None if is(Keyword::MT_PRIORITY) => return Err(ParamError::Invalid("MT-PRIORITY without value")),
+                Some(value) if is(Keyword::AUTH) => match exact!(value.as_bytes(), auth_mailbox) {
+                    Ok((_, mailbox)) => auth = Some(mailbox.map_or(AuthParam::Unknown, AuthParam::Mailbox)),
+                    _ => return Err(ParamError::Invalid("Invalid AUTH")),
+                },
+                None if is(Keyword::AUTH) => return Err(ParamError::Invalid("AUTH without value")),
+                None if is(Keyword::SMTPUTF8) => smtputf8 = true,
+                Some(_) if is(Keyword::SMTPUTF8) => return Err(ParamError::Invalid("SMTPUTF8 with value")),
+                Some(value) if is(Keyword::BY) => match exact!(value.as_bytes(), deliver_by) {
+                    Ok((_, b)) => by = Some(b),
+                    _ => return Err(ParamError::Invalid("Invalid BY")),
+                },
+                None if is(Keyword::BY) => return Err(ParamError::Invalid("BY without value")),
+                None if is(Keyword::REQUIRETLS) => requiretls = true,
+                Some(_) if is(Keyword::REQUIRETLS) => return Err(ParamError::Invalid("REQUIRETLS with value")),
+                _ => rest.push(param),
+            }
+        }
+
+        if self.contains(&Keyword::HOLDFOR) && self.contains(&Keyword::HOLDUNTIL) {
+            return Err(ParamError::Invalid("HOLDFOR and HOLDUNTIL are exclusive"));
+        }
+
+        let pairs: Vec<_> = rest.iter().map(|p| (&*p.0, p.1.as_deref())).collect();
+        let (dsn, other) = dsn_mail_params(&pairs).map_err(ParamError::Invalid)?;
+        let other = rest.into_iter().filter(|p| other.iter().any(|(k, _)| *k == &*p.0)).cloned().collect();
+
+        Ok(MailParams { body, size, auth, smtputf8, requiretls, by, hold, priority, dsn, other })
+    }
+
+    /// Interpret the parameters of a RCPT TO command.
+    pub fn rcpt_params(&self) -> Result<RcptParams, ParamError> {
+        let mut notify = None;
+        let mut orcpt = None;
+        let mut rrvs = None;
+        let mut other = Vec::new();
+
+        for param in &self.0 {
+            let is = |keyword: Keyword| param.0 == keyword;
+            match param.1.as_deref() {
+                Some(value) if is(Keyword::NOTIFY) => match dsn_notify(value) {
+                    Ok(("", n)) => notify = Some(n),
+                    _ => return Err(ParamError::Invalid("Invalid NOTIFY")),
+                },
+                Some(value) if is(Keyword::ORCPT) => match orcpt_address(value.as_bytes()) {
+                    Ok((b"", (kind, addr))) => orcpt = Some((kind.into_owned(), addr.into_owned())),
+                    _ => return Err(ParamError::Invalid("Invalid ORCPT")),
+                },
+                Some(value) if is(Keyword::RRVS) => match exact!(value.as_bytes(), self::rrvs) {
+                    Ok((_, r)) => rrvs = Some(r),
+                    _ => return Err(ParamError::Invalid("Invalid RRVS")),
+                },
+                None if is(Keyword::NOTIFY) => return Err(ParamError::Invalid("NOTIFY without value")),
+                None if is(Keyword::ORCPT) => return Err(ParamError::Invalid("ORCPT without value")),
+                None if is(Keyword::RRVS) => return Err(ParamError::Invalid("RRVS without value")),
+                _ => other.push(param.clone()),
+            }
+        }
+
+        Ok(RcptParams { notify, orcpt, rrvs, other })
+    }
+}
+
+impl MailParams {
+    /// Whether the declared SIZE is over `limit`, the maximum message
+    /// size advertised by a server.
+    ///
+    /// A limit of zero means that no fixed limit is enforced, as in
+    /// the SIZE capability. Messages without a declared size are not
+    /// over the limit.
+    /// # Examples
+    /// ```
+    /// use rustyknife::behaviour::Intl;
+    /// use rustyknife::esmtp::mail_params;
+    /// use rustyknife::rfc5321::mail_command;
+    ///
+    /// let (_, (_, params)) = mail_command::<Intl>(b"MAIL FROM:<> SIZE=2048\r\n").unwrap();
+    /// let params = mail_params(params).unwrap();
+    ///
+    /// assert!(params.exceeds_size_limit(1024));
+    /// assert!(!params.exceeds_size_limit(2048));
+    /// assert!(!params.exceeds_size_limit(0));
+    /// ```
+    pub fn exceeds_size_limit(&self, limit: u64) -> bool {
+        matches!(self.size, Some(size) if limit != 0 && size > limit)
+    }
+
+    /// Check that the declared body type can be transferred.
+    ///
+    /// `chunking` tells whether CHUNKING is in use for the transaction,
+    /// since a BINARYMIME body cannot be sent with DATA.
+    /// # Examples
+    /// ```
+    /// use rustyknife::behaviour::Intl;
+    /// use rustyknife::esmtp::mail_params;
+    /// use rustyknife::rfc5321::mail_command;
+    ///
+    /// let (_, (_, params)) = mail_command::<Intl>(b"MAIL FROM:<> BODY=BINARYMIME\r\n").unwrap();
+    /// let params = mail_params(params).unwrap();
+    ///
+    /// assert!(params.check_body_type(true).is_ok());
+    /// assert!(params.check_body_type(false).is_err());
+    /// ```
+    pub fn check_body_type(&self, chunking: bool) -> Result<(), ParamError> {
+        match self.body {
+            Some(body) if body.requires_chunking() && !chunking => Err(ParamError::Invalid("BINARYMIME requires CHUNKING")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that REQUIRETLS is only used over a protected session.
+    ///
+    /// `tls` tells whether the session is protected by TLS. As
+    /// specified in [RFC 8689], a server must reject REQUIRETLS
+    /// otherwise.
+    /// # Examples
+    /// ```
+    /// use rustyknife::behaviour::Intl;
+    /// use rustyknife::esmtp::mail_params;
+    /// use rustyknife::rfc5321::mail_command;
+    ///
+    /// let (_, (_, params)) = mail_command::<Intl>(b"MAIL FROM:<> REQUIRETLS\r\n").unwrap();
+    /// let params = mail_params(params).unwrap();
+    ///
+    /// assert!(params.check_requiretls(true).is_ok());
+    /// assert!(params.check_requiretls(false).is_err());
+    /// ```
+    ///
+    /// [RFC 8689]: https://tools.ietf.org/html/rfc8689#section-4.1
+    pub fn check_requiretls(&self, tls: bool) -> Result<(), ParamError> {
+        if self.requiretls && !tls {
+            Err(ParamError::Invalid("REQUIRETLS requires TLS"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Parse the SIZE keyword of an EHLO reply line, such as `"SIZE
+/// 10240000"`.
+///
+/// Returns the maximum message size, `None` when the server does
+/// not announce a fixed limit.
+/// # Examples
+/// ```
+/// use rustyknife::esmtp::size_capability;
+///
+/// assert_eq!(size_capability(b"SIZE 10240000").unwrap().1, Some(10240000));
+/// assert_eq!(size_capability(b"SIZE").unwrap().1, None);
+/// assert_eq!(size_capability(b"size 0").unwrap().1, None);
+/// ```
+pub fn size_capability(input: &[u8]) -> NomResult<Option<u64>> {
+    map(preceded(tag_no_case("SIZE"), opt(preceded(tag(" "), bounded_u64(20, u64::MAX)))),
+        |size| size.filter(|s| *s != 0))(input)
+}
+
+/// Extensions announced in an EHLO reply.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The domain on the first line of the reply.
+    pub domain: String,
+    /// The SIZE limit, `Some(0)` when announced without a fixed limit.
+    pub size: Option<u64>,
+    /// The SASL mechanisms announced by AUTH, uppercased.
+    pub auth: Vec<String>,
+    /// PIPELINING
+    pub pipelining: bool,
+    /// 8BITMIME
+    pub eightbitmime: bool,
+    /// SMTPUTF8
+    pub smtputf8: bool,
+    /// DSN
+    pub dsn: bool,
+    /// STARTTLS
+    pub starttls: bool,
+    /// Other extensions with their parameters, as written.
+    pub other: Vec<(String, Vec<String>)>,
+    /// Lines of known extensions with invalid parameters, as written.
+    pub malformed: Vec<String>,
+}
+
+fn is_ehlo_keyword(keyword: &str) -> bool {
+    keyword.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && keyword.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-')
+}
+
+impl Capabilities {
+    /// Interpret a successful EHLO reply.
+    ///
+    /// Returns `None` if the reply code is not 250. Lines that are not
+    /// a valid EHLO keyword are skipped. The obsolete `"AUTH=PLAIN"`
+    /// form is accepted. A SIZE line with an invalid value is kept in
+    /// [`malformed`](Self::malformed) and leaves the size unset.
+    /// # Examples
+    /// ```
+    /// use rustyknife::behaviour::Legacy;
+    /// use rustyknife::esmtp::Capabilities;
+    /// use rustyknife::reply::reply;
+    ///
+    /// let (_, ehlo) = reply::<Legacy>(b"250-mx.example.org Hello\r\n250-SIZE 10240000\r\n250-AUTH PLAIN login\r\n\
+    ///                                   250-PIPELINING\r\n250 X-CUSTOM a b\r\n").unwrap();
+    /// let caps = Capabilities::from_reply(&ehlo).unwrap();
+    ///
+    /// assert_eq!(caps.domain, "mx.example.org");
+    /// assert_eq!(caps.size, Some(10240000));
+    /// assert_eq!(caps.auth, ["PLAIN", "LOGIN"]);
+    /// assert!(caps.pipelining && !caps.starttls);
+    /// assert_eq!(caps.param("x-custom"), Some(&["a".to_string(), "b".to_string()][..]));
+    /// ```
+    pub fn from_reply(reply: &Reply) -> Option<Self> {
+        if reply.code != 250 {
+            return None;
+        }
+
+        let mut lines = reply.lines.iter();
+        let mut caps = Capabilities {
+            domain: lines.next()?.split(' ').next().unwrap_or_default().into(),
+            ..Capabilities::default()
+        };
+
+        for line in lines {
+            let mut words = line.split_whitespace();
+            let keyword = match words.next() {
+                Some(k) if k.len() > 5 && k.as_bytes()[..5].eq_ignore_ascii_case(b"AUTH=") => {
+                    caps.add_auth(Some(&k[5..]).into_iter().chain(words));
+                    continue;
+                }
+                Some(k) if is_ehlo_keyword(k) => k,
+                _ => continue,
+            };
+
+            match keyword {
+                _ if Keyword::SIZE == keyword => match exact!(line.as_bytes(), size_capability) {
+                    Ok((_, size)) => caps.size = Some(size.unwrap_or(0)),
+                    Err(_) => caps.malformed.push(line.clone()),
+                },
+                _ if Keyword::AUTH == keyword => caps.add_auth(words),
+                _ if Keyword::PIPELINING == keyword => caps.pipelining = true,
+                _ if Keyword::EIGHTBITMIME == keyword => caps.eightbitmime = true,
+                _ if Keyword::SMTPUTF8 == keyword => caps.smtputf8 = true,
+                _ if Keyword::DSN == keyword => caps.dsn = true,
+                _ if Keyword::STARTTLS == keyword => caps.starttls = true,
+                _ => caps.other.push((keyword.into(), words.map(Into::into).collect())),
+            }
+        }
+
+        Some(caps)
+    }
+
+    fn add_auth<'a, I: Iterator<Item=&'a str>>(&mut self, mechanisms: I) {
+        for mechanism in mechanisms.map(str::to_ascii_uppercase) {
+            if !self.auth.contains(&mechanism) {
+                self.auth.push(mechanism);
+            }
+        }
+    }
+
+    /// The parameters of another extension, compared case
+    /// insensitively.
+    pub fn param(&self, keyword: &str) -> Option<&[String]> {
+        self.other.iter().find(|(k, _)| k.eq_ignore_ascii_case(keyword)).map(|(_, p)| p.as_slice())
+    }
+
+    /// Whether the extension `keyword` was announced, known or not.
+    pub fn has(&self, keyword: &str) -> bool {
+        match keyword {
+            _ if Keyword::SIZE == keyword => self.size.is_some(),
+            _ if Keyword::AUTH == keyword => !self.auth.is_empty(),
+            _ if Keyword::PIPELINING == keyword => self.pipelining,
+            _ if Keyword::EIGHTBITMIME == keyword => self.eightbitmime,
+            _ if Keyword::SMTPUTF8 == keyword => self.smtputf8,
+            _ if Keyword::DSN == keyword => self.dsn,
+            _ if Keyword::STARTTLS == keyword => self.starttls,
+            _ => self.param(keyword).is_some(),
+        }
+    }
+}
+
+/// Interpret the parameters returned by [`mail_command`], rejecting
+/// duplicates.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::esmtp::{mail_params, AuthParam};
+/// use rustyknife::rfc5321::mail_command;
+///
+/// let (_, (_, params)) = mail_command::<Intl>(b"MAIL FROM:<bob@example.org> AUTH=bob+2Bx@example.org REQUIRETLS\r\n").unwrap();
+/// let params = mail_params(params).unwrap();
+///
+/// assert_eq!(params.auth, Some(AuthParam::Mailbox("bob+x@example.org".parse().unwrap())));
+/// assert!(params.requiretls);
+/// ```
+///
+/// [`mail_command`]: crate::rfc5321::mail_command
+pub fn mail_params(params: Vec<Param>) -> Result<MailParams, ParamError> {
+    EsmtpParams::try_from(params)?.mail_params()
+}
+
+/// Interpret the parameters returned by [`rcpt_command`], rejecting
+/// duplicates.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::esmtp::{rcpt_params, RrvsAction};
+/// use rustyknife::rfc5321::rcpt_command;
+///
+/// let (_, (_, params)) = rcpt_command::<Intl>(b"RCPT TO:<bob@example.org> NOTIFY=NEVER RRVS=2014-04-03T23:01:00Z;C X-A=1\r\n").unwrap();
+/// let params = rcpt_params(params).unwrap();
+///
+/// assert!(!params.notify.unwrap().on_failure);
+/// assert_eq!(params.rrvs.as_ref().unwrap().time, "2014-04-03T23:01:00Z");
+/// assert_eq!(params.rrvs.unwrap().action, Some(RrvsAction::Continue));
+/// assert_eq!(params.other.len(), 1);
+/// ```
+///
+/// [`rcpt_command`]: crate::rfc5321::rcpt_command
+pub fn rcpt_params(params: Vec<Param>) -> Result<RcptParams, ParamError> {
+    EsmtpParams::try_from(params)?.rcpt_params()
+}
+
+impl AsRef<[Param]> for EsmtpParams {
+    fn as_ref(&self) -> &[Param] {
+        &self.0
+    }
+}
+
+impl<'a> IntoIterator for &'a EsmtpParams {
+    type Item = &'a Param;
+    type IntoIter = std::slice::Iter<'a, Param>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+// A parameter set through a builder, with its value not checked yet.
+type RawParam = (Keyword, Option<String>);
+
+fn value_param(keyword: Keyword, value: String) -> RawParam {
+    (keyword, Some(value))
+}
+
+impl From<Param> for RawParam {
+    fn from(param: Param) -> Self {
+        (param.0, param.1.map(String::from))
+    }
+}
+
+// Replace any parameter with the same keyword.
+fn set_param(params: &mut Vec<RawParam>, param: RawParam) {
+    params.retain(|p| p.0 != param.0);
+    params.push(param);
+}
+
+fn check_values(params: Vec<RawParam>) -> Result<Vec<Param>, ParamError> {
+    params.into_iter()
+        .map(|(keyword, value)| match value {
+            Some(value) => Value::try_from(value.as_bytes())
+                .map(|value| Param(keyword, Some(value)))
+                .map_err(|_| ParamError::Invalid("Invalid parameter value")),
+            None => Ok(Param(keyword, None)),
+        })
+        .collect()
+}
+
+/// A MAIL FROM command, built with [`MailCommand::builder`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MailCommand {
+    /// The reverse path.
+    pub path: ReversePath,
+    /// The parameters.
+    pub params: Vec<Param>,
+}
+
+/// Builder for [`MailCommand`].
+///
+/// Each setter replaces the previous value of its parameter.
+#[derive(Clone, Debug)]
+pub struct MailCommandBuilder {
+    path: ReversePath,
+    params: Vec<RawParam>,
+}
+
+impl MailCommand {
+    /// Start building a MAIL FROM command.
+    /// # Examples
+    /// ```
+    /// use rustyknife::esmtp::{BodyType, MailCommand};
+    /// use rustyknife::rfc3461::DSNRet;
+    ///
+    /// let cmd = MailCommand::builder("<bob@example.org>".parse().unwrap())
+    ///     .body(BodyType::EightBitMime)
+    ///     .size(1024)
+    ///     .ret(DSNRet::Hdrs)
+    ///     .envid("id=1")
+    ///     .build().unwrap();
+    ///
+    /// assert_eq!(cmd.to_wire(), b"MAIL FROM:<bob@example.org> BODY=8BITMIME SIZE=1024 RET=HDRS ENVID=id+3D1\r\n");
+    /// ```
+    pub fn builder(path: ReversePath) -> MailCommandBuilder {
+        MailCommandBuilder { path, params: Vec::new() }
+    }
+
+    /// Serialize the command as a CRLF terminated line.
+    pub fn to_wire(&self) -> Vec<u8> {
+        Command::from(self.clone()).to_wire()
+    }
+}
+
+impl From<MailCommand> for Command {
+    fn from(cmd: MailCommand) -> Self {
+        Command::MAIL(cmd.path, cmd.params)
+    }
+}
+
+impl MailCommandBuilder {
+    fn set(mut self, param: RawParam) -> Self {
+        set_param(&mut self.params, param);
+        self
+    }
+
+    fn flag(self, keyword: Keyword) -> Self {
+        self.set((keyword, None))
+    }
+
+    /// Set the BODY parameter.
+    pub fn body(self, body: BodyType) -> Self {
+        self.set(value_param(Keyword::BODY, body.to_string()))
+    }
+
+    /// Set the SIZE parameter.
+    pub fn size(self, size: u64) -> Self {
+        self.set(value_param(Keyword::SIZE, size.to_string()))
+    }
+
+    /// Set the AUTH parameter, `None` for an unknown submitter.
+    pub fn auth(self, mailbox: Option<&Mailbox>) -> Self {
+        let value = mailbox.map_or_else(|| "<>".into(), |m| xtext_encode(m.to_string().as_bytes()));
+        self.set(value_param(Keyword::AUTH, value))
+    }
+
+    /// Set the SMTPUTF8 parameter.
+    pub fn smtputf8(self) -> Self {
+        self.flag(Keyword::SMTPUTF8)
+    }
+
+    /// Set the REQUIRETLS parameter.
+    pub fn requiretls(self) -> Self {
+        self.flag(Keyword::REQUIRETLS)
+    }
+
+    /// Set the BY parameter.
+    pub fn by(self, by: DeliverBy) -> Self {
+        let mode = match by.mode { ByMode::Notify => "N", ByMode::Return => "R" };
+        self.set(value_param(Keyword::BY, format!("{};{}{}", by.deadline, mode, if by.trace { "T" } else { "" })))
+    }
+
+    /// Set the HOLDFOR or HOLDUNTIL parameter.
+    pub fn hold(mut self, hold: Hold) -> Self {
+        self.params.retain(|p| p.0 != Keyword::HOLDFOR && p.0 != Keyword::HOLDUNTIL);
+        match hold {
+            Hold::For(seconds) => self.set(value_param(Keyword::HOLDFOR, seconds.to_string())),
+            Hold::Until(time) => self.set(value_param(Keyword::HOLDUNTIL, time)),
+        }
+    }
+
+    /// Set the MT-PRIORITY parameter.
+    pub fn priority(self, priority: i8) -> Self {
+        self.set(value_param(Keyword::MT_PRIORITY, priority.to_string()))
+    }
+
+    /// Set the DSN RET parameter.
+    pub fn ret(self, ret: DSNRet) -> Self {
+        self.set(value_param(Keyword::RET, match ret { DSNRet::Full => "FULL", DSNRet::Hdrs => "HDRS" }.into()))
+    }
+
+    /// Set the DSN ENVID parameter, xtext encoding it.
+    pub fn envid(self, envid: &str) -> Self {
+        self.set(value_param(Keyword::ENVID, xtext_encode(envid.as_bytes())))
+    }
+
+    /// Add any other parameter.
+    pub fn param(self, param: Param) -> Self {
+        self.set(param.into())
+    }
+
+    /// Check the parameters and build the command.
+    ///
+    /// Fails when a value is out of range or not a valid parameter
+    /// value, such as an MT-PRIORITY outside of -9 to 9, an ENVID over
+    /// 100 bytes once encoded or a HOLDUNTIL time with spaces.
+    pub fn build(self) -> Result<MailCommand, ParamError> {
+        let params = check_values(self.params)?;
+        mail_params(params.clone())?;
+        Ok(MailCommand { path: self.path, params })
+    }
+}
+
+/// A RCPT TO command, built with [`RcptCommand::builder`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RcptCommand {
+    /// The forward path.
+    pub path: ForwardPath,
+    /// The parameters.
+    pub params: Vec<Param>,
+}
+
+/// Builder for [`RcptCommand`].
+///
+/// Each setter replaces the previous value of its parameter.
+#[derive(Clone, Debug)]
+pub struct RcptCommandBuilder {
+    path: ForwardPath,
+    params: Vec<RawParam>,
+}
+
+impl RcptCommand {
+    /// Start building a RCPT TO command.
+    /// # Examples
+    /// ```
+    /// use rustyknife::esmtp::RcptCommand;
+    /// use rustyknife::rfc3461::Notify;
+    ///
+    /// let cmd = RcptCommand::builder("<bob@example.org>".parse().unwrap())
+    ///     .notify(Notify { on_success: false, on_failure: true, delay: true })
+    ///     .orcpt("bob+tag@example.org")
+    ///     .build().unwrap();
+    ///
+    /// assert_eq!(cmd.to_wire(), b"RCPT TO:<bob@example.org> NOTIFY=FAILURE,DELAY ORCPT=rfc822;bob+2Btag@example.org\r\n");
+    /// ```
+    pub fn builder(path: ForwardPath) -> RcptCommandBuilder {
+        RcptCommandBuilder { path, params: Vec::new() }
+    }
+
+    /// Serialize the command as a CRLF terminated line.
+    pub fn to_wire(&self) -> Vec<u8> {
+        Command::from(self.clone()).to_wire()
+    }
+}
+
+impl From<RcptCommand> for Command {
+    fn from(cmd: RcptCommand) -> Self {
+        Command::RCPT(cmd.path, cmd.params)
+    }
+}
+
+impl RcptCommandBuilder {
+    fn set(mut self, param: RawParam) -> Self {
+        set_param(&mut self.params, param);
+        self
+    }
+
+    /// Set the DSN NOTIFY parameter. Requesting no notification
+    /// renders as `NEVER`.
+    pub fn notify(self, notify: Notify) -> Self {
+        let items: Vec<_> = [(notify.on_success, "SUCCESS"), (notify.on_failure, "FAILURE"), (notify.delay, "DELAY")]
+            .iter().filter(|(set, _)| *set).map(|(_, name)| *name).collect();
+        let value = if items.is_empty() { "NEVER".into() } else { items.join(",") };
+        self.set(value_param(Keyword::NOTIFY, value))
+    }
+
+    /// Set the DSN ORCPT parameter to an `rfc822` address, xtext
+    /// encoding it.
+    pub fn orcpt(self, address: &str) -> Self {
+        self.set(value_param(Keyword::ORCPT, format!("rfc822;{}", xtext_encode(address.as_bytes()))))
+    }
+
+    /// Set the RRVS parameter.
+    pub fn rrvs(self, rrvs: Rrvs) -> Self {
+        let action = match rrvs.action {
+            Some(RrvsAction::Continue) => ";C",
+            Some(RrvsAction::Reject) => ";R",
+            None => "",
+        };
+        self.set(value_param(Keyword::RRVS, format!("{}{}", rrvs.time, action)))
+    }
+
+    /// Add any other parameter.
+    pub fn param(self, param: Param) -> Self {
+        self.set(param.into())
+    }
+
+    /// Check the parameters and build the command.
+    ///
+    /// Fails when a value is invalid, such as a malformed RRVS time.
+    pub fn build(self) -> Result<RcptCommand, ParamError> {
+        let params = check_values(self.params)?;
+        rcpt_params(params.clone())?;
+        Ok(RcptCommand { path: self.path, params })
+    }
+}
@@ -0,0 +1,445 @@
+//! Event based message parser
+//!
+//! [`EventParser`] is fed message bytes as they arrive and emits
+//! [`Event`]s describing the header fields and MIME structure of the
+//! message. Only the current line is buffered, making it suitable for
+//! proxies and filters that must not hold whole messages in memory.
+//!
+//! Multipart bodies are split along their boundaries and each body
+//! part is reported between [`Event::PartBegin`] and
+//! [`Event::PartEnd`]. Encapsulated messages such as `message/rfc822`
+//! are not descended into and are reported as body data.
+//!
+//! A [`Sink`] receives the consumed bytes in the same pass, to compute
+//! digests such as DKIM body hashes without buffering the message.
+
+use std::collections::VecDeque;
+use std::iter::FusedIterator;
+use std::mem;
+
+use sha2::Digest;
+
+use crate::headersection::header;
+use crate::mime::match_delimiter;
+use crate::options::MimeOptions;
+use crate::rfc2231::content_type;
+
+// Incomplete body lines longer than this are passed through, since they
+// cannot be a boundary delimiter.
+const MAX_PENDING_LINE: usize = 4096;
+
+/// Maximum size in bytes of a header field, including its folded
+/// lines. Longer fields are cut to this size and reported as invalid,
+/// the rest of the field is skipped.
+pub const MAX_FIELD_LEN: usize = 64 * 1024;
+
+/// Event emitted by the [`EventParser`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// Start of an entity: the message itself or one of its body parts.
+    ///
+    /// `depth` is 0 for the message and increases for nested parts.
+    PartBegin {
+        /// Nesting depth of the entity.
+        depth: usize,
+    },
+    /// Start of the header section of the current entity.
+    HeaderStart,
+    /// A header field of the current entity.
+    ///
+    /// Mirrors [`crate::headersection::HeaderField`]: a name and raw
+    /// value on success or the raw line when it has no valid name.
+    HeaderField(Result<(Vec<u8>, Vec<u8>), Vec<u8>>),
+    /// End of the header section of the current entity.
+    HeaderEnd,
+    /// Body data of the current entity.
+    ///
+    /// For multipart entities, this is the preamble and epilogue. The
+    /// line break preceding a boundary delimiter is not included.
+    BodyChunk(Vec<u8>),
+    /// End of the current entity.
+    PartEnd,
+}
+
+/// Section of the message a [`Sink`] receives bytes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    /// The header section of the message, including the empty line
+    /// ending it.
+    Header,
+    /// The body of the message, including the header sections of its
+    /// body parts.
+    Body,
+}
+
+/// Receives the raw bytes consumed by an [`EventParser`].
+///
+/// Every byte fed to the parser is written exactly once and in order,
+/// when the line holding it is processed. Pairs of sinks receive the
+/// same bytes.
+pub trait Sink {
+    /// Consume `data`, part of `section`.
+    fn write(&mut self, section: Section, data: &[u8]);
+}
+
+impl Sink for () {
+    fn write(&mut self, _: Section, _: &[u8]) {}
+}
+
+impl<S: Sink + ?Sized> Sink for &mut S {
+    fn write(&mut self, section: Section, data: &[u8]) {
+        (**self).write(section, data)
+    }
+}
+
+impl<A: Sink, B: Sink> Sink for (A, B) {
+    fn write(&mut self, section: Section, data: &[u8]) {
+        self.0.write(section, data);
+        self.1.write(section, data);
+    }
+}
+
+/// [`Sink`] hashing the whole message.
+#[derive(Clone, Debug, Default)]
+pub struct MessageDigest<D>(pub D);
+
+impl<D: Digest> Sink for MessageDigest<D> {
+    fn write(&mut self, _: Section, data: &[u8]) {
+        self.0.update(data)
+    }
+}
+
+/// [`Sink`] hashing the body of the message only, as done for the
+/// DKIM body hash before canonicalization.
+#[derive(Clone, Debug, Default)]
+pub struct BodyDigest<D>(pub D);
+
+impl<D: Digest> Sink for BodyDigest<D> {
+    fn write(&mut self, section: Section, data: &[u8]) {
+        if section == Section::Body {
+            self.0.update(data)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum State {
+    Headers,
+    Body,
+}
+
+/// Push parser emitting [`Event`]s.
+///
+/// Bytes are pushed with [`EventParser::feed`] and events are pulled
+/// with [`EventParser::next_event`] as they become available.
+/// [`EventParser::finish`] must be called at the end of the input to
+/// flush the remaining events. The [`Iterator`] implementation is
+/// fused: it ends the first time no event is available, so it is meant
+/// for draining the parser after `finish`.
+/// # Examples
+/// ```
+/// use rustyknife::events::{Event, EventParser};
+///
+/// let mut parser = EventParser::new();
+/// parser.feed(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n");
+/// parser.feed(b"\r\nhello\r\n--b--\r\n");
+/// parser.finish();
+///
+/// let events: Vec<_> = parser.collect();
+/// assert_eq!(events[0], Event::PartBegin { depth: 0 });
+/// assert_eq!(events[4], Event::PartBegin { depth: 1 });
+/// assert_eq!(events[7], Event::BodyChunk(b"hello".to_vec()));
+/// assert_eq!(events[8], Event::PartEnd);
+/// ```
+#[derive(Debug)]
+pub struct EventParser<S = ()> {
+    buf: Vec<u8>,
+    state: State,
+    field: Vec<u8>,
+    content_boundary: Option<Vec<u8>>,
+    // Open entities with the boundary of their children when multipart.
+    stack: Vec<Option<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pending_eol: Vec<u8>,
+    mid_line: bool,
+    events: VecDeque<Event>,
+    sink: S,
+    // Whether the header section of the message was consumed.
+    in_body: bool,
+    // Whether the rest of an oversized header field is being skipped.
+    skip_field: bool,
+    finished: bool,
+    fused: bool,
+}
+
+impl Default for EventParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn split_eol(line: &[u8]) -> (&[u8], &[u8]) {
+    let content = line.strip_suffix(b"\n").unwrap_or(line);
+    let content = content.strip_suffix(b"\r").unwrap_or(content);
+    line.split_at(content.len())
+}
+
+impl EventParser {
+    /// Create a parser positioned at the start of a message.
+    pub fn new() -> Self {
+        Self::with_sink(())
+    }
+}
+
+impl<S: Sink> EventParser<S> {
+    /// Create a parser writing the consumed bytes to `sink`.
+    /// # Examples
+    /// ```
+    /// use rustyknife::events::{BodyDigest, EventParser, MessageDigest};
+    /// use sha2::{Digest, Sha256};
+    ///
+    /// let mut parser = EventParser::with_sink((BodyDigest(Sha256::new()), MessageDigest(Sha256::new())));
+    /// parser.feed(b"Subject: hi\r\n\r\nhel");
+    /// parser.feed(b"lo\r\n");
+    /// parser.finish();
+    ///
+    /// let (body, message) = parser.into_sink();
+    /// assert_eq!(body.0.finalize(), Sha256::digest(b"hello\r\n"));
+    /// assert_eq!(message.0.finalize(), Sha256::digest(b"Subject: hi\r\n\r\nhello\r\n"));
+    /// ```
+    pub fn with_sink(sink: S) -> Self {
+        let mut events = VecDeque::new();
+        events.push_back(Event::PartBegin { depth: 0 });
+        events.push_back(Event::HeaderStart);
+
+        EventParser {
+            buf: Vec::new(),
+            state: State::Headers,
+            field: Vec::new(),
+            content_boundary: None,
+            stack: vec![None],
+            chunk: Vec::new(),
+            pending_eol: Vec::new(),
+            mid_line: false,
+            events,
+            sink,
+            in_body: false,
+            skip_field: false,
+            finished: false,
+            fused: false,
+        }
+    }
+
+    /// The sink.
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    /// Consume the parser, returning the sink. Call
+    /// [`finish`](Self::finish) first so that it received all the
+    /// input.
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+
+    /// Push more message bytes into the parser.
+    ///
+    /// Does nothing once [`finish`](Self::finish) was called.
+    pub fn feed(&mut self, data: &[u8]) {
+        if self.finished {
+            return;
+        }
+        self.buf.extend_from_slice(data);
+        let buf = mem::take(&mut self.buf);
+        let mut start = 0;
+
+        while let Some(nl) = buf[start..].iter().position(|c| *c == b'\n') {
+            let end = start + nl + 1;
+            self.line(&buf[start..end]);
+            start = end;
+        }
+
+        let rem = &buf[start..];
+        if self.state == State::Body && rem.len() >= MAX_PENDING_LINE {
+            self.sink.write(Section::Body, rem);
+            self.body_data(rem);
+            self.mid_line = true;
+        } else if self.state == State::Headers && rem.len() > MAX_FIELD_LEN {
+            // Always oversized, the rest of the line is skipped.
+            self.line(rem);
+            self.mid_line = true;
+        } else {
+            self.buf.extend_from_slice(rem);
+        }
+        self.flush_chunk();
+    }
+
+    /// Signal the end of the input.
+    ///
+    /// Any incomplete line is processed and all open entities are
+    /// closed. Further calls do nothing.
+    pub fn finish(&mut self) {
+        if mem::replace(&mut self.finished, true) {
+            return;
+        }
+        let rem = mem::take(&mut self.buf);
+        if !rem.is_empty() {
+            self.line(&rem);
+        }
+
+        match self.state {
+            State::Headers => {
+                self.flush_field();
+                self.events.push_back(Event::HeaderEnd);
+            }
+            State::Body => {
+                let eol = mem::take(&mut self.pending_eol);
+                self.chunk.extend(eol);
+                self.flush_chunk();
+            }
+        }
+
+        for _ in self.stack.drain(..) {
+            self.events.push_back(Event::PartEnd);
+        }
+    }
+
+    /// Pull the next available event.
+    ///
+    /// Returns `None` when more input is required or when all events
+    /// have been consumed.
+    pub fn next_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    fn line(&mut self, line: &[u8]) {
+        self.sink.write(if self.in_body { Section::Body } else { Section::Header }, line);
+
+        match self.state {
+            State::Headers => self.header_line(line),
+            State::Body => self.body_line(line),
+        }
+    }
+
+    fn header_line(&mut self, line: &[u8]) {
+        let folded = line.starts_with(b" ") || line.starts_with(b"\t");
+        if self.skip_field && (self.mid_line || folded) {
+            self.mid_line = !line.ends_with(b"\n");
+            return;
+        }
+        self.skip_field = false;
+
+        if !self.field.is_empty() && folded {
+            self.field.extend_from_slice(line);
+        } else {
+            self.flush_field();
+
+            if split_eol(line).0.is_empty() {
+                self.events.push_back(Event::HeaderEnd);
+                *self.stack.last_mut().unwrap() = self.content_boundary.take();
+                self.in_body = true;
+                self.state = State::Body;
+                return;
+            }
+            self.field.extend_from_slice(line);
+        }
+
+        if self.field.len() > MAX_FIELD_LEN {
+            let mut raw = mem::take(&mut self.field);
+            raw.truncate(MAX_FIELD_LEN);
+            self.events.push_back(Event::HeaderField(Err(raw)));
+            self.skip_field = true;
+        }
+    }
+
+    fn flush_field(&mut self) {
+        if self.field.is_empty() {
+            return;
+        }
+        let raw = mem::take(&mut self.field);
+        let raw = split_eol(&raw).0;
+        // The header parser needs to see the end of the field.
+        let mut field = raw.to_vec();
+        field.extend_from_slice(b"\r\n\r\n");
+
+        let event = match header(&field) {
+            Ok((_, Some(Ok((name, value))))) => {
+                if name.eq_ignore_ascii_case(b"Content-Type") {
+                    if let Ok((_, (mtype, params))) = content_type(value) {
+                        if mtype.starts_with("multipart/") {
+                            self.content_boundary = params.into_iter().find(|(n, _)| n == "boundary").map(|(_, b)| b.into_bytes());
+                        }
+                    }
+                }
+                Ok((name.to_vec(), value.to_vec()))
+            }
+            _ => Err(raw.to_vec()),
+        };
+        self.events.push_back(Event::HeaderField(event));
+    }
+
+    fn body_line(&mut self, line: &[u8]) {
+        if !self.mid_line {
+            let options = MimeOptions::default();
+            let matched = self.stack.iter().enumerate().rev()
+                .find_map(|(i, b)| b.as_ref().and_then(|b| match_delimiter(line, b, &options)).map(|close| (i, close)));
+
+            if let Some((level, close)) = matched {
+                self.delimiter(level, close);
+                return;
+            }
+        }
+        self.body_data(line);
+        self.mid_line = false;
+    }
+
+    fn body_data(&mut self, data: &[u8]) {
+        let (content, eol) = split_eol(data);
+        let pending = mem::replace(&mut self.pending_eol, eol.to_vec());
+
+        self.chunk.extend(pending);
+        self.chunk.extend_from_slice(content);
+    }
+
+    fn delimiter(&mut self, level: usize, close: bool) {
+        // The line break before the delimiter belongs to the delimiter.
+        self.pending_eol.clear();
+        self.flush_chunk();
+
+        while self.stack.len() > level + 1 {
+            self.stack.pop();
+            self.events.push_back(Event::PartEnd);
+        }
+
+        if close {
+            self.stack[level] = None;
+            self.state = State::Body;
+        } else {
+            self.events.push_back(Event::PartBegin { depth: level + 1 });
+            self.events.push_back(Event::HeaderStart);
+            self.stack.push(None);
+            self.state = State::Headers;
+        }
+    }
+
+    fn flush_chunk(&mut self) {
+        if !self.chunk.is_empty() {
+            self.events.push_back(Event::BodyChunk(mem::take(&mut self.chunk)));
+        }
+    }
+}
+
+impl<S: Sink> Iterator for EventParser<S> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.fused {
+            return None;
+        }
+        let event = self.next_event();
+        self.fused = event.is_none();
+        event
+    }
+}
+
+impl<S: Sink> FusedIterator for EventParser<S> {}
@@ -0,0 +1,121 @@
+//! Header fingerprints for deduplication and clustering
+//!
+//! Fingerprints are SHA-256 digests over a canonical form of selected
+//! headers, so that they stay stable across folding, whitespace and
+//! case changes introduced in transit.
+
+use sha2::{Digest, Sha256};
+
+use crate::behaviour::Intl;
+use crate::headersection::HeaderField;
+use crate::rfc5321::canonical_mailbox;
+use crate::rfc5322::{from, unstructured};
+
+/// A SHA-256 digest.
+pub type Fingerprint = [u8; 32];
+
+/// Canonicalize a header field with the DKIM ["relaxed"] algorithm.
+///
+/// The name is lowercased, the value is unfolded, runs of whitespace
+/// are collapsed to a single space and leading and trailing
+/// whitespace is removed. The result is terminated by CRLF.
+/// # Examples
+/// ```
+/// use rustyknife::fingerprint::relaxed_header;
+///
+/// assert_eq!(relaxed_header(b"Subject", b" Hello \r\n\t  world  "), b"subject:Hello world\r\n");
+/// ```
+///
+/// ["relaxed"]: https://tools.ietf.org/html/rfc6376#section-3.4.2
+pub fn relaxed_header(name: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut out = name.trim_ascii().to_ascii_lowercase();
+    out.push(b':');
+
+    for word in value.split(|c| b" \t\r\n".contains(c)).filter(|w| !w.is_empty()) {
+        if *out.last().unwrap() != b':' {
+            out.push(b' ');
+        }
+        out.extend_from_slice(word);
+    }
+    out.extend_from_slice(b"\r\n");
+
+    out
+}
+
+/// Hash the relaxed canonical form of the headers named in `names`.
+///
+/// Names are matched case insensitively and hashed in the order of
+/// `names`. Repeated headers are all included, in message order.
+/// Missing headers and unparseable fields are skipped.
+/// # Examples
+/// ```
+/// use rustyknife::fingerprint::header_hash;
+/// use rustyknife::headersection::header_section;
+///
+/// let (_, a) = header_section(b"From: bob@example.org\r\nSubject: Hi  there\r\n\r\n").unwrap();
+/// let (_, b) = header_section(b"subject: Hi\r\n there\r\nFROM:bob@example.org\r\n\r\n").unwrap();
+///
+/// assert_eq!(header_hash(&a, &["From", "Subject"]), header_hash(&b, &["From", "Subject"]));
+/// ```
+pub fn header_hash(headers: &[HeaderField], names: &[&str]) -> Fingerprint {
+    let mut hasher = Sha256::new();
+
+    for name in names {
+        for (hname, value) in headers.iter().filter_map(|f| f.ok()) {
+            if hname.trim_ascii().eq_ignore_ascii_case(name.as_bytes()) {
+                hasher.update(relaxed_header(hname, value));
+            }
+        }
+    }
+
+    hasher.finalize().into()
+}
+
+fn first_value<'a>(headers: &[HeaderField<'a>], name: &str) -> Option<&'a [u8]> {
+    headers.iter().filter_map(|f| f.ok())
+        .find(|(n, _)| n.eq_ignore_ascii_case(name.as_bytes()))
+        .map(|(_, v)| v)
+}
+
+/// Compute a fingerprint of a message from its sender addresses,
+/// subject and Message-ID.
+///
+/// Sender addresses are compared without display names, in the
+/// canonical form of [`Path::canonicalize`] and case insensitively,
+/// the subject after decoding encoded words and collapsing
+/// whitespace. Unlike [`header_hash`], this identifies the same
+/// message after re-encoding by an intermediary.
+/// # Examples
+/// ```
+/// use rustyknife::fingerprint::message_fingerprint;
+/// use rustyknife::headersection::header_section;
+///
+/// let (_, a) = header_section(b"From: Bob <Bob@Example.org>\r\nSubject: =?utf-8?q?caf=C3=A9?=\r\nMessage-ID: <1@example.org>\r\n\r\n").unwrap();
+/// let (_, b) = header_section("Message-ID:  <1@example.org>\r\nFrom: bob@example.org\r\nSubject: café\r\n\r\n".as_bytes()).unwrap();
+///
+/// assert_eq!(message_fingerprint(&a), message_fingerprint(&b));
+/// ```
+///
+/// [`Path::canonicalize`]: crate::rfc5321::Path::canonicalize
+pub fn message_fingerprint(headers: &[HeaderField]) -> Fingerprint {
+    let mut hasher = Sha256::new();
+
+    if let Some(Ok((_, addresses))) = first_value(headers, "From").map(from::<Intl>) {
+        for mailbox in addresses.iter().flat_map(|a| a.mailboxes()) {
+            hasher.update(canonical_mailbox(&mailbox.address).to_string().to_lowercase());
+            hasher.update(b"\n");
+        }
+    }
+    hasher.update(b"\0");
+
+    if let Some(Ok((_, subject))) = first_value(headers, "Subject").map(unstructured::<Intl>) {
+        hasher.update(subject.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+    hasher.update(b"\0");
+
+    if let Some(msgid) = first_value(headers, "Message-ID") {
+        hasher.update(relaxed_header(b"", msgid));
+    }
+
+    hasher.finalize().into()
+}
@@ -11,10 +11,11 @@ use std::str;
 
 use nom::branch::alt;
 use nom::bytes::streaming::{tag, take_while1, take_until};
-use nom::combinator::{opt, map, map_opt, recognize};
+use nom::combinator::{opt, map, map_opt, recognize, verify};
 use nom::multi::{many0, many1};
 use nom::sequence::{pair, terminated, separated_pair};
 
+use crate::options::ParserOptions;
 use crate::util::*;
 
 fn fws(input: &[u8]) -> NomResult<Cow<str>> {
@@ -71,6 +72,13 @@ fn field_name(input: &[u8]) -> NomResult<&[u8]> {
     take_while1(|c| match c {33..=57 | 59..=126 => true, _ => false})(input)
 }
 
+// RFC 6532 keeps field names in US-ASCII, but some internationalized
+// generators emit UTF-8 names.
+fn field_name_utf8(input: &[u8]) -> NomResult<&[u8]> {
+    verify(take_while1(|c| matches!(c, 33..=57 | 59..=126 | 128..=255)),
+           |name: &[u8]| str::from_utf8(name).is_ok())(input)
+}
+
 fn until_crlf(input: &[u8]) -> NomResult<&[u8]> {
     map_opt(take_until("\r\n"),
             |i: &[u8]| if !i.is_empty() {
@@ -90,6 +98,10 @@ fn field(input: &[u8]) -> NomResult<HeaderField> {
     map(terminated(separated_pair(field_name, tag(":"), unstructured), crlf), Ok)(input)
 }
 
+fn field_utf8(input: &[u8]) -> NomResult<HeaderField> {
+    map(terminated(separated_pair(field_name_utf8, tag(":"), unstructured), crlf), Ok)(input)
+}
+
 // Extension to be able to walk through crap.
 fn invalid_field(input: &[u8]) -> NomResult<HeaderField> {
     map(terminated(until_crlf, crlf), Err)(input)
@@ -104,8 +116,116 @@ pub fn header_section(input: &[u8]) -> NomResult<Vec<HeaderField>> {
                opt(crlf))(input)
 }
 
+/// Header splitter configured by `options`.
+///
+/// With [`ParserOptions::allow_utf8`], field names made of valid
+/// UTF-8 are returned in the [`Ok`] variant instead of [`Err`], as
+/// expected from an [RFC 6532] message. Otherwise this is the same
+/// as [`header_section`].
+///
+/// [RFC 6532]: https://tools.ietf.org/html/rfc6532
+/// # Examples
+/// ```
+/// use rustyknife::headersection::{header_section, header_section_with};
+/// use rustyknife::options::ParserOptions;
+///
+/// let input = "Sujet-Traduit-É: Bonjour\r\n\r\n".as_bytes();
+/// let legacy = ParserOptions { allow_utf8: false, ..ParserOptions::default() };
+///
+/// assert!(header_section(input).unwrap().1[0].is_err());
+/// assert!(header_section_with(input, &legacy).unwrap().1[0].is_err());
+/// assert_eq!(header_section_with(input, &ParserOptions::default()).unwrap().1,
+///            [Ok(("Sujet-Traduit-É".as_bytes(), b" Bonjour".as_ref()))]);
+/// ```
+pub fn header_section_with<'a>(input: &'a [u8], options: &ParserOptions) -> NomResult<'a, Vec<HeaderField<'a>>> {
+    if options.allow_utf8 {
+        terminated(many0(alt((field_utf8, invalid_field))), opt(crlf))(input)
+    } else {
+        header_section(input)
+    }
+}
+
 /// Parse a single header
 pub fn header(input: &[u8]) -> NomResult<Option<HeaderField>> {
     alt((map(alt((field, invalid_field)), Some),
          map(crlf, |_| None)))(input)
 }
+
+/// Ordering of the fields written by [`write_header_section`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeaderOrder {
+    /// Keep the fields in the order given.
+    Preserve,
+    /// Group the fields in the order suggested by [RFC 5322 section 3.6]:
+    /// trace and resent fields, originator, destination,
+    /// identification, informational fields, MIME fields and then any
+    /// other field. The relative order within a group is kept, so
+    /// resent blocks stay with their trace fields.
+    ///
+    /// [RFC 5322 section 3.6]: https://tools.ietf.org/html/rfc5322#section-3.6
+    Canonical,
+}
+
+// Rank of a field name in the canonical order.
+fn canonical_rank(name: &[u8]) -> usize {
+    const GROUPS: &[&[&str]] = &[
+        &["Return-Path", "Received"],
+        &["Date", "From", "Sender", "Reply-To"],
+        &["To", "Cc", "Bcc"],
+        &["Message-ID", "In-Reply-To", "References"],
+        &["Subject", "Comments", "Keywords"],
+        &["MIME-Version"],
+    ];
+    let is = |n: &str| n.as_bytes().eq_ignore_ascii_case(name);
+
+    if name.len() > 7 && name[..7].eq_ignore_ascii_case(b"Resent-") {
+        return 0;
+    }
+    if name.len() > 8 && name[..8].eq_ignore_ascii_case(b"Content-") {
+        return GROUPS.len();
+    }
+    GROUPS.iter().position(|group| group.iter().any(|n| is(n))).unwrap_or(GROUPS.len() + 1)
+}
+
+/// Serialize `fields` as a header section, including the empty line
+/// separating it from the body.
+///
+/// Field names are written with their exact casing and values byte
+/// for byte, invalid fields are written as is.
+/// # Examples
+/// ```
+/// use rustyknife::headersection::{header_section, write_header_section, HeaderOrder};
+///
+/// let (_, fields) = header_section(b"subject: hi\r\nX-Tag: a\r\nFROM: bob@example.org\r\nReceived: x\r\n\r\n").unwrap();
+///
+/// assert_eq!(write_header_section(&fields, HeaderOrder::Preserve),
+///            b"subject: hi\r\nX-Tag: a\r\nFROM: bob@example.org\r\nReceived: x\r\n\r\n");
+/// assert_eq!(write_header_section(&fields, HeaderOrder::Canonical),
+///            b"Received: x\r\nFROM: bob@example.org\r\nsubject: hi\r\nX-Tag: a\r\n\r\n");
+/// ```
+pub fn write_header_section(fields: &[HeaderField], order: HeaderOrder) -> Vec<u8> {
+    let mut fields: Vec<_> = fields.iter().collect();
+    let mut out = Vec::new();
+
+    if order == HeaderOrder::Canonical {
+        fields.sort_by_key(|f| match f {
+            Ok((name, _)) => canonical_rank(name),
+            Err(_) => usize::MAX,
+        });
+    }
+
+    for field in fields {
+        match field {
+            Ok((name, value)) => {
+                out.extend_from_slice(name);
+                out.push(b':');
+                out.extend_from_slice(value);
+            }
+            Err(raw) => out.extend_from_slice(raw),
+        }
+        out.extend_from_slice(b"\r\n");
+    }
+    out.extend_from_slice(b"\r\n");
+
+    out
+}
@@ -0,0 +1,214 @@
+//! Homograph audit of parsed addresses
+//!
+//! Phishing messages use internationalized domains that render like
+//! a well known ASCII name, such as `"xn--pypal-4ve.com"` displayed
+//! as `"pаypal.com"` with a Cyrillic `"а"`, and hide characters in
+//! display names. The checks here only look at the characters, they
+//! implement a small subset of the [Unicode security mechanisms].
+//!
+//! [Unicode security mechanisms]: https://www.unicode.org/reports/tr39/
+
+use std::fmt::{self, Display};
+
+use crate::rfc5322::{Address, Mailbox};
+use crate::types::{Domain, DomainPart};
+
+/// Writing system of a character, as far as the audit is concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Script {
+    /// Latin, including the ASCII letters.
+    Latin,
+    /// Greek.
+    Greek,
+    /// Cyrillic.
+    Cyrillic,
+    /// Armenian.
+    Armenian,
+    /// Hebrew.
+    Hebrew,
+    /// Arabic.
+    Arabic,
+    /// Han ideographs.
+    Han,
+    /// Hiragana and Katakana.
+    Kana,
+    /// Hangul.
+    Hangul,
+    /// Any other script.
+    Other,
+}
+
+impl Script {
+    /// The script of `c`, or [`None`] for digits, punctuation and
+    /// other characters shared by all scripts.
+    pub fn of(c: char) -> Option<Script> {
+        Some(match c as u32 {
+            0x41..=0x5a | 0x61..=0x7a | 0xc0..=0xd6 | 0xd8..=0xf6 | 0xf8..=0x24f | 0x1e00..=0x1eff => Script::Latin,
+            0x370..=0x3ff | 0x1f00..=0x1fff => Script::Greek,
+            0x400..=0x52f => Script::Cyrillic,
+            0x530..=0x58f => Script::Armenian,
+            0x590..=0x5ff => Script::Hebrew,
+            0x600..=0x6ff | 0x750..=0x77f => Script::Arabic,
+            0x3400..=0x4dbf | 0x4e00..=0x9fff => Script::Han,
+            0x3040..=0x30ff => Script::Kana,
+            0x1100..=0x11ff | 0xac00..=0xd7af => Script::Hangul,
+            _ if c.is_ascii() || !c.is_alphabetic() => return None,
+            _ => Script::Other,
+        })
+    }
+}
+
+/// A suspicious property found by the audit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// A domain label mixes scripts that are not normally written
+    /// together.
+    MixedScript {
+        /// The label, in Unicode form.
+        label: String,
+        /// The scripts found in the label.
+        scripts: Vec<Script>,
+    },
+    /// A non-ASCII domain label looks like the ASCII `skeleton`.
+    ///
+    /// Compare the skeleton against the names to protect.
+    Confusable {
+        /// The label, in Unicode form.
+        label: String,
+        /// The ASCII label it looks like.
+        skeleton: String,
+    },
+    /// A display name contains an invisible or formatting character.
+    Invisible {
+        /// The display name.
+        name: String,
+        /// Byte offset of the character in `name`.
+        offset: usize,
+        /// The character.
+        c: char,
+    },
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::MixedScript { label, scripts } => write!(f, "label {:?} mixes scripts {:?}", label, scripts),
+            Finding::Confusable { label, skeleton } => write!(f, "label {:?} looks like {:?}", label, skeleton),
+            Finding::Invisible { name, offset, c } => {
+                write!(f, "display name {:?} has invisible U+{:04X} at {}", name, *c as u32, offset)
+            }
+        }
+    }
+}
+
+// Scripts commonly written together, from the "highly restrictive"
+// level of UTS #39.
+const COMBINATIONS: &[&[Script]] = &[
+    &[Script::Latin, Script::Han, Script::Kana],
+    &[Script::Latin, Script::Han, Script::Hangul],
+];
+
+// Non-ASCII letters that render like an ASCII letter.
+const CONFUSABLES: &[(char, char)] = &[
+    ('а', 'a'), ('в', 'b'), ('е', 'e'), ('һ', 'h'), ('і', 'i'), ('ј', 'j'), ('к', 'k'), ('ӏ', 'l'),
+    ('м', 'm'), ('о', 'o'), ('р', 'p'), ('ԛ', 'q'), ('ѕ', 's'), ('т', 't'), ('у', 'y'), ('х', 'x'),
+    ('ԁ', 'd'), ('ԝ', 'w'), ('с', 'c'), ('ɡ', 'g'), ('ı', 'i'), ('ɑ', 'a'), ('ο', 'o'), ('α', 'a'),
+    ('ν', 'v'), ('ι', 'i'), ('κ', 'k'), ('ρ', 'p'), ('υ', 'u'), ('χ', 'x'), ('ε', 'e'), ('τ', 't'),
+    ('օ', 'o'), ('ս', 'u'), ('ց', 'g'), ('ո', 'n'), ('ⅼ', 'l'), ('ｏ', 'o'),
+];
+
+fn is_invisible(c: char) -> bool {
+    matches!(c, '\u{ad}' | '\u{34f}' | '\u{180e}' | '\u{200b}'..='\u{200f}' | '\u{202a}'..='\u{202e}'
+             | '\u{2060}'..='\u{2064}' | '\u{2066}'..='\u{2069}' | '\u{feff}')
+}
+
+fn skeleton(label: &str) -> String {
+    label.chars()
+        .map(|c| CONFUSABLES.iter().find(|(u, _)| *u == c).map(|(_, a)| *a).unwrap_or(c))
+        .collect()
+}
+
+fn audit_label(label: &str, out: &mut Vec<Finding>) {
+    if label.is_ascii() {
+        return;
+    }
+
+    let mut scripts: Vec<Script> = label.chars().filter_map(Script::of).collect();
+    scripts.sort();
+    scripts.dedup();
+    if scripts.len() > 1 && !COMBINATIONS.iter().any(|set| scripts.iter().all(|s| set.contains(s))) {
+        out.push(Finding::MixedScript { label: label.into(), scripts });
+    }
+
+    let skeleton = skeleton(label);
+    if skeleton.is_ascii() {
+        out.push(Finding::Confusable { label: label.into(), skeleton });
+    }
+}
+
+/// Audit the labels of `domain`, after converting punycode labels to
+/// Unicode. ASCII labels are never reported.
+/// # Examples
+/// ```
+/// use rustyknife::homograph::{audit_domain, Finding, Script};
+/// use rustyknife::types::Domain;
+///
+/// let domain = Domain::from_smtp(b"xn--pypal-4ve.com").unwrap();
+/// assert_eq!(audit_domain(&domain), [
+///     Finding::MixedScript { label: "pаypal".into(), scripts: vec![Script::Latin, Script::Cyrillic] },
+///     Finding::Confusable { label: "pаypal".into(), skeleton: "paypal".into() },
+/// ]);
+///
+/// assert!(audit_domain(&Domain::from_smtp("bücher.example".as_bytes()).unwrap()).is_empty());
+/// ```
+pub fn audit_domain(domain: &Domain) -> Vec<Finding> {
+    let mut out = Vec::new();
+
+    for label in domain.to_unicode_string().split('.') {
+        audit_label(label, &mut out);
+    }
+
+    out
+}
+
+fn audit_name(name: &str, out: &mut Vec<Finding>) {
+    out.extend(name.char_indices().filter(|(_, c)| is_invisible(*c))
+               .map(|(offset, c)| Finding::Invisible { name: name.into(), offset, c }));
+}
+
+/// Audit the display name and the domain of `mailbox`.
+pub fn audit_mailbox(mailbox: &Mailbox) -> Vec<Finding> {
+    let mut out = Vec::new();
+
+    if let Some(name) = &mailbox.dname {
+        audit_name(name, &mut out);
+    }
+    if let DomainPart::Domain(domain) = mailbox.address.domain_part() {
+        out.extend(audit_domain(domain));
+    }
+
+    out
+}
+
+/// Audit every mailbox of `addresses`, along with group names.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::homograph::{audit_addresses, Finding};
+/// use rustyknife::rfc5322::from;
+///
+/// let (_, addrs) = from::<Intl>("\"Support\u{200b}\" <help@example.org>".as_bytes()).unwrap();
+/// assert_eq!(audit_addresses(&addrs), [Finding::Invisible { name: "Support\u{200b}".into(), offset: 7, c: '\u{200b}' }]);
+/// ```
+pub fn audit_addresses(addresses: &[Address]) -> Vec<Finding> {
+    let mut out = Vec::new();
+
+    for address in addresses {
+        if let Address::Group(group) = address {
+            audit_name(&group.dname, &mut out);
+        }
+        out.extend(address.mailboxes().iter().flat_map(audit_mailbox));
+    }
+
+    out
+}
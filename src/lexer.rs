@@ -0,0 +1,132 @@
+//! Lexical tokenizer for [RFC 5322] structured header fields
+//!
+//! Splits a header value into atoms, quoted strings, comments,
+//! whitespace and special characters, with the byte span of each
+//! token. This is meant as a building block for parsers of
+//! nonstandard structured headers such as `"X-Spam-Status:"`.
+//!
+//! [RFC 5322]: https://tools.ietf.org/html/rfc5322#section-3.2
+
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::str;
+
+use nom::combinator::recognize;
+
+use crate::rfc5322::{comment, concat_qs, fws, _inner_quoted_string, UTF8Policy};
+use crate::util::*;
+
+const SPECIALS: &[u8] = b"()<>[]:;@\\,.\"";
+
+/// The kind of a lexical [`Token`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A run of atom characters.
+    Atom,
+    /// A quoted string. The value holds the unquoted content.
+    QuotedString,
+    /// A possibly nested comment. The value holds the text between the
+    /// outer parentheses.
+    Comment,
+    /// Whitespace on a single line.
+    Whitespace,
+    /// Whitespace containing a line fold. The value holds the
+    /// unfolded whitespace.
+    Fold,
+    /// A single special character such as `'@'` or `';'`.
+    Special(u8),
+    /// A single byte that cannot start any token, such as a stray
+    /// control character or, with [`Legacy`], each byte of a UTF-8
+    /// sequence. The quote opening an unterminated quoted string is a
+    /// [`Special`](Self::Special) instead.
+    ///
+    /// [`Legacy`]: crate::behaviour::Legacy
+    Invalid(u8),
+}
+
+/// A lexical token with its location in the input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token<'a> {
+    /// The kind of token.
+    pub kind: TokenKind,
+    /// Byte range of the token in the input.
+    pub span: Range<usize>,
+    /// The token as it appears in the input.
+    pub raw: &'a [u8],
+    /// The semantic value of the token.
+    pub value: Cow<'a, str>,
+}
+
+/// Iterator over the lexical tokens of a header value.
+///
+/// Created by [`tokens`].
+pub struct Tokens<'a, P> {
+    input: &'a [u8],
+    pos: usize,
+    policy: PhantomData<P>,
+}
+
+impl<'a, P: UTF8Policy> Tokens<'a, P> {
+    fn lex(&self, rem: &'a [u8]) -> (usize, TokenKind, Cow<'a, str>) {
+        if let Ok((r, ws)) = fws(rem) {
+            let raw = &rem[..rem.len() - r.len()];
+            let kind = if raw.contains(&b'\n') { TokenKind::Fold } else { TokenKind::Whitespace };
+            return (raw.len(), kind, ws);
+        }
+        if let Ok((_, raw)) = recognize(comment::<P>)(rem) {
+            return (raw.len(), TokenKind::Comment, String::from_utf8_lossy(&raw[1..raw.len()-1]));
+        }
+        if let Ok((r, qs)) = _inner_quoted_string::<P>(rem) {
+            return (rem.len() - r.len(), TokenKind::QuotedString, concat_qs(qs.into_iter()).into());
+        }
+        if let Ok((_, atom)) = recognize_many1(P::atext)(rem) {
+            return (atom.len(), TokenKind::Atom, str::from_utf8(atom).unwrap().into());
+        }
+
+        let c = rem[0];
+        let kind = if SPECIALS.contains(&c) { TokenKind::Special(c) } else { TokenKind::Invalid(c) };
+        (1, kind, String::from_utf8_lossy(&rem[..1]))
+    }
+}
+
+impl<'a, P: UTF8Policy> Iterator for Tokens<'a, P> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rem = &self.input[self.pos..];
+        if rem.is_empty() {
+            return None;
+        }
+
+        let (len, kind, value) = self.lex(rem);
+        let span = self.pos..self.pos + len;
+        self.pos += len;
+
+        Some(Token { kind, raw: &self.input[span.clone()], span, value })
+    }
+}
+
+/// Split a structured header value into lexical tokens.
+///
+/// Lexing never fails. Bytes that do not start a valid token are
+/// returned as [`TokenKind::Invalid`] and lexing resumes after them.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::lexer::{tokens, TokenKind};
+///
+/// let toks: Vec<_> = tokens::<Intl>(b"Yes, score=7.1 (spam) \"a b\"").collect();
+/// let kinds: Vec<_> = toks.iter().map(|t| t.kind).collect();
+///
+/// assert_eq!(kinds, [TokenKind::Atom, TokenKind::Special(b','), TokenKind::Whitespace,
+///                    TokenKind::Atom, TokenKind::Special(b'.'), TokenKind::Atom,
+///                    TokenKind::Whitespace, TokenKind::Comment, TokenKind::Whitespace,
+///                    TokenKind::QuotedString]);
+/// assert_eq!(toks[3].value, "score=7");
+/// assert_eq!(toks[7].span, 15..21);
+/// assert_eq!(toks[9].value, "a b");
+/// ```
+pub fn tokens<P: UTF8Policy>(input: &[u8]) -> Tokens<'_, P> {
+    Tokens { input, pos: 0, policy: PhantomData }
+}
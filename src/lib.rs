@@ -18,6 +18,15 @@ pub mod behaviour {
     ///  * Activates message/global (RFC6532) support for message content.
     ///  * Activates SMTPUTF8 support for SMTP.
     pub struct Intl;
+
+    /// Wraps [`Legacy`] or [`Intl`] to choose whether [RFC 2047]
+    /// encoded words inside quoted strings are decoded.
+    ///
+    /// Overrides the `quoted-string-rfc2047` feature, which only sets
+    /// the default for the unwrapped behaviours.
+    ///
+    /// [RFC 2047]: https://tools.ietf.org/html/rfc2047
+    pub struct QuotedStringRFC2047<P, const DECODE: bool>(std::marker::PhantomData<P>);
 }
 
 #[macro_use]
@@ -28,9 +37,43 @@ pub mod rfc2231;
 pub mod rfc5321;
 pub mod rfc5322;
 pub mod rfc3461;
+pub mod rfc3463;
 pub mod types;
 pub mod headersection;
 pub mod xforward;
+pub mod limits;
+pub mod smuggling;
+pub mod lexer;
+pub mod events;
+pub mod options;
+pub mod trace;
+pub mod rewrite;
+pub mod redact;
+pub mod sniff;
+pub mod lmtp;
+pub mod esmtp;
+pub mod reply;
+pub mod fingerprint;
+pub mod mime;
+pub mod mediatype;
+pub mod builder;
+pub mod bulk;
+pub mod transparency;
+pub mod sasl;
+pub mod srs;
+pub mod subject;
+pub mod thread;
+pub mod useragent;
+pub mod dsn;
+pub mod mdn;
+pub mod session;
+pub mod submission;
+pub mod transcript;
+pub mod homograph;
+pub mod spoofing;
+pub mod truncate;
+#[cfg(feature = "tokio")]
+pub mod codec;
 
 #[cfg(feature = "python")]
 mod pymod;
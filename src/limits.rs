@@ -0,0 +1,213 @@
+//! Resource limits for the header decoders and the MIME and SMTP parsers
+//!
+//! A crafted header may contain thousands of [RFC 2047] encoded words
+//! or [RFC 2231] parameter continuations, a crafted message millions
+//! of nested parts and a hostile SMTP client megabyte long commands.
+//! The `*_limited` variants of the parsers stop processing and return
+//! [`DecodeError::LimitExceeded`] once one of these caps is reached.
+//!
+//! [RFC 2047]: https://tools.ietf.org/html/rfc2047
+//! [RFC 2231]: https://tools.ietf.org/html/rfc2231
+
+use std::fmt::{self, Display};
+
+/// Caps applied while decoding a single header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecoderLimits {
+    /// Maximum size in bytes of the decoded output of a header.
+    pub max_decoded_len: usize,
+    /// Maximum number of encoded words decoded in a header.
+    pub max_encoded_words: usize,
+    /// Maximum number of parameter sections (`name*0`, `name*1`,
+    /// ...) in a header.
+    pub max_continuations: usize,
+}
+
+impl DecoderLimits {
+    /// Limits that never trigger.
+    pub fn unlimited() -> Self {
+        DecoderLimits {
+            max_decoded_len: usize::MAX,
+            max_encoded_words: usize::MAX,
+            max_continuations: usize::MAX,
+        }
+    }
+}
+
+impl Default for DecoderLimits {
+    /// Limits generous enough for any legitimate header.
+    fn default() -> Self {
+        DecoderLimits {
+            max_decoded_len: 64 * 1024,
+            max_encoded_words: 1024,
+            max_continuations: 256,
+        }
+    }
+}
+
+/// Caps applied while splitting a message into its MIME parts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MimeLimits {
+    /// Maximum nesting level of multipart entities, zero for the
+    /// message itself.
+    pub max_depth: usize,
+    /// Maximum number of body parts in the whole message.
+    pub max_parts: usize,
+    /// Maximum size in bytes of the header section of each entity.
+    pub max_header_len: usize,
+}
+
+impl MimeLimits {
+    /// Limits that never trigger.
+    pub fn unlimited() -> Self {
+        MimeLimits {
+            max_depth: usize::MAX,
+            max_parts: usize::MAX,
+            max_header_len: usize::MAX,
+        }
+    }
+}
+
+impl Default for MimeLimits {
+    /// Limits generous enough for any legitimate message.
+    fn default() -> Self {
+        MimeLimits {
+            max_depth: 32,
+            max_parts: 10_000,
+            max_header_len: 1024 * 1024,
+        }
+    }
+}
+
+/// Caps applied while parsing an SMTP command.
+///
+/// The defaults are the minimum sizes that servers must accept, as
+/// specified in [RFC 5321 section 4.5.3.1].
+///
+/// [RFC 5321 section 4.5.3.1]: https://tools.ietf.org/html/rfc5321#section-4.5.3.1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SmtpLimits {
+    /// Maximum size in bytes of a local part.
+    pub max_local_part: usize,
+    /// Maximum size in bytes of a domain.
+    pub max_domain: usize,
+    /// Maximum size in bytes of a path, including the angle brackets.
+    pub max_path: usize,
+    /// Maximum size in bytes of a command line, including the CRLF.
+    ///
+    /// Extensions such as DSN add parameters to MAIL and RCPT, so
+    /// servers announcing them should raise this limit.
+    pub max_command_line: usize,
+}
+
+impl Default for SmtpLimits {
+    fn default() -> Self {
+        SmtpLimits {
+            max_local_part: 64,
+            max_domain: 255,
+            max_path: 256,
+            max_command_line: 512,
+        }
+    }
+}
+
+/// Caps on the behaviour of an SMTP client, enforced by
+/// [`SessionGuard`](crate::session::SessionGuard).
+///
+/// Errors are syntax errors and unknown verbs combined. The defaults
+/// follow common MTA settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SessionLimits {
+    /// Number of command lines after which the session is dropped.
+    pub max_commands: usize,
+    /// Number of errors from which each command is flagged.
+    pub warn_errors: usize,
+    /// Number of errors from which replies should be delayed.
+    pub slow_errors: usize,
+    /// Number of errors at which the session is dropped.
+    pub max_errors: usize,
+    /// Number of unknown verbs at which the session is dropped.
+    pub max_unknown_verbs: usize,
+}
+
+impl Default for SessionLimits {
+    fn default() -> Self {
+        SessionLimits {
+            max_commands: 1000,
+            warn_errors: 3,
+            slow_errors: 10,
+            max_errors: 20,
+            max_unknown_verbs: 10,
+        }
+    }
+}
+
+/// Identifies which of the [`DecoderLimits`], [`MimeLimits`] or
+/// [`SmtpLimits`] was exceeded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    /// [`DecoderLimits::max_decoded_len`]
+    DecodedLen,
+    /// [`DecoderLimits::max_encoded_words`]
+    EncodedWords,
+    /// [`DecoderLimits::max_continuations`]
+    Continuations,
+    /// [`ParserOptions::max_comment_depth`](crate::options::ParserOptions::max_comment_depth)
+    CommentDepth,
+    /// [`MimeLimits::max_depth`]
+    MimeDepth,
+    /// [`MimeLimits::max_parts`]
+    MimeParts,
+    /// [`MimeLimits::max_header_len`]
+    HeaderLen,
+    /// [`SmtpLimits::max_local_part`]
+    LocalPart,
+    /// [`SmtpLimits::max_domain`]
+    Domain,
+    /// [`SmtpLimits::max_path`]
+    Path,
+    /// [`SmtpLimits::max_command_line`]
+    CommandLine,
+}
+
+/// Error returned by the `*_limited` and `*_with` parsers.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DecodeError {
+    /// The input is not syntaxically valid.
+    Syntax(nom::Err<()>),
+    /// A configured limit was exceeded.
+    LimitExceeded(Limit),
+    /// A parameter was repeated and duplicates are rejected.
+    DuplicateParameter(String),
+}
+
+impl From<nom::Err<()>> for DecodeError {
+    fn from(err: nom::Err<()>) -> Self {
+        DecodeError::Syntax(err)
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Syntax(_) => write!(f, "syntax error"),
+            DecodeError::LimitExceeded(Limit::DecodedLen) => write!(f, "decoded value too long"),
+            DecodeError::LimitExceeded(Limit::EncodedWords) => write!(f, "too many encoded words"),
+            DecodeError::LimitExceeded(Limit::Continuations) => write!(f, "too many parameter continuations"),
+            DecodeError::LimitExceeded(Limit::CommentDepth) => write!(f, "comments nested too deeply"),
+            DecodeError::LimitExceeded(Limit::MimeDepth) => write!(f, "MIME parts nested too deeply"),
+            DecodeError::LimitExceeded(Limit::MimeParts) => write!(f, "too many MIME parts"),
+            DecodeError::LimitExceeded(Limit::HeaderLen) => write!(f, "header section too long"),
+            DecodeError::LimitExceeded(Limit::LocalPart) => write!(f, "local part too long"),
+            DecodeError::LimitExceeded(Limit::Domain) => write!(f, "domain too long"),
+            DecodeError::LimitExceeded(Limit::Path) => write!(f, "path too long"),
+            DecodeError::LimitExceeded(Limit::CommandLine) => write!(f, "command line too long"),
+            DecodeError::DuplicateParameter(name) => write!(f, "duplicate parameter {}", name),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Result type returned by the `*_limited` and `*_with` parsers.
+pub type LimitedResult<'a, O> = Result<(&'a [u8], O), DecodeError>;
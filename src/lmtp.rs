@@ -0,0 +1,74 @@
+//! [Local Mail Transfer Protocol] command parser
+//!
+//! LMTP reuses the SMTP commands, except that the session is opened
+//! with LHLO. HELO and EHLO are not valid in LMTP.
+//!
+//! [Local Mail Transfer Protocol]: https://tools.ietf.org/html/rfc2033
+
+use nom::branch::alt;
+use nom::bytes::complete::tag_no_case;
+use nom::combinator::map;
+use nom::sequence::delimited;
+
+use crate::rfc5234::crlf;
+use crate::rfc5321::*;
+use crate::types::*;
+use crate::util::*;
+
+/// Parse an LMTP LHLO command.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::lmtp::lhlo_command;
+///
+/// let (_, domain) = lhlo_command::<Intl>(b"LHLO mx.example.org\r\n").unwrap();
+/// assert_eq!(domain.to_string(), "mx.example.org");
+/// ```
+pub fn lhlo_command<P: UTF8Policy>(input: &[u8]) -> NomResult<DomainPart> {
+    delimited(tag_no_case("LHLO "), _domain_part::<P>, crlf)(input)
+}
+
+/// The LMTP command set
+///
+/// The data on each variant corresponds to the return type of the
+/// *_command functions.
+#[derive(Debug)]
+#[allow(missing_docs)]
+pub enum Command {
+    LHLO(DomainPart),
+    MAIL(ReversePath, Vec<Param>),
+    RCPT(ForwardPath, Vec<Param>),
+    DATA,
+    RSET,
+    NOOP(Option<SMTPString>),
+    QUIT,
+    VRFY(SMTPString),
+    EXPN(SMTPString),
+    HELP(Option<SMTPString>),
+}
+
+/// Parse any LMTP command.
+///
+/// HELO and EHLO are rejected.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::lmtp::{command, Command};
+///
+/// assert!(matches!(command::<Intl>(b"LHLO client.example\r\n"), Ok((_, Command::LHLO(_)))));
+/// assert!(command::<Intl>(b"EHLO client.example\r\n").is_err());
+/// ```
+pub fn command<P: UTF8Policy>(input: &[u8]) -> NomResult<Command> {
+    alt((
+        map(lhlo_command::<P>, Command::LHLO),
+        map(mail_command::<P>, |(a, p)| Command::MAIL(a, p)),
+        map(rcpt_command::<P>, |(a, p)| Command::RCPT(a, p)),
+        map(data_command, |_| Command::DATA),
+        map(rset_command, |_| Command::RSET),
+        map(noop_command::<P>, Command::NOOP),
+        map(quit_command, |_| Command::QUIT),
+        map(vrfy_command::<P>, Command::VRFY),
+        map(expn_command::<P>, Command::EXPN),
+        map(help_command::<P>, Command::HELP),
+    ))(input)
+}
@@ -0,0 +1,116 @@
+//! [Message disposition notifications] (MDN)
+//!
+//! The `message/disposition-notification` part of a read receipt,
+//! sent when a recipient displays, deletes or otherwise processes a
+//! message that requested one with `"Disposition-Notification-To:"`.
+//! See [`MessageBuilder::disposition_notification`] to build the
+//! complete report message.
+//!
+//! [Message disposition notifications]: https://tools.ietf.org/html/rfc8098
+//! [`MessageBuilder::disposition_notification`]: crate::builder::MessageBuilder::disposition_notification
+
+use std::fmt::{self, Display};
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use crate::dsn::{field, typed_field, DsnError};
+
+/// What happened to the message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DispositionType {
+    /// `"displayed"`
+    Displayed,
+    /// `"deleted"`
+    Deleted,
+    /// `"dispatched"`
+    Dispatched,
+    /// `"processed"`
+    Processed,
+}
+
+impl Display for DispositionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            DispositionType::Displayed => "displayed",
+            DispositionType::Deleted => "deleted",
+            DispositionType::Dispatched => "dispatched",
+            DispositionType::Processed => "processed",
+        })
+    }
+}
+
+/// The fields of a `message/disposition-notification` part.
+///
+/// Typed fields are the address type and the address, written as is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DispositionNotification {
+    /// The `"Reporting-UA:"` field, such as `"mua.example.org; Webmail 2.1"`.
+    pub reporting_ua: Option<String>,
+    /// The `"Original-Recipient:"` field, from the
+    /// `"Original-Recipient:"` header of the message.
+    pub original_recipient: Option<(String, String)>,
+    /// The `"Final-Recipient:"` field, the recipient sending the
+    /// notification.
+    pub final_recipient: (String, String),
+    /// The `"Original-Message-ID:"` field, without angle brackets.
+    pub original_message_id: Option<String>,
+    /// Whether the disposition was performed automatically rather
+    /// than by the user.
+    pub automatic_action: bool,
+    /// Whether the notification was sent without asking the user.
+    pub sent_automatically: bool,
+    /// What happened to the message.
+    pub disposition: DispositionType,
+}
+
+impl DispositionNotification {
+    /// Serialize the fields, each terminated by CRLF.
+    ///
+    /// Fails when a value contains a line break or another control
+    /// character.
+    /// # Examples
+    /// ```
+    /// use rustyknife::dsn::DsnError;
+    /// use rustyknife::mdn::{DispositionNotification, DispositionType};
+    ///
+    /// let mdn = DispositionNotification {
+    ///     reporting_ua: None,
+    ///     original_recipient: None,
+    ///     final_recipient: ("rfc822".into(), "bob@example.org".into()),
+    ///     original_message_id: Some("1@example.org".into()),
+    ///     automatic_action: false,
+    ///     sent_automatically: false,
+    ///     disposition: DispositionType::Displayed,
+    /// };
+    ///
+    /// assert_eq!(mdn.to_bytes().unwrap(), b"Final-Recipient: rfc822; bob@example.org\r\n\
+    ///                                       Original-Message-ID: <1@example.org>\r\n\
+    ///                                       Disposition: manual-action/MDN-sent-manually; displayed\r\n");
+    ///
+    /// let forged = DispositionNotification { reporting_ua: Some("ua\r\nX-Injected: 1".into()), ..mdn };
+    /// assert_eq!(forged.to_bytes(), Err(DsnError::InvalidValue("Reporting-UA")));
+    /// ```
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DsnError> {
+        let mut out = String::new();
+
+        if let Some(ua) = &self.reporting_ua {
+            field(&mut out, "Reporting-UA", ua)?;
+        }
+        if let Some(original) = &self.original_recipient {
+            typed_field(&mut out, "Original-Recipient", original)?;
+        }
+        typed_field(&mut out, "Final-Recipient", &self.final_recipient)?;
+        if let Some(id) = &self.original_message_id {
+            field(&mut out, "Original-Message-ID", &format!("<{}>", id.trim_start_matches('<').trim_end_matches('>')))?;
+        }
+        field(&mut out, "Disposition", &format!("{}/{}; {}",
+                                                if self.automatic_action { "automatic-action" } else { "manual-action" },
+                                                if self.sent_automatically { "MDN-sent-automatically" } else { "MDN-sent-manually" },
+                                                self.disposition))?;
+
+        Ok(out.into_bytes())
+    }
+}
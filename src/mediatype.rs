@@ -0,0 +1,327 @@
+//! [IANA media types] and their classification
+//!
+//! Provides the registered top-level types, constants for common
+//! subtypes and helpers to classify a `"type/subtype"` string, so that
+//! policies can share a single table instead of scattered string
+//! lists.
+//!
+//! The built-in table only covers common types, it is not a copy of
+//! the full registry.
+//!
+//! [IANA media types]: https://www.iana.org/assignments/media-types/media-types.xhtml
+
+use std::fmt::{self, Display};
+
+/// A registered top-level media type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TopLevel {
+    /// `"application"`
+    Application,
+    /// `"audio"`
+    Audio,
+    /// `"example"`, only for use in documentation.
+    Example,
+    /// `"font"`
+    Font,
+    /// `"haptics"`
+    Haptics,
+    /// `"image"`
+    Image,
+    /// `"message"`
+    Message,
+    /// `"model"`
+    Model,
+    /// `"multipart"`
+    Multipart,
+    /// `"text"`
+    Text,
+    /// `"video"`
+    Video,
+}
+
+impl TopLevel {
+    /// Look up a lowercase top-level type name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "application" => TopLevel::Application,
+            "audio" => TopLevel::Audio,
+            "example" => TopLevel::Example,
+            "font" => TopLevel::Font,
+            "haptics" => TopLevel::Haptics,
+            "image" => TopLevel::Image,
+            "message" => TopLevel::Message,
+            "model" => TopLevel::Model,
+            "multipart" => TopLevel::Multipart,
+            "text" => TopLevel::Text,
+            "video" => TopLevel::Video,
+            _ => return None,
+        })
+    }
+
+    /// Whether this is a discrete type, holding a single body rather
+    /// than other entities like `multipart` and `message`.
+    pub fn is_discrete(self) -> bool {
+        !matches!(self, TopLevel::Message | TopLevel::Multipart)
+    }
+}
+
+/// `"text/plain"`
+pub const TEXT_PLAIN: &str = "text/plain";
+/// `"text/html"`
+pub const TEXT_HTML: &str = "text/html";
+/// `"text/calendar"`
+pub const TEXT_CALENDAR: &str = "text/calendar";
+/// `"text/csv"`
+pub const TEXT_CSV: &str = "text/csv";
+/// `"text/markdown"`
+pub const TEXT_MARKDOWN: &str = "text/markdown";
+/// `"text/rfc822-headers"`
+pub const TEXT_RFC822_HEADERS: &str = "text/rfc822-headers";
+/// `"text/vcard"`
+pub const TEXT_VCARD: &str = "text/vcard";
+/// `"image/gif"`
+pub const IMAGE_GIF: &str = "image/gif";
+/// `"image/jpeg"`
+pub const IMAGE_JPEG: &str = "image/jpeg";
+/// `"image/png"`
+pub const IMAGE_PNG: &str = "image/png";
+/// `"image/svg+xml"`
+pub const IMAGE_SVG: &str = "image/svg+xml";
+/// `"image/webp"`
+pub const IMAGE_WEBP: &str = "image/webp";
+/// `"audio/mpeg"`
+pub const AUDIO_MPEG: &str = "audio/mpeg";
+/// `"audio/ogg"`
+pub const AUDIO_OGG: &str = "audio/ogg";
+/// `"video/mp4"`
+pub const VIDEO_MP4: &str = "video/mp4";
+/// `"video/mpeg"`
+pub const VIDEO_MPEG: &str = "video/mpeg";
+/// `"font/woff2"`
+pub const FONT_WOFF2: &str = "font/woff2";
+/// `"application/octet-stream"`
+pub const APPLICATION_OCTET_STREAM: &str = "application/octet-stream";
+/// `"application/pdf"`
+pub const APPLICATION_PDF: &str = "application/pdf";
+/// `"application/json"`
+pub const APPLICATION_JSON: &str = "application/json";
+/// `"application/xml"`
+pub const APPLICATION_XML: &str = "application/xml";
+/// `"application/zip"`
+pub const APPLICATION_ZIP: &str = "application/zip";
+/// `"application/gzip"`
+pub const APPLICATION_GZIP: &str = "application/gzip";
+/// `"application/zstd"`
+pub const APPLICATION_ZSTD: &str = "application/zstd";
+/// `"application/vnd.rar"`
+pub const APPLICATION_RAR: &str = "application/vnd.rar";
+/// `"application/java-archive"`
+pub const APPLICATION_JAVA_ARCHIVE: &str = "application/java-archive";
+/// `"application/ics"`
+pub const APPLICATION_ICS: &str = "application/ics";
+/// `"application/pgp-encrypted"`
+pub const APPLICATION_PGP_ENCRYPTED: &str = "application/pgp-encrypted";
+/// `"application/pgp-signature"`
+pub const APPLICATION_PGP_SIGNATURE: &str = "application/pgp-signature";
+/// `"application/pkcs7-mime"`
+pub const APPLICATION_PKCS7_MIME: &str = "application/pkcs7-mime";
+/// `"application/pkcs7-signature"`
+pub const APPLICATION_PKCS7_SIGNATURE: &str = "application/pkcs7-signature";
+/// `"application/vnd.microsoft.portable-executable"`
+pub const APPLICATION_PORTABLE_EXECUTABLE: &str = "application/vnd.microsoft.portable-executable";
+/// `"message/rfc822"`
+pub const MESSAGE_RFC822: &str = "message/rfc822";
+/// `"message/global"`
+pub const MESSAGE_GLOBAL: &str = "message/global";
+/// `"message/delivery-status"`
+pub const MESSAGE_DELIVERY_STATUS: &str = "message/delivery-status";
+/// `"message/disposition-notification"`
+pub const MESSAGE_DISPOSITION_NOTIFICATION: &str = "message/disposition-notification";
+/// `"message/global-headers"`
+pub const MESSAGE_GLOBAL_HEADERS: &str = "message/global-headers";
+/// `"message/global-delivery-status"`
+pub const MESSAGE_GLOBAL_DELIVERY_STATUS: &str = "message/global-delivery-status";
+/// `"message/global-disposition-notification"`
+pub const MESSAGE_GLOBAL_DISPOSITION_NOTIFICATION: &str = "message/global-disposition-notification";
+/// `"multipart/mixed"`
+pub const MULTIPART_MIXED: &str = "multipart/mixed";
+/// `"multipart/alternative"`
+pub const MULTIPART_ALTERNATIVE: &str = "multipart/alternative";
+/// `"multipart/related"`
+pub const MULTIPART_RELATED: &str = "multipart/related";
+/// `"multipart/digest"`
+pub const MULTIPART_DIGEST: &str = "multipart/digest";
+/// `"multipart/report"`
+pub const MULTIPART_REPORT: &str = "multipart/report";
+/// `"multipart/signed"`
+pub const MULTIPART_SIGNED: &str = "multipart/signed";
+/// `"multipart/encrypted"`
+pub const MULTIPART_ENCRYPTED: &str = "multipart/encrypted";
+
+const REGISTERED: &[&str] = &[
+    TEXT_PLAIN, TEXT_HTML, TEXT_CALENDAR, TEXT_CSV, TEXT_MARKDOWN, TEXT_RFC822_HEADERS, TEXT_VCARD,
+    "text/css", "text/enriched", "text/javascript", "text/richtext", "text/xml",
+    IMAGE_GIF, IMAGE_JPEG, IMAGE_PNG, IMAGE_SVG, IMAGE_WEBP,
+    "image/avif", "image/bmp", "image/heic", "image/tiff", "image/vnd.microsoft.icon",
+    AUDIO_MPEG, AUDIO_OGG, "audio/aac", "audio/mp4", "audio/opus", "audio/wav",
+    VIDEO_MP4, VIDEO_MPEG, "video/ogg", "video/quicktime", "video/webm",
+    FONT_WOFF2, "font/otf", "font/ttf", "font/woff",
+    APPLICATION_OCTET_STREAM, APPLICATION_PDF, APPLICATION_JSON, APPLICATION_XML, APPLICATION_ZIP,
+    APPLICATION_GZIP, APPLICATION_ZSTD, APPLICATION_RAR, APPLICATION_JAVA_ARCHIVE, APPLICATION_ICS,
+    APPLICATION_PGP_ENCRYPTED, APPLICATION_PGP_SIGNATURE, APPLICATION_PKCS7_MIME,
+    APPLICATION_PKCS7_SIGNATURE, APPLICATION_PORTABLE_EXECUTABLE,
+    "application/msword", "application/rtf", "application/vnd.ms-excel",
+    "application/vnd.ms-powerpoint", "application/vnd.oasis.opendocument.text",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    MESSAGE_RFC822, MESSAGE_GLOBAL, MESSAGE_DELIVERY_STATUS, MESSAGE_DISPOSITION_NOTIFICATION,
+    MESSAGE_GLOBAL_HEADERS, MESSAGE_GLOBAL_DELIVERY_STATUS, MESSAGE_GLOBAL_DISPOSITION_NOTIFICATION,
+    "message/external-body", "message/partial",
+    MULTIPART_MIXED, MULTIPART_ALTERNATIVE, MULTIPART_RELATED, MULTIPART_DIGEST,
+    MULTIPART_REPORT, MULTIPART_SIGNED, MULTIPART_ENCRYPTED, "multipart/form-data",
+    "multipart/parallel",
+];
+
+const ARCHIVES: &[&str] = &[
+    APPLICATION_ZIP, APPLICATION_GZIP, APPLICATION_ZSTD, APPLICATION_RAR, APPLICATION_JAVA_ARCHIVE,
+    "application/x-7z-compressed", "application/x-ace-compressed", "application/x-arj",
+    "application/x-bzip2", "application/x-cab", "application/vnd.ms-cab-compressed",
+    "application/x-gzip", "application/x-iso9660-image", "application/x-lzh-compressed",
+    "application/x-rar-compressed", "application/x-tar", "application/x-xz",
+    "application/x-zip-compressed",
+];
+
+const EXECUTABLES: &[&str] = &[
+    APPLICATION_PORTABLE_EXECUTABLE,
+    "application/hta", "application/java-vm", "application/x-bat", "application/x-dosexec",
+    "application/x-elf", "application/x-executable", "application/x-mach-binary",
+    "application/x-ms-shortcut", "application/x-msdos-program", "application/x-msdownload",
+    "application/x-msi", "application/x-sh", "application/x-shellscript",
+    "application/javascript", "application/x-javascript", "text/javascript",
+    "application/vnd.ms-htmlhelp", "application/x-ms-application",
+];
+
+/// A media type without its parameters, such as `"text/plain"`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MediaType {
+    essence: String,
+    slash: usize,
+}
+
+impl MediaType {
+    /// Parse a `"type/subtype"` string, ignoring case.
+    ///
+    /// The type returned by [`content_type`] can be used as is.
+    ///
+    /// [`content_type`]: crate::rfc2231::content_type
+    /// # Examples
+    /// ```
+    /// use rustyknife::mediatype::{MediaType, TopLevel};
+    ///
+    /// let mt = MediaType::parse("Image/SVG+XML").unwrap();
+    ///
+    /// assert_eq!(mt.to_string(), "image/svg+xml");
+    /// assert_eq!(mt.top_level(), Some(TopLevel::Image));
+    /// assert_eq!(mt.suffix(), Some("xml"));
+    /// assert!(MediaType::parse("text").is_none());
+    /// ```
+    pub fn parse(value: &str) -> Option<Self> {
+        let essence = value.trim().to_ascii_lowercase();
+        let slash = essence.find('/')?;
+
+        if slash == 0 || slash + 1 == essence.len() || essence[slash + 1..].contains('/') {
+            return None;
+        }
+
+        Some(MediaType { essence, slash })
+    }
+
+    /// The lowercase `"type/subtype"` string.
+    pub fn essence(&self) -> &str {
+        &self.essence
+    }
+
+    /// The top-level type name, such as `"text"`.
+    pub fn type_name(&self) -> &str {
+        &self.essence[..self.slash]
+    }
+
+    /// The subtype name, such as `"plain"`.
+    pub fn subtype(&self) -> &str {
+        &self.essence[self.slash + 1..]
+    }
+
+    /// The structured syntax suffix, such as `"xml"` for `"image/svg+xml"`.
+    pub fn suffix(&self) -> Option<&str> {
+        self.subtype().rsplit_once('+').map(|(_, suffix)| suffix)
+    }
+
+    /// The top-level type, if registered.
+    pub fn top_level(&self) -> Option<TopLevel> {
+        TopLevel::from_name(self.type_name())
+    }
+
+    /// Whether this type is found in the built-in table of common
+    /// registered types.
+    ///
+    /// Unlisted types may still be registered, while `x-` subtypes
+    /// never are.
+    pub fn is_registered(&self) -> bool {
+        REGISTERED.contains(&self.essence())
+    }
+
+    /// Whether this is an experimental `x-` type or subtype.
+    pub fn is_experimental(&self) -> bool {
+        self.type_name().starts_with("x-") || self.subtype().starts_with("x-") || self.subtype().starts_with("x.")
+    }
+
+    /// Whether this is a `text` type.
+    pub fn is_text(&self) -> bool {
+        self.top_level() == Some(TopLevel::Text)
+    }
+
+    /// Whether this is an `image` type.
+    pub fn is_image(&self) -> bool {
+        self.top_level() == Some(TopLevel::Image)
+    }
+
+    /// Whether this is an `audio` or `video` type.
+    pub fn is_audio_video(&self) -> bool {
+        matches!(self.top_level(), Some(TopLevel::Audio) | Some(TopLevel::Video))
+    }
+
+    /// Whether this is a `multipart` type.
+    pub fn is_multipart(&self) -> bool {
+        self.top_level() == Some(TopLevel::Multipart)
+    }
+
+    /// Whether this is a `message` type.
+    pub fn is_message(&self) -> bool {
+        self.top_level() == Some(TopLevel::Message)
+    }
+
+    /// Whether this is a known archive or compressed file type, which
+    /// may hide other content from scanners.
+    pub fn is_archive(&self) -> bool {
+        ARCHIVES.contains(&self.essence())
+    }
+
+    /// Whether this is a known executable, installer or script type.
+    /// # Examples
+    /// ```
+    /// use rustyknife::mediatype::MediaType;
+    ///
+    /// assert!(MediaType::parse("application/x-msdownload").unwrap().is_executable());
+    /// assert!(!MediaType::parse("application/pdf").unwrap().is_executable());
+    /// ```
+    pub fn is_executable(&self) -> bool {
+        EXECUTABLES.contains(&self.essence())
+    }
+}
+
+impl Display for MediaType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.essence)
+    }
+}
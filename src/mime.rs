@@ -0,0 +1,1271 @@
+//! MIME entity tree
+//!
+//! Splits a message into its [`Entity`] tree along multipart
+//! boundaries, as specified in [RFC 2046]. Parsing is zero copy: the
+//! header fields and bodies borrow from the input. Bodies are decoded
+//! on demand.
+//!
+//! Encapsulated messages such as `message/rfc822` are not descended
+//! into, use [`attached_messages`] to parse them.
+//!
+//! [RFC 2046]: https://tools.ietf.org/html/rfc2046
+
+use std::borrow::Cow;
+use std::str;
+
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::esmtp::BodyType;
+use crate::headersection::{header_section, HeaderField};
+use crate::limits::{DecodeError, Limit, MimeLimits};
+use crate::mediatype::MediaType;
+use crate::options::MimeOptions;
+use crate::rfc2231::{content_disposition, content_transfer_encoding, content_type, ContentDisposition, ContentTransferEncoding};
+
+/// A message or one of its body parts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entity<'a> {
+    /// The header fields.
+    pub headers: Vec<HeaderField<'a>>,
+    /// The lowercase MIME type.
+    ///
+    /// When the `"Content-Type"` is absent or invalid, this is
+    /// `"message/rfc822"` for the parts of a `multipart/digest` and
+    /// `"text/plain"` otherwise, as specified in [RFC 2045] and [RFC
+    /// 2046].
+    ///
+    /// [RFC 2045]: https://tools.ietf.org/html/rfc2045#section-5.2
+    /// [RFC 2046]: https://tools.ietf.org/html/rfc2046#section-5.1.5
+    pub content_type: String,
+    /// The decoded `"Content-Type"` parameters, `charset=us-ascii` for
+    /// a defaulted `"text/plain"`.
+    pub params: Vec<(String, String)>,
+    /// The raw body, still transfer encoded. For multipart entities,
+    /// this includes the preamble, the delimiters and the epilogue.
+    pub body: &'a [u8],
+    /// The body parts of a multipart entity.
+    pub parts: Vec<Entity<'a>>,
+    /// The text before the first delimiter of a multipart entity.
+    pub preamble: &'a [u8],
+    /// The text after the close delimiter of a multipart entity.
+    pub epilogue: &'a [u8],
+}
+
+fn lines(input: &[u8]) -> impl Iterator<Item=(usize, &[u8])> {
+    let mut start = 0;
+
+    std::iter::from_fn(move || {
+        if start >= input.len() {
+            return None;
+        }
+        let end = input[start..].iter().position(|c| *c == b'\n').map_or(input.len(), |nl| start + nl + 1);
+        let line = (start, &input[start..end]);
+        start = end;
+        Some(line)
+    })
+}
+
+// Returns Some(true) for a close delimiter, Some(false) for a delimiter.
+pub(crate) fn match_delimiter(line: &[u8], boundary: &[u8], options: &MimeOptions) -> Option<bool> {
+    let line = if options.indented_delimiters {
+        &line[line.iter().take_while(|c| b" \t".contains(c)).count()..]
+    } else {
+        line
+    };
+    let rest = line.strip_prefix(b"--")?;
+    let rest = match rest.get(..boundary.len()) {
+        Some(b) if b == boundary || (options.boundary_ignore_case && b.eq_ignore_ascii_case(boundary)) => &rest[boundary.len()..],
+        _ => return None,
+    };
+    let (rest, close) = match rest.strip_prefix(b"--") {
+        Some(rest) => (rest, true),
+        None => (rest, false),
+    };
+
+    if rest.iter().all(|c| b" \t\r\n".contains(c)) {
+        Some(close)
+    } else {
+        None
+    }
+}
+
+// Header fields of a section that is not followed by an empty line,
+// which the streaming header parser cannot handle.
+fn unterminated_headers(input: &[u8]) -> Vec<HeaderField> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+
+    for (pos, line) in lines(input) {
+        if pos != start && !line.starts_with(b" ") && !line.starts_with(b"\t") {
+            fields.push(split_field(&input[start..pos]));
+            start = pos;
+        }
+    }
+    if start < input.len() {
+        fields.push(split_field(&input[start..]));
+    }
+
+    fields
+}
+
+fn split_field(raw: &[u8]) -> HeaderField {
+    let raw = raw.strip_suffix(b"\n").unwrap_or(raw);
+    let raw = raw.strip_suffix(b"\r").unwrap_or(raw);
+
+    match raw.iter().position(|c| *c == b':') {
+        Some(colon) if colon > 0 && raw[..colon].iter().all(|c| (33..=126).contains(c)) => Ok((&raw[..colon], &raw[colon+1..])),
+        _ => Err(raw),
+    }
+}
+
+// Split an entity into its header fields and body.
+fn split_entity(input: &[u8]) -> (Vec<HeaderField>, &[u8]) {
+    match header_section(input) {
+        Ok((body, fields)) => (fields, body),
+        Err(_) => (unterminated_headers(input), &input[input.len()..]),
+    }
+}
+
+fn strip_eol(content: &[u8]) -> &[u8] {
+    let content = content.strip_suffix(b"\n").unwrap_or(content);
+    content.strip_suffix(b"\r").unwrap_or(content)
+}
+
+struct Multipart<'a> {
+    preamble: &'a [u8],
+    parts: Vec<&'a [u8]>,
+    epilogue: &'a [u8],
+}
+
+// Split a multipart body into the content of its parts.
+fn split_parts<'a>(body: &'a [u8], boundary: &[u8], options: &MimeOptions) -> Multipart<'a> {
+    // Empty slices at the start of the body when there is no delimiter.
+    let mut multipart = Multipart { preamble: &body[..0], parts: Vec::new(), epilogue: &body[..0] };
+    let mut part_start = None;
+
+    for (pos, line) in lines(body) {
+        let close = match match_delimiter(line, boundary, options) {
+            Some(close) => close,
+            None => continue,
+        };
+
+        // The line break before the delimiter belongs to the delimiter.
+        match part_start {
+            Some(start) => multipart.parts.push(strip_eol(&body[start..pos])),
+            None => multipart.preamble = strip_eol(&body[..pos]),
+        }
+        if close {
+            multipart.epilogue = &body[pos + line.len()..];
+            return multipart;
+        }
+        part_start = Some(pos + line.len());
+    }
+
+    // Missing close delimiter, the last part runs to the end.
+    if let Some(start) = part_start {
+        if options.keep_unterminated_part {
+            multipart.parts.push(&body[start..]);
+        }
+    }
+
+    multipart
+}
+
+/// Parse a message into its entity tree.
+///
+/// Parsing does not fail: invalid header fields are kept as
+/// [`Err`](HeaderField) and multipart bodies without any delimiter
+/// have no parts. The [default](MimeLimits::default) depth and part
+/// count limits are applied by leaving the multipart bodies that would
+/// exceed them without parts. Use [`parse_message_limited`] to detect
+/// these messages instead.
+/// # Examples
+/// ```
+/// use rustyknife::mime::parse_message;
+///
+/// let message = parse_message(b"Content-Type: multipart/alternative; boundary=b\r\n\r\n\
+///                               --b\r\n\r\nplain\r\n\
+///                               --b\r\nContent-Type: text/html\r\n\r\n<p>html</p>\r\n\
+///                               --b--\r\n");
+///
+/// assert_eq!(message.content_type, "multipart/alternative");
+/// assert_eq!(message.parts[0].content_type, "text/plain");
+/// assert_eq!(message.parts[0].body, b"plain");
+/// assert_eq!(message.parts[1].content_type, "text/html");
+/// ```
+pub fn parse_message(input: &[u8]) -> Entity {
+    parse_entity(input, &MimeOptions::default(), false, 0, &mut 0, true).unwrap()
+}
+
+/// Same as [`parse_message`] with the tolerance for broken multipart
+/// bodies and the limits set in `options`.
+/// # Examples
+/// ```
+/// use rustyknife::mime::parse_message_with;
+/// use rustyknife::options::MimeOptions;
+///
+/// let input = b"Content-Type: multipart/mixed; boundary=abc\r\n\r\n\
+///               --ABC\r\n\r\none\r\n--ABC--\r\n";
+/// let options = MimeOptions { boundary_ignore_case: true, ..MimeOptions::default() };
+///
+/// assert!(parse_message_with(input, &MimeOptions::default()).unwrap().parts.is_empty());
+/// assert_eq!(parse_message_with(input, &options).unwrap().parts[0].body, b"one");
+/// ```
+pub fn parse_message_with<'a>(input: &'a [u8], options: &MimeOptions) -> Result<Entity<'a>, DecodeError> {
+    parse_entity(input, options, false, 0, &mut 0, false)
+}
+
+/// Same as [`parse_message`] but fails with
+/// [`DecodeError::LimitExceeded`] when the message is nested too
+/// deeply, has too many parts or a part has an oversized header
+/// section.
+/// # Examples
+/// ```
+/// use rustyknife::limits::{DecodeError, Limit, MimeLimits};
+/// use rustyknife::mime::parse_message_limited;
+///
+/// let input = b"Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+///               --b\r\n\r\none\r\n--b\r\n\r\ntwo\r\n--b--\r\n";
+/// let limits = MimeLimits { max_parts: 1, ..MimeLimits::default() };
+///
+/// assert!(parse_message_limited(input, &MimeLimits::default()).is_ok());
+/// assert_eq!(parse_message_limited(input, &limits), Err(DecodeError::LimitExceeded(Limit::MimeParts)));
+/// ```
+pub fn parse_message_limited<'a>(input: &'a [u8], limits: &MimeLimits) -> Result<Entity<'a>, DecodeError> {
+    parse_message_with(input, &MimeOptions { limits: *limits, ..MimeOptions::default() })
+}
+
+// With `lenient`, the multipart bodies exceeding the depth or part
+// count limits are left without parts instead of failing, and header
+// sections are not checked since they are already split.
+fn parse_entity<'a>(input: &'a [u8], options: &MimeOptions, in_digest: bool, depth: usize, count: &mut usize,
+                    lenient: bool) -> Result<Entity<'a>, DecodeError> {
+    let limits = &options.limits;
+    let (headers, body) = split_entity(input);
+    if !lenient && input.len() - body.len() > limits.max_header_len {
+        return Err(DecodeError::LimitExceeded(Limit::HeaderLen));
+    }
+
+    let (default_type, default_params) = if in_digest {
+        ("message/rfc822", vec![])
+    } else {
+        ("text/plain", vec![("charset".into(), "us-ascii".into())])
+    };
+    let mut entity = Entity {
+        headers,
+        content_type: default_type.into(),
+        params: default_params,
+        body,
+        parts: Vec::new(),
+        preamble: &body[..0],
+        epilogue: &body[..0],
+    };
+
+    if let Some(Ok((_, (mtype, params)))) = entity.header(b"Content-Type").map(content_type) {
+        entity.content_type = mtype;
+        entity.params = params;
+    }
+
+    if entity.is_multipart() {
+        if let Some(boundary) = entity.param("boundary") {
+            let Multipart { preamble, parts, epilogue } = split_parts(body, boundary.as_bytes(), options);
+            entity.preamble = preamble;
+            entity.epilogue = epilogue;
+            if parts.is_empty() {
+                return Ok(entity);
+            }
+            if depth >= limits.max_depth || *count + parts.len() > limits.max_parts {
+                if lenient {
+                    return Ok(entity);
+                }
+                let limit = if depth >= limits.max_depth { Limit::MimeDepth } else { Limit::MimeParts };
+                return Err(DecodeError::LimitExceeded(limit));
+            }
+            *count += parts.len();
+            let in_digest = entity.content_type == "multipart/digest";
+            entity.parts = parts.into_iter()
+                .map(|p| parse_entity(p, options, in_digest, depth + 1, count, lenient))
+                .collect::<Result<_, _>>()?;
+        }
+    }
+
+    Ok(entity)
+}
+
+/// Quoted-printable encoding violations, see
+/// [`decode_quoted_printable_with`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QpViolation {
+    /// An escape with lowercase hexadecimal digits, such as `"=3d"`.
+    LowercaseHex,
+    /// A `"="` at the very end of the input.
+    BareEquals,
+    /// A `"="` not followed by two hexadecimal digits or a line break.
+    InvalidEscape,
+    /// Whitespace at the end of a line.
+    TrailingWhitespace,
+}
+
+/// Which [`QpViolation`]s are tolerated by
+/// [`decode_quoted_printable_with`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QpOptions {
+    /// Decode lowercase hexadecimal digits.
+    pub lowercase_hex: bool,
+    /// Ignore a `"="` at the end of the input, as a soft line break.
+    pub bare_equals: bool,
+    /// Keep invalid escapes literally.
+    pub invalid_escape: bool,
+    /// Remove trailing whitespace, which may have been added in transit.
+    pub trailing_whitespace: bool,
+}
+
+impl QpOptions {
+    /// Fail on any violation.
+    pub fn strict() -> Self {
+        QpOptions { lowercase_hex: false, bare_equals: false, invalid_escape: false, trailing_whitespace: false }
+    }
+
+    fn allows(&self, violation: QpViolation) -> bool {
+        match violation {
+            QpViolation::LowercaseHex => self.lowercase_hex,
+            QpViolation::BareEquals => self.bare_equals,
+            QpViolation::InvalidEscape => self.invalid_escape,
+            QpViolation::TrailingWhitespace => self.trailing_whitespace,
+        }
+    }
+}
+
+impl Default for QpOptions {
+    /// Tolerate every violation.
+    fn default() -> Self {
+        QpOptions { lowercase_hex: true, bare_equals: true, invalid_escape: true, trailing_whitespace: true }
+    }
+}
+
+/// The number of violations corrected by
+/// [`decode_quoted_printable_with`], by kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QpCorrections {
+    /// [`QpViolation::LowercaseHex`]
+    pub lowercase_hex: usize,
+    /// [`QpViolation::BareEquals`]
+    pub bare_equals: usize,
+    /// [`QpViolation::InvalidEscape`]
+    pub invalid_escape: usize,
+    /// [`QpViolation::TrailingWhitespace`]
+    pub trailing_whitespace: usize,
+}
+
+impl QpCorrections {
+    /// The total number of corrections.
+    pub fn total(&self) -> usize {
+        self.lowercase_hex + self.bare_equals + self.invalid_escape + self.trailing_whitespace
+    }
+
+    fn count(&mut self, violation: QpViolation) {
+        *match violation {
+            QpViolation::LowercaseHex => &mut self.lowercase_hex,
+            QpViolation::BareEquals => &mut self.bare_equals,
+            QpViolation::InvalidEscape => &mut self.invalid_escape,
+            QpViolation::TrailingWhitespace => &mut self.trailing_whitespace,
+        } += 1;
+    }
+}
+
+/// Decode a quoted-printable body.
+///
+/// Every [`QpViolation`] is tolerated: invalid escapes are kept
+/// literally and trailing whitespace is removed from each line.
+/// # Examples
+/// ```
+/// use rustyknife::mime::decode_quoted_printable;
+///
+/// assert_eq!(decode_quoted_printable(b"caf=C3=A9 =\r\nau lait  \r\n100=%"), "café au lait\r\n100=%".as_bytes());
+/// ```
+pub fn decode_quoted_printable(input: &[u8]) -> Vec<u8> {
+    decode_quoted_printable_with(input, &QpOptions::default()).unwrap().0
+}
+
+/// Decode a quoted-printable body, tolerating the violations allowed
+/// by `options`.
+///
+/// Returns the decoded body and the number of corrections applied, or
+/// the offset and kind of the first violation that is not tolerated.
+/// # Examples
+/// ```
+/// use rustyknife::mime::{decode_quoted_printable_with, QpOptions, QpViolation};
+///
+/// let (decoded, corrections) = decode_quoted_printable_with(b"a=3d=\r\nb =", &QpOptions::default()).unwrap();
+/// assert_eq!(decoded, b"a=b ");
+/// assert_eq!(corrections.total(), 2);
+///
+/// assert_eq!(decode_quoted_printable_with(b"a=3d", &QpOptions::strict()), Err((1, QpViolation::LowercaseHex)));
+/// ```
+pub fn decode_quoted_printable_with(input: &[u8], options: &QpOptions) -> Result<(Vec<u8>, QpCorrections), (usize, QpViolation)> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut corrections = QpCorrections::default();
+    let mut correct = |offset, violation| {
+        if options.allows(violation) {
+            corrections.count(violation);
+            Ok(())
+        } else {
+            Err((offset, violation))
+        }
+    };
+
+    for (pos, line) in lines(input) {
+        let content = line.strip_suffix(b"\n").unwrap_or(line);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+        let eol = &line[content.len()..];
+        let trimmed = content.len() - content.iter().rev().take_while(|c| b" \t".contains(c)).count();
+        if trimmed != content.len() {
+            correct(pos + trimmed, QpViolation::TrailingWhitespace)?;
+        }
+        let content = &content[..trimmed];
+
+        let mut i = 0;
+        let mut soft_break = false;
+        while i < content.len() {
+            if content[i] == b'=' {
+                if i + 1 == content.len() {
+                    if eol.is_empty() {
+                        correct(pos + i, QpViolation::BareEquals)?;
+                    }
+                    soft_break = true;
+                    break;
+                }
+                match content.get(i+1..i+3) {
+                    Some(hex) if hex.iter().all(u8::is_ascii_hexdigit) => {
+                        if hex.iter().any(u8::is_ascii_lowercase) {
+                            correct(pos + i, QpViolation::LowercaseHex)?;
+                        }
+                        out.push(u8::from_str_radix(std::str::from_utf8(hex).unwrap(), 16).unwrap());
+                        i += 3;
+                        continue;
+                    },
+                    _ => correct(pos + i, QpViolation::InvalidEscape)?,
+                }
+            }
+            out.push(content[i]);
+            i += 1;
+        }
+        if !soft_break {
+            out.extend_from_slice(eol);
+        }
+    }
+
+    Ok((out, corrections))
+}
+
+fn is_base64_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'+' || c == b'/'
+}
+
+/// Decode a base64 body.
+///
+/// Characters outside of the base64 alphabet, including padding, are
+/// ignored. Fails only when the remaining data has an impossible
+/// length.
+/// # Examples
+/// ```
+/// use rustyknife::mime::decode_base64;
+///
+/// assert_eq!(decode_base64(b"aGVs\r\nbG8 =*").unwrap(), b"hello");
+/// assert!(decode_base64(b"aGVsb").is_none());
+/// ```
+pub fn decode_base64(input: &[u8]) -> Option<Vec<u8>> {
+    let clean: Vec<u8> = input.iter().copied().filter(|c| is_base64_char(*c)).collect();
+    base64::decode_config(&clean, base64::STANDARD_NO_PAD).ok()
+}
+
+/// Base64 encoding violations, see [`decode_base64_strict`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Base64Violation {
+    /// A line longer than the 76 characters allowed by [RFC 2045].
+    ///
+    /// [RFC 2045]: https://tools.ietf.org/html/rfc2045#section-6.8
+    LineTooLong,
+    /// A character outside of the base64 alphabet.
+    InvalidCharacter,
+    /// Missing or misplaced padding, or nonzero unused bits in the
+    /// last character.
+    InvalidPadding,
+}
+
+/// Decode a base64 body, rejecting any deviation from the canonical
+/// form.
+///
+/// Lines must be at most 76 characters long, excluding the line
+/// break, and contain only characters from the alphabet. The data must
+/// be padded. Returns the offset and kind of the first violation.
+/// # Examples
+/// ```
+/// use rustyknife::mime::{decode_base64_strict, Base64Violation};
+///
+/// assert_eq!(decode_base64_strict(b"aGVs\r\nbG8=\r\n").unwrap(), b"hello");
+/// assert_eq!(decode_base64_strict(b"aGVs bG8="), Err((4, Base64Violation::InvalidCharacter)));
+/// assert_eq!(decode_base64_strict(b"aGVsbG8"), Err((7, Base64Violation::InvalidPadding)));
+/// ```
+pub fn decode_base64_strict(input: &[u8]) -> Result<Vec<u8>, (usize, Base64Violation)> {
+    let mut clean = Vec::with_capacity(input.len());
+    let mut padding = None;
+
+    for (pos, line) in lines(input) {
+        let content = line.strip_suffix(b"\n").unwrap_or(line);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+        if content.len() > 76 {
+            return Err((pos + 76, Base64Violation::LineTooLong));
+        }
+
+        for (i, c) in content.iter().enumerate() {
+            match (c, padding) {
+                (b'=', _) => {
+                    padding.get_or_insert(pos + i);
+                    clean.push(*c);
+                },
+                (c, None) if is_base64_char(*c) => clean.push(*c),
+                (c, Some(_)) if is_base64_char(*c) => return Err((pos + i, Base64Violation::InvalidPadding)),
+                _ => return Err((pos + i, Base64Violation::InvalidCharacter)),
+            }
+        }
+    }
+
+    let invalid = (padding.unwrap_or(input.len()), Base64Violation::InvalidPadding);
+    if clean.len() % 4 != 0 {
+        return Err(invalid);
+    }
+    base64::decode_config(&clean, base64::STANDARD).map_err(|_| invalid)
+}
+
+/// Encode `data` as quoted-printable.
+///
+/// CRLF sequences are kept as hard line breaks, lines are wrapped
+/// with soft line breaks at 76 characters and whitespace at the end
+/// of a line is escaped.
+/// # Examples
+/// ```
+/// use rustyknife::mime::{decode_quoted_printable, encode_quoted_printable};
+///
+/// let encoded = encode_quoted_printable("café = 1 \r\n".as_bytes());
+///
+/// assert_eq!(encoded, "caf=C3=A9 =3D 1=20\r\n");
+/// assert_eq!(decode_quoted_printable(encoded.as_bytes()), "café = 1 \r\n".as_bytes());
+/// ```
+pub fn encode_quoted_printable(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len());
+    let mut rest = data;
+
+    loop {
+        let end = rest.windows(2).position(|w| w == b"\r\n");
+        let line = &rest[..end.unwrap_or(rest.len())];
+        let mut len = 0;
+
+        for (i, c) in line.iter().enumerate() {
+            let literal = match c {
+                b' ' | b'\t' => i + 1 != line.len(),
+                b'=' => false,
+                c => (33..=126).contains(c),
+            };
+            let width = if literal { 1 } else { 3 };
+
+            if len + width > 75 {
+                out.push_str("=\r\n");
+                len = 0;
+            }
+            if literal {
+                out.push(char::from(*c));
+            } else {
+                out.push_str(&format!("={:02X}", c));
+            }
+            len += width;
+        }
+
+        match end {
+            Some(end) => {
+                out.push_str("\r\n");
+                rest = &rest[end + 2..];
+            }
+            None => break,
+        }
+    }
+
+    out
+}
+
+/// Encode `data` as base64 in CRLF terminated lines of 76 characters.
+/// # Examples
+/// ```
+/// use rustyknife::mime::{decode_base64_strict, encode_base64};
+///
+/// let encoded = encode_base64(&[0; 60]);
+///
+/// assert_eq!(encoded.lines().map(str::len).collect::<Vec<_>>(), [76, 4]);
+/// assert_eq!(decode_base64_strict(encoded.as_bytes()).unwrap(), [0; 60]);
+/// ```
+pub fn encode_base64(data: &[u8]) -> String {
+    let encoded = base64::encode(data);
+    let mut out = String::with_capacity(encoded.len() + encoded.len() / 38 + 2);
+
+    for chunk in encoded.as_bytes().chunks(76) {
+        out.push_str(str::from_utf8(chunk).unwrap());
+        out.push_str("\r\n");
+    }
+
+    out
+}
+
+impl<'a> Entity<'a> {
+    /// The value of the first header field named `name`, compared case
+    /// insensitively.
+    pub fn header(&self, name: &[u8]) -> Option<&'a [u8]> {
+        self.headers.iter().filter_map(|f| f.ok())
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    /// The value of the `"Content-Type"` parameter `name`.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    /// Whether this entity has a `multipart/*` type.
+    pub fn is_multipart(&self) -> bool {
+        self.content_type.starts_with("multipart/")
+    }
+
+    /// The content type for classification with [`MediaType`].
+    pub fn media_type(&self) -> Option<MediaType> {
+        MediaType::parse(&self.content_type)
+    }
+
+    /// The `"Content-Transfer-Encoding"`, 7bit when absent or invalid.
+    pub fn transfer_encoding(&self) -> ContentTransferEncoding {
+        match self.header(b"Content-Transfer-Encoding").map(content_transfer_encoding) {
+            Some(Ok((b"", cte))) => cte,
+            _ => ContentTransferEncoding::SevenBit,
+        }
+    }
+
+    /// The body with the transfer encoding undone.
+    ///
+    /// Characters outside of the base64 alphabet are ignored. The raw
+    /// body is returned when it cannot be decoded or when the encoding
+    /// is unknown.
+    pub fn decoded_body(&self) -> Cow<'a, [u8]> {
+        match self.transfer_encoding() {
+            ContentTransferEncoding::QuotedPrintable => decode_quoted_printable(self.body).into(),
+            ContentTransferEncoding::Base64 => decode_base64(self.body).map_or(self.body.into(), Cow::from),
+            _ => self.body.into(),
+        }
+    }
+
+    /// The body decoded to text using the `charset` parameter.
+    ///
+    /// Unknown and missing charsets are decoded as UTF-8. So is
+    /// US-ASCII, the default, since 8 bit text mislabeled as such is
+    /// most often UTF-8. Invalid sequences are replaced.
+    pub fn decoded_text(&self) -> String {
+        let encoding = self.param("charset").filter(|c| !c.eq_ignore_ascii_case("us-ascii"))
+            .and_then(|c| Encoding::for_label(c.as_bytes())).unwrap_or(UTF_8);
+        encoding.decode_without_bom_handling(&self.decoded_body()).0.into_owned()
+    }
+
+    /// Iterate over this entity and all its descendants, depth first.
+    /// # Examples
+    /// ```
+    /// use rustyknife::mime::parse_message;
+    ///
+    /// let message = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+    ///                               --b\r\nContent-Type: multipart/alternative; boundary=c\r\n\r\n\
+    ///                               --c\r\n\r\nplain\r\n--c--\r\n\
+    ///                               --b\r\nContent-Type: image/png\r\n\r\n\r\n--b--\r\n");
+    /// let types: Vec<_> = message.iter().map(|e| e.content_type.as_str()).collect();
+    ///
+    /// assert_eq!(types, ["multipart/mixed", "multipart/alternative", "text/plain", "image/png"]);
+    /// ```
+    pub fn iter(&self) -> Entities<'_, 'a> {
+        Entities { stack: vec![self] }
+    }
+}
+
+/// A message encapsulated in a `message/rfc822` or `message/global`
+/// part.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttachedMessage<'a> {
+    /// The offset of the message in the parent buffer.
+    pub offset: usize,
+    /// The raw message.
+    pub raw: &'a [u8],
+    /// The parsed message.
+    pub message: Entity<'a>,
+}
+
+/// Parse a message and extract the messages attached to it.
+///
+/// The attached messages are themselves not searched, call this
+/// again on their [`raw`](AttachedMessage::raw) content to do so.
+/// Attached messages with a base64 or quoted-printable transfer
+/// encoding are skipped since they do not appear verbatim in the
+/// parent buffer.
+/// # Examples
+/// ```
+/// use rustyknife::mime::attached_messages;
+///
+/// let input = b"Content-Type: multipart/report; boundary=b\r\n\r\n\
+///               --b\r\n\r\nDelivery failed\r\n\
+///               --b\r\nContent-Type: message/rfc822\r\n\r\nSubject: hi\r\n\r\noriginal\r\n--b--\r\n";
+/// let attached = attached_messages(input);
+///
+/// assert_eq!(attached[0].offset, 107);
+/// assert_eq!(attached[0].raw, b"Subject: hi\r\n\r\noriginal");
+/// assert_eq!(attached[0].message.header(b"Subject"), Some(&b" hi"[..]));
+/// ```
+pub fn attached_messages(input: &[u8]) -> Vec<AttachedMessage> {
+    parse_message(input).iter()
+        .filter(|e| matches!(e.content_type.as_str(), "message/rfc822" | "message/global"))
+        .filter(|e| !matches!(e.transfer_encoding(), ContentTransferEncoding::Base64 | ContentTransferEncoding::QuotedPrintable))
+        .map(|e| AttachedMessage {
+            offset: e.body.as_ptr() as usize - input.as_ptr() as usize,
+            raw: e.body,
+            message: parse_message(e.body),
+        })
+        .collect()
+}
+
+fn strip_angle_brackets(id: &str) -> Option<String> {
+    let id = id.trim();
+    let id = id.strip_prefix('<').and_then(|i| i.strip_suffix('>')).unwrap_or(id);
+
+    if id.is_empty() { None } else { Some(id.into()) }
+}
+
+/// A file attached to a message, see [`Entity::attachments`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attachment<'e, 'a> {
+    /// The file name, see [`Entity::filename`].
+    pub filename: Option<String>,
+    /// The lowercase MIME type.
+    pub content_type: &'e str,
+    /// The size in bytes of the decoded content.
+    pub size: usize,
+    /// The `"Content-ID"`, without angle brackets.
+    pub content_id: Option<String>,
+    /// The `"Content-Disposition"`.
+    pub disposition: Option<ContentDisposition>,
+    /// The attached entity.
+    pub entity: &'e Entity<'a>,
+}
+
+impl<'a> Entity<'a> {
+    /// The parsed `"Content-Disposition"` and its parameters.
+    pub fn disposition(&self) -> Option<(ContentDisposition, Vec<(String, String)>)> {
+        match self.header(b"Content-Disposition").map(content_disposition) {
+            Some(Ok((_, disposition))) => Some(disposition),
+            _ => None,
+        }
+    }
+
+    /// The `"Content-ID"`, without angle brackets.
+    pub fn content_id(&self) -> Option<String> {
+        strip_angle_brackets(&String::from_utf8_lossy(self.header(b"Content-ID")?))
+    }
+
+    /// The file name from the `filename` parameter of the
+    /// `"Content-Disposition"`, falling back to the `name` parameter of
+    /// the `"Content-Type"`.
+    ///
+    /// Directory components are removed, so that the name can be used
+    /// safely as a path.
+    pub fn filename(&self) -> Option<String> {
+        let disposition = self.disposition().and_then(|(_, params)| {
+            params.into_iter().find(|(n, _)| n.eq_ignore_ascii_case("filename")).map(|(_, v)| v)
+        });
+        let name = disposition.or_else(|| self.param("name").map(String::from))?;
+        let name = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+
+        if name.is_empty() || name == "." || name == ".." {
+            None
+        } else {
+            Some(name.into())
+        }
+    }
+
+    /// Iterate over the attachments in this entity tree.
+    ///
+    /// Attachments are the parts with an `attachment` disposition or a
+    /// file name, except multipart entities.
+    /// # Examples
+    /// ```
+    /// use rustyknife::mime::parse_message;
+    ///
+    /// let message = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+    ///                               --b\r\n\r\nSee attached.\r\n\
+    ///                               --b\r\nContent-Type: application/pdf\r\n\
+    ///                               Content-Disposition: attachment; filename*=utf-8''r%C3%A9sum%C3%A9.pdf\r\n\
+    ///                               Content-Transfer-Encoding: base64\r\n\r\nJVBERg==\r\n--b--\r\n");
+    /// let attachments: Vec<_> = message.attachments().collect();
+    ///
+    /// assert_eq!(attachments.len(), 1);
+    /// assert_eq!(attachments[0].filename.as_deref(), Some("résumé.pdf"));
+    /// assert_eq!(attachments[0].content_type, "application/pdf");
+    /// assert_eq!(attachments[0].size, 4);
+    /// ```
+    pub fn attachments(&self) -> impl Iterator<Item=Attachment<'_, 'a>> {
+        self.iter().filter(|e| !e.is_multipart()).filter_map(|entity| {
+            let disposition = entity.disposition().map(|(d, _)| d);
+            let filename = entity.filename();
+            if disposition != Some(ContentDisposition::Attachment) && filename.is_none() {
+                return None;
+            }
+
+            Some(Attachment {
+                filename,
+                content_type: &entity.content_type,
+                size: entity.decoded_body().len(),
+                content_id: entity.content_id(),
+                disposition,
+                entity,
+            })
+        })
+    }
+}
+
+/// A composite entity with a forbidden transfer encoding, see
+/// [`Entity::encoding_violations`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncodingViolation<'e, 'a> {
+    /// The offending entity.
+    pub entity: &'e Entity<'a>,
+    /// Its transfer encoding.
+    pub encoding: ContentTransferEncoding,
+}
+
+impl<'a> Entity<'a> {
+    /// Whether the transfer encoding is allowed for the type of this
+    /// entity.
+    ///
+    /// Multipart and `message/rfc822` entities may only use 7bit, 8bit
+    /// or binary, as specified in [RFC 2045 section 6.4].
+    /// `message/partial` and `message/external-body` are further
+    /// restricted to 7bit by [RFC 2046]. Other types accept any
+    /// encoding.
+    ///
+    /// [RFC 2045 section 6.4]: https://tools.ietf.org/html/rfc2045#section-6.4
+    /// [RFC 2046]: https://tools.ietf.org/html/rfc2046#section-5.2.2
+    pub fn has_valid_encoding(&self) -> bool {
+        use ContentTransferEncoding::*;
+
+        match (self.content_type.as_str(), self.transfer_encoding()) {
+            ("message/partial", enc) | ("message/external-body", enc) => enc == SevenBit,
+            ("message/rfc822", enc) => matches!(enc, SevenBit | EightBit | Binary),
+            (_, enc) if self.is_multipart() => matches!(enc, SevenBit | EightBit | Binary),
+            _ => true,
+        }
+    }
+
+    /// Find the entities in this tree with a transfer encoding that is
+    /// not allowed for their type, see [`Entity::has_valid_encoding`].
+    ///
+    /// Decoders disagree on how to handle such entities, which can be
+    /// abused to hide content from filters.
+    /// # Examples
+    /// ```
+    /// use rustyknife::mime::parse_message;
+    /// use rustyknife::rfc2231::ContentTransferEncoding;
+    ///
+    /// let message = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\n\
+    ///                               Content-Transfer-Encoding: base64\r\n\r\n\
+    ///                               --b\r\n\r\ntext\r\n--b--\r\n");
+    /// let violations = message.encoding_violations();
+    ///
+    /// assert_eq!(violations.len(), 1);
+    /// assert_eq!(violations[0].encoding, ContentTransferEncoding::Base64);
+    /// ```
+    pub fn encoding_violations(&self) -> Vec<EncodingViolation<'_, 'a>> {
+        self.iter().filter(|e| !e.has_valid_encoding())
+            .map(|entity| EncodingViolation { entity, encoding: entity.transfer_encoding() })
+            .collect()
+    }
+}
+
+/// Byte level statistics of a body, see [`body_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BodyStats {
+    /// The number of NUL bytes.
+    pub nul_bytes: usize,
+    /// The number of bytes above 127.
+    pub eight_bit_bytes: usize,
+    /// The length of the longest line, excluding the line ending.
+    pub max_line_len: usize,
+    /// The number of CRLF line endings.
+    pub crlf: usize,
+    /// The number of bare LF line endings.
+    pub bare_lf: usize,
+    /// The number of bare CR line endings.
+    pub bare_cr: usize,
+}
+
+impl BodyStats {
+    /// Whether all line endings are CRLF.
+    pub fn crlf_consistent(&self) -> bool {
+        self.bare_lf == 0 && self.bare_cr == 0
+    }
+
+    /// The least capable BODY type able to carry the data unencoded.
+    ///
+    /// 7BIT and 8BITMIME require CRLF line endings, lines of at most
+    /// 998 bytes and no NUL bytes, as specified in [RFC 6152].
+    ///
+    /// [RFC 6152]: https://tools.ietf.org/html/rfc6152#section-3
+    pub fn required_body_type(&self) -> BodyType {
+        if self.nul_bytes > 0 || self.max_line_len > 998 || !self.crlf_consistent() {
+            BodyType::BinaryMime
+        } else if self.eight_bit_bytes > 0 {
+            BodyType::EightBitMime
+        } else {
+            BodyType::SevenBit
+        }
+    }
+}
+
+/// Compute the [`BodyStats`] of `data`.
+/// # Examples
+/// ```
+/// use rustyknife::esmtp::BodyType;
+/// use rustyknife::mime::body_stats;
+///
+/// let stats = body_stats("café\r\nau lait\n\0".as_bytes());
+///
+/// assert_eq!((stats.nul_bytes, stats.eight_bit_bytes, stats.max_line_len), (1, 2, 7));
+/// assert_eq!((stats.crlf, stats.bare_lf, stats.bare_cr), (1, 1, 0));
+/// assert_eq!(stats.required_body_type(), BodyType::BinaryMime);
+/// ```
+pub fn body_stats(data: &[u8]) -> BodyStats {
+    let mut stats = BodyStats::default();
+    let mut line_len = 0;
+    let mut iter = data.iter().peekable();
+
+    while let Some(c) = iter.next() {
+        match c {
+            b'\r' if iter.peek() == Some(&&b'\n') => {
+                iter.next();
+                stats.crlf += 1;
+            },
+            b'\r' => stats.bare_cr += 1,
+            b'\n' => stats.bare_lf += 1,
+            c => {
+                if *c == 0 {
+                    stats.nul_bytes += 1;
+                } else if *c > 127 {
+                    stats.eight_bit_bytes += 1;
+                }
+                line_len += 1;
+                stats.max_line_len = stats.max_line_len.max(line_len);
+                continue;
+            },
+        }
+        line_len = 0;
+    }
+
+    stats
+}
+
+impl<'a> Entity<'a> {
+    /// The [`BodyStats`] of each leaf part in this tree, computed over
+    /// the decoded body.
+    /// # Examples
+    /// ```
+    /// use rustyknife::mime::parse_message;
+    ///
+    /// let message = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+    ///                               --b\r\n\r\ntext\r\n\
+    ///                               --b\r\nContent-Transfer-Encoding: base64\r\n\r\nAAE=\r\n--b--\r\n");
+    /// let stats: Vec<_> = message.part_stats().map(|(_, s)| s.nul_bytes).collect();
+    ///
+    /// assert_eq!(stats, [0, 1]);
+    /// ```
+    pub fn part_stats(&self) -> impl Iterator<Item=(&Entity<'a>, BodyStats)> {
+        self.iter().filter(|e| e.parts.is_empty() && !e.is_multipart())
+            .map(|e| (e, body_stats(&e.decoded_body())))
+    }
+}
+
+/// The text bodies of a message, see [`Entity::body_parts`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BodyParts<'e, 'a> {
+    /// The `text/plain` body.
+    pub text: Option<&'e Entity<'a>>,
+    /// The `text/html` body.
+    pub html: Option<&'e Entity<'a>>,
+}
+
+impl<'a> Entity<'a> {
+    /// Select the `text/plain` and `text/html` bodies to display, the
+    /// way mail clients do.
+    ///
+    /// In a `multipart/alternative`, the last alternative containing
+    /// the type is preferred. In a `multipart/related`, only the root
+    /// part given by the `start` parameter, or else the first part, is
+    /// considered. In other multipart types, the first part containing
+    /// the type is selected. Parts with an `attachment` disposition
+    /// and encapsulated messages are skipped.
+    /// # Examples
+    /// ```
+    /// use rustyknife::mime::parse_message;
+    ///
+    /// let message = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+    ///                               --b\r\nContent-Type: multipart/alternative; boundary=c\r\n\r\n\
+    ///                               --c\r\n\r\nplain\r\n\
+    ///                               --c\r\nContent-Type: text/html\r\n\r\n<p>html</p>\r\n--c--\r\n\
+    ///                               --b\r\nContent-Type: text/plain\r\nContent-Disposition: attachment\r\n\r\nfile\r\n--b--\r\n");
+    /// let bodies = message.body_parts();
+    ///
+    /// assert_eq!(bodies.text.unwrap().body, b"plain");
+    /// assert_eq!(bodies.html.unwrap().body, b"<p>html</p>");
+    /// ```
+    pub fn body_parts(&self) -> BodyParts<'_, 'a> {
+        BodyParts {
+            text: self.select_body("text/plain"),
+            html: self.select_body("text/html"),
+        }
+    }
+
+    fn select_body(&self, mtype: &str) -> Option<&Entity<'a>> {
+        match self.content_type.as_str() {
+            "multipart/alternative" => self.parts.iter().rev().find_map(|p| p.select_body(mtype)),
+            "multipart/related" => {
+                let start = self.param("start").and_then(strip_angle_brackets);
+                let root = start.and_then(|s| self.parts.iter().find(|p| p.content_id().as_ref() == Some(&s)));
+                root.or_else(|| self.parts.first())?.select_body(mtype)
+            },
+            _ if self.is_multipart() => self.parts.iter().find_map(|p| p.select_body(mtype)),
+            t if t == mtype && !matches!(self.disposition(), Some((ContentDisposition::Attachment, _))) => Some(self),
+            _ => None,
+        }
+    }
+}
+
+/// The iTIP method of a calendar part, as registered in [RFC 5546].
+///
+/// [RFC 5546]: https://tools.ietf.org/html/rfc5546#section-1.4
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(missing_docs)] // Variants are named after the registered values.
+pub enum CalendarMethod {
+    Publish,
+    Request,
+    Reply,
+    Add,
+    Cancel,
+    Refresh,
+    Counter,
+    DeclineCounter,
+    /// Any other value, uppercased.
+    Other(String),
+}
+
+impl From<&str> for CalendarMethod {
+    /// Values are matched case insensitively.
+    fn from(value: &str) -> Self {
+        match value.to_ascii_uppercase().as_str() {
+            "PUBLISH" => CalendarMethod::Publish,
+            "REQUEST" => CalendarMethod::Request,
+            "REPLY" => CalendarMethod::Reply,
+            "ADD" => CalendarMethod::Add,
+            "CANCEL" => CalendarMethod::Cancel,
+            "REFRESH" => CalendarMethod::Refresh,
+            "COUNTER" => CalendarMethod::Counter,
+            "DECLINECOUNTER" => CalendarMethod::DeclineCounter,
+            other => CalendarMethod::Other(other.into()),
+        }
+    }
+}
+
+impl<'a> Entity<'a> {
+    /// Whether this entity is a `text/calendar` part.
+    pub fn is_calendar(&self) -> bool {
+        self.content_type == "text/calendar"
+    }
+
+    /// The iTIP method from the `method` parameter of a calendar part.
+    pub fn calendar_method(&self) -> Option<CalendarMethod> {
+        self.param("method").filter(|_| self.is_calendar()).map(CalendarMethod::from)
+    }
+
+    /// Find the first calendar part in this entity tree, such as a
+    /// meeting invitation.
+    ///
+    /// Use [`Entity::decoded_text`] to get the iCalendar data.
+    /// # Examples
+    /// ```
+    /// use rustyknife::mime::{parse_message, CalendarMethod};
+    ///
+    /// let message = parse_message(b"Content-Type: multipart/alternative; boundary=b\r\n\r\n\
+    ///                               --b\r\n\r\nYou are invited\r\n\
+    ///                               --b\r\nContent-Type: text/calendar; method=REQUEST; charset=utf-8\r\n\
+    ///                               Content-Transfer-Encoding: base64\r\n\r\n\
+    ///                               QkVHSU46VkNBTEVOREFS\r\n--b--\r\n");
+    /// let calendar = message.find_calendar().unwrap();
+    ///
+    /// assert_eq!(calendar.calendar_method(), Some(CalendarMethod::Request));
+    /// assert_eq!(calendar.decoded_text(), "BEGIN:VCALENDAR");
+    /// ```
+    pub fn find_calendar(&self) -> Option<&Entity<'a>> {
+        self.iter().find(|e| e.is_calendar())
+    }
+}
+
+/// The security protocol of a [`Security`] structure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// S/MIME, [RFC 8551].
+    ///
+    /// [RFC 8551]: https://tools.ietf.org/html/rfc8551
+    Smime,
+    /// PGP/MIME, [RFC 3156].
+    ///
+    /// [RFC 3156]: https://tools.ietf.org/html/rfc3156
+    Pgp,
+    /// Any other protocol.
+    Other,
+}
+
+impl Protocol {
+    fn from_type(mtype: &str) -> Self {
+        match mtype.to_ascii_lowercase().as_str() {
+            "application/pkcs7-signature" | "application/x-pkcs7-signature" |
+            "application/pkcs7-mime" | "application/x-pkcs7-mime" => Protocol::Smime,
+            "application/pgp-signature" | "application/pgp-encrypted" => Protocol::Pgp,
+            _ => Protocol::Other,
+        }
+    }
+}
+
+/// The `smime-type` parameter of an `application/pkcs7-mime` entity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SmimeType {
+    /// `enveloped-data`, an encrypted message.
+    EnvelopedData,
+    /// `authEnveloped-data`, an authenticated encrypted message.
+    AuthEnvelopedData,
+    /// `signed-data`, an opaque signed message.
+    SignedData,
+    /// `compressed-data`, a compressed message.
+    CompressedData,
+    /// `certs-only`, a certificate management message.
+    CertsOnly,
+    /// Any other value, lowercased.
+    Other(String),
+}
+
+impl From<&str> for SmimeType {
+    /// Values are matched case insensitively.
+    fn from(value: &str) -> Self {
+        match value.to_ascii_lowercase().as_str() {
+            "enveloped-data" => SmimeType::EnvelopedData,
+            "authenveloped-data" => SmimeType::AuthEnvelopedData,
+            "signed-data" => SmimeType::SignedData,
+            "compressed-data" => SmimeType::CompressedData,
+            "certs-only" => SmimeType::CertsOnly,
+            other => SmimeType::Other(other.into()),
+        }
+    }
+}
+
+/// The structure of a signed or encrypted entity.
+///
+/// Only the MIME layout is recognized, no cryptographic operation is
+/// performed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Security<'e, 'a> {
+    /// `multipart/signed`, [RFC 1847].
+    ///
+    /// [RFC 1847]: https://tools.ietf.org/html/rfc1847#section-2.1
+    Signed {
+        /// The protocol, from the `protocol` parameter.
+        protocol: Protocol,
+        /// The `micalg` parameter.
+        micalg: Option<&'e str>,
+        /// The signed content.
+        payload: &'e Entity<'a>,
+        /// The signature.
+        signature: &'e Entity<'a>,
+    },
+    /// `multipart/encrypted`, [RFC 1847].
+    ///
+    /// [RFC 1847]: https://tools.ietf.org/html/rfc1847#section-2.2
+    Encrypted {
+        /// The protocol, from the `protocol` parameter.
+        protocol: Protocol,
+        /// The control information, such as the PGP version.
+        control: &'e Entity<'a>,
+        /// The encrypted content.
+        payload: &'e Entity<'a>,
+    },
+    /// `application/pkcs7-mime`, the payload is the entity itself.
+    Pkcs7 {
+        /// The `smime-type` parameter.
+        smime_type: Option<SmimeType>,
+    },
+}
+
+impl<'a> Entity<'a> {
+    /// Recognize the structure of a signed or encrypted entity.
+    ///
+    /// Returns `None` for other entities and for malformed
+    /// `multipart/signed` or `multipart/encrypted` entities, which
+    /// require a `protocol` parameter and exactly two parts.
+    /// # Examples
+    /// ```
+    /// use rustyknife::mime::{parse_message, Protocol, Security};
+    ///
+    /// let message = parse_message(b"Content-Type: multipart/signed; boundary=b;\r\n\
+    ///                               \tprotocol=\"application/pgp-signature\"; micalg=pgp-sha256\r\n\r\n\
+    ///                               --b\r\n\r\nsigned text\r\n\
+    ///                               --b\r\nContent-Type: application/pgp-signature\r\n\r\nsig\r\n--b--\r\n");
+    ///
+    /// match message.security() {
+    ///     Some(Security::Signed { protocol, micalg, payload, signature }) => {
+    ///         assert_eq!(protocol, Protocol::Pgp);
+    ///         assert_eq!(micalg, Some("pgp-sha256"));
+    ///         assert_eq!(payload.body, b"signed text");
+    ///         assert_eq!(signature.body, b"sig");
+    ///     },
+    ///     _ => panic!(),
+    /// }
+    /// ```
+    pub fn security(&self) -> Option<Security<'_, 'a>> {
+        match self.content_type.as_str() {
+            "multipart/signed" | "multipart/encrypted" => {
+                let protocol = Protocol::from_type(self.param("protocol")?);
+                match (self.content_type.as_str(), self.parts.as_slice()) {
+                    ("multipart/signed", [payload, signature]) => Some(Security::Signed {
+                        protocol, micalg: self.param("micalg"), payload, signature,
+                    }),
+                    ("multipart/encrypted", [control, payload]) => Some(Security::Encrypted { protocol, control, payload }),
+                    _ => None,
+                }
+            },
+            "application/pkcs7-mime" | "application/x-pkcs7-mime" => Some(Security::Pkcs7 {
+                smime_type: self.param("smime-type").map(SmimeType::from),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Depth first iterator over an entity tree, see [`Entity::iter`].
+#[derive(Debug)]
+pub struct Entities<'e, 'a> {
+    stack: Vec<&'e Entity<'a>>,
+}
+
+impl<'e, 'a> Iterator for Entities<'e, 'a> {
+    type Item = &'e Entity<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entity = self.stack.pop()?;
+        self.stack.extend(entity.parts.iter().rev());
+        Some(entity)
+    }
+}
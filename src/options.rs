@@ -0,0 +1,217 @@
+//! Runtime parser configuration
+//!
+//! [`ParserOptions`] groups the settings that would otherwise require
+//! choosing between [`Legacy`] and [`Intl`] at compile time or
+//! calling the `*_limited` variants of the decoders. The `*_with`
+//! entry points of [`rfc5322`] and [`rfc2231`] accept it.
+//! [`MimeOptions`] does the same for the [`mime`] parser.
+//!
+//! [`Legacy`]: crate::behaviour::Legacy
+//! [`Intl`]: crate::behaviour::Intl
+//! [`rfc5322`]: crate::rfc5322
+//! [`rfc2231`]: crate::rfc2231
+//! [`mime`]: crate::mime
+
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::limits::{DecodeError, DecoderLimits, Limit, MimeLimits};
+
+/// What to do when a MIME or ESMTP parameter appears more than once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Keep the first occurrence.
+    KeepFirst,
+    /// Keep the last occurrence.
+    KeepLast,
+    /// Fail with [`DecodeError::DuplicateParameter`], or
+    /// [`ParamError::Duplicate`] for ESMTP parameters.
+    ///
+    /// [`ParamError::Duplicate`]: crate::esmtp::ParamError::Duplicate
+    Reject,
+}
+
+/// Settings for the `*_with` parser entry points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParserOptions {
+    /// Resource limits applied while decoding.
+    pub limits: DecoderLimits,
+    /// Accept [RFC 6532] UTF-8 in headers, as with [`Intl`].
+    ///
+    /// [RFC 6532]: https://tools.ietf.org/html/rfc6532
+    /// [`Intl`]: crate::behaviour::Intl
+    pub allow_utf8: bool,
+    /// Decode [RFC 2047] encoded words inside quoted strings, such as
+    /// display names and MIME parameter values.
+    ///
+    /// This is not allowed by the RFC but common in the wild. Defaults
+    /// to the `quoted-string-rfc2047` feature.
+    ///
+    /// [RFC 2047]: https://tools.ietf.org/html/rfc2047
+    pub quoted_string_rfc2047: bool,
+    /// Encoding used when an encoded word or parameter declares an
+    /// unknown charset.
+    ///
+    /// Encoded words in the display names of address headers always
+    /// fall back to UTF-8.
+    pub charset_fallback: &'static Encoding,
+    /// Maximum nesting level of comments in structured headers.
+    pub max_comment_depth: usize,
+    /// Handling of repeated MIME parameters.
+    pub duplicate_params: DuplicatePolicy,
+    /// Keep the addresses of an invalid address list that do parse,
+    /// as with [`parse_address_headers`]. The list is only an error if
+    /// no address could be recovered.
+    ///
+    /// [`parse_address_headers`]: crate::rfc5322::parse_address_headers
+    pub recover_addresses: bool,
+}
+
+impl Default for ParserOptions {
+    /// Same behavior as the plain parsers with [`Intl`], with the
+    /// default [`DecoderLimits`].
+    ///
+    /// [`Intl`]: crate::behaviour::Intl
+    fn default() -> Self {
+        ParserOptions {
+            limits: DecoderLimits::default(),
+            allow_utf8: true,
+            quoted_string_rfc2047: cfg!(feature = "quoted-string-rfc2047"),
+            charset_fallback: UTF_8,
+            max_comment_depth: 32,
+            duplicate_params: DuplicatePolicy::KeepLast,
+            recover_addresses: false,
+        }
+    }
+}
+
+/// Settings for [`parse_message_with`].
+///
+/// The defaults follow [RFC 2046]. The tolerances match what common
+/// mail clients render for broken multipart bodies, so that filters
+/// see the same parts as the recipient.
+///
+/// [`parse_message_with`]: crate::mime::parse_message_with
+/// [RFC 2046]: https://tools.ietf.org/html/rfc2046#section-5.1.1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MimeOptions {
+    /// Resource limits applied while splitting.
+    pub limits: MimeLimits,
+    /// Keep the last part of a multipart body that lacks a close
+    /// delimiter. It runs to the end of the body. Otherwise, it is
+    /// dropped.
+    pub keep_unterminated_part: bool,
+    /// Recognize delimiter lines that start with spaces or tabs.
+    pub indented_delimiters: bool,
+    /// Match delimiters against the boundary parameter case
+    /// insensitively.
+    pub boundary_ignore_case: bool,
+}
+
+impl Default for MimeOptions {
+    /// Strict delimiter matching, keeping unterminated parts, with the
+    /// default [`MimeLimits`].
+    fn default() -> Self {
+        MimeOptions {
+            limits: MimeLimits::default(),
+            keep_unterminated_part: true,
+            indented_delimiters: false,
+            boundary_ignore_case: false,
+        }
+    }
+}
+
+/// Settings for [`validate_address_with`].
+///
+/// [`validate_address_with`]: crate::rfc5321::validate_address_with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddressOptions {
+    /// Accept UTF-8 in the local part and domain, as with [`Intl`].
+    ///
+    /// [`Intl`]: crate::behaviour::Intl
+    pub allow_utf8: bool,
+    /// Accept address literals such as `"bob@[192.0.2.1]"`.
+    pub allow_address_literals: bool,
+    /// Accept quoted local parts such as `"\"bob smith\"@example.org"`.
+    pub allow_quoted_local_part: bool,
+    /// Require at least one dot in domain names.
+    pub require_dot_in_domain: bool,
+    /// Maximum length of the whole address in bytes.
+    pub max_len: usize,
+    /// Maximum length of the local part in bytes, including quotes.
+    pub max_local_part_len: usize,
+    /// Maximum length of the domain part in bytes.
+    pub max_domain_len: usize,
+}
+
+impl AddressOptions {
+    /// Settings for addresses typed into forms: no literals nor
+    /// quoted local parts, a dotted domain and the [RFC 5321] length
+    /// limits.
+    ///
+    /// [RFC 5321]: https://tools.ietf.org/html/rfc5321#section-4.5.3.1
+    pub fn strict() -> Self {
+        AddressOptions {
+            allow_utf8: true,
+            allow_address_literals: false,
+            allow_quoted_local_part: false,
+            require_dot_in_domain: true,
+            max_len: 254,
+            max_local_part_len: 64,
+            max_domain_len: 255,
+        }
+    }
+}
+
+impl Default for AddressOptions {
+    /// Same behavior as [`validate_address`] with [`Intl`].
+    ///
+    /// [`validate_address`]: crate::rfc5321::validate_address
+    /// [`Intl`]: crate::behaviour::Intl
+    fn default() -> Self {
+        AddressOptions {
+            allow_utf8: true,
+            allow_address_literals: true,
+            allow_quoted_local_part: true,
+            require_dot_in_domain: false,
+            max_len: usize::MAX,
+            max_local_part_len: usize::MAX,
+            max_domain_len: usize::MAX,
+        }
+    }
+}
+
+// Reject inputs with comments nested deeper than `max` before they
+// reach the recursive comment parser.
+pub(crate) fn check_comment_depth(input: &[u8], max: usize) -> Result<(), DecodeError> {
+    let mut depth = 0usize;
+    let mut quoted = false;
+    let mut iter = input.iter();
+
+    while let Some(c) = iter.next() {
+        match c {
+            b'\\' => { iter.next(); }
+            b'"' if depth == 0 => quoted = !quoted,
+            b'(' if !quoted => {
+                depth += 1;
+                if depth > max {
+                    return Err(DecodeError::LimitExceeded(Limit::CommentDepth));
+                }
+            }
+            b')' if !quoted => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+// Reject inputs with more encoded words than allowed before they reach
+// the parsers that decode them as they go. The `"=?="` ending a padded
+// base64 word is not the start of another word.
+pub(crate) fn check_encoded_words(input: &[u8], limits: &DecoderLimits) -> Result<(), DecodeError> {
+    if input.windows(3).filter(|w| w[..2] == *b"=?" && w[2] != b'=').count() > limits.max_encoded_words {
+        Err(DecodeError::LimitExceeded(Limit::EncodedWords))
+    } else {
+        Ok(())
+    }
+}
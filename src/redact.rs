@@ -0,0 +1,149 @@
+//! Header redaction for sharing messages
+//!
+//! Masks personal information in the header section of a message
+//! while keeping its structure: field names, folding and the length
+//! of every value are preserved and the redacted headers still parse.
+//! This makes it possible to attach problematic messages to bug
+//! reports.
+
+use std::net::IpAddr;
+use std::str;
+
+use crate::behaviour::Intl;
+use crate::headersection::{header_section, HeaderField};
+use crate::lexer::{tokens, TokenKind};
+use crate::rewrite::ADDRESS_HEADERS;
+
+// Headers holding addresses besides `ADDRESS_HEADERS`.
+const OTHER_ADDRESS_HEADERS: &[&str] = &[
+    "Return-Path", "Delivered-To", "X-Original-To", "Envelope-To", "Received",
+];
+
+/// Selects what to redact.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Redaction {
+    /// Names of the headers whose value is entirely masked.
+    pub headers: Vec<String>,
+    /// Mask the local part of addresses in address and trace headers.
+    pub local_parts: bool,
+    /// Mask IP addresses in `"Received:"` headers.
+    pub received_ips: bool,
+}
+
+fn name_in(name: &[u8], list: &[&str]) -> bool {
+    list.iter().any(|h| h.as_bytes().eq_ignore_ascii_case(name))
+}
+
+// Replace every byte that is not whitespace.
+fn mask_all(value: &mut [u8]) {
+    for c in value.iter_mut().filter(|c| !b" \t\r\n".contains(c)) {
+        *c = b'x';
+    }
+}
+
+// Mask the atoms and quoted strings directly preceding each `"@"`.
+fn mask_local_parts(value: &mut [u8]) {
+    let toks: Vec<_> = tokens::<Intl>(value).map(|t| (t.kind, t.span)).collect();
+
+    for (i, (kind, _)) in toks.iter().enumerate() {
+        if *kind != TokenKind::Special(b'@') {
+            continue;
+        }
+        for (kind, span) in toks[..i].iter().rev() {
+            match kind {
+                TokenKind::Atom => value[span.clone()].iter_mut().for_each(|c| *c = b'x'),
+                TokenKind::QuotedString => value[span.start+1..span.end-1].iter_mut().for_each(|c| *c = b'x'),
+                TokenKind::Special(b'.') => (),
+                _ => break,
+            }
+        }
+    }
+}
+
+// Zero the digits of every IP address.
+fn mask_ips(value: &mut [u8]) {
+    let is_ip_char = |c: &u8| c.is_ascii_hexdigit() || *c == b'.' || *c == b':';
+    let mut pos = 0;
+
+    while pos < value.len() {
+        let len = value[pos..].iter().take_while(|c| is_ip_char(c)).count();
+        if len == 0 {
+            pos += 1;
+            continue;
+        }
+
+        let mut start = pos;
+        // Skip the tag of "[IPv6:...]" address literals.
+        if value[..pos].ends_with(b"IPv") && value[pos..].starts_with(b"6:") {
+            start += 2;
+        }
+        let run = &mut value[start..pos+len];
+        if str::from_utf8(run).ok().and_then(|r| r.parse::<IpAddr>().ok()).is_some() {
+            run.iter_mut().filter(|c| c.is_ascii_hexdigit()).for_each(|c| *c = b'0');
+        }
+        pos += len;
+    }
+}
+
+impl Redaction {
+    /// Redact a single header field, returning it terminated by CRLF.
+    pub fn redact_field(&self, field: &HeaderField) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        match field {
+            Ok((name, value)) => {
+                let mut value = value.to_vec();
+
+                if self.headers.iter().any(|h| h.as_bytes().eq_ignore_ascii_case(name)) {
+                    mask_all(&mut value);
+                } else {
+                    if self.local_parts && (name_in(name, ADDRESS_HEADERS) || name_in(name, OTHER_ADDRESS_HEADERS)) {
+                        mask_local_parts(&mut value);
+                    }
+                    if self.received_ips && name.eq_ignore_ascii_case(b"Received") {
+                        mask_ips(&mut value);
+                    }
+                }
+                out.extend_from_slice(name);
+                out.push(b':');
+                out.extend_from_slice(&value);
+            }
+            Err(raw) => out.extend_from_slice(raw),
+        }
+        out.extend_from_slice(b"\r\n");
+
+        out
+    }
+
+    /// Redact the header section of `message`.
+    ///
+    /// The body is copied unchanged. The output has the same length as
+    /// the input. Fails if the header section cannot be split, for
+    /// example when it is not terminated by an empty line.
+    /// # Examples
+    /// ```
+    /// use rustyknife::redact::Redaction;
+    ///
+    /// let redaction = Redaction { headers: vec!["Subject".into()], local_parts: true, received_ips: true };
+    /// let message = b"Received: from a.example ([192.0.2.17]) by b.example for <bob@b.example>;\r\n 1 Jun 2021 10:00:00 +0000\r\n\
+    ///                 From: \"Bob\" <bob.smith@b.example>\r\n\
+    ///                 Subject: Private matter\r\n\
+    ///                 \r\n\
+    ///                 Body\r\n";
+    ///
+    /// assert_eq!(redaction.redact_message(message).unwrap(), &b"Received: from a.example ([000.0.0.00]) by b.example for <xxx@b.example>;\r\n 1 Jun 2021 10:00:00 +0000\r\n\
+    ///                 From: \"Bob\" <xxx.xxxxx@b.example>\r\n\
+    ///                 Subject: xxxxxxx xxxxxx\r\n\
+    ///                 \r\n\
+    ///                 Body\r\n"[..]);
+    /// ```
+    #[allow(clippy::result_unit_err)]
+    pub fn redact_message(&self, message: &[u8]) -> Result<Vec<u8>, ()> {
+        let (body, fields) = header_section(message).map_err(|_| ())?;
+        let mut out: Vec<u8> = fields.iter().flat_map(|f| self.redact_field(f)).collect();
+
+        out.extend_from_slice(&message[out.len()..message.len() - body.len()]);
+        out.extend_from_slice(body);
+        Ok(out)
+    }
+}
@@ -0,0 +1,383 @@
+//! [SMTP replies]
+//!
+//! Reply text is restricted to US-ASCII unless SMTPUTF8 was
+//! negotiated for the session, as specified in [RFC 6531]. Pass
+//! [`Legacy`] or [`Intl`] accordingly when parsing or building
+//! replies.
+//!
+//! [SMTP replies]: https://tools.ietf.org/html/rfc5321#section-4.2
+//! [RFC 6531]: https://tools.ietf.org/html/rfc6531#section-3.7.4
+//! [`Legacy`]: crate::behaviour::Legacy
+//! [`Intl`]: crate::behaviour::Intl
+
+use std::fmt::{self, Display};
+use std::str;
+
+use nom::bytes::complete::{tag, take_while_m_n};
+use nom::combinator::{map, opt, verify};
+use nom::multi::many0;
+use nom::sequence::{pair, preceded, terminated};
+
+use crate::rfc3463::{enhanced_status_code, EnhancedStatusCode};
+use crate::rfc5234::crlf;
+use crate::rfc5321::UTF8Policy;
+use crate::util::*;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+// Maximum length of a reply line, including the code and the CRLF.
+// See RFC 5321 section 4.5.3.1.5.
+const MAX_LINE: usize = 512;
+
+/// Outcome indicated by the first digit of a [`ReplyCode`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplyClass {
+    /// `2yz`, the command succeeded.
+    PositiveCompletion,
+    /// `3yz`, the command was accepted and more input is expected.
+    PositiveIntermediate,
+    /// `4yz`, the command failed and may succeed if repeated later.
+    TransientNegative,
+    /// `5yz`, the command failed and should not be repeated as is.
+    PermanentNegative,
+}
+
+/// Subject indicated by the second digit of a [`ReplyCode`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplyCategory {
+    /// `x0z`, syntax errors and unimplemented commands.
+    Syntax,
+    /// `x1z`, replies to requests for information.
+    Information,
+    /// `x2z`, replies about the transmission channel.
+    Connections,
+    /// `x3z` and `x4z`, unspecified.
+    Unspecified,
+    /// `x5z`, status of the mail system for the requested action.
+    MailSystem,
+}
+
+/// An SMTP reply code such as 250.
+///
+/// Any number can be stored, [`is_valid`](Self::is_valid) tells
+/// whether it is allowed in a reply. Codes produced by [`reply`]
+/// are always valid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReplyCode(pub u16);
+
+#[allow(missing_docs)] // Constants are named after the RFC 5321 reply texts.
+impl ReplyCode {
+    pub const SYSTEM_STATUS: ReplyCode = ReplyCode(211);
+    pub const HELP: ReplyCode = ReplyCode(214);
+    pub const SERVICE_READY: ReplyCode = ReplyCode(220);
+    pub const SERVICE_CLOSING: ReplyCode = ReplyCode(221);
+    pub const AUTH_SUCCEEDED: ReplyCode = ReplyCode(235);
+    pub const OK: ReplyCode = ReplyCode(250);
+    pub const USER_NOT_LOCAL_WILL_FORWARD: ReplyCode = ReplyCode(251);
+    pub const CANNOT_VRFY: ReplyCode = ReplyCode(252);
+    pub const AUTH_CHALLENGE: ReplyCode = ReplyCode(334);
+    pub const START_MAIL_INPUT: ReplyCode = ReplyCode(354);
+    pub const SERVICE_NOT_AVAILABLE: ReplyCode = ReplyCode(421);
+    pub const MAILBOX_BUSY: ReplyCode = ReplyCode(450);
+    pub const LOCAL_ERROR: ReplyCode = ReplyCode(451);
+    pub const INSUFFICIENT_STORAGE: ReplyCode = ReplyCode(452);
+    pub const PARAMETERS_NOT_ACCOMMODATED: ReplyCode = ReplyCode(455);
+    pub const SYNTAX_ERROR: ReplyCode = ReplyCode(500);
+    pub const PARAMETER_SYNTAX_ERROR: ReplyCode = ReplyCode(501);
+    pub const NOT_IMPLEMENTED: ReplyCode = ReplyCode(502);
+    pub const BAD_SEQUENCE: ReplyCode = ReplyCode(503);
+    pub const PARAMETER_NOT_IMPLEMENTED: ReplyCode = ReplyCode(504);
+    pub const MAILBOX_UNAVAILABLE: ReplyCode = ReplyCode(550);
+    pub const USER_NOT_LOCAL: ReplyCode = ReplyCode(551);
+    pub const EXCEEDED_STORAGE: ReplyCode = ReplyCode(552);
+    pub const MAILBOX_NAME_NOT_ALLOWED: ReplyCode = ReplyCode(553);
+    pub const TRANSACTION_FAILED: ReplyCode = ReplyCode(554);
+    pub const PARAMETERS_NOT_RECOGNIZED: ReplyCode = ReplyCode(555);
+}
+
+impl ReplyCode {
+    /// Whether the code is between 200 and 559 with a second digit
+    /// of at most 5.
+    pub fn is_valid(self) -> bool {
+        (200..600).contains(&self.0) && self.0 / 10 % 10 <= 5
+    }
+
+    /// The class of a valid code.
+    /// # Examples
+    /// ```
+    /// use rustyknife::reply::{ReplyCategory, ReplyClass, ReplyCode};
+    ///
+    /// assert_eq!(ReplyCode::START_MAIL_INPUT.class(), Some(ReplyClass::PositiveIntermediate));
+    /// assert_eq!(ReplyCode(421).category(), Some(ReplyCategory::Connections));
+    /// assert!(ReplyCode::MAILBOX_BUSY.is_transient());
+    /// assert_eq!(ReplyCode(199).class(), None);
+    /// ```
+    pub fn class(self) -> Option<ReplyClass> {
+        if !self.is_valid() {
+            return None;
+        }
+        Some(match self.0 / 100 {
+            2 => ReplyClass::PositiveCompletion,
+            3 => ReplyClass::PositiveIntermediate,
+            4 => ReplyClass::TransientNegative,
+            _ => ReplyClass::PermanentNegative,
+        })
+    }
+
+    /// The category of a valid code.
+    pub fn category(self) -> Option<ReplyCategory> {
+        if !self.is_valid() {
+            return None;
+        }
+        Some(match self.0 / 10 % 10 {
+            0 => ReplyCategory::Syntax,
+            1 => ReplyCategory::Information,
+            2 => ReplyCategory::Connections,
+            3 | 4 => ReplyCategory::Unspecified,
+            _ => ReplyCategory::MailSystem,
+        })
+    }
+
+    /// `2yz`
+    pub fn is_positive_completion(self) -> bool {
+        self.class() == Some(ReplyClass::PositiveCompletion)
+    }
+
+    /// `3yz`
+    pub fn is_positive_intermediate(self) -> bool {
+        self.class() == Some(ReplyClass::PositiveIntermediate)
+    }
+
+    /// `4yz`
+    pub fn is_transient(self) -> bool {
+        self.class() == Some(ReplyClass::TransientNegative)
+    }
+
+    /// `5yz`
+    pub fn is_permanent(self) -> bool {
+        self.class() == Some(ReplyClass::PermanentNegative)
+    }
+}
+
+impl From<u16> for ReplyCode {
+    fn from(code: u16) -> Self {
+        ReplyCode(code)
+    }
+}
+
+impl From<ReplyCode> for u16 {
+    fn from(code: ReplyCode) -> Self {
+        code.0
+    }
+}
+
+impl PartialEq<u16> for ReplyCode {
+    fn eq(&self, other: &u16) -> bool {
+        self.0 == *other
+    }
+}
+
+impl Display for ReplyCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A server reply, possibly spanning multiple lines.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Reply {
+    /// The three digit reply code.
+    pub code: ReplyCode,
+    /// The text of each line, without the code and separator.
+    pub lines: Vec<String>,
+}
+
+fn reply_code(input: &[u8]) -> NomResult<ReplyCode> {
+    verify(map(take_while_m_n(3, 3, |c: u8| c.is_ascii_digit()),
+               |c| ReplyCode(str::from_utf8(c).unwrap().parse().unwrap())),
+           |c| c.is_valid())(input)
+}
+
+fn textstring<P: UTF8Policy>(input: &[u8]) -> NomResult<&[u8]> {
+    recognize_many0(P::reply_text_char)(input)
+}
+
+fn text<P: UTF8Policy>(input: &[u8]) -> NomResult<String> {
+    map(textstring::<P>, |t| str::from_utf8(t).unwrap().into())(input)
+}
+
+/// Parse a complete reply.
+///
+/// All lines must carry the same reply code.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::{Intl, Legacy};
+/// use rustyknife::reply::{reply, Reply, ReplyCode};
+///
+/// let (_, parsed) = reply::<Legacy>(b"250-mx.example.org\r\n250 SMTPUTF8\r\n").unwrap();
+/// assert_eq!(parsed, Reply { code: ReplyCode::OK, lines: vec!["mx.example.org".into(), "SMTPUTF8".into()] });
+///
+/// assert!(reply::<Legacy>("550 Boîte inconnue\r\n".as_bytes()).is_err());
+/// assert!(reply::<Intl>("550 Boîte inconnue\r\n".as_bytes()).is_ok());
+/// ```
+pub fn reply<P: UTF8Policy>(input: &[u8]) -> NomResult<Reply> {
+    map(verify(pair(many0(pair(reply_code, terminated(preceded(tag("-"), text::<P>), crlf))),
+                    pair(reply_code, terminated(opt(preceded(tag(" "), text::<P>)), crlf))),
+               |(cont, (code, _))| cont.iter().all(|(c, _)| c == code)),
+        |(cont, (code, last))| {
+            let mut lines: Vec<String> = cont.into_iter().map(|(_, l)| l).collect();
+            lines.push(last.unwrap_or_default());
+            Reply { code, lines }
+        })(input)
+}
+
+/// Parse the pipelined replies at the start of `input`.
+///
+/// Parsing stops at the first reply that is incomplete or invalid,
+/// the remaining input starts there. A client reading from the
+/// network keeps the remainder and retries once more data arrived.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Legacy;
+/// use rustyknife::reply::replies;
+///
+/// let (rem, parsed) = replies::<Legacy>(b"250 OK\r\n550-No such\r\n550 user\r\n354 Go").unwrap();
+/// assert_eq!(parsed.iter().map(|r| r.code.0).collect::<Vec<_>>(), [250, 550]);
+/// assert_eq!(parsed[1].lines, ["No such", "user"]);
+/// assert_eq!(rem, b"354 Go");
+/// ```
+pub fn replies<P: UTF8Policy>(input: &[u8]) -> NomResult<Vec<Reply>> {
+    many0(reply::<P>)(input)
+}
+
+impl Reply {
+    /// Build a reply with one line of text per element of `lines`.
+    pub fn new<C: Into<ReplyCode>, T: Into<String>>(code: C, lines: Vec<T>) -> Self {
+        Reply { code: code.into(), lines: lines.into_iter().map(Into::into).collect() }
+    }
+
+    /// Build a reply with the enhanced status code `status` at the
+    /// start of each line.
+    ///
+    /// `text` is split on line feeds and wrapped at spaces so that
+    /// every line fits in 512 octets. Words that are too long are
+    /// split. The class of `status` should match the first digit of
+    /// `code`.
+    /// # Examples
+    /// ```
+    /// use rustyknife::behaviour::Legacy;
+    /// use rustyknife::reply::Reply;
+    ///
+    /// let reply = Reply::with_status(550, "5.7.1".parse().unwrap(), "Message rejected\nSee https://example.org/policy");
+    /// assert_eq!(reply.to_smtp::<Legacy>().unwrap(),
+    ///            b"550-5.7.1 Message rejected\r\n550 5.7.1 See https://example.org/policy\r\n");
+    ///
+    /// let long = Reply::with_status(250, "2.0.0".parse().unwrap(), &"word ".repeat(200));
+    /// assert_eq!(long.lines.len(), 2);
+    /// assert!(long.to_smtp::<Legacy>().is_ok());
+    /// ```
+    pub fn with_status<C: Into<ReplyCode>>(code: C, status: EnhancedStatusCode, text: &str) -> Self {
+        let status = status.to_string();
+        // Code, separator, status, space and CRLF.
+        let room = MAX_LINE - (3 + 1 + status.len() + 1 + 2);
+        let mut lines = Vec::new();
+
+        for paragraph in text.split('\n').map(|p| p.trim_end_matches('\r')) {
+            let mut line = String::new();
+            for word in paragraph.split(' ') {
+                if !line.is_empty() && line.len() + 1 + word.len() > room {
+                    lines.push(std::mem::take(&mut line));
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                for c in word.chars() {
+                    if line.len() + c.len_utf8() > room {
+                        lines.push(std::mem::take(&mut line));
+                    }
+                    line.push(c);
+                }
+            }
+            lines.push(line);
+        }
+
+        Reply {
+            code: code.into(),
+            lines: lines.into_iter().map(|l| if l.is_empty() { status.clone() } else { format!("{} {}", status, l) }).collect(),
+        }
+    }
+
+    /// The [RFC 2034] enhanced status code at the start of the first
+    /// line.
+    ///
+    /// The code must be followed by a space or end the line and its
+    /// class must match the first digit of the reply code.
+    ///
+    /// [RFC 2034]: https://tools.ietf.org/html/rfc2034
+    /// # Examples
+    /// ```
+    /// use rustyknife::reply::Reply;
+    ///
+    /// let reply = Reply::new(550, vec!["5.1.1 No such user"]);
+    /// assert_eq!(reply.enhanced_code().unwrap().to_string(), "5.1.1");
+    ///
+    /// assert_eq!(Reply::new(250, vec!["5.1.1 Mismatched"]).enhanced_code(), None);
+    /// assert_eq!(Reply::new(250, vec!["2.0.0OK"]).enhanced_code(), None);
+    /// ```
+    pub fn enhanced_code(&self) -> Option<EnhancedStatusCode> {
+        let (rem, code) = enhanced_status_code(self.lines.first()?.as_bytes()).ok()?;
+
+        if (rem.is_empty() || rem[0] == b' ') && u16::from(code.class_digit()) == self.code.0 / 100 {
+            Some(code)
+        } else {
+            None
+        }
+    }
+
+    /// Serialize the reply, checking the code and that the text is
+    /// allowed by the policy `P`.
+    ///
+    /// Fails on an invalid reply code, when a line is over 512 octets
+    /// or when it contains characters that the session does not
+    /// permit, such as UTF-8 without SMTPUTF8 or line breaks.
+    /// # Examples
+    /// ```
+    /// use rustyknife::behaviour::{Intl, Legacy};
+    /// use rustyknife::reply::Reply;
+    ///
+    /// let reply = Reply::new(250, vec!["mx.example.org", "8BITMIME"]);
+    /// assert_eq!(reply.to_smtp::<Legacy>().unwrap(), b"250-mx.example.org\r\n250 8BITMIME\r\n");
+    ///
+    /// let reply = Reply::new(550, vec!["Boîte inconnue"]);
+    /// assert!(reply.to_smtp::<Legacy>().is_err());
+    /// assert_eq!(reply.to_smtp::<Intl>().unwrap(), "550 Boîte inconnue\r\n".as_bytes());
+    /// ```
+    #[allow(clippy::result_unit_err)]
+    pub fn to_smtp<P: UTF8Policy>(&self) -> Result<Vec<u8>, ()> {
+        if !self.code.is_valid() {
+            return Err(());
+        }
+        let code = self.code.to_string();
+        if self.lines.iter().any(|l| code.len() + 1 + l.len() + 2 > MAX_LINE || exact!(l.as_bytes(), textstring::<P>).is_err()) {
+            return Err(());
+        }
+
+        let mut out = Vec::new();
+        let last = self.lines.len().saturating_sub(1);
+        for (i, line) in self.lines.iter().enumerate() {
+            out.extend_from_slice(code.as_bytes());
+            out.push(if i == last { b' ' } else { b'-' });
+            out.extend_from_slice(line.as_bytes());
+            out.extend_from_slice(b"\r\n");
+        }
+        if self.lines.is_empty() {
+            out.extend_from_slice(code.as_bytes());
+            out.extend_from_slice(b"\r\n");
+        }
+
+        Ok(out)
+    }
+}
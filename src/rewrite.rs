@@ -0,0 +1,259 @@
+//! Address rewriting in header sections
+//!
+//! Walks the address headers of a message and lets the caller
+//! replace any [`Mailbox`], as done by masquerading and domain
+//! migration gateways. Only the headers where an address changed are
+//! serialized again, all other fields are kept byte for byte.
+//!
+//! Milter implementations can turn the result into a
+//! [`HeaderChangeSet`] instead of sending the whole header section
+//! back to the MTA.
+
+use crate::headersection::HeaderField;
+use crate::rfc5322::{from, Address, AddressList, Mailbox, UTF8Policy};
+
+/// Names of the headers holding addresses, as handled by
+/// [`rewrite_addresses`].
+pub const ADDRESS_HEADERS: &[&str] = &[
+    "From", "Sender", "Reply-To", "To", "Cc", "Bcc",
+    "Resent-From", "Resent-Sender", "Resent-To", "Resent-Cc", "Resent-Bcc",
+];
+
+/// A header field after rewriting.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RewrittenField<'a> {
+    /// The field is left untouched.
+    Unchanged(HeaderField<'a>),
+    /// At least one address of the field was replaced. Contains the
+    /// header name and the new value, including its leading space.
+    Rewritten(&'a [u8], String),
+}
+
+impl<'a> RewrittenField<'a> {
+    /// Serialize the field, terminated by CRLF.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            RewrittenField::Unchanged(Ok((name, value))) => {
+                out.extend_from_slice(name);
+                out.push(b':');
+                out.extend_from_slice(value);
+            }
+            RewrittenField::Unchanged(Err(raw)) => out.extend_from_slice(raw),
+            RewrittenField::Rewritten(name, value) => {
+                out.extend_from_slice(name);
+                out.push(b':');
+                out.extend_from_slice(value.as_bytes());
+            }
+        }
+        out.extend_from_slice(b"\r\n");
+        out
+    }
+}
+
+fn is_address_header(name: &[u8]) -> bool {
+    ADDRESS_HEADERS.iter().any(|h| h.as_bytes().eq_ignore_ascii_case(name))
+}
+
+/// Rewrite the addresses of `headers` with `map`.
+///
+/// `map` is called with the header name and each mailbox of the
+/// headers listed in [`ADDRESS_HEADERS`], including group members.
+/// It returns the replacement mailbox or `None` to keep it.
+///
+/// Fields that fail to parse are left untouched. Rewritten values
+/// are serialized using only US-ASCII when possible, see
+/// [`AddressList::to_ascii_string`].
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::headersection::header_section;
+/// use rustyknife::rewrite::rewrite_addresses;
+/// use rustyknife::rfc5322::Mailbox;
+/// use rustyknife::types::{DomainPart, Mailbox as SMTPMailbox};
+///
+/// let (_, headers) = header_section(b"From: Bob <bob@old.example>\r\nSubject: hi\r\n\r\n").unwrap();
+/// let new_domain = DomainPart::from_smtp(b"new.example").unwrap();
+///
+/// let fields = rewrite_addresses::<Intl, _>(&headers, |_, mbox| {
+///     (mbox.address.domain_part().to_string() == "old.example").then(|| Mailbox {
+///         dname: mbox.dname.clone(),
+///         address: SMTPMailbox::from_parts(mbox.address.local_part().clone(), new_domain.clone()),
+///     })
+/// });
+/// let out: Vec<u8> = fields.iter().flat_map(|f| f.to_bytes()).collect();
+///
+/// assert_eq!(out, b"From: Bob <bob@new.example>\r\nSubject: hi\r\n");
+/// ```
+pub fn rewrite_addresses<'a, P, F>(headers: &[HeaderField<'a>], mut map: F) -> Vec<RewrittenField<'a>>
+    where P: UTF8Policy,
+          F: FnMut(&[u8], &Mailbox) -> Option<Mailbox>,
+{
+    headers.iter().map(|field| {
+        let (name, value) = match field {
+            Ok((name, value)) if is_address_header(name) => (*name, *value),
+            _ => return RewrittenField::Unchanged(*field),
+        };
+        let mut addresses = match from::<P>(value) {
+            Ok((b"", addresses)) => addresses,
+            _ => return RewrittenField::Unchanged(*field),
+        };
+
+        let mut changed = false;
+        for address in &mut addresses {
+            let mailboxes = match address {
+                Address::Mailbox(m) => std::slice::from_mut(m),
+                Address::Group(g) => &mut g.members[..],
+            };
+            for mailbox in mailboxes {
+                if let Some(new) = map(name, mailbox) {
+                    changed |= new != *mailbox;
+                    *mailbox = new;
+                }
+            }
+        }
+
+        if changed {
+            let list = AddressList(&addresses);
+            let value = list.to_ascii_string().unwrap_or_else(|_| list.to_string());
+            RewrittenField::Rewritten(name, format!(" {}", value))
+        } else {
+            RewrittenField::Unchanged(*field)
+        }
+    }).collect()
+}
+
+/// A single header edit, addressed as in the milter protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HeaderChange {
+    /// Append a header at the end of the section, `SMFIR_ADDHEADER`.
+    Add {
+        /// Header name.
+        name: String,
+        /// Header value.
+        value: String,
+    },
+    /// Insert a header before the field at `index`, counting from 0
+    /// over the whole section, `SMFIR_INSHEADER`.
+    Insert {
+        /// Position of the new header.
+        index: usize,
+        /// Header name.
+        name: String,
+        /// Header value.
+        value: String,
+    },
+    /// Replace the value of the `index`th occurrence of `name`,
+    /// counting from 1 and ignoring case, `SMFIR_CHGHEADER`.
+    Change {
+        /// Header name.
+        name: String,
+        /// Occurrence of the header.
+        index: usize,
+        /// New value.
+        value: String,
+    },
+    /// Remove the `index`th occurrence of `name`, sent as
+    /// `SMFIR_CHGHEADER` with an empty value.
+    Delete {
+        /// Header name.
+        name: String,
+        /// Occurrence of the header.
+        index: usize,
+    },
+}
+
+/// An ordered list of header edits.
+///
+/// Values are stored without the space following the colon, as
+/// expected by `smfi_chgheader` and friends.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeaderChangeSet {
+    /// The edits, in the order they must be sent.
+    pub changes: Vec<HeaderChange>,
+}
+
+fn milter_value(value: &str) -> String {
+    value.strip_prefix(' ').unwrap_or(value).into()
+}
+
+impl HeaderChangeSet {
+    /// Create an empty change set.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Whether there is nothing to change.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Append a header.
+    pub fn add(&mut self, name: &str, value: &str) -> &mut Self {
+        self.changes.push(HeaderChange::Add { name: name.into(), value: milter_value(value) });
+        self
+    }
+
+    /// Insert a header at position `index` of the section.
+    pub fn insert(&mut self, index: usize, name: &str, value: &str) -> &mut Self {
+        self.changes.push(HeaderChange::Insert { index, name: name.into(), value: milter_value(value) });
+        self
+    }
+
+    /// Change the `index`th occurrence of `name`, counting from 1.
+    pub fn change(&mut self, name: &str, index: usize, value: &str) -> &mut Self {
+        self.changes.push(HeaderChange::Change { name: name.into(), index, value: milter_value(value) });
+        self
+    }
+
+    /// Delete the `index`th occurrence of `name`, counting from 1.
+    pub fn delete(&mut self, name: &str, index: usize) -> &mut Self {
+        self.changes.push(HeaderChange::Delete { name: name.into(), index });
+        self
+    }
+
+    /// Build the change set matching the output of
+    /// [`rewrite_addresses`].
+    ///
+    /// Occurrences are counted over the fields that parsed, as the
+    /// MTA does not see invalid fields as headers.
+    /// # Examples
+    /// ```
+    /// use rustyknife::behaviour::Intl;
+    /// use rustyknife::headersection::header_section;
+    /// use rustyknife::rewrite::{rewrite_addresses, HeaderChange, HeaderChangeSet};
+    /// use rustyknife::rfc5322::Mailbox;
+    /// use rustyknife::types::Mailbox as SMTPMailbox;
+    ///
+    /// let (_, headers) = header_section(b"To: a@example.org\r\nto: b@example.org\r\n\r\n").unwrap();
+    /// let fields = rewrite_addresses::<Intl, _>(&headers, |_, mbox| {
+    ///     (mbox.address.to_string() == "b@example.org").then(|| Mailbox {
+    ///         dname: None,
+    ///         address: SMTPMailbox::from_smtp(b"c@example.org").unwrap(),
+    ///     })
+    /// });
+    ///
+    /// assert_eq!(HeaderChangeSet::from_rewritten(&fields).changes, [
+    ///     HeaderChange::Change { name: "to".into(), index: 2, value: "c@example.org".into() },
+    /// ]);
+    /// ```
+    pub fn from_rewritten(fields: &[RewrittenField]) -> Self {
+        let mut set = HeaderChangeSet::new();
+        let mut seen: Vec<&[u8]> = Vec::new();
+
+        for field in fields {
+            let name = match field {
+                RewrittenField::Unchanged(Ok((name, _))) | RewrittenField::Rewritten(name, _) => *name,
+                RewrittenField::Unchanged(Err(_)) => continue,
+            };
+            seen.push(name);
+
+            if let RewrittenField::Rewritten(_, value) = field {
+                let index = seen.iter().filter(|n| n.eq_ignore_ascii_case(name)).count();
+                set.change(&String::from_utf8_lossy(name), index, value);
+            }
+        }
+
+        set
+    }
+}
@@ -58,9 +58,9 @@ fn _encoded_word(input: &[u8]) -> NomResult<(Cow<str>, Vec<u8>)> {
         })(input)
 }
 
-fn decode_charset((charset, bytes): (Cow<str>, Vec<u8>)) -> String
+fn decode_charset((charset, bytes): (Cow<str>, Vec<u8>), fallback: &'static Encoding) -> String
 {
-    Encoding::for_label(charset.as_bytes()).unwrap_or(UTF_8).decode_without_bom_handling(&bytes).0.to_string()
+    Encoding::for_label(charset.as_bytes()).unwrap_or(fallback).decode_without_bom_handling(&bytes).0.to_string()
 }
 
 /// Decode an encoded word.
@@ -73,5 +73,94 @@ fn decode_charset((charset, bytes): (Cow<str>, Vec<u8>)) -> String
 /// assert_eq!(decoded, "忍法写メ光飛ばし(笑)");
 /// ```
 pub fn encoded_word(input: &[u8]) -> NomResult<String> {
-    map(_encoded_word, decode_charset)(input)
+    map(_encoded_word, |ew| decode_charset(ew, UTF_8))(input)
+}
+
+// Decode an encoded word, using `fallback` for unknown charsets.
+pub(crate) fn encoded_word_fallback(fallback: &'static Encoding) -> impl Fn(&[u8]) -> NomResult<String> {
+    move |input| map(_encoded_word, |ew| decode_charset(ew, fallback))(input)
+}
+
+// Longest encoded word allowed by the RFC.
+const MAX_ENCODED_WORD: usize = 75;
+
+/// Where an encoded word is to be placed, as described in [RFC 2047
+/// section 5].
+///
+/// Each context restricts the characters that may appear unencoded.
+///
+/// [RFC 2047 section 5]: https://tools.ietf.org/html/rfc2047#section-5
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodeContext {
+    /// Unstructured text, such as `"Subject:"`.
+    Text,
+    /// Inside a comment. Parentheses and backslashes are encoded.
+    Comment,
+    /// A word of a phrase, such as a display name. Only letters,
+    /// digits and `"!*+-/"` are left unencoded.
+    Phrase,
+}
+
+impl EncodeContext {
+    fn is_literal(self, c: char) -> bool {
+        match self {
+            EncodeContext::Text => c.is_ascii_graphic() && !"=?_".contains(c),
+            EncodeContext::Comment => c.is_ascii_graphic() && !"=?_()\\".contains(c),
+            EncodeContext::Phrase => c.is_ascii_alphanumeric() || "!*+-/".contains(c),
+        }
+    }
+}
+
+/// Encode `text` as a sequence of encoded words usable in `context`.
+///
+/// Uses the Q encoding with UTF-8. Words are split on character
+/// boundaries to stay within 75 characters.
+/// # Examples
+/// ```
+/// use rustyknife::rfc2047::{encode, EncodeContext};
+///
+/// assert_eq!(encode("(caf\u{e9})", EncodeContext::Text), "=?utf-8?q?(caf=C3=A9)?=");
+/// assert_eq!(encode("(caf\u{e9})", EncodeContext::Comment), "=?utf-8?q?=28caf=C3=A9=29?=");
+/// ```
+pub fn encode(text: &str, context: EncodeContext) -> String {
+    const PREFIX: &str = "=?utf-8?q?";
+    const SUFFIX: &str = "?=";
+    let mut words = Vec::new();
+    let mut word = String::new();
+
+    for c in text.chars() {
+        let mut buf = [0; 4];
+        let encoded: String = match c {
+            ' ' => "_".into(),
+            _ if context.is_literal(c) => c.into(),
+            _ => c.encode_utf8(&mut buf).bytes().map(|b| format!("={:02X}", b)).collect(),
+        };
+
+        if PREFIX.len() + word.len() + encoded.len() + SUFFIX.len() > MAX_ENCODED_WORD {
+            words.push(format!("{}{}{}", PREFIX, word, SUFFIX));
+            word.clear();
+        }
+        word.push_str(&encoded);
+    }
+    if !word.is_empty() || words.is_empty() {
+        words.push(format!("{}{}{}", PREFIX, word, SUFFIX));
+    }
+
+    words.join(" ")
+}
+
+/// Encode `text` as a sequence of encoded words usable in a phrase,
+/// such as a display name.
+///
+/// Shorthand for [`encode`] with [`EncodeContext::Phrase`].
+/// # Examples
+/// ```
+/// use rustyknife::rfc2047::{encode_phrase, encoded_word};
+///
+/// let encoded = encode_phrase("Ren\u{e9} Dupont");
+/// assert_eq!(encoded, "=?utf-8?q?Ren=C3=A9_Dupont?=");
+/// assert_eq!(encoded_word(encoded.as_bytes()).unwrap().1, "Ren\u{e9} Dupont");
+/// ```
+pub fn encode_phrase(text: &str) -> String {
+    encode(text, EncodeContext::Phrase)
 }
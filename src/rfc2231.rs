@@ -23,10 +23,14 @@ use nom::combinator::{map, opt, recognize, verify};
 use nom::multi::many0;
 use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
 
+use crate::behaviour::{Intl, QuotedStringRFC2047};
 use crate::util::*;
+use crate::limits::{DecodeError, DecoderLimits, Limit, LimitedResult};
+use crate::options::{DuplicatePolicy, ParserOptions};
 use crate::rfc3461::hexpair;
 use crate::rfc5234::crlf;
-use crate::rfc5322::{ofws, quoted_string};
+use crate::rfc5322::{ofws, quoted_string, UTF8Policy};
+use crate::types::QuotedString;
 
 #[derive(Debug)]
 struct Parameter<'a> {
@@ -56,12 +60,12 @@ fn _equals(input: &[u8]) -> NomResult<()> {
     map(tuple((ofws, tag("="), ofws)), |_| ())(input)
 }
 
-fn parameter(input: &[u8]) -> NomResult<Parameter> {
-    alt((regular_parameter, extended_parameter))(input)
+fn parameter<P: UTF8Policy>(input: &[u8]) -> NomResult<Parameter> {
+    alt((regular_parameter::<P>, extended_parameter))(input)
 }
 
-fn regular_parameter(input: &[u8]) -> NomResult<Parameter> {
-    map(separated_pair(regular_parameter_name, _equals, value),
+fn regular_parameter<P: UTF8Policy>(input: &[u8]) -> NomResult<Parameter> {
+    map(separated_pair(regular_parameter_name, _equals, value::<P>),
         |(name, value)| Parameter{name, value: Value::Regular(value)})(input)
 }
 
@@ -140,33 +144,120 @@ fn extended_other_values(input: &[u8]) -> NomResult<Vec<u8>> {
     many0(alt((ext_octet, attribute_char)))(input)
 }
 
-fn value(input: &[u8]) -> NomResult<Cow<str>> {
+fn value<P: UTF8Policy>(input: &[u8]) -> NomResult<Cow<str>> {
     alt((map(token, Cow::from),
-         map(quoted_string::<crate::behaviour::Intl>, |qs| Cow::from(qs.0))))(input)
+         map(quoted_string::<P>, |qs| Cow::from(qs.0))))(input)
 }
 
 fn _mime_type(input: &[u8]) -> NomResult<&[u8]> {
     recognize(tuple((token, tag("/"), token)))(input)
 }
 
-fn _parameter_list(input: &[u8]) -> NomResult<Vec<Parameter>> {
-    terminated(many0(preceded(pair(tag(";"), ofws), parameter)),
+fn _parameter_list<P: UTF8Policy>(input: &[u8]) -> NomResult<Vec<Parameter>> {
+    terminated(many0(preceded(pair(tag(";"), ofws), parameter::<P>)),
                pair(opt(tag(";")), opt(crlf)))(input)
 }
 
+// Parameter list parsed one parameter at a time, so that a header with
+// thousands of sections is refused before they are all collected. The
+// raw size of the values is checked too, the decoded size being checked
+// again once the sections are assembled.
+fn _parameter_list_limited<'a, P: UTF8Policy>(input: &'a [u8], limits: &DecoderLimits) -> LimitedResult<'a, Vec<Parameter<'a>>> {
+    let mut out = Vec::new();
+    let mut rem = input;
+    let mut sections = 0;
+    let mut len = 0;
+
+    while let Ok((r, param)) = preceded(pair(tag(";"), ofws), parameter::<P>)(rem) {
+        if param.name.section.is_some() {
+            sections += 1;
+            if sections > limits.max_continuations {
+                return Err(DecodeError::LimitExceeded(Limit::Continuations));
+            }
+        }
+
+        len += param.name.name.len() + match &param.value {
+            Value::Regular(v) => v.len(),
+            Value::Extended(ExtendedValue::Initial { value, .. }) | Value::Extended(ExtendedValue::Other(value)) => value.len(),
+        };
+        if len > limits.max_decoded_len {
+            return Err(DecodeError::LimitExceeded(Limit::DecodedLen));
+        }
+
+        out.push(param);
+        rem = r;
+    }
+
+    let (rem, _) = pair(opt(tag(";")), opt(crlf))(rem)?;
+
+    Ok((rem, out))
+}
+
+// Parameter list with the quoted string decoding chosen in `options`.
+fn _parameter_list_with<'a>(input: &'a [u8], options: &ParserOptions) -> LimitedResult<'a, Vec<Parameter<'a>>> {
+    if options.quoted_string_rfc2047 {
+        _parameter_list_limited::<QuotedStringRFC2047<Intl, true>>(input, &options.limits)
+    } else {
+        _parameter_list_limited::<QuotedStringRFC2047<Intl, false>>(input, &options.limits)
+    }
+}
+
 #[derive(Debug)]
 enum Segment<'a> {
     Encoded(Vec<u8>),
     Decoded(Cow<'a, str>),
 }
 
-fn decode_segments(mut input: Vec<(u32, Segment)>, encoding: &'static Encoding) -> String {
+/// The form a decoded parameter value was taken from.
+///
+/// When a parameter appears in several forms, the greatest one wins.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ParameterSource {
+    /// The regular `name=value` form.
+    Regular,
+    /// The extended `name*=charset''value` form.
+    Extended,
+    /// Continuation sections, such as `name*0*=charset''value` and
+    /// `name*1=value`.
+    Continuations,
+}
+
+/// A decoded parameter along with details on its encoding.
+///
+/// See [`content_type_detailed`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterInfo {
+    /// The lowercase parameter name.
+    pub name: String,
+    /// The decoded value.
+    pub value: String,
+    /// The form the value was taken from.
+    pub source: ParameterSource,
+    /// The charset label declared in the extended form, as written.
+    pub charset: Option<String>,
+    /// Whether the declared charset is unknown or some encoded bytes
+    /// were invalid in it. The charset fallback or replacement
+    /// characters were used instead.
+    pub decode_error: bool,
+}
+
+fn charset_codec(label: Option<&[u8]>, fallback: &'static Encoding) -> (&'static Encoding, bool) {
+    match label {
+        Some(label) => Encoding::for_label(decode_ascii(label).as_bytes()).map_or((fallback, false), |codec| (codec, true)),
+        None => (fallback, true),
+    }
+}
+
+fn decode_segments(mut input: Vec<(u32, Segment)>, encoding: &'static Encoding) -> (String, bool) {
     input.sort_by(|a, b| a.0.cmp(&b.0));
     let mut out = String::new();
     let mut encoded = Vec::new();
+    let mut errors = false;
 
-    let decode = |bytes: &mut Vec<_>, out: &mut String| {
-        out.push_str(&encoding.decode_without_bom_handling(bytes).0);
+    let mut decode = |bytes: &mut Vec<_>, out: &mut String| {
+        let (decoded, had_errors) = encoding.decode_without_bom_handling(bytes);
+        out.push_str(&decoded);
+        errors |= had_errors;
         bytes.clear();
     };
 
@@ -179,14 +270,20 @@ fn decode_segments(mut input: Vec<(u32, Segment)>, encoding: &'static Encoding)
     }
     decode(&mut encoded, &mut out);
 
-    out
+    (out, errors)
 }
 
-fn decode_parameter_list(input: Vec<Parameter>) -> Vec<(String, String)> {
-    let mut simple = HashMap::<String, String>::new();
-    let mut simple_encoded = HashMap::<String, String>::new();
+fn decode_parameter_info(input: Vec<Parameter>, fallback: &'static Encoding) -> Vec<ParameterInfo> {
+    let mut out = HashMap::<String, ParameterInfo>::new();
     let mut composite = HashMap::<String, Vec<(u32, Segment)>>::new();
-    let mut composite_encoding = HashMap::new();
+    let mut composite_charset = HashMap::new();
+
+    let mut insert = |info: ParameterInfo| {
+        match out.get(&info.name) {
+            Some(prev) if prev.source > info.source => (),
+            _ => { out.insert(info.name.clone(), info); },
+        }
+    };
 
     for Parameter{name, value} in input {
         let name_norm = name.name.to_lowercase();
@@ -194,13 +291,14 @@ fn decode_parameter_list(input: Vec<Parameter>) -> Vec<(String, String)> {
         match name.section {
             None => {
                 match value {
-                    Value::Regular(v) => { simple.insert(name_norm, v.into()); },
+                    Value::Regular(v) => insert(ParameterInfo{name: name_norm, value: v.into(), source: ParameterSource::Regular,
+                                                              charset: None, decode_error: false}),
                     Value::Extended(ExtendedValue::Initial{value, encoding: encoding_name, ..}) => {
-                        let codec = match encoding_name {
-                            Some(encoding_name) => Encoding::for_label(decode_ascii(encoding_name).as_bytes()).unwrap_or(UTF_8),
-                            None => UTF_8,
-                        };
-                        simple_encoded.insert(name_norm, codec.decode_without_bom_handling(value.as_slice()).0.to_string()); // TODO: eliminate to_string
+                        let (codec, known) = charset_codec(encoding_name, fallback);
+                        let (decoded, had_errors) = codec.decode_without_bom_handling(value.as_slice());
+
+                        insert(ParameterInfo{name: name_norm, value: decoded.into(), source: ParameterSource::Extended,
+                                             charset: encoding_name.map(|e| decode_ascii(e).into()), decode_error: had_errors || !known});
                     }
                     Value::Extended(ExtendedValue::Other(..)) => unreachable!(),
                 }
@@ -212,9 +310,7 @@ fn decode_parameter_list(input: Vec<Parameter>) -> Vec<(String, String)> {
                     Value::Regular(v) => ent.push((section, Segment::Decoded(v))),
                     Value::Extended(ExtendedValue::Initial{value, encoding: encoding_name, ..}) => {
                         if let Some(encoding_name) = encoding_name {
-                            if let Some(codec) = Encoding::for_label(decode_ascii(encoding_name).as_bytes()) {
-                                composite_encoding.insert(name_norm, codec);
-                            }
+                            composite_charset.insert(name_norm, encoding_name);
                         }
                         ent.push((section, Segment::Encoded(value.to_vec())))
                     }
@@ -224,26 +320,276 @@ fn decode_parameter_list(input: Vec<Parameter>) -> Vec<(String, String)> {
         }
     }
 
-    let mut composite_out = Vec::new();
     for (name, segments) in composite {
-        let codec = composite_encoding.get(&name).cloned().unwrap_or(UTF_8);
-        composite_out.push((name, decode_segments(segments, codec)));
+        let charset = composite_charset.get(&name).copied();
+        let (codec, known) = charset_codec(charset, fallback);
+        let (value, had_errors) = decode_segments(segments, codec);
+
+        insert(ParameterInfo{name, value, source: ParameterSource::Continuations,
+                             charset: charset.map(|e| decode_ascii(e).into()), decode_error: had_errors || !known});
+    }
+
+    out.into_values().collect()
+}
+
+fn decode_parameter_list(input: Vec<Parameter>, fallback: &'static Encoding) -> Vec<(String, String)> {
+    decode_parameter_info(input, fallback).into_iter().map(|p| (p.name, p.value)).collect()
+}
+
+// Apply the duplicate policy before decoding. The regular and extended
+// forms of a parameter are distinct, the latter taking precedence.
+fn dedup_parameters(input: Vec<Parameter>, policy: DuplicatePolicy) -> Result<Vec<Parameter>, DecodeError> {
+    let mut seen = HashMap::new();
+    let mut out = Vec::new();
+
+    for param in input {
+        let key = (param.name.name.to_lowercase(), param.name.section, matches!(param.value, Value::Extended(_)));
+
+        match (seen.get(&key), policy) {
+            (None, _) => { seen.insert(key, out.len()); out.push(Some(param)); }
+            (Some(_), DuplicatePolicy::Reject) => return Err(DecodeError::DuplicateParameter(key.0)),
+            (Some(_), DuplicatePolicy::KeepFirst) => (),
+            (Some(&idx), DuplicatePolicy::KeepLast) => out[idx] = Some(param),
+        }
+    }
+
+    Ok(out.into_iter().flatten().collect())
+}
+
+fn decode_parameter_info_limited(input: Vec<Parameter>, limits: &DecoderLimits, fallback: &'static Encoding) -> Result<Vec<ParameterInfo>, DecodeError> {
+    let out = decode_parameter_info(input, fallback);
+
+    if out.iter().map(|p| p.name.len() + p.value.len()).sum::<usize>() > limits.max_decoded_len {
+        Err(DecodeError::LimitExceeded(Limit::DecodedLen))
+    } else {
+        Ok(out)
+    }
+}
+
+fn decode_parameter_list_limited(input: Vec<Parameter>, limits: &DecoderLimits, fallback: &'static Encoding) -> Result<Vec<(String, String)>, DecodeError> {
+    Ok(decode_parameter_info_limited(input, limits, fallback)?.into_iter().map(|p| (p.name, p.value)).collect())
+}
+
+fn is_token(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|c| (33..=126).contains(&c) && !b"()<>@,;:\\\"/[]?=".contains(&c))
+}
+
+fn needs_extended(value: &str) -> bool {
+    !value.bytes().all(|c| (32..=126).contains(&c))
+}
+
+fn encode_section(name: &str, section: Option<usize>, value: &str, extended: bool, initial: bool) -> String {
+    let mut out = String::from(name);
+
+    if let Some(section) = section {
+        out.push_str(&format!("*{}", section));
     }
 
-    for (name, value) in simple_encoded.into_iter().chain(composite_out.into_iter()) {
-        simple.insert(name, value);
+    if extended {
+        out.push_str("*=");
+        if initial {
+            out.push_str("utf-8''");
+        }
+        for c in value.bytes() {
+            if is_attribute_char(c) {
+                out.push(char::from(c));
+            } else {
+                out.push_str(&format!("%{:02X}", c));
+            }
+        }
+    } else if is_token(value) {
+        out.push('=');
+        out.push_str(value);
+    } else {
+        out.push('=');
+        out.push_str(&QuotedString(value.to_string()).quoted());
     }
 
-    simple.into_iter().collect()
+    out
+}
+
+/// Serialize a single decoded parameter.
+///
+/// The parameter is written as `"; name=value"` like in
+/// [`encode_parameter_list`] when it fits in `max_len` bytes.
+/// Otherwise the value is split into as few RFC 2231 continuations
+/// as possible, each on its own folded line and no longer than
+/// `max_len` bytes. Only the sections containing non-ASCII
+/// characters use the extended syntax, along with the initial section
+/// which then carries the charset.
+///
+/// Use this when re-emitting a parameter after modifying it, such as
+/// a sanitized `filename` in a `"Content-Disposition"` header.
+/// # Examples
+/// ```
+/// use rustyknife::rfc2231::encode_parameter;
+///
+/// assert_eq!(encode_parameter("filename", "report.pdf", 78), "; filename=report.pdf");
+/// assert_eq!(encode_parameter("filename", "a long report.pdf", 25),
+///            ";\r\n filename*0=\"a long repor\";\r\n filename*1=t.pdf");
+/// assert_eq!(encode_parameter("filename", "rapport été.pdf", 30),
+///            ";\r\n filename*0*=utf-8''rapport%20;\r\n filename*1*=%C3%A9t%C3%A9.pdf");
+/// ```
+pub fn encode_parameter(name: &str, value: &str, max_len: usize) -> String {
+    let extended = needs_extended(value);
+    let single = encode_section(name, None, value, extended, extended);
+
+    if single.len() <= max_len || value.is_empty() {
+        return format!("; {}", single);
+    }
+
+    let mut out = String::new();
+    let mut rest = value;
+    let mut section = 0;
+
+    while !rest.is_empty() {
+        let mut end = 0;
+        let mut encoded = String::new();
+
+        for (i, c) in rest.char_indices() {
+            let candidate = &rest[..i + c.len_utf8()];
+            let candidate_extended = extended && (section == 0 || needs_extended(candidate));
+            let candidate_encoded = encode_section(name, Some(section), candidate, candidate_extended, extended && section == 0);
+
+            if candidate_encoded.len() > max_len && end != 0 {
+                break;
+            }
+            end = candidate.len();
+            encoded = candidate_encoded;
+        }
+
+        out.push_str(";\r\n ");
+        out.push_str(&encoded);
+        rest = &rest[end..];
+        section += 1;
+    }
+
+    out
+}
+
+/// Serialize a decoded parameter list.
+///
+/// This is the reverse of the parameter decoding done by
+/// [`content_type`] and [`content_disposition`]. Each parameter is
+/// written as `"; name=value"`. Values are written as tokens when
+/// possible, as quoted strings when they only contain printable ASCII
+/// and with the extended `name*=utf-8''value` syntax otherwise.
+///
+/// No continuations are produced, see [`encode_parameter`] to split
+/// long values.
+/// # Examples
+/// ```
+/// use rustyknife::rfc2231::encode_parameter_list;
+///
+/// let params = [("charset".into(), "utf-8".into()),
+///               ("name".into(), "my file.txt".into()),
+///               ("filename".into(), "été.txt".into())];
+///
+/// assert_eq!(encode_parameter_list(&params),
+///            "; charset=utf-8; name=\"my file.txt\"; filename*=utf-8''%C3%A9t%C3%A9.txt");
+/// ```
+pub fn encode_parameter_list(params: &[(String, String)]) -> String {
+    params.iter().map(|(name, value)| encode_parameter(name, value, usize::MAX)).collect()
 }
 
 /// Parse a MIME `"Content-Type"` header.
 ///
-/// Returns a tuple of the MIME type and parameters.
+/// Returns a tuple of the MIME type and parameters. Encoded words in
+/// quoted values are decoded if the `quoted-string-rfc2047` feature
+/// is enabled, use [`content_type_with`] to choose for each call.
 pub fn content_type(input: &[u8]) -> NomResult<(String, Vec<(String, String)>)> {
     map(pair(delimited(ofws, _mime_type, ofws),
-             _parameter_list),
-        |(mt, p)| (decode_ascii(mt).to_lowercase(), decode_parameter_list(p)))(input)
+             _parameter_list::<Intl>),
+        |(mt, p)| (decode_ascii(mt).to_lowercase(), decode_parameter_list(p, UTF_8)))(input)
+}
+
+/// Parse a MIME `"Content-Type"` header while enforcing `limits`.
+///
+/// Same as [`content_type`], but the number of parameter sections
+/// and the total size of the decoded parameters are capped.
+/// # Examples
+/// ```
+/// use rustyknife::limits::{DecodeError, DecoderLimits, Limit};
+/// use rustyknife::rfc2231::content_type_limited;
+///
+/// let limits = DecoderLimits { max_continuations: 1, ..Default::default() };
+/// let res = content_type_limited(b"text/plain; name*0=a; name*1=b", &limits);
+///
+/// assert_eq!(res, Err(DecodeError::LimitExceeded(Limit::Continuations)));
+/// ```
+pub fn content_type_limited<'a>(input: &'a [u8], limits: &DecoderLimits) -> LimitedResult<'a, (String, Vec<(String, String)>)> {
+    let (rem, mt) = delimited(ofws, _mime_type, ofws)(input)?;
+    let (rem, p) = _parameter_list_limited::<Intl>(rem, limits)?;
+
+    Ok((rem, (decode_ascii(mt).to_lowercase(), decode_parameter_list_limited(p, limits, UTF_8)?)))
+}
+
+/// Parse a MIME `"Content-Type"` header according to `options`.
+///
+/// Applies the limits, charset fallback, duplicate parameter policy
+/// and [RFC 2047] decoding choice of [`ParserOptions`].
+///
+/// When [`ParserOptions::quoted_string_rfc2047`] is set, encoded
+/// words inside quoted values, including quoted continuation
+/// sections, are decoded. Otherwise they are kept verbatim. The
+/// setting overrides the `quoted-string-rfc2047` feature for this
+/// call only.
+///
+/// A parameter given in several forms resolves to a single value,
+/// regardless of the order of the forms in the header:
+///  1. continuations (`name*0`, `name*1*`, ...) are used if present,
+///  2. then the extended form (`name*=charset''value`),
+///  3. then the regular form (`name=value`).
+///
+/// Encoded words are never decoded in the extended form, which names
+/// its own charset.
+///
+/// [RFC 2047]: https://tools.ietf.org/html/rfc2047
+/// # Examples
+/// ```
+/// use rustyknife::limits::DecodeError;
+/// use rustyknife::options::{DuplicatePolicy, ParserOptions};
+/// use rustyknife::rfc2231::content_type_with;
+///
+/// let options = ParserOptions { duplicate_params: DuplicatePolicy::Reject, ..Default::default() };
+/// let res = content_type_with(b"text/plain; charset=utf-8; Charset=latin1", &options);
+///
+/// assert_eq!(res, Err(DecodeError::DuplicateParameter("charset".into())));
+/// ```
+pub fn content_type_with<'a>(input: &'a [u8], options: &ParserOptions) -> LimitedResult<'a, (String, Vec<(String, String)>)> {
+    let (rem, mt) = delimited(ofws, _mime_type, ofws)(input)?;
+    let (rem, p) = _parameter_list_with(rem, options)?;
+    let p = dedup_parameters(p, options.duplicate_params)?;
+
+    Ok((rem, (decode_ascii(mt).to_lowercase(), decode_parameter_list_limited(p, &options.limits, options.charset_fallback)?)))
+}
+
+/// Parse a MIME `"Content-Type"` header according to `options`,
+/// describing how each parameter was encoded.
+///
+/// Same as [`content_type_with`], but each parameter comes with the
+/// form it was taken from, its declared charset and whether decoding
+/// it failed. This can reveal evasion attempts, such as a harmless
+/// `filename` overridden by a `filename*` continuation.
+/// # Examples
+/// ```
+/// use rustyknife::options::ParserOptions;
+/// use rustyknife::rfc2231::{content_type_detailed, ParameterSource};
+///
+/// let input = b"application/pdf; name=doc.pdf; name*0*=iso-8859-1''%E9t%E9; name*1=.exe";
+/// let (_, (_, params)) = content_type_detailed(input, &ParserOptions::default()).unwrap();
+///
+/// assert_eq!(params[0].value, "été.exe");
+/// assert_eq!(params[0].source, ParameterSource::Continuations);
+/// assert_eq!(params[0].charset.as_deref(), Some("iso-8859-1"));
+/// assert!(!params[0].decode_error);
+/// ```
+pub fn content_type_detailed<'a>(input: &'a [u8], options: &ParserOptions) -> LimitedResult<'a, (String, Vec<ParameterInfo>)> {
+    let (rem, mt) = delimited(ofws, _mime_type, ofws)(input)?;
+    let (rem, p) = _parameter_list_with(rem, options)?;
+    let p = dedup_parameters(p, options.duplicate_params)?;
+
+    Ok((rem, (decode_ascii(mt).to_lowercase(), decode_parameter_info_limited(p, &options.limits, options.charset_fallback)?)))
 }
 
 fn _x_token(input: &[u8]) -> NomResult<&str> {
@@ -251,7 +597,7 @@ fn _x_token(input: &[u8]) -> NomResult<&str> {
 }
 
 /// Value from a MIME `"Content-Disposition"` header.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ContentDisposition {
     /// "inline"
     Inline,
@@ -286,15 +632,62 @@ fn _disposition(input: &[u8]) -> NomResult<ContentDisposition> {
 
 /// Parse a MIME `"Content-Disposition"` header.
 ///
-/// Returns a tuple of [`ContentDisposition`] and parameters.
+/// Returns a tuple of [`ContentDisposition`] and parameters. See
+/// [`content_type`] for the decoding of encoded words.
 pub fn content_disposition(input: &[u8]) -> NomResult<(ContentDisposition, Vec<(String, String)>)> {
     map(pair(delimited(ofws, _disposition, ofws),
-             _parameter_list),
-        |(disp, p)| (disp, decode_parameter_list(p)))(input)
+             _parameter_list::<Intl>),
+        |(disp, p)| (disp, decode_parameter_list(p, UTF_8)))(input)
+}
+
+/// Parse a MIME `"Content-Disposition"` header while enforcing `limits`.
+///
+/// See [`content_type_limited`].
+pub fn content_disposition_limited<'a>(input: &'a [u8], limits: &DecoderLimits) -> LimitedResult<'a, (ContentDisposition, Vec<(String, String)>)> {
+    let (rem, disp) = delimited(ofws, _disposition, ofws)(input)?;
+    let (rem, p) = _parameter_list_limited::<Intl>(rem, limits)?;
+
+    Ok((rem, (disp, decode_parameter_list_limited(p, limits, UTF_8)?)))
+}
+
+/// Parse a MIME `"Content-Disposition"` header according to `options`.
+///
+/// See [`content_type_with`] for the decoding options and the
+/// precedence between the forms of a parameter.
+/// # Examples
+/// ```
+/// use rustyknife::options::ParserOptions;
+/// use rustyknife::rfc2231::content_disposition_with;
+///
+/// let input = b"attachment; filename=\"=?utf-8?q?r=C3=A9sum=C3=A9.pdf?=\"";
+/// let decode = ParserOptions { quoted_string_rfc2047: true, ..Default::default() };
+/// let verbatim = ParserOptions { quoted_string_rfc2047: false, ..Default::default() };
+///
+/// assert_eq!(content_disposition_with(input, &decode).unwrap().1 .1[0].1, "résumé.pdf");
+/// assert_eq!(content_disposition_with(input, &verbatim).unwrap().1 .1[0].1, "=?utf-8?q?r=C3=A9sum=C3=A9.pdf?=");
+/// ```
+pub fn content_disposition_with<'a>(input: &'a [u8], options: &ParserOptions) -> LimitedResult<'a, (ContentDisposition, Vec<(String, String)>)> {
+    let (rem, disp) = delimited(ofws, _disposition, ofws)(input)?;
+    let (rem, p) = _parameter_list_with(rem, options)?;
+    let p = dedup_parameters(p, options.duplicate_params)?;
+
+    Ok((rem, (disp, decode_parameter_list_limited(p, &options.limits, options.charset_fallback)?)))
+}
+
+/// Parse a MIME `"Content-Disposition"` header according to
+/// `options`, describing how each parameter was encoded.
+///
+/// See [`content_type_detailed`].
+pub fn content_disposition_detailed<'a>(input: &'a [u8], options: &ParserOptions) -> LimitedResult<'a, (ContentDisposition, Vec<ParameterInfo>)> {
+    let (rem, disp) = delimited(ofws, _disposition, ofws)(input)?;
+    let (rem, p) = _parameter_list_with(rem, options)?;
+    let p = dedup_parameters(p, options.duplicate_params)?;
+
+    Ok((rem, (disp, decode_parameter_info_limited(p, &options.limits, options.charset_fallback)?)))
 }
 
 /// Value from a MIME `"Content-Transfer-Encoding"` header.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ContentTransferEncoding {
     /// "7bit"
     SevenBit,
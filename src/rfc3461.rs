@@ -5,6 +5,9 @@
 use std::borrow::Cow;
 use std::str;
 
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
 use crate::util::*;
 
 use charset::decode_ascii;
@@ -35,6 +38,30 @@ pub(crate) fn xtext(input: &[u8]) -> NomResult<Vec<u8>> {
     many0(alt((xchar, hexchar)))(input)
 }
 
+/// Encode a value as xtext.
+///
+/// The characters `'+'`, `'='`, controls, spaces and octets outside
+/// the ASCII range are written as a `'+'` followed by two uppercase
+/// hexadecimal digits.
+/// # Examples
+/// ```
+/// use rustyknife::rfc3461::xtext_encode;
+///
+/// assert_eq!(xtext_encode(b"bob+tag=x@example.org"), "bob+2Btag+3Dx@example.org");
+/// ```
+pub fn xtext_encode(input: &[u8]) -> String {
+    let mut out = String::with_capacity(input.len());
+
+    for c in input {
+        match c {
+            33..=42 | 44..=60 | 62..=126 => out.push(char::from(*c)),
+            _ => out.push_str(&format!("+{:02X}", c)),
+        }
+    }
+
+    out
+}
+
 fn _printable_xtext(input: &[u8]) -> NomResult<Vec<u8>> {
     verify(xtext, |xtext: &[u8]| {
         xtext.iter().all(|c| match c { 9..=13 | 32..=126 => true, _ => false})
@@ -59,6 +86,7 @@ pub fn orcpt_address(input: &[u8]) -> NomResult<(Cow<str>, Cow<str>)> {
 
 /// The DSN return type desired by the sender.
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum DSNRet {
     /// Return full the full message content.
     Full,
@@ -68,6 +96,7 @@ pub enum DSNRet {
 
 /// DSN parameters for the MAIL command.
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DSNMailParams {
     /// A mail transaction identifier provided by the sender.
     ///
@@ -138,9 +167,15 @@ pub fn dsn_mail_params<'a>(input: &[Param<'a>]) -> Result<(DSNMailParams, Vec<Pa
     Ok((DSNMailParams{envid: envid_val, ret: ret_val}, out))
 }
 
+/// Conditions for which the sender requests a DSN.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Notify {
+    /// Notify on successful delivery.
     pub on_success: bool,
+    /// Notify on delivery failure.
     pub on_failure: bool,
+    /// Notify when delivery is delayed.
     pub delay: bool,
 }
 
@@ -174,6 +209,8 @@ fn notify_item(input: &str) -> Result<(&str, &str), nom::Err<()>> {
     ))(input)
 }
 
+/// Parse the value of the ESMTP NOTIFY parameter that may be present
+/// on a RCPT TO command.
 pub fn dsn_notify(input: &str) -> Result<(&str, Notify), nom::Err<()>> {
     alt((
         map(tag_no_case("never"), |_| Notify {
@@ -0,0 +1,170 @@
+//! [Enhanced mail system status codes]
+//!
+//! Used in SMTP replies when the server advertises
+//! [ENHANCEDSTATUSCODES] and in the `"Status:"` field of delivery
+//! status notifications.
+//!
+//! [Enhanced mail system status codes]: https://tools.ietf.org/html/rfc3463
+//! [ENHANCEDSTATUSCODES]: https://tools.ietf.org/html/rfc2034
+
+use std::fmt::{self, Display};
+use std::str;
+
+use nom::bytes::complete::{tag, take_while_m_n};
+use nom::combinator::{map, map_opt, verify};
+use nom::sequence::{preceded, tuple};
+
+use crate::util::*;
+
+/// The class of a status code, its first number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StatusClass {
+    /// `2`, the action succeeded.
+    Success,
+    /// `4`, the failure is transient, the action may succeed later.
+    PersistentTransientFailure,
+    /// `5`, the failure is permanent.
+    PermanentFailure,
+}
+
+/// The subject of a status code, its second number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StatusSubject {
+    /// `X.0.X`
+    Other,
+    /// `X.1.X`, problems with the address.
+    Addressing,
+    /// `X.2.X`, problems with the destination mailbox.
+    Mailbox,
+    /// `X.3.X`, problems with the destination mail system.
+    MailSystem,
+    /// `X.4.X`, network and routing problems.
+    Network,
+    /// `X.5.X`, problems with the mail delivery protocol.
+    Protocol,
+    /// `X.6.X`, problems with the message content or media.
+    Content,
+    /// `X.7.X`, security or policy problems.
+    Policy,
+}
+
+/// An enhanced status code such as `"5.1.1"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EnhancedStatusCode {
+    /// The class.
+    pub class: StatusClass,
+    /// The subject number, see [`EnhancedStatusCode::category`].
+    pub subject: u16,
+    /// The detail number.
+    pub detail: u16,
+}
+
+// 1*3digit without leading zero, as for subject and detail.
+fn number(input: &[u8]) -> NomResult<u16> {
+    map(verify(take_while_m_n(1, 3, |c: u8| c.is_ascii_digit()), |n: &[u8]| n == b"0" || n[0] != b'0'),
+        |n| str::from_utf8(n).unwrap().parse().unwrap())(input)
+}
+
+fn class(input: &[u8]) -> NomResult<StatusClass> {
+    map_opt(take_while_m_n(1, 1, |c: u8| c.is_ascii_digit()), |c: &[u8]| match c[0] {
+        b'2' => Some(StatusClass::Success),
+        b'4' => Some(StatusClass::PersistentTransientFailure),
+        b'5' => Some(StatusClass::PermanentFailure),
+        _ => None,
+    })(input)
+}
+
+/// Parse an enhanced status code at the start of `input`.
+/// # Examples
+/// ```
+/// use rustyknife::rfc3463::{enhanced_status_code, StatusClass};
+///
+/// let (rem, code) = enhanced_status_code(b"5.1.1 No such user").unwrap();
+/// assert_eq!(rem, b" No such user");
+/// assert_eq!(code.class, StatusClass::PermanentFailure);
+/// assert_eq!((code.subject, code.detail), (1, 1));
+///
+/// assert!(enhanced_status_code(b"3.1.1").is_err());
+/// assert!(enhanced_status_code(b"5.01.1").is_err());
+/// ```
+pub fn enhanced_status_code(input: &[u8]) -> NomResult<EnhancedStatusCode> {
+    map(tuple((class, preceded(tag("."), number), preceded(tag("."), number))),
+        |(class, subject, detail)| EnhancedStatusCode { class, subject, detail })(input)
+}
+
+nom_fromstr!(EnhancedStatusCode, enhanced_status_code);
+
+impl EnhancedStatusCode {
+    /// Build a code from its three numbers.
+    ///
+    /// Returns [`None`] for an invalid class or a subject or detail
+    /// over 999.
+    pub fn new(class: u8, subject: u16, detail: u16) -> Option<Self> {
+        let class = match class {
+            2 => StatusClass::Success,
+            4 => StatusClass::PersistentTransientFailure,
+            5 => StatusClass::PermanentFailure,
+            _ => return None,
+        };
+
+        if subject > 999 || detail > 999 {
+            return None;
+        }
+        Some(EnhancedStatusCode { class, subject, detail })
+    }
+
+    /// The class as a number, matching the first digit of the SMTP
+    /// reply code.
+    pub fn class_digit(&self) -> u8 {
+        match self.class {
+            StatusClass::Success => 2,
+            StatusClass::PersistentTransientFailure => 4,
+            StatusClass::PermanentFailure => 5,
+        }
+    }
+
+    /// Whether this code reports a success.
+    pub fn is_success(&self) -> bool {
+        self.class == StatusClass::Success
+    }
+
+    /// Whether this code reports a transient failure.
+    pub fn is_transient(&self) -> bool {
+        self.class == StatusClass::PersistentTransientFailure
+    }
+
+    /// Whether this code reports a permanent failure.
+    pub fn is_permanent(&self) -> bool {
+        self.class == StatusClass::PermanentFailure
+    }
+
+    /// The subject, or [`None`] if it is not registered.
+    /// # Examples
+    /// ```
+    /// use rustyknife::rfc3463::{EnhancedStatusCode, StatusSubject};
+    ///
+    /// let code: EnhancedStatusCode = "4.7.1".parse().unwrap();
+    /// assert_eq!(code.category(), Some(StatusSubject::Policy));
+    /// assert!(code.is_transient());
+    /// assert_eq!(code.to_string(), "4.7.1");
+    /// ```
+    pub fn category(&self) -> Option<StatusSubject> {
+        Some(match self.subject {
+            0 => StatusSubject::Other,
+            1 => StatusSubject::Addressing,
+            2 => StatusSubject::Mailbox,
+            3 => StatusSubject::MailSystem,
+            4 => StatusSubject::Network,
+            5 => StatusSubject::Protocol,
+            6 => StatusSubject::Content,
+            7 => StatusSubject::Policy,
+            _ => return None,
+        })
+    }
+}
+
+impl Display for EnhancedStatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.class_digit(), self.subject, self.detail)
+    }
+}
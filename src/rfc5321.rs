@@ -2,23 +2,31 @@
 //!
 //! [SMTP]: https://tools.ietf.org/html/rfc5321
 
+use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::fmt::{self, Display};
+use std::hash::{Hash, Hasher};
 use std::net::{Ipv4Addr, Ipv6Addr};
+use std::ops::Range;
 use std::str::{self, FromStr};
 
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
 use nom::branch::alt;
-use nom::bytes::complete::{tag, tag_no_case, take_while1, take_while_m_n};
+use nom::bytes::complete::{tag, tag_no_case, take_while, take_while1, take_while_m_n};
 use nom::character::{is_alphanumeric, is_digit, is_hex_digit};
-use nom::combinator::{map, map_res, opt, recognize, verify};
+use nom::combinator::{consumed, map, map_res, opt, recognize, verify};
+use nom::Needed;
 use nom::error::ParseError;
-use nom::multi::{many0, many1, many_m_n};
-use nom::sequence::{delimited, pair, preceded, separated_pair, terminated};
+use nom::multi::{many0, many1, many_m_n, separated_list1};
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
 
 use crate::behaviour::{Legacy, Intl};
+use crate::limits::{DecodeError, Limit, LimitedResult, SmtpLimits};
+use crate::options::AddressOptions;
+use crate::reply::{Reply, ReplyCode};
+use crate::rfc3463::EnhancedStatusCode;
 use crate::rfc5322::utf8_non_ascii;
 use crate::rfc5234::{crlf, wsp};
 use crate::types::*;
@@ -29,6 +37,7 @@ pub trait UTF8Policy {
     fn atext(input: &[u8]) -> NomResult<char>;
     fn qtext_smtp(input: &[u8]) -> NomResult<char>;
     fn esmtp_value_char(input: &[u8]) -> NomResult<char>;
+    fn reply_text_char(input: &[u8]) -> NomResult<char>;
     fn sub_domain(input: &[u8]) -> NomResult<&[u8]>;
 }
 
@@ -45,6 +54,10 @@ impl UTF8Policy for Legacy {
         map(take1_filter(|c| match c {33..=60 | 62..=126 => true, _ => false}), char::from)(input)
     }
 
+    fn reply_text_char(input: &[u8]) -> NomResult<char> {
+        map(take1_filter(|c| matches!(c, 9 | 32..=126)), char::from)(input)
+    }
+
     fn sub_domain(input: &[u8]) -> NomResult<&[u8]> {
         recognize(pair(let_dig, opt(ldh_str)))(input)
     }
@@ -63,6 +76,10 @@ impl UTF8Policy for Intl {
         alt((Legacy::esmtp_value_char, utf8_non_ascii))(input)
     }
 
+    fn reply_text_char(input: &[u8]) -> NomResult<char> {
+        alt((Legacy::reply_text_char, utf8_non_ascii))(input)
+    }
+
     fn sub_domain(input: &[u8]) -> NomResult<&[u8]> {
         verify(recognize_many1(alt((map(take1_filter(_is_ldh), char::from), utf8_non_ascii))), |label| {
             idna::Config::default()
@@ -151,12 +168,107 @@ impl<'a> Display for Params<'a> {
 ///
 /// Used as the left side in an ESMTP parameter.  For example, it
 /// represents the "BODY" string in a parameter "BODY=8BIT".
-#[derive(Clone, PartialEq)]
+///
+/// Keywords are compared and hashed case insensitively, including
+/// against strings.
+/// # Examples
+/// ```
+/// use rustyknife::rfc5321::{Keyword, Param};
+///
+/// let param: Param = "body=8BITMIME".parse().unwrap();
+///
+/// assert_eq!(param.0, Keyword::BODY);
+/// assert_eq!(param.0, "Body");
+/// assert_eq!(param.0.to_string(), "body");
+/// ```
+#[derive(Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct Keyword(pub(crate) String);
-string_newtype!(Keyword);
+pub struct Keyword(pub(crate) Cow<'static, str>);
 nom_fromstr!(Keyword, esmtp_keyword);
 
+#[allow(missing_docs)]
+impl Keyword {
+    pub const AUTH: Keyword = Keyword(Cow::Borrowed("AUTH"));
+    pub const BODY: Keyword = Keyword(Cow::Borrowed("BODY"));
+    pub const BY: Keyword = Keyword(Cow::Borrowed("BY"));
+    pub const DSN: Keyword = Keyword(Cow::Borrowed("DSN"));
+    pub const EIGHTBITMIME: Keyword = Keyword(Cow::Borrowed("8BITMIME"));
+    pub const ENVID: Keyword = Keyword(Cow::Borrowed("ENVID"));
+    pub const HOLDFOR: Keyword = Keyword(Cow::Borrowed("HOLDFOR"));
+    pub const HOLDUNTIL: Keyword = Keyword(Cow::Borrowed("HOLDUNTIL"));
+    pub const MT_PRIORITY: Keyword = Keyword(Cow::Borrowed("MT-PRIORITY"));
+    pub const NOTIFY: Keyword = Keyword(Cow::Borrowed("NOTIFY"));
+    pub const ORCPT: Keyword = Keyword(Cow::Borrowed("ORCPT"));
+    pub const PIPELINING: Keyword = Keyword(Cow::Borrowed("PIPELINING"));
+    pub const REQUIRETLS: Keyword = Keyword(Cow::Borrowed("REQUIRETLS"));
+    pub const RET: Keyword = Keyword(Cow::Borrowed("RET"));
+    pub const RRVS: Keyword = Keyword(Cow::Borrowed("RRVS"));
+    pub const SIZE: Keyword = Keyword(Cow::Borrowed("SIZE"));
+    pub const SMTPUTF8: Keyword = Keyword(Cow::Borrowed("SMTPUTF8"));
+    pub const STARTTLS: Keyword = Keyword(Cow::Borrowed("STARTTLS"));
+}
+
+impl Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Debug for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl AsRef<[u8]> for Keyword {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl std::ops::Deref for Keyword {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<Keyword> for String {
+    fn from(value: Keyword) -> String {
+        value.0.into_owned()
+    }
+}
+
+impl PartialEq for Keyword {
+    fn eq(&self, other: &Keyword) -> bool {
+        self.0.eq_ignore_ascii_case(&other.0)
+    }
+}
+
+impl Eq for Keyword {}
+
+impl PartialEq<str> for Keyword {
+    fn eq(&self, other: &str) -> bool {
+        self.0.eq_ignore_ascii_case(other)
+    }
+}
+
+impl PartialEq<&str> for Keyword {
+    fn eq(&self, other: &&str) -> bool {
+        self.0.eq_ignore_ascii_case(other)
+    }
+}
+
+impl Hash for Keyword {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for c in self.0.bytes() {
+            state.write_u8(c.to_ascii_uppercase());
+        }
+        // Same terminator as str, so that sequences hash differently.
+        state.write_u8(0xff);
+    }
+}
+
 /// ESMTP parameter value.
 ///
 /// Used as the right side in an ESMTP parameter.  For example, it
@@ -171,16 +283,119 @@ nom_fromstr!(Value, esmtp_value::<Intl>);
 ///
 /// The source route is absent when `self.1.is_empty()`.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Path(pub Mailbox, pub Vec<Domain>);
 nom_fromstr!(Path, path::<Intl>);
 
+fn canonical_domain(domain: &Domain) -> Domain {
+    Domain(domain.0.to_lowercase())
+}
+
+pub(crate) fn canonical_mailbox(mailbox: &Mailbox) -> Mailbox {
+    let local_part = mailbox.0.canonicalize();
+
+    let domain_part = match &mailbox.1 {
+        DomainPart::Domain(d) => DomainPart::Domain(canonical_domain(d)),
+        DomainPart::Address(AddressLiteral::Tagged(tag, value)) => {
+            DomainPart::Address(AddressLiteral::Tagged(tag.to_lowercase(), value.clone()))
+        }
+        DomainPart::Address(literal) => DomainPart::Address(literal.upgrade().unwrap_or_else(|_| literal.clone())),
+    };
+
+    Mailbox(local_part, domain_part)
+}
+
+impl Path {
+    /// Create a path to `mailbox` without a source route.
+    pub fn new(mailbox: Mailbox) -> Self {
+        Path(mailbox, Vec::new())
+    }
+
+    /// Create a path to `mailbox` through the relays in `route`.
+    ///
+    /// Source routes are obsolete and must be ignored by receivers,
+    /// but they are still sent by some legacy systems.
+    /// # Examples
+    /// ```
+    /// use rustyknife::rfc5321::{ForwardPath, Path};
+    /// use rustyknife::types::{Domain, Mailbox};
+    ///
+    /// let mailbox = Mailbox::from_smtp(b"user@dest.example").unwrap();
+    /// let route = ["relay1.example", "relay2.example"].iter().map(|d| Domain::from_smtp(d.as_bytes()).unwrap());
+    /// let path = Path::with_route(route, mailbox);
+    ///
+    /// assert_eq!(path.to_string(), "<@relay1.example,@relay2.example:user@dest.example>");
+    /// assert_eq!(ForwardPath::from(path).to_string(), "<@relay1.example,@relay2.example:user@dest.example>");
+    /// ```
+    pub fn with_route<I: IntoIterator<Item=Domain>>(route: I, mailbox: Mailbox) -> Self {
+        Path(mailbox, route.into_iter().collect())
+    }
+
+    /// Return the canonical form of this path.
+    ///
+    /// The obsolete source route is dropped, the domain is lowercased,
+    /// needless quoting is removed from the local part and address
+    /// literals are normalized. Two paths designating the same mailbox
+    /// have the same canonical form, making it suitable for comparison
+    /// and deduplication.
+    ///
+    /// The local part is case sensitive and is left untouched.
+    /// # Examples
+    /// ```
+    /// use rustyknife::rfc5321::Path;
+    ///
+    /// let a: Path = "<@relay.example:\"bob\"@Example.ORG>".parse().unwrap();
+    /// let b: Path = "<bob@example.org>".parse().unwrap();
+    ///
+    /// assert_eq!(a.canonicalize(), b);
+    /// ```
+    pub fn canonicalize(&self) -> Self {
+        Path(canonical_mailbox(&self.0), Vec::new())
+    }
+
+    /// Whether this path designates a postmaster mailbox.
+    ///
+    /// The local part is compared case insensitively after removing
+    /// needless quoting, as mandated for `"postmaster"`.
+    pub fn is_postmaster(&self) -> bool {
+        self.0 .0.canonicalize().to_string().eq_ignore_ascii_case("postmaster")
+    }
+}
+
+/// Renders the obsolete source route, if any.
+impl Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<")?;
+        for (i, domain) in self.1.iter().enumerate() {
+            write!(f, "{}@{}", if i == 0 { "" } else { "," }, domain)?;
+        }
+        if !self.1.is_empty() {
+            write!(f, ":")?;
+        }
+        write!(f, "{}>", self.0)
+    }
+}
+
 /// A generic SMTP string built from an atom or a quoted string
 #[derive(Clone, PartialEq)]
 pub struct SMTPString(pub(crate) String);
 string_newtype!(SMTPString);
 
+impl SMTPString {
+    /// Returns this string as an atom, or enclosed in double quotes
+    /// when it is not a valid atom.
+    pub fn quoted(&self) -> String {
+        if exact!(self.0.as_bytes(), atom::<Intl>).is_ok() {
+            self.0.clone()
+        } else {
+            QuotedString(self.0.clone()).quoted()
+        }
+    }
+}
+
 /// Represents a forward path from the `"RCPT TO"` command.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ForwardPath {
     /// `"<person@example.org>"`
     Path(Path),
@@ -191,6 +406,47 @@ pub enum ForwardPath {
 nom_fromstr!(ForwardPath, _forward_path::<Intl>);
 
 impl ForwardPath {
+    /// The unqualified `"<postmaster>"` path.
+    pub fn postmaster() -> Self {
+        ForwardPath::PostMaster(None)
+    }
+
+    /// The `"<postmaster@domain>"` path.
+    /// # Examples
+    /// ```
+    /// use rustyknife::rfc5321::ForwardPath;
+    /// use rustyknife::types::Domain;
+    ///
+    /// let path = ForwardPath::postmaster_at(Domain::from_smtp(b"example.org").unwrap());
+    /// assert_eq!(path.to_string(), "<postmaster@example.org>");
+    /// assert_eq!(path, "<POSTMASTER@example.org>".parse().unwrap());
+    /// ```
+    pub fn postmaster_at(domain: Domain) -> Self {
+        ForwardPath::PostMaster(Some(domain))
+    }
+
+    /// Whether this path designates a postmaster mailbox, regardless
+    /// of its original casing or quoting.
+    ///
+    /// Paths with a source route such as
+    /// `"<@relay.example:PostMaster@example.org>"` are not parsed as
+    /// [`ForwardPath::PostMaster`] but are still detected.
+    /// # Examples
+    /// ```
+    /// use rustyknife::rfc5321::ForwardPath;
+    ///
+    /// for path in &["<postmaster>", "<PostMaster@example.org>", "<@relay.example:\"postMaster\"@example.org>"] {
+    ///     assert!(path.parse::<ForwardPath>().unwrap().is_postmaster());
+    /// }
+    /// assert!(!"<postmistress@example.org>".parse::<ForwardPath>().unwrap().is_postmaster());
+    /// ```
+    pub fn is_postmaster(&self) -> bool {
+        match self {
+            ForwardPath::Path(p) => p.is_postmaster(),
+            ForwardPath::PostMaster(_) => true,
+        }
+    }
+
     /// Convert this path into a mailbox.
     ///
     /// The postmaster domain must be provided since this path might
@@ -209,10 +465,35 @@ impl ForwardPath {
     }
 }
 
+impl ForwardPath {
+    /// Return the canonical form of this path.
+    ///
+    /// See [`Path::canonicalize`]. The wire form is obtained through
+    /// [`Display`].
+    /// # Examples
+    /// ```
+    /// use rustyknife::rfc5321::ForwardPath;
+    ///
+    /// let path: ForwardPath = "<@a.example,@b.example:\"Bob\"@[ipv6:2001:DB8:0::1]>".parse().unwrap();
+    /// assert_eq!(path.canonicalize().to_string(), "<Bob@[IPv6:2001:db8::1]>");
+    ///
+    /// let path: ForwardPath = "<PostMaster@EXAMPLE.org>".parse().unwrap();
+    /// assert_eq!(path.canonicalize().to_string(), "<postmaster@example.org>");
+    /// ```
+    pub fn canonicalize(&self) -> Self {
+        match self {
+            ForwardPath::Path(p) => ForwardPath::Path(p.canonicalize()),
+            ForwardPath::PostMaster(d) => ForwardPath::PostMaster(d.as_ref().map(canonical_domain)),
+        }
+    }
+}
+
+/// The source route is kept, the postmaster local part is always
+/// rendered in lowercase.
 impl Display for ForwardPath {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ForwardPath::Path(p) => write!(f, "<{}>", p.0),
+            ForwardPath::Path(p) => write!(f, "{}", p),
             ForwardPath::PostMaster(None) => write!(f, "<postmaster>"),
             ForwardPath::PostMaster(Some(d)) => write!(f, "<postmaster@{}>", d),
         }
@@ -221,6 +502,7 @@ impl Display for ForwardPath {
 
 /// Represents a reverse path from the `"MAIL FROM"` command.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ReversePath {
     /// MAIL FROM: \<person@example.org\>
     Path(Path),
@@ -229,15 +511,40 @@ pub enum ReversePath {
 }
 nom_fromstr!(ReversePath, reverse_path::<Intl>);
 
+impl ReversePath {
+    /// Return the canonical form of this path.
+    ///
+    /// See [`Path::canonicalize`]. The null path is left untouched.
+    pub fn canonicalize(&self) -> Self {
+        match self {
+            ReversePath::Path(p) => ReversePath::Path(p.canonicalize()),
+            ReversePath::Null => ReversePath::Null,
+        }
+    }
+}
+
+/// The source route is kept.
 impl Display for ReversePath {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ReversePath::Path(p) => write!(f, "<{}>", p.0),
+            ReversePath::Path(p) => write!(f, "{}", p),
             ReversePath::Null => write!(f, "<>"),
         }
     }
 }
 
+impl From<Path> for ForwardPath {
+    fn from(path: Path) -> Self {
+        ForwardPath::Path(path)
+    }
+}
+
+impl From<Path> for ReversePath {
+    fn from(path: Path) -> Self {
+        ReversePath::Path(path)
+    }
+}
+
 impl From<ReversePath> for Option<Mailbox> {
     fn from(path: ReversePath) -> Option<Mailbox> {
         match path {
@@ -253,7 +560,7 @@ fn _is_ldh(c: u8) -> bool {
 
 fn esmtp_keyword(input: &[u8]) -> NomResult<Keyword> {
     map(recognize(pair(take1_filter(is_alphanumeric), recognize_many0(take1_filter(_is_ldh)))),
-        |x| Keyword(std::str::from_utf8(x).unwrap().into()))(input)
+        |x| Keyword(std::str::from_utf8(x).unwrap().to_owned().into()))(input)
 }
 
 fn esmtp_value<P: UTF8Policy>(input: &[u8]) -> NomResult<Value> {
@@ -266,10 +573,6 @@ fn esmtp_param<P: UTF8Policy>(input: &[u8]) -> NomResult<Param> {
         |(n, v)| Param(n, v))(input)
 }
 
-fn _esmtp_params<P: UTF8Policy>(input: &[u8]) -> NomResult<Vec<Param>> {
-    fold_prefix0(esmtp_param::<P>, preceded(many1(wsp), esmtp_param::<P>))(input)
-}
-
 fn ldh_str(input: &[u8]) -> NomResult<&[u8]> {
     let (_, mut out) = take_while1(_is_ldh)(input)?;
 
@@ -397,6 +700,62 @@ pub fn helo_command<P: UTF8Policy>(input: &[u8]) -> NomResult<Domain> {
     delimited(tag_no_case("HELO "), domain::<P>, crlf)(input)
 }
 
+/// A policy violation found by [`validate_helo`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeloViolation {
+    /// An IPv4 address without brackets, such as `"192.0.2.1"`.
+    BareAddress,
+    /// A hostname without any dot, such as `"localhost"`.
+    SingleLabel,
+    /// A hostname whose top level label is numeric, such as `"192.0.2"`.
+    NumericTld,
+    /// An address literal that is not an IPv4 or IPv6 address.
+    UnknownLiteral,
+}
+
+impl Display for HeloViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeloViolation::BareAddress => write!(f, "address literal must be enclosed in brackets"),
+            HeloViolation::SingleLabel => write!(f, "hostname is not fully qualified"),
+            HeloViolation::NumericTld => write!(f, "hostname has a numeric top level domain"),
+            HeloViolation::UnknownLiteral => write!(f, "address literal is not an IP address"),
+        }
+    }
+}
+
+impl std::error::Error for HeloViolation {}
+
+/// Apply common MTA policy checks to an EHLO or HELO argument.
+///
+/// [RFC 5321 section 4.1.1.1] requires a fully qualified domain name
+/// or an IP address literal. Anything else is usually a
+/// misconfigured or malicious client.
+///
+/// [RFC 5321 section 4.1.1.1]: https://tools.ietf.org/html/rfc5321#section-4.1.1.1
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5321::{ehlo_command, validate_helo, HeloViolation};
+///
+/// let check = |input: &[u8]| validate_helo(&ehlo_command::<Intl>(input).unwrap().1);
+///
+/// assert_eq!(check(b"EHLO mx.example.org\r\n"), Ok(()));
+/// assert_eq!(check(b"EHLO [192.0.2.1]\r\n"), Ok(()));
+/// assert_eq!(check(b"EHLO 192.0.2.1\r\n"), Err(HeloViolation::BareAddress));
+/// assert_eq!(check(b"EHLO localhost\r\n"), Err(HeloViolation::SingleLabel));
+/// ```
+pub fn validate_helo(argument: &DomainPart) -> Result<(), HeloViolation> {
+    match argument {
+        DomainPart::Address(AddressLiteral::IP(_)) => Ok(()),
+        DomainPart::Address(_) => Err(HeloViolation::UnknownLiteral),
+        DomainPart::Domain(d) if d.is_bare_address() => Err(HeloViolation::BareAddress),
+        DomainPart::Domain(d) if d.is_fqdn() => Ok(()),
+        DomainPart::Domain(d) if d.trim_end_matches('.').contains('.') => Err(HeloViolation::NumericTld),
+        DomainPart::Domain(_) => Err(HeloViolation::SingleLabel),
+    }
+}
+
 /// Parse an SMTP MAIL FROM command.
 ///
 /// Returns a tuple with the reverse path and ESMTP parameters.
@@ -411,10 +770,84 @@ pub fn helo_command<P: UTF8Policy>(input: &[u8]) -> NomResult<Domain> {
 /// assert_eq!(params, [Param::new("BODY", Some("8BIT")).unwrap()]);
 /// ```
 pub fn mail_command<P: UTF8Policy>(input: &[u8]) -> NomResult<(ReversePath, Vec<Param>)> {
-    map(delimited(tag_no_case("MAIL FROM:"),
-                  pair(reverse_path::<P>, opt(preceded(tag(" "), _esmtp_params::<P>))),
-                  crlf),
-        |(addr, params)| (addr, params.unwrap_or_default()))(input)
+    map(mail_command_spanned::<P>, |cmd| cmd.into_parts())(input)
+}
+
+/// A parsed value with its byte range in the input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spanned<T> {
+    /// The byte range in the input.
+    pub span: Range<usize>,
+    /// The parsed value.
+    pub value: T,
+}
+
+/// A MAIL FROM or RCPT TO command with the byte range of each
+/// component.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpannedPathCommand<T> {
+    /// The range of the command verb and colon, such as `"MAIL FROM:"`.
+    pub verb: Range<usize>,
+    /// The path, including the angle brackets.
+    pub path: Spanned<T>,
+    /// The ESMTP parameters.
+    pub params: Vec<Spanned<Param>>,
+}
+
+impl<T> SpannedPathCommand<T> {
+    /// Drop the spans.
+    pub fn into_parts(self) -> (T, Vec<Param>) {
+        (self.path.value, self.params.into_iter().map(|p| p.value).collect())
+    }
+}
+
+fn _path_command_spanned<'a, T, P, F>(verb: &'static str, path: F) -> impl FnMut(&'a [u8]) -> NomResult<'a, SpannedPathCommand<T>>
+    where P: UTF8Policy,
+          F: FnMut(&'a [u8]) -> NomResult<'a, T> + Copy,
+{
+    move |input: &'a [u8]| {
+        let span = |part: &[u8]| {
+            let start = part.as_ptr() as usize - input.as_ptr() as usize;
+            start..start + part.len()
+        };
+        let (rem, (v, (raw_path, path), params)) = terminated(tuple((
+            tag_no_case(verb),
+            consumed(path),
+            opt(preceded(tag(" "), fold_prefix0(consumed(esmtp_param::<P>), preceded(many1(wsp), consumed(esmtp_param::<P>))))),
+        )), crlf)(input)?;
+
+        Ok((rem, SpannedPathCommand {
+            verb: span(v),
+            path: Spanned { span: span(raw_path), value: path },
+            params: params.unwrap_or_default().into_iter().map(|(raw, value)| Spanned { span: span(raw), value }).collect(),
+        }))
+    }
+}
+
+/// Parse an SMTP MAIL FROM command, also returning the byte range of
+/// each component in `input`.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5321::mail_command_spanned;
+///
+/// let input = b"MAIL FROM:<bob@example.org> BODY=8BIT SMTPUTF8\r\n";
+/// let (_, cmd) = mail_command_spanned::<Intl>(input).unwrap();
+///
+/// assert_eq!(cmd.verb, 0..10);
+/// assert_eq!(&input[cmd.path.span], b"<bob@example.org>");
+/// assert_eq!(&input[cmd.params[1].span.clone()], b"SMTPUTF8");
+/// ```
+pub fn mail_command_spanned<P: UTF8Policy>(input: &[u8]) -> NomResult<SpannedPathCommand<ReversePath>> {
+    _path_command_spanned::<_, P, _>("MAIL FROM:", reverse_path::<P>)(input)
+}
+
+/// Parse an SMTP RCPT TO command, also returning the byte range of
+/// each component in `input`.
+///
+/// See [`mail_command_spanned`].
+pub fn rcpt_command_spanned<P: UTF8Policy>(input: &[u8]) -> NomResult<SpannedPathCommand<ForwardPath>> {
+    _path_command_spanned::<_, P, _>("RCPT TO:", _forward_path::<P>)(input)
 }
 
 fn _forward_path<P: UTF8Policy>(input: &[u8]) -> NomResult<ForwardPath> {
@@ -438,11 +871,7 @@ fn _forward_path<P: UTF8Policy>(input: &[u8]) -> NomResult<ForwardPath> {
 /// assert_eq!(params, [Param::new("NOTIFY", Some("NEVER")).unwrap()]);
 /// ```
 pub fn rcpt_command<P: UTF8Policy>(input: &[u8]) -> NomResult<(ForwardPath, Vec<Param>)> {
-    map(delimited(
-        tag_no_case("RCPT TO:"),
-        pair(_forward_path::<P>, opt(preceded(tag(" "), _esmtp_params::<P>))),
-        crlf,
-    ), |(path, params)| (path, params.unwrap_or_default()))(input)
+    map(rcpt_command_spanned::<P>, |cmd| cmd.into_parts())(input)
 }
 
 /// Parse an SMTP DATA command.
@@ -462,8 +891,28 @@ fn _smtp_string<P: UTF8Policy>(input: &[u8]) -> NomResult<SMTPString> {
 
 /// Parse an SMTP NOOP command.
 pub fn noop_command<P: UTF8Policy>(input: &[u8]) -> NomResult<Option<SMTPString>> {
+    map(noop_command_raw::<P>, |arg| arg.map(|(_, s)| s))(input)
+}
+
+/// Parse an SMTP NOOP command, also returning the raw argument.
+///
+/// The raw argument is returned as it appears in the command,
+/// including quotes and escapes, for deployments that put structured
+/// data in it.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5321::noop_command_raw;
+///
+/// let (_, arg) = noop_command_raw::<Intl>(b"NOOP \"id=1 \\\"x\\\"\"\r\n").unwrap();
+/// let (raw, decoded) = arg.unwrap();
+///
+/// assert_eq!(raw, b"\"id=1 \\\"x\\\"\"");
+/// assert_eq!(&*decoded, "id=1 \"x\"");
+/// ```
+pub fn noop_command_raw<P: UTF8Policy>(input: &[u8]) -> NomResult<Option<(&[u8], SMTPString)>> {
     delimited(tag_no_case("NOOP"),
-              opt(preceded(tag(" "), _smtp_string::<P>)),
+              opt(preceded(tag(" "), consumed(_smtp_string::<P>))),
               crlf)(input)
 }
 
@@ -474,18 +923,39 @@ pub fn quit_command(input: &[u8]) -> NomResult<()> {
 
 /// Parse an SMTP VRFY command.
 pub fn vrfy_command<P: UTF8Policy>(input: &[u8]) -> NomResult<SMTPString> {
-    delimited(tag_no_case("VRFY "), _smtp_string::<P>, crlf)(input)
+    map(vrfy_command_raw::<P>, |(_, s)| s)(input)
+}
+
+/// Parse an SMTP VRFY command, also returning the raw argument.
+///
+/// See [`noop_command_raw`].
+pub fn vrfy_command_raw<P: UTF8Policy>(input: &[u8]) -> NomResult<(&[u8], SMTPString)> {
+    delimited(tag_no_case("VRFY "), consumed(_smtp_string::<P>), crlf)(input)
 }
 
 /// Parse an SMTP EXPN command.
 pub fn expn_command<P: UTF8Policy>(input: &[u8]) -> NomResult<SMTPString> {
-    delimited(tag_no_case("EXPN "), _smtp_string::<P>, crlf)(input)
+    map(expn_command_raw::<P>, |(_, s)| s)(input)
+}
+
+/// Parse an SMTP EXPN command, also returning the raw argument.
+///
+/// See [`noop_command_raw`].
+pub fn expn_command_raw<P: UTF8Policy>(input: &[u8]) -> NomResult<(&[u8], SMTPString)> {
+    delimited(tag_no_case("EXPN "), consumed(_smtp_string::<P>), crlf)(input)
 }
 
 /// Parse an SMTP HELP command.
 pub fn help_command<P: UTF8Policy>(input: &[u8]) -> NomResult<Option<SMTPString>> {
+    map(help_command_raw::<P>, |arg| arg.map(|(_, s)| s))(input)
+}
+
+/// Parse an SMTP HELP command, also returning the raw argument.
+///
+/// See [`noop_command_raw`].
+pub fn help_command_raw<P: UTF8Policy>(input: &[u8]) -> NomResult<Option<(&[u8], SMTPString)>> {
     delimited(tag_no_case("HELP"),
-              opt(preceded(tag(" "), _smtp_string::<P>)),
+              opt(preceded(tag(" "), consumed(_smtp_string::<P>))),
               crlf)(input)
 }
 
@@ -507,6 +977,137 @@ pub enum Command {
     VRFY(SMTPString),
     EXPN(SMTPString),
     HELP(Option<SMTPString>),
+    ATRN(Vec<Domain>),
+}
+
+/// Renders the command line without the terminating CRLF, see
+/// [`Command::to_wire`].
+impl Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let with_params = |f: &mut fmt::Formatter, params: &[Param]| {
+            if params.is_empty() { Ok(()) } else { write!(f, " {}", Params(params)) }
+        };
+        let with_arg = |f: &mut fmt::Formatter, verb: &str, arg: &Option<SMTPString>| match arg {
+            Some(arg) => write!(f, "{} {}", verb, arg.quoted()),
+            None => write!(f, "{}", verb),
+        };
+
+        match self {
+            Command::EHLO(domain) => write!(f, "EHLO {}", domain),
+            Command::HELO(domain) => write!(f, "HELO {}", domain),
+            Command::MAIL(path, params) => {
+                write!(f, "MAIL FROM:{}", path)?;
+                with_params(f, params)
+            },
+            Command::RCPT(path, params) => {
+                write!(f, "RCPT TO:{}", path)?;
+                with_params(f, params)
+            },
+            Command::DATA => write!(f, "DATA"),
+            Command::RSET => write!(f, "RSET"),
+            Command::NOOP(arg) => with_arg(f, "NOOP", arg),
+            Command::QUIT => write!(f, "QUIT"),
+            Command::VRFY(arg) => write!(f, "VRFY {}", arg.quoted()),
+            Command::EXPN(arg) => write!(f, "EXPN {}", arg.quoted()),
+            Command::HELP(arg) => with_arg(f, "HELP", arg),
+            Command::ATRN(domains) if domains.is_empty() => write!(f, "ATRN"),
+            Command::ATRN(domains) => {
+                write!(f, "ATRN ")?;
+                for (i, domain) in domains.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 { "" } else { "," }, domain)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl Command {
+    /// Serialize the command as a CRLF terminated line, ready to be
+    /// sent to a server.
+    /// # Examples
+    /// ```
+    /// use rustyknife::behaviour::Intl;
+    /// use rustyknife::rfc5321::command;
+    ///
+    /// let (_, cmd) = command::<Intl>(b"mail from:<bob@example.org> size=100\r\n").unwrap();
+    /// assert_eq!(cmd.to_wire(), b"MAIL FROM:<bob@example.org> size=100\r\n");
+    ///
+    /// let (_, cmd) = command::<Intl>(b"VRFY \"Bob Smith\"\r\n").unwrap();
+    /// assert_eq!(cmd.to_string(), "VRFY \"Bob Smith\"");
+    /// ```
+    pub fn to_wire(&self) -> Vec<u8> {
+        format!("{}\r\n", self).into_bytes()
+    }
+}
+
+/// The commands of a callout probe checking whether a server accepts
+/// a recipient, see [`rcpt_probe`].
+#[derive(Debug)]
+pub struct RcptProbe {
+    /// The EHLO command.
+    pub ehlo: Command,
+    /// A MAIL command with the null reverse path, so that the probe
+    /// cannot cause a bounce.
+    pub mail: Command,
+    /// The RCPT command for the probed address.
+    pub rcpt: Command,
+    /// Whether the address requires the SMTPUTF8 extension. The
+    /// probe is inconclusive if the server does not advertise it.
+    pub smtputf8: bool,
+}
+
+impl RcptProbe {
+    /// The commands in the order they are sent.
+    pub fn commands(&self) -> [&Command; 3] {
+        [&self.ehlo, &self.mail, &self.rcpt]
+    }
+}
+
+/// Generate the commands probing whether a server accepts
+/// `recipient`, for callout verification.
+///
+/// Internationalized domains are converted to punycode so that
+/// SMTPUTF8 is only requested when the local part is not ASCII.
+/// Needless quoting of the local part is removed. The session should
+/// be ended with QUIT after the RCPT reply.
+/// # Examples
+/// ```
+/// use rustyknife::rfc5321::rcpt_probe;
+/// use rustyknife::types::{DomainPart, Mailbox};
+///
+/// let helo = DomainPart::from_smtp(b"probe.example.net").unwrap();
+///
+/// let probe = rcpt_probe(&helo, &Mailbox::from_smtp("bob@bücher.example".as_bytes()).unwrap());
+/// assert_eq!(probe.ehlo.to_wire(), b"EHLO probe.example.net\r\n");
+/// assert_eq!(probe.mail.to_wire(), b"MAIL FROM:<>\r\n");
+/// assert_eq!(probe.rcpt.to_wire(), b"RCPT TO:<bob@xn--bcher-kva.example>\r\n");
+/// assert!(!probe.smtputf8);
+///
+/// let probe = rcpt_probe(&helo, &Mailbox::from_smtp("rené@bücher.example".as_bytes()).unwrap());
+/// assert_eq!(probe.mail.to_string(), "MAIL FROM:<> SMTPUTF8");
+/// assert_eq!(probe.rcpt.to_string(), "RCPT TO:<rené@bücher.example>");
+/// ```
+pub fn rcpt_probe(helo: &DomainPart, recipient: &Mailbox) -> RcptProbe {
+    let ascii_domain = |domain: &DomainPart| match domain {
+        DomainPart::Domain(d) => d.to_ascii_string().ok().map(|a| DomainPart::Domain(Domain(a))),
+        DomainPart::Address(_) => Some(domain.clone()),
+    };
+
+    let local = recipient.local_part().canonicalize();
+    let (domain, smtputf8) = match ascii_domain(recipient.domain_part()) {
+        Some(domain) if local.to_string().is_ascii() => (domain, false),
+        _ => (recipient.domain_part().clone(), true),
+    };
+
+    let params = if smtputf8 { vec![Param(Keyword::SMTPUTF8, None)] } else { Vec::new() };
+
+    RcptProbe {
+        ehlo: Command::EHLO(ascii_domain(helo).unwrap_or_else(|| helo.clone())),
+        mail: Command::MAIL(ReversePath::Null, params),
+        rcpt: Command::RCPT(ForwardPath::Path(Path(Mailbox(local, domain), Vec::new())), Vec::new()),
+        smtputf8,
+    }
 }
 
 /// Parse any basic SMTP command.
@@ -523,9 +1124,345 @@ pub fn command<P: UTF8Policy>(input: &[u8]) -> NomResult<Command> {
         map(vrfy_command::<P>, Command::VRFY),
         map(expn_command::<P>, Command::EXPN),
         map(help_command::<P>, Command::HELP),
+        map(atrn_command::<P>, Command::ATRN),
     ))(input)
 }
 
+/// Split any command line into its verb and raw argument.
+///
+/// Only the verb syntax is checked: letters, digits and hyphens,
+/// starting with a letter. The argument is everything after the
+/// first space, without the terminating CRLF, and is empty when
+/// absent. Bare CR and LF are rejected.
+///
+/// Use this as the last alternative of a dispatch loop to handle
+/// extension commands such as XCLIENT or XVERP.
+/// # Examples
+/// ```
+/// use rustyknife::rfc5321::extension_command;
+///
+/// assert_eq!(extension_command(b"XVERP +=\r\n").unwrap().1, ("XVERP", &b"+="[..]));
+/// assert_eq!(extension_command(b"X-PING\r\n").unwrap().1, ("X-PING", &b""[..]));
+/// assert!(extension_command(b"X?Y\r\n").is_err());
+/// ```
+pub fn extension_command(input: &[u8]) -> NomResult<(&str, &[u8])> {
+    terminated(
+        pair(map(recognize(pair(take1_filter(|c| c.is_ascii_alphabetic()), recognize_many0(take1_filter(_is_ldh)))),
+                 |verb| str::from_utf8(verb).unwrap()),
+             map(opt(preceded(tag(" "), take_while(|c| c != b'\r' && c != b'\n'))), Option::unwrap_or_default)),
+        crlf,
+    )(input)
+}
+
+fn check_domain(domain: &str, limits: &SmtpLimits) -> Result<(), DecodeError> {
+    if domain.len() > limits.max_domain {
+        Err(DecodeError::LimitExceeded(Limit::Domain))
+    } else {
+        Ok(())
+    }
+}
+
+fn check_path(path: &Path, limits: &SmtpLimits) -> Result<(), DecodeError> {
+    if path.0.local_part().to_string().len() > limits.max_local_part {
+        return Err(DecodeError::LimitExceeded(Limit::LocalPart));
+    }
+    if let DomainPart::Domain(domain) = path.0.domain_part() {
+        check_domain(domain, limits)?;
+    }
+    if path.to_string().len() > limits.max_path {
+        return Err(DecodeError::LimitExceeded(Limit::Path));
+    }
+
+    Ok(())
+}
+
+/// Same as [`command`] but fails with [`DecodeError::LimitExceeded`]
+/// when the command line, a path or one of its parts is over `limits`.
+///
+/// The line length is checked before parsing, so that oversized
+/// commands are not copied.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::limits::{DecodeError, Limit, SmtpLimits};
+/// use rustyknife::rfc5321::command_limited;
+///
+/// let limits = SmtpLimits::default();
+/// assert!(command_limited::<Intl>(b"RCPT TO:<bob@example.org>\r\n", &limits).is_ok());
+///
+/// let input = format!("RCPT TO:<{}@example.org>\r\n", "a".repeat(65));
+/// assert_eq!(command_limited::<Intl>(input.as_bytes(), &limits).unwrap_err(), DecodeError::LimitExceeded(Limit::LocalPart));
+/// ```
+pub fn command_limited<'a, P: UTF8Policy>(input: &'a [u8], limits: &SmtpLimits) -> LimitedResult<'a, Command> {
+    let line_len = input.windows(2).position(|w| w == b"\r\n").map_or(input.len(), |pos| pos + 2);
+    if line_len > limits.max_command_line {
+        return Err(DecodeError::LimitExceeded(Limit::CommandLine));
+    }
+
+    let (rem, cmd) = command::<P>(input)?;
+    match &cmd {
+        Command::MAIL(ReversePath::Path(path), _) | Command::RCPT(ForwardPath::Path(path), _) => check_path(path, limits)?,
+        Command::RCPT(ForwardPath::PostMaster(Some(domain)), _) | Command::HELO(domain) => check_domain(domain, limits)?,
+        Command::EHLO(DomainPart::Domain(domain)) => check_domain(domain, limits)?,
+        Command::ATRN(domains) => domains.iter().try_for_each(|d| check_domain(d, limits))?,
+        _ => (),
+    }
+
+    Ok((rem, cmd))
+}
+
+/// Reason a line was rejected by [`command_classified`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CommandError {
+    /// The verb is not one of the base commands.
+    UnknownVerb,
+    /// The arguments of the command, given in upper case, are
+    /// invalid.
+    BadSyntax(String),
+    /// The path of a MAIL or RCPT command is valid but its ESMTP
+    /// parameters are not.
+    BadParameter(String),
+}
+
+impl CommandError {
+    /// The reply code to answer with.
+    pub fn reply_code(&self) -> ReplyCode {
+        match self {
+            CommandError::UnknownVerb => ReplyCode::SYNTAX_ERROR,
+            CommandError::BadSyntax(_) => ReplyCode::PARAMETER_SYNTAX_ERROR,
+            CommandError::BadParameter(_) => ReplyCode::PARAMETERS_NOT_RECOGNIZED,
+        }
+    }
+
+    /// The enhanced status code to answer with.
+    ///
+    /// Invalid paths use the address syntax codes, `"5.1.7"` for the
+    /// sender and `"5.1.3"` for a recipient.
+    pub fn status(&self) -> EnhancedStatusCode {
+        let (subject, detail) = match self {
+            CommandError::UnknownVerb => (5, 1),
+            CommandError::BadSyntax(verb) if verb == "MAIL" => (1, 7),
+            CommandError::BadSyntax(verb) if verb == "RCPT" => (1, 3),
+            CommandError::BadSyntax(_) => (5, 2),
+            CommandError::BadParameter(_) => (5, 4),
+        };
+        EnhancedStatusCode::new(5, subject, detail).unwrap()
+    }
+
+    /// The suggested reply, with the enhanced status code.
+    pub fn to_reply(&self) -> Reply {
+        Reply::with_status(self.reply_code(), self.status(), &self.to_string())
+    }
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::UnknownVerb => write!(f, "Command unrecognized"),
+            CommandError::BadSyntax(verb) => write!(f, "Syntax error in {} arguments", verb),
+            CommandError::BadParameter(verb) => write!(f, "Invalid {} parameters", verb),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+// Verbs of the commands parsed by `command`.
+pub(crate) const VERBS: &[&str] = &["EHLO", "HELO", "MAIL", "RCPT", "DATA", "RSET", "NOOP", "QUIT", "VRFY", "EXPN", "HELP", "ATRN"];
+
+fn classify<P: UTF8Policy>(input: &[u8]) -> CommandError {
+    let end = input.iter().position(|c| b" \r\n".contains(c)).unwrap_or(input.len());
+    let verb = match VERBS.iter().find(|v| v.as_bytes().eq_ignore_ascii_case(&input[..end])) {
+        Some(verb) => verb.to_string(),
+        None => return CommandError::UnknownVerb,
+    };
+
+    let params = match verb.as_str() {
+        "MAIL" => tuple((tag_no_case("MAIL FROM:"), map(reverse_path::<P>, |_| ()), tag(" ")))(input).is_ok(),
+        "RCPT" => tuple((tag_no_case("RCPT TO:"), map(_forward_path::<P>, |_| ()), tag(" ")))(input).is_ok(),
+        _ => false,
+    };
+
+    if params {
+        CommandError::BadParameter(verb)
+    } else {
+        CommandError::BadSyntax(verb)
+    }
+}
+
+/// Same as [`command`] but tells why the line was rejected.
+///
+/// Returns [`nom::Err::Incomplete`] while the input holds no line
+/// break, since the line cannot be judged yet. Parameters are only
+/// checked for syntax, a server must still answer
+/// [`ReplyCode::PARAMETERS_NOT_RECOGNIZED`] for the ones it does not
+/// implement.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5321::{command_classified, CommandError};
+///
+/// assert!(command_classified::<Intl>(b"RSET\r\n").is_ok());
+/// assert!(matches!(command_classified::<Intl>(b"RSE"), Err(nom::Err::Incomplete(_))));
+///
+/// let reply = |input| match command_classified::<Intl>(input) {
+///     Err(nom::Err::Error(err)) => err.to_reply().to_smtp::<Intl>().unwrap(),
+///     _ => unreachable!(),
+/// };
+/// assert_eq!(reply(b"MAIL FROM:<bob@example.org> SIZE=\r\n"), b"555 5.5.4 Invalid MAIL parameters\r\n");
+/// assert_eq!(reply(b"rcpt to:bob@example.org\r\n"), b"501 5.1.3 Syntax error in RCPT arguments\r\n");
+/// assert_eq!(reply(b"STARTTLS\r\n"), b"500 5.5.1 Command unrecognized\r\n");
+/// ```
+pub fn command_classified<P: UTF8Policy>(input: &[u8]) -> Result<(&[u8], Command), nom::Err<CommandError>> {
+    if !input.contains(&b'\n') {
+        return Err(nom::Err::Incomplete(Needed::Unknown));
+    }
+    command::<P>(input).map_err(|_| nom::Err::Error(classify::<P>(input)))
+}
+
+/// Parse a [PIPELINING] batch of commands.
+///
+/// Parsing stops at the first incomplete or invalid line, which starts
+/// the remaining input, or after a command that must end a batch:
+/// EHLO, HELO, DATA, VRFY, EXPN, NOOP, QUIT and ATRN. Data following
+/// them, such as the message content after DATA or the turned around
+/// session after ATRN, must not be parsed as commands. The offset of
+/// the first unparsed line is the length of the input minus the length
+/// of the remaining input.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5321::commands;
+///
+/// let input = b"MAIL FROM:<bob@example.org>\r\nRCPT TO:<alice@example.org>\r\nDATA\r\nSubject: hi\r\n";
+/// let (rem, cmds) = commands::<Intl>(input).unwrap();
+///
+/// assert_eq!(cmds.len(), 3);
+/// assert_eq!(rem, b"Subject: hi\r\n");
+/// assert_eq!(input.len() - rem.len(), 64);
+/// ```
+///
+/// [PIPELINING]: https://tools.ietf.org/html/rfc2920#section-3.1
+pub fn commands<P: UTF8Policy>(mut input: &[u8]) -> NomResult<Vec<Command>> {
+    let mut out = Vec::new();
+
+    while let Ok((rem, cmd)) = command::<P>(input) {
+        input = rem;
+        let last = matches!(cmd, Command::EHLO(_) | Command::HELO(_) | Command::DATA | Command::VRFY(_) |
+                                 Command::EXPN(_) | Command::NOOP(_) | Command::QUIT | Command::ATRN(_));
+        out.push(cmd);
+        if last {
+            break;
+        }
+    }
+
+    Ok((input, out))
+}
+
+/// Adapt a command parser to a buffer that is still being received.
+///
+/// Returns [`nom::Err::Incomplete`] until the buffer holds a complete
+/// line, then applies `parser`. This works with any of the
+/// `*_command` functions and with [`command`]. The caller must cap the
+/// amount of data buffered, since an overlong line is only rejected
+/// once its CRLF is received.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5321::{command, streaming, Command};
+///
+/// assert!(matches!(streaming(command::<Intl>)(b"MAIL FROM:<bob@exa"), Err(nom::Err::Incomplete(_))));
+/// assert!(matches!(streaming(command::<Intl>)(b"QUIT\r"), Err(nom::Err::Incomplete(_))));
+///
+/// let (rem, cmd) = streaming(command::<Intl>)(b"QUIT\r\nRSET").unwrap();
+/// assert!(matches!(cmd, Command::QUIT));
+/// assert_eq!(rem, b"RSET");
+///
+/// assert!(matches!(streaming(command::<Intl>)(b"BOGUS\r\n"), Err(nom::Err::Error(_))));
+/// ```
+pub fn streaming<'a, O, F>(mut parser: F) -> impl FnMut(&'a [u8]) -> NomResult<'a, O>
+    where F: FnMut(&'a [u8]) -> NomResult<'a, O>,
+{
+    move |input| {
+        if input.windows(2).any(|w| w == b"\r\n") {
+            parser(input)
+        } else if input.ends_with(b"\r") {
+            Err(nom::Err::Incomplete(Needed::new(1)))
+        } else {
+            Err(nom::Err::Incomplete(Needed::Unknown))
+        }
+    }
+}
+
+// Rewrite the common deviations of a command line to the strict syntax.
+fn normalize_line(line: &[u8]) -> Vec<u8> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let line = &line[..line.len() - line.iter().rev().take_while(|c| b" \t".contains(c)).count()];
+    let mut out = Vec::with_capacity(line.len() + 4);
+
+    let prefix = [&b"MAIL FROM:"[..], b"RCPT TO:"].iter()
+        .find(|p| matches!(line.get(..p.len()), Some(l) if l.eq_ignore_ascii_case(p)))
+        .map_or(0, |p| p.len());
+    out.extend_from_slice(&line[..prefix]);
+    let rest = &line[prefix..];
+
+    if prefix > 0 {
+        let rest = &rest[rest.iter().take_while(|c| **c == b' ').count()..];
+        if rest.is_empty() || rest.starts_with(b"<") {
+            out.extend_from_slice(rest);
+        } else {
+            let end = rest.iter().position(|c| *c == b' ').unwrap_or(rest.len());
+            out.push(b'<');
+            out.extend_from_slice(&rest[..end]);
+            out.push(b'>');
+            out.extend_from_slice(&rest[end..]);
+        }
+    } else {
+        out.extend_from_slice(rest);
+    }
+    out.extend_from_slice(b"\r\n");
+
+    out
+}
+
+/// Adapt a command parser to accept the deviations of sloppy clients.
+///
+/// The following are accepted:
+/// - LF line endings.
+/// - Trailing whitespace.
+/// - Spaces after the colon of `"MAIL FROM:"` and `"RCPT TO:"`.
+/// - Paths without angle brackets.
+///
+/// Like [`streaming`], returns [`nom::Err::Incomplete`] until a
+/// complete line is available. The strict parsers are not affected.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5321::{lenient, mail_command};
+///
+/// let (rem, (path, _)) = lenient(mail_command::<Intl>)(b"MAIL FROM: bob@example.org SIZE=10\nRSET\n").unwrap();
+/// assert_eq!(path.to_string(), "<bob@example.org>");
+/// assert_eq!(rem, b"RSET\n");
+///
+/// assert!(mail_command::<Intl>(b"MAIL FROM: bob@example.org SIZE=10\n").is_err());
+/// ```
+pub fn lenient<'a, O, F>(mut parser: F) -> impl FnMut(&'a [u8]) -> NomResult<'a, O>
+    where F: for<'b> FnMut(&'b [u8]) -> NomResult<'b, O>,
+{
+    move |input| {
+        let end = match input.iter().position(|c| *c == b'\n') {
+            Some(nl) => nl + 1,
+            None => return Err(nom::Err::Incomplete(Needed::Unknown)),
+        };
+
+        match parser(&normalize_line(&input[..end])) {
+            Ok((b"", out)) => Ok((&input[end..], out)),
+            _ => Err(nom::Err::Error(())),
+        }
+    }
+}
+
 /// Validates an email address.
 ///
 /// Does not accept the empty address.
@@ -542,11 +1479,193 @@ pub fn validate_address<P: UTF8Policy>(i: &[u8]) -> bool {
     exact!(i, mailbox::<P>).is_ok()
 }
 
+/// Grammar rule that an address failed to match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressRule {
+    /// The address is empty.
+    Empty,
+    /// Invalid character in an unquoted local part.
+    LocalPart,
+    /// Invalid character in a quoted local part, or missing closing
+    /// quote.
+    QuotedString,
+    /// The `"@"` separating the local part from the domain is missing.
+    MissingAt,
+    /// Invalid character in a domain name.
+    Domain,
+    /// Invalid address literal, or missing closing bracket.
+    AddressLiteral,
+    /// Address literals are not allowed.
+    AddressLiteralForbidden,
+    /// Quoted local parts are not allowed.
+    QuotedLocalPartForbidden,
+    /// The domain has no dot.
+    UnqualifiedDomain,
+    /// The address is too long, the offset is that of the first byte
+    /// past the limit.
+    TooLong,
+    /// The local part is too long, the offset is that of the first
+    /// byte past the limit.
+    LocalPartTooLong,
+    /// The domain part is too long, the offset is that of the first
+    /// byte past the limit.
+    DomainTooLong,
+}
+
+/// Why an address failed validation, see [`validate_address_detailed`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddressError {
+    /// Byte offset of the first invalid byte.
+    pub offset: usize,
+    /// Grammar rule being matched at `offset`.
+    pub rule: AddressRule,
+}
+
+impl Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.rule {
+            AddressRule::Empty => write!(f, "empty address"),
+            AddressRule::LocalPart => write!(f, "invalid character in local part at byte {}", self.offset),
+            AddressRule::QuotedString => write!(f, "invalid quoted local part at byte {}", self.offset),
+            AddressRule::MissingAt => write!(f, "expected \"@\" at byte {}", self.offset),
+            AddressRule::Domain => write!(f, "invalid character in domain at byte {}", self.offset),
+            AddressRule::AddressLiteral => write!(f, "invalid address literal at byte {}", self.offset),
+            AddressRule::AddressLiteralForbidden => write!(f, "address literal not allowed at byte {}", self.offset),
+            AddressRule::QuotedLocalPartForbidden => write!(f, "quoted local part not allowed"),
+            AddressRule::UnqualifiedDomain => write!(f, "domain at byte {} has no dot", self.offset),
+            AddressRule::TooLong => write!(f, "address too long at byte {}", self.offset),
+            AddressRule::LocalPartTooLong => write!(f, "local part too long at byte {}", self.offset),
+            AddressRule::DomainTooLong => write!(f, "domain too long at byte {}", self.offset),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+/// Validates an email address, reporting where validation failed.
+///
+/// Accepts the same addresses as [`validate_address`].
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5321::{validate_address_detailed, AddressRule};
+///
+/// assert!(validate_address_detailed::<Intl>(b"bob@example.org").is_ok());
+///
+/// let err = validate_address_detailed::<Intl>(b"bob@example..org").unwrap_err();
+/// assert_eq!(err.rule, AddressRule::Domain);
+/// assert_eq!(err.to_string(), "invalid character in domain at byte 11");
+/// ```
+pub fn validate_address_detailed<P: UTF8Policy>(i: &[u8]) -> Result<(), AddressError> {
+    let error = |rem: &[u8], rule| Err(AddressError { offset: i.len() - rem.len(), rule });
+
+    if i.is_empty() {
+        return error(i, AddressRule::Empty);
+    }
+
+    let rem = if i.starts_with(b"\"") {
+        let (rem, _) = many0(qcontent_smtp::<P>)(&i[1..]).unwrap_or((&i[1..], vec![]));
+        match rem.strip_prefix(b"\"") {
+            Some(rem) if rem.starts_with(b"@") => rem,
+            Some(rem) => return error(rem, AddressRule::MissingAt),
+            None => return error(rem, AddressRule::QuotedString),
+        }
+    } else {
+        match dot_string::<P>(i) {
+            Ok((rem, _)) if rem.starts_with(b"@") => rem,
+            Ok((rem, _)) if rem.is_empty() => return error(rem, AddressRule::MissingAt),
+            Ok((rem, _)) => return error(rem, AddressRule::LocalPart),
+            Err(_) => return error(i, AddressRule::LocalPart),
+        }
+    };
+    let rem = &rem[1..];
+
+    if let Some(literal) = rem.strip_prefix(b"[") {
+        match _inner_address_literal(literal) {
+            Ok((b"]", _)) => Ok(()),
+            Ok((rem, _)) => error(rem.strip_prefix(b"]").unwrap_or(rem), AddressRule::AddressLiteral),
+            Err(_) => error(literal, AddressRule::AddressLiteral),
+        }
+    } else {
+        match domain::<P>(rem) {
+            Ok((b"", _)) => Ok(()),
+            Ok((rem, _)) => error(rem, AddressRule::Domain),
+            Err(_) => error(rem, AddressRule::Domain),
+        }
+    }
+}
+
+/// Validates an email address according to `options`.
+///
+/// The grammar is checked first, as with
+/// [`validate_address_detailed`], then the restrictions of `options`.
+/// # Examples
+/// ```
+/// use rustyknife::options::AddressOptions;
+/// use rustyknife::rfc5321::{validate_address_with, AddressRule};
+///
+/// let options = AddressOptions::strict();
+///
+/// assert!(validate_address_with(b"bob@example.org", &options).is_ok());
+/// assert_eq!(validate_address_with(b"bob@[192.0.2.1]", &options).unwrap_err().rule,
+///            AddressRule::AddressLiteralForbidden);
+/// assert_eq!(validate_address_with(b"bob@localhost", &options).unwrap_err().rule,
+///            AddressRule::UnqualifiedDomain);
+/// ```
+pub fn validate_address_with(i: &[u8], options: &AddressOptions) -> Result<(), AddressError> {
+    if options.allow_utf8 {
+        validate_address_detailed::<Intl>(i)?;
+    } else {
+        validate_address_detailed::<Legacy>(i)?;
+    }
+
+    // Domains cannot contain "@", unlike quoted local parts.
+    let at = i.iter().rposition(|c| *c == b'@').unwrap();
+    let domain = &i[at + 1..];
+    let error = |offset, rule| Err(AddressError { offset, rule });
+
+    if i[0] == b'"' && !options.allow_quoted_local_part {
+        error(0, AddressRule::QuotedLocalPartForbidden)
+    } else if domain[0] == b'[' && !options.allow_address_literals {
+        error(at + 1, AddressRule::AddressLiteralForbidden)
+    } else if domain[0] != b'[' && options.require_dot_in_domain && !domain.contains(&b'.') {
+        error(at + 1, AddressRule::UnqualifiedDomain)
+    } else if at > options.max_local_part_len {
+        error(options.max_local_part_len, AddressRule::LocalPartTooLong)
+    } else if domain.len() > options.max_domain_len {
+        error(at + 1 + options.max_domain_len, AddressRule::DomainTooLong)
+    } else if i.len() > options.max_len {
+        error(options.max_len, AddressRule::TooLong)
+    } else {
+        Ok(())
+    }
+}
+
 /// Parse a STARTTLS command from RFC 3207
 pub fn starttls_command(input: &[u8]) -> NomResult<()> {
     map(tag_no_case("STARTTLS\r\n"), |_| ())(input)
 }
 
+/// Parse an ATRN command from RFC 2645
+///
+/// Returns the requested domains. An empty list requests all the
+/// domains the client is authorized for.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5321::atrn_command;
+///
+/// let (_, domains) = atrn_command::<Intl>(b"ATRN example.org,example.net\r\n").unwrap();
+/// assert_eq!(domains.len(), 2);
+/// assert!(atrn_command::<Intl>(b"ATRN\r\n").unwrap().1.is_empty());
+/// ```
+pub fn atrn_command<P: UTF8Policy>(input: &[u8]) -> NomResult<Vec<Domain>> {
+    map(delimited(tag_no_case("ATRN"),
+                  opt(preceded(tag(" "), separated_list1(tag(","), domain::<P>))),
+                  crlf),
+        |domains| domains.unwrap_or_default())(input)
+}
+
 /// Parse a BDAT command from RFC 3030
 pub fn bdat_command(input: &[u8]) -> NomResult<(u64, bool)> {
     terminated(
@@ -559,7 +1678,147 @@ pub fn bdat_command(input: &[u8]) -> NomResult<(u64, bool)> {
 }
 
 fn bdat_chunk_size(input: &[u8]) -> NomResult<u64> {
-    map_res(take_while_m_n(1, 20, is_digit), |s| {
-        std::str::from_utf8(s).unwrap().parse()
-    })(input)
+    bounded_u64(20, u64::MAX)(input)
+}
+
+/// Progress of a [`BdatReceiver`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BdatStatus {
+    /// The current chunk still expects this many bytes.
+    Pending(u64),
+    /// The current chunk was fully received, more chunks follow.
+    ChunkComplete,
+    /// The LAST chunk was fully received, holds the message size.
+    MessageComplete(u64),
+}
+
+/// Error returned by a [`BdatReceiver`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BdatError {
+    /// The chunk ended this many bytes short of its declared size.
+    Underrun(u64),
+    /// This many bytes were received past the declared chunk size.
+    Overrun(u64),
+    /// A chunk was declared after the LAST chunk.
+    AfterLast,
+    /// The message ended without a LAST chunk.
+    MissingLast,
+}
+
+impl Display for BdatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BdatError::Underrun(missing) => write!(f, "chunk is missing {} bytes", missing),
+            BdatError::Overrun(extra) => write!(f, "chunk has {} extra bytes", extra),
+            BdatError::AfterLast => write!(f, "chunk after the last chunk"),
+            BdatError::MissingLast => write!(f, "missing last chunk"),
+        }
+    }
+}
+
+impl std::error::Error for BdatError {}
+
+/// Tracks the chunks of a message sent with BDAT.
+///
+/// Call [`command`](Self::command) for each parsed [`bdat_command`]
+/// and [`receive`](Self::receive) for the data following it, in any
+/// number of pieces. [`split`](Self::split) separates the data of the
+/// current chunk from any pipelined commands following it.
+/// # Examples
+/// ```
+/// use rustyknife::rfc5321::{bdat_command, BdatReceiver, BdatStatus};
+///
+/// let mut receiver = BdatReceiver::new();
+/// let (rem, (size, last)) = bdat_command(b"BDAT 5 LAST\r\nhel").unwrap();
+///
+/// assert_eq!(receiver.command(size, last), Ok(BdatStatus::Pending(5)));
+/// assert_eq!(receiver.receive(rem), Ok(BdatStatus::Pending(2)));
+///
+/// let (chunk, rem) = receiver.split(b"loQUIT\r\n");
+/// assert_eq!(receiver.receive(chunk), Ok(BdatStatus::MessageComplete(5)));
+/// assert_eq!(rem, b"QUIT\r\n");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BdatReceiver {
+    remaining: u64,
+    received: u64,
+    last: bool,
+}
+
+impl BdatReceiver {
+    /// Create a receiver expecting the first chunk of a message.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn status(&self) -> BdatStatus {
+        match (self.remaining, self.last) {
+            (0, true) => BdatStatus::MessageComplete(self.received),
+            (0, false) => BdatStatus::ChunkComplete,
+            (remaining, _) => BdatStatus::Pending(remaining),
+        }
+    }
+
+    /// Start a chunk of `size` bytes, the last one if `last` is set.
+    ///
+    /// Fails with [`BdatError::Underrun`] if the previous chunk is
+    /// incomplete and with [`BdatError::AfterLast`] if the message is
+    /// already complete.
+    pub fn command(&mut self, size: u64, last: bool) -> Result<BdatStatus, BdatError> {
+        if self.last {
+            return Err(BdatError::AfterLast);
+        }
+        if self.remaining != 0 {
+            return Err(BdatError::Underrun(self.remaining));
+        }
+        self.remaining = size;
+        self.last = last;
+
+        Ok(self.status())
+    }
+
+    /// Account for `data` received as part of the current chunk.
+    ///
+    /// Fails with [`BdatError::Overrun`] without consuming anything if
+    /// `data` goes past the end of the chunk.
+    pub fn receive(&mut self, data: &[u8]) -> Result<BdatStatus, BdatError> {
+        let len = data.len() as u64;
+
+        if len > self.remaining {
+            return Err(BdatError::Overrun(len - self.remaining));
+        }
+        self.remaining -= len;
+        self.received = self.received.saturating_add(len);
+
+        Ok(self.status())
+    }
+
+    /// Split `input` into the remaining data of the current chunk and
+    /// the input following it.
+    pub fn split<'a>(&self, input: &'a [u8]) -> (&'a [u8], &'a [u8]) {
+        input.split_at(usize::try_from(self.remaining).unwrap_or(usize::MAX).min(input.len()))
+    }
+
+    /// Check that the message is complete, returning its size.
+    ///
+    /// Fails with [`BdatError::Underrun`] if data is missing from the
+    /// current chunk, or with [`BdatError::MissingLast`] when the LAST
+    /// chunk was not seen.
+    pub fn finish(&self) -> Result<u64, BdatError> {
+        match self.status() {
+            BdatStatus::MessageComplete(size) => Ok(size),
+            BdatStatus::Pending(missing) => Err(BdatError::Underrun(missing)),
+            BdatStatus::ChunkComplete => Err(BdatError::MissingLast),
+        }
+    }
+
+    /// The number of bytes received so far for the whole message.
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Forget the current message, as after RSET.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
 }
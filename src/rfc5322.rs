@@ -2,27 +2,47 @@
 //!
 //! Comments are ignored. [RFC 2047] decoding is applied where appropriate.
 //!
+//! The address types implement [`Display`] to produce their
+//! normalized form. For any valid input `x`,
+//! `serialize(parse(x)) == normalize(x)` where normalization removes
+//! comments and folding whitespace, decodes encoded words and quotes
+//! display names only when needed. Parsing the normalized form again
+//! yields the same value.
+//!
 //! [Internet Message Format]: https://tools.ietf.org/html/rfc5322
 //! [RFC 2047]: https://tools.ietf.org/html/rfc2047
 
 use std::borrow::Cow;
+use std::fmt::{self, Display};
 use std::str;
 use std::mem;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use encoding_rs::{Encoding, UTF_8};
 
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take};
-use nom::combinator::{map, map_opt, opt, recognize};
+use nom::bytes::complete::{tag, take, take_while1, take_while_m_n};
+use nom::combinator::{map, map_opt, opt, recognize, verify};
 use nom::multi::{fold_many0, many0, many1};
-use nom::sequence::{delimited, pair, preceded, separated_pair, terminated};
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
 
 use crate::behaviour::*;
-use crate::rfc2047::encoded_word;
+use crate::headersection::HeaderField;
+use crate::limits::{DecodeError, DecoderLimits, Limit, LimitedResult};
+use crate::options::{check_comment_depth, check_encoded_words, ParserOptions};
+use crate::rfc2047::{encode_phrase, encoded_word, encoded_word_fallback};
 use crate::rfc5234::*;
+use crate::sniff::Sniffer;
 use crate::types::{self, *};
 use crate::util::*;
 
 #[allow(missing_docs)] // Mostly internal
 pub trait UTF8Policy {
+    /// Decode [RFC 2047] encoded words inside quoted strings.
+    ///
+    /// [RFC 2047]: https://tools.ietf.org/html/rfc2047
+    const QS_RFC2047: bool = cfg!(feature = "quoted-string-rfc2047");
+
     fn vchar(input: &[u8]) -> NomResult<char>;
     fn ctext(input: &[u8]) -> NomResult<char>;
     fn atext(input: &[u8]) -> NomResult<char>;
@@ -54,6 +74,30 @@ impl UTF8Policy for Legacy {
     }
 }
 
+impl<P: UTF8Policy, const DECODE: bool> UTF8Policy for QuotedStringRFC2047<P, DECODE> {
+    const QS_RFC2047: bool = DECODE;
+
+    fn vchar(input: &[u8]) -> NomResult<char> {
+        P::vchar(input)
+    }
+
+    fn ctext(input: &[u8]) -> NomResult<char> {
+        P::ctext(input)
+    }
+
+    fn atext(input: &[u8]) -> NomResult<char> {
+        P::atext(input)
+    }
+
+    fn qtext(input: &[u8]) -> NomResult<char> {
+        P::qtext(input)
+    }
+
+    fn dtext(input: &[u8]) -> NomResult<char> {
+        P::dtext(input)
+    }
+}
+
 impl UTF8Policy for Intl {
     fn vchar(input: &[u8]) -> NomResult<char> {
         alt((Legacy::vchar, utf8_non_ascii))(input)
@@ -83,7 +127,7 @@ fn quoted_pair<P: UTF8Policy>(input: &[u8]) -> NomResult<char> {
 }
 
 #[derive(Clone, Debug)]
-enum CommentContent<'a> {
+pub(crate) enum CommentContent<'a> {
     Text(Cow<'a, str>),
     Comment(Vec<CommentContent<'a>>),
     QP(char),
@@ -95,7 +139,7 @@ fn ccontent<P: UTF8Policy>(input: &[u8]) -> NomResult<CommentContent> {
          map(comment::<P>, CommentContent::Comment)))(input)
 }
 
-fn fws(input: &[u8]) -> NomResult<Cow<str>> {
+pub(crate) fn fws(input: &[u8]) -> NomResult<Cow<str>> {
     //CRLF is "semantically invisible"
     map(pair(opt(terminated(recognize_many0(wsp), crlf)),
              recognize_many1(wsp)),
@@ -137,7 +181,7 @@ fn _concat_comment<'a, I: IntoIterator<Item=CommentContent<'a>>>(comments: I) ->
     out
 }
 
-fn comment<P: UTF8Policy>(input: &[u8]) -> NomResult<Vec<CommentContent>> {
+pub(crate) fn comment<P: UTF8Policy>(input: &[u8]) -> NomResult<Vec<CommentContent>> {
     map(delimited(tag("("),
                   pair(fold_many0(pair(ofws, ccontent::<P>), Vec::new(), |mut acc, (fws, cc)| {
                       acc.push(CommentContent::Text(fws));
@@ -152,23 +196,19 @@ fn cfws<P: UTF8Policy>(input: &[u8]) -> NomResult<&[u8]> {
     alt((recognize(pair(many1(pair(ofws, comment::<P>)), ofws)), recognize(fws)))(input)
 }
 
-#[cfg(feature = "quoted-string-rfc2047")]
-fn qcontent<P: UTF8Policy>(input: &[u8]) -> NomResult<QContent> {
-    alt((map(encoded_word, QContent::EncodedWord),
-         map(recognize_many1(P::qtext), |q| QContent::Literal(String::from_utf8_lossy(q))),
-         map(quoted_pair::<P>, QContent::QP))
-    )(input)
-}
-
-#[cfg(not(feature = "quoted-string-rfc2047"))]
 fn qcontent<P: UTF8Policy>(input: &[u8]) -> NomResult<QContent> {
+    if P::QS_RFC2047 {
+        if let Ok((rem, ew)) = encoded_word(input) {
+            return Ok((rem, QContent::EncodedWord(ew)));
+        }
+    }
     alt((map(recognize_many1(P::qtext), |q| QContent::Literal(String::from_utf8_lossy(q))),
          map(quoted_pair::<P>, QContent::QP))
     )(input)
 }
 
 // quoted-string not surrounded by CFWS
-fn _inner_quoted_string<P: UTF8Policy>(input: &[u8]) -> NomResult<Vec<QContent>> {
+pub(crate) fn _inner_quoted_string<P: UTF8Policy>(input: &[u8]) -> NomResult<Vec<QContent>> {
     map(delimited(tag("\""),
                   pair(many0(pair(opt(fws), qcontent::<P>)), opt(fws)),
                   tag("\"")),
@@ -176,7 +216,6 @@ fn _inner_quoted_string<P: UTF8Policy>(input: &[u8]) -> NomResult<Vec<QContent>>
             let mut out = Vec::with_capacity(a.len()*2+1);
             for (ws, cont) in a {
                 match (ws, &cont, out.last()) {
-                    #[cfg(feature = "quoted-string-rfc2047")]
                     (_, QContent::EncodedWord(_), Some(QContent::EncodedWord(_))) => (),
                     (Some(ws),_, _) => { out.push(QContent::Literal(ws)); },
                     _ => (),
@@ -220,10 +259,224 @@ pub enum Address {
     Group(Group),
 }
 
+fn _is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c) || !c.is_ascii() && !c.is_control()
+}
+
+// Atoms separated by single spaces, needing no quoting.
+fn _is_plain_phrase(phrase: &str) -> bool {
+    !phrase.is_empty() && phrase.split(' ').all(|word| !word.is_empty() && word.chars().all(_is_atext))
+}
+
+fn write_phrase(f: &mut fmt::Formatter, phrase: &str) -> fmt::Result {
+    if _is_plain_phrase(phrase) {
+        write!(f, "{}", phrase)
+    } else {
+        write!(f, "{}", QuotedString(phrase.into()).quoted())
+    }
+}
+
+// Same as `write_phrase`, with non-ASCII phrases as encoded words.
+fn ascii_phrase(phrase: &str) -> String {
+    if !phrase.is_ascii() {
+        encode_phrase(phrase)
+    } else if _is_plain_phrase(phrase) {
+        phrase.into()
+    } else {
+        QuotedString(phrase.into()).quoted()
+    }
+}
+
+impl Display for Mailbox {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.dname {
+            Some(dname) => {
+                write_phrase(f, dname)?;
+                write!(f, " <{}>", self.address)
+            }
+            None => write!(f, "{}", self.address),
+        }
+    }
+}
+
+impl Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_phrase(f, &self.dname)?;
+        write!(f, ":")?;
+        for (i, member) in self.members.iter().enumerate() {
+            if i == 0 {
+                write!(f, " {}", member)?;
+            } else {
+                write!(f, ", {}", member)?;
+            }
+        }
+        write!(f, ";")
+    }
+}
+
+impl Mailbox {
+    /// Render this mailbox using only US-ASCII.
+    ///
+    /// Non-ASCII display names are encoded as [RFC 2047] encoded words
+    /// and the address is rendered with [`types::Mailbox::to_ascii_string`],
+    /// which fails for non-ASCII local parts.
+    ///
+    /// [RFC 2047]: https://tools.ietf.org/html/rfc2047
+    /// # Examples
+    /// ```
+    /// use rustyknife::behaviour::Intl;
+    /// use rustyknife::rfc5322::{from, Address};
+    ///
+    /// let (_, addrs) = from::<Intl>("Ren\u{e9} <rene@b\u{fc}cher.example>".as_bytes()).unwrap();
+    /// let mbox = match &addrs[0] { Address::Mailbox(m) => m, _ => unreachable!() };
+    ///
+    /// assert_eq!(mbox.to_ascii_string(), Ok("=?utf-8?q?Ren=C3=A9?= <rene@xn--bcher-kva.example>".into()));
+    /// ```
+    #[allow(clippy::result_unit_err)]
+    pub fn to_ascii_string(&self) -> Result<String, ()> {
+        let address = self.address.to_ascii_string()?;
+
+        Ok(match &self.dname {
+            Some(dname) => format!("{} <{}>", ascii_phrase(dname), address),
+            None => address,
+        })
+    }
+}
+
+impl Group {
+    /// Render this group using only US-ASCII.
+    ///
+    /// See [`Mailbox::to_ascii_string`].
+    #[allow(clippy::result_unit_err)]
+    pub fn to_ascii_string(&self) -> Result<String, ()> {
+        let members = self.members.iter().map(Mailbox::to_ascii_string).collect::<Result<Vec<_>, _>>()?;
+
+        if members.is_empty() {
+            Ok(format!("{}:;", ascii_phrase(&self.dname)))
+        } else {
+            Ok(format!("{}: {};", ascii_phrase(&self.dname), members.join(", ")))
+        }
+    }
+}
+
+impl Address {
+    /// The mailboxes designated by this address.
+    ///
+    /// A group expands to its members, possibly none.
+    pub fn mailboxes(&self) -> &[Mailbox] {
+        match self {
+            Address::Mailbox(m) => std::slice::from_ref(m),
+            Address::Group(g) => &g.members,
+        }
+    }
+
+    /// Render this address using only US-ASCII.
+    ///
+    /// See [`Mailbox::to_ascii_string`].
+    #[allow(clippy::result_unit_err)]
+    pub fn to_ascii_string(&self) -> Result<String, ()> {
+        match self {
+            Address::Mailbox(m) => m.to_ascii_string(),
+            Address::Group(g) => g.to_ascii_string(),
+        }
+    }
+}
+
+impl Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Mailbox(m) => write!(f, "{}", m),
+            Address::Group(g) => write!(f, "{}", g),
+        }
+    }
+}
+
+/// Merge address lists such as To, Cc and Bcc, removing duplicates.
+///
+/// Mailboxes are compared with [`types::Mailbox::eq_ignore_case`] and
+/// the first occurrence is kept, along with its display name. When
+/// `expand_groups` is set, groups are replaced by their members.
+/// Otherwise groups are kept with their duplicate members removed.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5322::{from, merge_address_lists, AddressList};
+///
+/// let (_, to) = from::<Intl>(b"Bob <bob@example.org>, alice@example.org").unwrap();
+/// let (_, cc) = from::<Intl>(b"BOB@Example.ORG, Team: alice@example.org, carol@example.org;").unwrap();
+///
+/// let merged = merge_address_lists(&[&to, &cc], false);
+/// assert_eq!(AddressList(&merged).to_string(), "Bob <bob@example.org>, alice@example.org, Team: carol@example.org;");
+///
+/// let merged = merge_address_lists(&[&to, &cc], true);
+/// assert_eq!(AddressList(&merged).to_string(), "Bob <bob@example.org>, alice@example.org, carol@example.org");
+/// ```
+pub fn merge_address_lists(lists: &[&[Address]], expand_groups: bool) -> Vec<Address> {
+    let mut seen = std::collections::HashSet::new();
+    let mut out = Vec::new();
+
+    for address in lists.iter().flat_map(|l| l.iter()) {
+        match address {
+            Address::Group(g) if !expand_groups => {
+                let members = g.members.iter().filter(|m| seen.insert(m.address.caseless_key())).cloned().collect();
+                out.push(Address::Group(Group { dname: g.dname.clone(), members }));
+            }
+            _ => out.extend(address.mailboxes().iter()
+                            .filter(|m| seen.insert(m.address.caseless_key()))
+                            .map(|m| Address::Mailbox(m.clone()))),
+        }
+    }
+
+    out
+}
+
+/// Newtype over a slice of [`Address`] for display purposes.
+///
+/// Addresses are separated by a comma and a space.
+pub struct AddressList<'a>(pub &'a [Address]);
+
+impl<'a> AddressList<'a> {
+    /// Render this list using only US-ASCII.
+    ///
+    /// See [`Mailbox::to_ascii_string`].
+    #[allow(clippy::result_unit_err)]
+    pub fn to_ascii_string(&self) -> Result<String, ()> {
+        Ok(self.0.iter().map(Address::to_ascii_string).collect::<Result<Vec<_>, _>>()?.join(", "))
+    }
+
+    /// Expand groups into their members, yielding the addresses to
+    /// use as envelope recipients.
+    /// # Examples
+    /// ```
+    /// use rustyknife::behaviour::Intl;
+    /// use rustyknife::rfc5322::{from, AddressList};
+    ///
+    /// let (_, addrs) = from::<Intl>(b"a@example.org, Team: b@example.org, C <c@example.org>;, Empty:;").unwrap();
+    /// let rcpts: Vec<_> = AddressList(&addrs).envelope_addresses().iter().map(|m| m.to_string()).collect();
+    ///
+    /// assert_eq!(rcpts, ["a@example.org", "b@example.org", "c@example.org"]);
+    /// ```
+    pub fn envelope_addresses(&self) -> Vec<types::Mailbox> {
+        self.0.iter().flat_map(Address::mailboxes).map(|m| m.address.clone()).collect()
+    }
+}
+
+impl<'a> Display for AddressList<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, address) in self.0.iter().enumerate() {
+            if i == 0 {
+                write!(f, "{}", address)?;
+            } else {
+                write!(f, ", {}", address)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
-enum QContent<'a> {
+pub(crate) enum QContent<'a> {
     Literal(Cow<'a, str>),
-    #[cfg(feature = "quoted-string-rfc2047")]
     EncodedWord(String),
     QP(char),
 }
@@ -243,13 +496,12 @@ impl <'a> From<&'a Text<'a>> for &'a str {
     }
 }
 
-fn concat_qs<'a, A: Iterator<Item=QContent<'a>>>(input: A) -> String {
+pub(crate) fn concat_qs<'a, A: Iterator<Item=QContent<'a>>>(input: A) -> String {
     let mut out = String::new();
 
     for qc in input {
         match qc {
             QContent::Literal(lit) => out.push_str(&lit),
-            #[cfg(feature = "quoted-string-rfc2047")]
             QContent::EncodedWord(ew) => out.push_str(&ew),
             QContent::QP(c) => out.push(c),
         }
@@ -346,20 +598,54 @@ pub(crate) fn addr_spec<P: UTF8Policy>(input: &[u8]) -> NomResult<types::Mailbox
         |(lp, domain)| types::Mailbox(lp, domain))(input)
 }
 
-fn angle_addr<P: UTF8Policy>(input: &[u8]) -> NomResult<types::Mailbox> {
+fn obs_domain_list<P: UTF8Policy>(input: &[u8]) -> NomResult<Vec<DomainPart>> {
+    map(pair(preceded(pair(many0(alt((cfws::<P>, tag(",")))), tag("@")), domain::<P>),
+             many0(preceded(pair(tag(","), opt(cfws::<P>)), opt(preceded(tag("@"), domain::<P>))))),
+        |(first, rest)| std::iter::once(first).chain(rest.into_iter().flatten()).collect())(input)
+}
+
+fn obs_route<P: UTF8Policy>(input: &[u8]) -> NomResult<Vec<DomainPart>> {
+    terminated(obs_domain_list::<P>, tag(":"))(input)
+}
+
+fn _angle_addr_route<P: UTF8Policy>(input: &[u8]) -> NomResult<(Vec<DomainPart>, types::Mailbox)> {
     delimited(pair(opt(cfws::<P>), tag("<")),
-              addr_spec::<P>,
+              pair(map(opt(obs_route::<P>), Option::unwrap_or_default), addr_spec::<P>),
               pair(tag(">"), opt(cfws::<P>)))(input)
 }
 
-fn name_addr<P: UTF8Policy>(input: &[u8]) -> NomResult<Mailbox> {
-    map(pair(opt(display_name::<P>), angle_addr::<P>),
-        |(dname, address)| Mailbox{dname, address})(input)
+fn angle_addr<P: UTF8Policy>(input: &[u8]) -> NomResult<types::Mailbox> {
+    map(_angle_addr_route::<P>, |(_, address)| address)(input)
+}
+
+fn _name_addr_route<P: UTF8Policy>(input: &[u8]) -> NomResult<(Vec<DomainPart>, Mailbox)> {
+    map(pair(opt(display_name::<P>), _angle_addr_route::<P>),
+        |(dname, (route, address))| (route, Mailbox{dname, address}))(input)
+}
+
+/// Parse a single mailbox, returning its obsolete source route.
+///
+/// The route is the list of domains preceding the address, as in
+/// `"<@hop1,@hop2:user@example.org>"`, and is usually empty. It is
+/// dropped by the other address parsers.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5322::mailbox_with_route;
+///
+/// let (_, (route, mailbox)) = mailbox_with_route::<Intl>(b"Bob <@hop1.example:bob@example.org>").unwrap();
+///
+/// assert_eq!(route.len(), 1);
+/// assert_eq!(route[0].to_string(), "hop1.example");
+/// assert_eq!(mailbox.to_string(), "Bob <bob@example.org>");
+/// ```
+pub fn mailbox_with_route<P: UTF8Policy>(input: &[u8]) -> NomResult<(Vec<DomainPart>, Mailbox)> {
+    alt((_name_addr_route::<P>,
+         map(addr_spec::<P>, |a| (vec![], Mailbox{dname: None, address: a}))))(input)
 }
 
 fn mailbox<P: UTF8Policy>(input: &[u8]) -> NomResult<Mailbox> {
-    alt((name_addr::<P>,
-         map(addr_spec::<P>, |a| Mailbox{dname: None, address: a})))(input)
+    map(mailbox_with_route::<P>, |(_, mailbox)| mailbox)(input)
 }
 
 fn mailbox_list<P: UTF8Policy>(input: &[u8]) -> NomResult<Vec<Mailbox>> {
@@ -418,6 +704,120 @@ pub fn unstructured<P: UTF8Policy>(input: &[u8]) -> NomResult<String> {
         })(input)
 }
 
+/// Parse an unstructured header while enforcing `limits`.
+///
+/// Same as [`unstructured`], but decoding stops as soon as the
+/// number of encoded words or the size of the decoded text exceeds
+/// the configured limits.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::limits::{DecodeError, DecoderLimits, Limit};
+/// use rustyknife::rfc5322::unstructured_limited;
+///
+/// let limits = DecoderLimits { max_encoded_words: 1, ..Default::default() };
+/// let res = unstructured_limited::<Intl>(b"=?utf-8?q?a?= =?utf-8?q?b?=", &limits);
+///
+/// assert_eq!(res, Err(DecodeError::LimitExceeded(Limit::EncodedWords)));
+/// ```
+pub fn unstructured_limited<'a, P: UTF8Policy>(input: &'a [u8], limits: &DecoderLimits) -> LimitedResult<'a, String> {
+    _unstructured_limited::<P>(input, limits, UTF_8)
+}
+
+/// Parse the content of an unstructured header according to `options`.
+///
+/// Same as [`unstructured_limited`], with the UTF-8 policy and charset
+/// fallback taken from [`ParserOptions`].
+/// # Examples
+/// ```
+/// use rustyknife::options::ParserOptions;
+/// use rustyknife::rfc5322::unstructured_with;
+///
+/// let options = ParserOptions { charset_fallback: encoding_rs::WINDOWS_1252, ..Default::default() };
+/// let (_, text) = unstructured_with(b"=?x-unknown?q?caf=E9?=", &options).unwrap();
+///
+/// assert_eq!(text, "caf\u{e9}");
+/// ```
+pub fn unstructured_with<'a>(input: &'a [u8], options: &ParserOptions) -> LimitedResult<'a, String> {
+    if options.allow_utf8 {
+        _unstructured_limited::<Intl>(input, &options.limits, options.charset_fallback)
+    } else {
+        _unstructured_limited::<Legacy>(input, &options.limits, options.charset_fallback)
+    }
+}
+
+fn _unstructured_limited<'a, P: UTF8Policy>(input: &'a [u8], limits: &DecoderLimits, fallback: &'static Encoding) -> LimitedResult<'a, String> {
+    let encoded_word = encoded_word_fallback(fallback);
+    let mut out = String::new();
+    let mut rem = input;
+    let mut words = 0;
+    let mut prev_encoded = false;
+
+    loop {
+        let (after_ws, ws) = ofws(rem)?;
+
+        if let Ok((r, ew)) = encoded_word(after_ws) {
+            words += 1;
+            if words > limits.max_encoded_words {
+                return Err(DecodeError::LimitExceeded(Limit::EncodedWords));
+            }
+            // Whitespace between adjacent encoded words is not displayed.
+            if !prev_encoded {
+                out.push_str(&ws);
+            }
+            out.push_str(&ew);
+            prev_encoded = true;
+            rem = r;
+        } else if let Ok((r, text)) = many1(alt((P::vchar, _8bit_char)))(after_ws) {
+            out.push_str(&ws);
+            out.extend(text);
+            prev_encoded = false;
+            rem = r;
+        } else {
+            break;
+        }
+
+        if out.len() > limits.max_decoded_len {
+            return Err(DecodeError::LimitExceeded(Limit::DecodedLen));
+        }
+    }
+
+    let (rem, ws) = recognize_many0(wsp)(rem)?;
+    out.push_str(str::from_utf8(ws).unwrap());
+
+    if out.len() > limits.max_decoded_len {
+        return Err(DecodeError::LimitExceeded(Limit::DecodedLen));
+    }
+
+    Ok((rem, out))
+}
+
+/// Parse the content of an unstructured header, detecting the charset
+/// of undeclared 8-bit text.
+///
+/// Raw 8-bit text is decoded with the [`Sniffer`] before [RFC 2047]
+/// decoding takes place. Returns the decoded text along with the
+/// charset chosen for the raw text.
+///
+/// [RFC 2047]: https://tools.ietf.org/html/rfc2047
+/// # Examples
+/// ```
+/// use rustyknife::rfc5322::unstructured_sniffed;
+/// use rustyknife::sniff::Sniffer;
+///
+/// let (_, (text, charset)) = unstructured_sniffed(b"Re: r\xe9union =?utf-8?q?d=C3=A9cal=C3=A9e?=", &Sniffer::default()).unwrap();
+///
+/// assert_eq!(text, "Re: r\u{e9}union d\u{e9}cal\u{e9}e");
+/// assert_eq!(charset, encoding_rs::WINDOWS_1252);
+/// ```
+pub fn unstructured_sniffed<'a>(input: &'a [u8], sniffer: &Sniffer) -> NomResult<'a, (String, &'static Encoding)> {
+    let (rem, raw) = recognize(unstructured::<Legacy>)(input)?;
+    let (text, charset) = sniffer.decode(raw);
+    let (_, decoded) = exact!(text.as_bytes(), unstructured::<Intl>).map_err(|_| nom::Err::Error(()))?;
+
+    Ok((rem, (decoded, charset)))
+}
+
 /// Parse the content of a `"From:"` header.
 ///
 /// Returns a list of addresses, since [RFC 6854] allows multiple mail
@@ -441,3 +841,426 @@ pub fn sender<P: UTF8Policy>(i: &[u8]) -> NomResult<Address> {
 pub fn reply_to<P: UTF8Policy>(i: &[u8]) -> NomResult<Vec<Address>> {
     address_list_crlf::<P>(i)
 }
+
+// Call `parser` with the policy matching `options`.
+macro_rules! with_policy {
+    ($options:expr, $parser:ident, $input:expr) => {
+        match ($options.allow_utf8, $options.quoted_string_rfc2047) {
+            (true, true) => $parser::<QuotedStringRFC2047<Intl, true>>($input),
+            (true, false) => $parser::<QuotedStringRFC2047<Intl, false>>($input),
+            (false, true) => $parser::<QuotedStringRFC2047<Legacy, true>>($input),
+            (false, false) => $parser::<QuotedStringRFC2047<Legacy, false>>($input),
+        }
+    }
+}
+
+/// Parse the content of a `"From:"` header according to `options`.
+///
+/// The UTF-8 policy, quoted string decoding, comment nesting limit,
+/// decoder limits and address recovery are taken from
+/// [`ParserOptions`].
+/// # Examples
+/// ```
+/// use rustyknife::limits::{DecodeError, Limit};
+/// use rustyknife::options::ParserOptions;
+/// use rustyknife::rfc5322::from_with;
+///
+/// let options = ParserOptions { max_comment_depth: 2, ..Default::default() };
+/// assert!(from_with(b"bob@example.org (a (b))", &options).is_ok());
+/// assert_eq!(from_with(b"bob@example.org (a (b (c)))", &options),
+///            Err(DecodeError::LimitExceeded(Limit::CommentDepth)));
+///
+/// let options = ParserOptions { recover_addresses: true, ..Default::default() };
+/// let (_, list) = from_with(b"invalid, Alice <alice@example.org>", &options).unwrap();
+/// assert_eq!(list.len(), 1);
+/// ```
+pub fn from_with<'a>(i: &'a [u8], options: &ParserOptions) -> LimitedResult<'a, Vec<Address>> {
+    _address_list_with(i, options)
+}
+
+/// Parse the content of a `"Sender:"` header according to `options`.
+///
+/// See [`from_with`].
+pub fn sender_with<'a>(i: &'a [u8], options: &ParserOptions) -> LimitedResult<'a, Address> {
+    check_comment_depth(i, options.max_comment_depth)?;
+    check_encoded_words(i, &options.limits)?;
+    let (rem, address) = with_policy!(options, sender, i)?;
+    _check_display_names(std::slice::from_ref(&address), &options.limits)?;
+
+    Ok((rem, address))
+}
+
+/// Parse the content of a `"Reply-To:"` header according to `options`.
+///
+/// See [`from_with`].
+pub fn reply_to_with<'a>(i: &'a [u8], options: &ParserOptions) -> LimitedResult<'a, Vec<Address>> {
+    _address_list_with(i, options)
+}
+
+fn _address_list_with<'a>(i: &'a [u8], options: &ParserOptions) -> LimitedResult<'a, Vec<Address>> {
+    check_comment_depth(i, options.max_comment_depth)?;
+    check_encoded_words(i, &options.limits)?;
+
+    let (rem, list) = match with_policy!(options, address_list_crlf, i) {
+        Ok((rem, list)) if rem.is_empty() || !options.recover_addresses => (rem, list),
+        Err(e) if !options.recover_addresses => return Err(e.into()),
+        _ => match with_policy!(options, _address_list_recover, i) {
+            list if list.is_empty() => return Err(DecodeError::Syntax(nom::Err::Error(()))),
+            list => (&i[i.len()..], list),
+        }
+    };
+    _check_display_names(&list, &options.limits)?;
+
+    Ok((rem, list))
+}
+
+// The display names are the only decoded part of an address header.
+fn _check_display_names(list: &[Address], limits: &DecoderLimits) -> Result<(), DecodeError> {
+    let len = |mailbox: &Mailbox| mailbox.dname.as_ref().map_or(0, String::len);
+    let total: usize = list.iter().map(|address| match address {
+        Address::Mailbox(mailbox) => len(mailbox),
+        Address::Group(group) => group.dname.len() + group.members.iter().map(len).sum::<usize>(),
+    }).sum();
+
+    if total > limits.max_decoded_len {
+        Err(DecodeError::LimitExceeded(Limit::DecodedLen))
+    } else {
+        Ok(())
+    }
+}
+
+// Offset of the next comma separating two addresses, skipping over
+// quoted strings, comments and angle brackets.
+fn _next_separator(input: &[u8]) -> Option<usize> {
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut depth = 0usize;
+
+    for (i, c) in input.iter().enumerate() {
+        match (escaped, quoted, c) {
+            (true, _, _) => { escaped = false; continue; },
+            (false, _, b'\\') => escaped = true,
+            (false, _, b'"') => quoted = !quoted,
+            (false, false, b'(') | (false, false, b'<') => depth += 1,
+            (false, false, b')') | (false, false, b'>') => depth = depth.saturating_sub(1),
+            (false, false, b',') if depth == 0 => return Some(i),
+            _ => (),
+        }
+    }
+    None
+}
+
+// Parse an address list, skipping over the addresses that do not parse.
+fn _address_list_recover<P: UTF8Policy>(mut input: &[u8]) -> Vec<Address> {
+    let mut out = Vec::new();
+
+    loop {
+        match terminated(address::<P>, opt(crlf))(input) {
+            Ok((rem, addr)) if rem.is_empty() || rem[0] == b',' => {
+                out.push(addr);
+                input = rem;
+            }
+            _ => match _next_separator(input) {
+                Some(sep) => input = &input[sep..],
+                None => break,
+            }
+        }
+
+        if input.is_empty() {
+            break;
+        }
+        input = &input[1..];
+    }
+
+    out
+}
+
+/// Parse many address header values in one call.
+///
+/// Each input is parsed as the content of an address list header
+/// such as `"From:"`, `"To:"` or `"Cc:"` and must be consumed
+/// entirely. Returns one result per input, in the same order.
+///
+/// When `recover` is set, the addresses of an invalid list that do
+/// parse are kept instead of failing the whole value. The result is
+/// only an error if no address could be recovered.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5322::parse_address_headers;
+///
+/// let inputs: &[&[u8]] = &[b"bob@example.org", b"invalid, Alice <alice@example.org>"];
+///
+/// let strict = parse_address_headers::<Intl>(inputs, false);
+/// assert!(strict[0].is_ok());
+/// assert!(strict[1].is_err());
+///
+/// let recovered = parse_address_headers::<Intl>(inputs, true);
+/// assert_eq!(recovered[1].as_ref().unwrap().len(), 1);
+/// ```
+pub fn parse_address_headers<P: UTF8Policy>(inputs: &[&[u8]], recover: bool) -> Vec<Result<Vec<Address>, ()>> {
+    inputs.iter().map(|input| {
+        match exact!(*input, address_list_crlf::<P>) {
+            Ok((_, parsed)) => Ok(parsed),
+            Err(_) if recover => {
+                let parsed = _address_list_recover::<P>(input);
+                if parsed.is_empty() { Err(()) } else { Ok(parsed) }
+            }
+            Err(_) => Err(()),
+        }
+    }).collect()
+}
+
+fn _envelope_address<P: UTF8Policy>(input: &[u8]) -> NomResult<types::Mailbox> {
+    alt((angle_addr::<P>, addr_spec::<P>))(input)
+}
+
+/// Parse the content of a `"Delivered-To:"` header.
+///
+/// This header is added by Postfix and other MTAs upon final
+/// delivery. Returns the recipient address.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5322::delivered_to;
+///
+/// let (_, address) = delivered_to::<Intl>(b" bob@example.org\r\n").unwrap();
+/// assert_eq!(address.to_string(), "bob@example.org");
+/// ```
+pub fn delivered_to<P: UTF8Policy>(i: &[u8]) -> NomResult<types::Mailbox> {
+    terminated(_envelope_address::<P>, opt(crlf))(i)
+}
+
+/// Parse the content of a `"X-Original-To:"` header.
+///
+/// Returns the recipient address as given by the sending MTA before
+/// any alias expansion.
+pub fn x_original_to<P: UTF8Policy>(i: &[u8]) -> NomResult<types::Mailbox> {
+    terminated(_envelope_address::<P>, opt(crlf))(i)
+}
+
+/// Parse the content of an `"Envelope-To:"` header.
+///
+/// Returns a list of addresses, since Exim may record many envelope
+/// recipients in a single header.
+pub fn envelope_to<P: UTF8Policy>(i: &[u8]) -> NomResult<Vec<types::Mailbox>> {
+    terminated(fold_prefix0(_envelope_address::<P>, preceded(tag(","), _envelope_address::<P>)),
+               opt(crlf))(i)
+}
+
+/// Header that recorded an envelope recipient upon delivery.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeliveryHeader {
+    /// `"Delivered-To:"`
+    DeliveredTo,
+    /// `"X-Original-To:"`
+    XOriginalTo,
+    /// `"Envelope-To:"`
+    EnvelopeTo,
+}
+
+/// Collect the envelope recipients recorded in a header section.
+///
+/// Walks the `"Delivered-To:"`, `"X-Original-To:"` and
+/// `"Envelope-To:"` headers in order of appearance. Header names are
+/// matched case-insensitively and values that do not parse are
+/// skipped.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::headersection::header_section;
+/// use rustyknife::rfc5322::{delivery_addresses, DeliveryHeader};
+///
+/// let (_, headers) = header_section(b"Delivered-To: bob@example.org\r\n\
+///                                     X-Original-To: alias@example.org\r\n\r\n").unwrap();
+/// let found = delivery_addresses::<Intl>(&headers);
+///
+/// assert_eq!(found[0].0, DeliveryHeader::DeliveredTo);
+/// assert_eq!(found[0].1.to_string(), "bob@example.org");
+/// assert_eq!(found[1].0, DeliveryHeader::XOriginalTo);
+/// assert_eq!(found[1].1.to_string(), "alias@example.org");
+/// ```
+pub fn delivery_addresses<P: UTF8Policy>(headers: &[HeaderField]) -> Vec<(DeliveryHeader, types::Mailbox)> {
+    let mut out = Vec::new();
+
+    for (name, value) in headers.iter().filter_map(|h| h.as_ref().ok()) {
+        let parsed = if name.eq_ignore_ascii_case(b"Delivered-To") {
+            exact!(*value, delivered_to::<P>).ok().map(|(_, m)| vec![(DeliveryHeader::DeliveredTo, m)])
+        } else if name.eq_ignore_ascii_case(b"X-Original-To") {
+            exact!(*value, x_original_to::<P>).ok().map(|(_, m)| vec![(DeliveryHeader::XOriginalTo, m)])
+        } else if name.eq_ignore_ascii_case(b"Envelope-To") {
+            exact!(*value, envelope_to::<P>).ok().map(|(_, l)| l.into_iter().map(|m| (DeliveryHeader::EnvelopeTo, m)).collect())
+        } else {
+            None
+        };
+
+        out.extend(parsed.into_iter().flatten());
+    }
+
+    out
+}
+
+fn msg_id<P: UTF8Policy>(input: &[u8]) -> NomResult<String> {
+    map(delimited(pair(opt(cfws::<P>), tag("<")),
+                  separated_pair(local_part::<P>, tag("@"), domain::<P>),
+                  pair(tag(">"), opt(cfws::<P>))),
+        |(left, right)| format!("{}@{}", left, right))(input)
+}
+
+fn _obs_msg_id_list<P: UTF8Policy>(input: &[u8]) -> NomResult<Vec<String>> {
+    map(terminated(many1(alt((map(msg_id::<P>, Some), map(word::<P>, |_| None)))), opt(crlf)),
+        |ids| ids.into_iter().flatten().collect())(input)
+}
+
+/// Parse the content of a `"Message-ID:"` header.
+///
+/// Returns the identifier without its angle brackets. The obsolete
+/// syntax allowing comments and quoted strings inside the identifier
+/// is accepted and normalized.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5322::message_id;
+///
+/// let (_, id) = message_id::<Intl>(b" <1234.abcd@example.org> (generated)\r\n").unwrap();
+/// assert_eq!(id, "1234.abcd@example.org");
+/// ```
+pub fn message_id<P: UTF8Policy>(i: &[u8]) -> NomResult<String> {
+    terminated(msg_id::<P>, opt(crlf))(i)
+}
+
+/// Parse the content of a `"References:"` header.
+///
+/// Returns the identifiers in order. Words found between identifiers,
+/// as allowed by the obsolete syntax, are skipped.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5322::references;
+///
+/// let (_, ids) = references::<Intl>(b" <1@example.org>\r\n <2@example.org>").unwrap();
+/// assert_eq!(ids, ["1@example.org", "2@example.org"]);
+/// ```
+pub fn references<P: UTF8Policy>(i: &[u8]) -> NomResult<Vec<String>> {
+    _obs_msg_id_list::<P>(i)
+}
+
+/// Parse the content of an `"In-Reply-To:"` header.
+///
+/// Same as [`references`]. Older clients add a phrase describing the
+/// original message, which is skipped.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5322::in_reply_to;
+///
+/// let (_, ids) = in_reply_to::<Intl>(b" Your message of \"Monday\" <1@example.org>").unwrap();
+/// assert_eq!(ids, ["1@example.org"]);
+/// ```
+pub fn in_reply_to<P: UTF8Policy>(i: &[u8]) -> NomResult<Vec<String>> {
+    _obs_msg_id_list::<P>(i)
+}
+
+const DAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const MONTH_NAMES: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+// Obsolete zone names and their offset in hours. Military zones are
+// treated as "-0000", as recommended by the RFC.
+const OBS_ZONES: &[(&str, i64)] = &[
+    ("UT", 0), ("GMT", 0), ("EST", -5), ("EDT", -4), ("CST", -6), ("CDT", -5),
+    ("MST", -7), ("MDT", -6), ("PST", -8), ("PDT", -7),
+];
+
+fn ocfws(input: &[u8]) -> NomResult<Option<&[u8]>> {
+    opt(cfws::<Intl>)(input)
+}
+
+fn number(min: usize, max: usize) -> impl Fn(&[u8]) -> NomResult<u32> {
+    move |input| map(take_while_m_n(min, max, |c: u8| c.is_ascii_digit()),
+                     |n| str::from_utf8(n).unwrap().parse().unwrap())(input)
+}
+
+fn name_index<'a>(names: &'static [&'static str]) -> impl Fn(&'a [u8]) -> NomResult<'a, usize> {
+    move |input| map_opt(take(3usize), |n: &[u8]| names.iter().position(|m| m.as_bytes().eq_ignore_ascii_case(n)))(input)
+}
+
+// Zone offset in seconds east of UTC.
+fn zone(input: &[u8]) -> NomResult<i64> {
+    alt((map(verify(pair(alt((tag("+"), tag("-"))), number(4, 4)), |(_, z)| z % 100 < 60),
+             |(sign, z)| i64::from(z / 100 * 3600 + z % 100 * 60) * if sign == b"-" { -1 } else { 1 }),
+         map_opt(take_while1(|c: u8| c.is_ascii_alphabetic()), |z: &[u8]| {
+             OBS_ZONES.iter().find(|(n, _)| n.as_bytes().eq_ignore_ascii_case(z)).map(|(_, h)| h * 3600)
+                 .or(if z.len() == 1 && !z.eq_ignore_ascii_case(b"j") { Some(0) } else { None })
+         })))(input)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
+
+// Month is from 1 to 12.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+
+    days_from_civil(next_year, next_month, 1) - days_from_civil(year, month, 1)
+}
+
+/// Parse an [RFC 5322 date-time], such as the content of a
+/// `"Date:"` header or the end of a `"Received:"` header.
+///
+/// The time is converted to UTC. The obsolete syntax is accepted:
+/// two or three digit years, zone names such as `"EST"` and comments
+/// anywhere. A leap second is counted as the last second of the
+/// minute. The day of the week is not checked against the date.
+///
+/// [RFC 5322 date-time]: https://tools.ietf.org/html/rfc5322#section-3.3
+/// # Examples
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use rustyknife::rfc5322::date_time;
+///
+/// let (_, date) = date_time(b" Sun, 9 Sep 2001 03:46:40 +0200 (CEST)\r\n").unwrap();
+/// assert_eq!(date, UNIX_EPOCH + Duration::from_secs(1_000_000_000));
+///
+/// let (_, date) = date_time(b"8 Sep 01 21:46:40 EDT").unwrap();
+/// assert_eq!(date, UNIX_EPOCH + Duration::from_secs(1_000_000_000));
+///
+/// assert!(date_time(b"31 Apr 2021 10:00:00 +0000").is_err());
+/// ```
+pub fn date_time(input: &[u8]) -> NomResult<SystemTime> {
+    let (rem, _) = opt(tuple((ocfws, name_index(&DAY_NAMES), ocfws, tag(","))))(input)?;
+    let (rem, (_, day, _, month, _, year)) =
+        tuple((ocfws, number(1, 2), ocfws, name_index(&MONTH_NAMES), ocfws, take_while_m_n(2, 4, |c: u8| c.is_ascii_digit())))(rem)?;
+    let (rem, (_, hour, _, minute, second, _, offset, _)) =
+        tuple((ocfws, number(2, 2), tag(":"), number(2, 2), opt(preceded(tag(":"), number(2, 2))), ocfws, zone, ocfws))(rem)?;
+    let (rem, _) = opt(crlf)(rem)?;
+
+    // Obsolete years depend on the number of digits, "049" is 1949.
+    let value: u32 = str::from_utf8(year).unwrap().parse().unwrap();
+    let year = match (year.len(), value) {
+        (2, 0..=49) => value + 2000,
+        (2, _) | (3, _) => value + 1900,
+        _ => value,
+    };
+    let (year, month, second) = (i64::from(year), month as i64 + 1, second.unwrap_or(0));
+    if day == 0 || i64::from(day) > days_in_month(year, month) || hour > 23 || minute > 59 || second > 60 {
+        return Err(nom::Err::Error(()));
+    }
+
+    let secs = days_from_civil(year, month, i64::from(day)) * 86400
+        + i64::from(hour * 3600 + minute * 60 + second.min(59)) - offset;
+    // Dates before the epoch may not be representable on every platform.
+    let time = if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+    };
+
+    time.map(|time| (rem, time)).ok_or(nom::Err::Error(()))
+}
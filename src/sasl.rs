@@ -0,0 +1,392 @@
+//! SASL payload decoders for SMTP [AUTH]
+//!
+//! Covers the [PLAIN], LOGIN and [CRAM-MD5] mechanisms. Every decoder
+//! takes a line as sent on the wire, without its CRLF, and handles
+//! the base64 layer as well as the `"="` empty response and the `"*"`
+//! cancellation defined by the AUTH extension.
+//!
+//! The AUTH command, the `"334"` server challenges and the client
+//! continuation lines can be parsed with [`auth_command`],
+//! [`challenge`] and [`continuation`] to drive the whole exchange.
+//!
+//! Verifying the credentials is left to the caller.
+//!
+//! [AUTH]: https://tools.ietf.org/html/rfc4954
+//! [PLAIN]: https://tools.ietf.org/html/rfc4616
+//! [CRAM-MD5]: https://tools.ietf.org/html/rfc2195
+
+use std::fmt::{self, Display};
+use std::str;
+
+use nom::branch::alt;
+use nom::bytes::complete::{tag, tag_no_case, take_while, take_while_m_n};
+use nom::combinator::{map, opt, recognize, verify};
+use nom::sequence::{delimited, pair, preceded, terminated};
+
+use crate::reply::{Reply, ReplyCode};
+use crate::rfc5234::crlf;
+use crate::util::*;
+
+/// Base64 of `"Username:"`, the first LOGIN challenge.
+pub const LOGIN_USERNAME_CHALLENGE: &str = "VXNlcm5hbWU6";
+/// Base64 of `"Password:"`, the second LOGIN challenge.
+pub const LOGIN_PASSWORD_CHALLENGE: &str = "UGFzc3dvcmQ6";
+
+/// Errors produced while decoding a SASL response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaslError {
+    /// The client cancelled the exchange with `"*"`.
+    Cancelled,
+    /// The response is not valid base64.
+    Base64,
+    /// The decoded response is not valid UTF-8.
+    Utf8,
+    /// The decoded response does not follow the mechanism syntax.
+    Syntax,
+}
+
+impl Display for SaslError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaslError::Cancelled => write!(f, "authentication cancelled"),
+            SaslError::Base64 => write!(f, "invalid base64"),
+            SaslError::Utf8 => write!(f, "invalid UTF-8"),
+            SaslError::Syntax => write!(f, "invalid syntax"),
+        }
+    }
+}
+
+impl std::error::Error for SaslError {}
+
+/// Decode a client response line.
+///
+/// `"="` is the empty initial response and `"*"` cancels the exchange.
+/// # Examples
+/// ```
+/// use rustyknife::sasl::{decode_response, SaslError};
+///
+/// assert_eq!(decode_response(b"aGk="), Ok(b"hi".to_vec()));
+/// assert_eq!(decode_response(b"="), Ok(vec![]));
+/// assert_eq!(decode_response(b"*"), Err(SaslError::Cancelled));
+/// assert_eq!(decode_response(b"aG!="), Err(SaslError::Base64));
+/// ```
+pub fn decode_response(input: &[u8]) -> Result<Vec<u8>, SaslError> {
+    match input {
+        b"*" => Err(SaslError::Cancelled),
+        b"=" => Ok(Vec::new()),
+        _ => base64::decode(input).map_err(|_| SaslError::Base64),
+    }
+}
+
+/// Encode a server challenge or client response line.
+///
+/// An empty `data` is encoded as `"="`, as required for initial
+/// responses.
+/// # Examples
+/// ```
+/// use rustyknife::sasl::encode_response;
+///
+/// assert_eq!(encode_response(b"hi"), "aGk=");
+/// assert_eq!(encode_response(b""), "=");
+/// ```
+pub fn encode_response(data: &[u8]) -> String {
+    if data.is_empty() {
+        "=".into()
+    } else {
+        base64::encode(data)
+    }
+}
+
+fn decode_utf8(input: &[u8]) -> Result<String, SaslError> {
+    let decoded = decode_response(input)?;
+    String::from_utf8(decoded).map_err(|_| SaslError::Utf8)
+}
+
+/// Credentials sent with the PLAIN mechanism.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Plain {
+    /// Identity to act as, if different from the authentication identity.
+    pub authzid: Option<String>,
+    /// Authentication identity.
+    pub authcid: String,
+    /// Password of the authentication identity.
+    pub password: String,
+}
+
+/// Decode a PLAIN response.
+///
+/// Both the authentication identity and the password must be
+/// present, an empty authorization identity is returned as `None`.
+/// # Examples
+/// ```
+/// use rustyknife::sasl::{plain, Plain, SaslError};
+///
+/// assert_eq!(plain(b"AHRpbQB0YW5zdGFhZnRhbnN0YWFm"), Ok(Plain {
+///     authzid: None,
+///     authcid: "tim".into(),
+///     password: "tanstaaftanstaaf".into(),
+/// }));
+/// assert_eq!(plain(b"AHRpbQA="), Err(SaslError::Syntax));
+/// ```
+pub fn plain(input: &[u8]) -> Result<Plain, SaslError> {
+    let decoded = decode_utf8(input)?;
+    let mut parts = decoded.split('\0');
+
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(authzid), Some(authcid), Some(password), None) if !authcid.is_empty() && !password.is_empty() => {
+            Ok(Plain {
+                authzid: Some(authzid).filter(|a| !a.is_empty()).map(String::from),
+                authcid: authcid.into(),
+                password: password.into(),
+            })
+        }
+        _ => Err(SaslError::Syntax),
+    }
+}
+
+/// Credentials collected by a [`LoginExchange`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Login {
+    /// Username sent in response to the first challenge.
+    pub username: String,
+    /// Password sent in response to the second challenge.
+    pub password: String,
+}
+
+/// Server side of the LOGIN mechanism.
+///
+/// The server sends [`challenge`](Self::challenge) and passes each
+/// client response to [`respond`](Self::respond) until the
+/// credentials are complete.
+/// # Examples
+/// ```
+/// use rustyknife::sasl::{Login, LoginExchange, LOGIN_PASSWORD_CHALLENGE};
+///
+/// let mut exchange = LoginExchange::with_initial_response(b"dGlt").unwrap();
+/// assert_eq!(exchange.challenge(), LOGIN_PASSWORD_CHALLENGE);
+/// assert_eq!(exchange.respond(b"c2VjcmV0"), Ok(Some(Login {
+///     username: "tim".into(),
+///     password: "secret".into(),
+/// })));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoginExchange {
+    username: Option<String>,
+}
+
+impl LoginExchange {
+    /// Start an exchange without an initial response.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start an exchange with the username sent along the AUTH command.
+    pub fn with_initial_response(input: &[u8]) -> Result<Self, SaslError> {
+        let mut exchange = Self::new();
+        exchange.respond(input)?;
+        Ok(exchange)
+    }
+
+    /// The base64 challenge to send next.
+    pub fn challenge(&self) -> &'static str {
+        match self.username {
+            None => LOGIN_USERNAME_CHALLENGE,
+            Some(_) => LOGIN_PASSWORD_CHALLENGE,
+        }
+    }
+
+    /// Process the client response to the last challenge.
+    ///
+    /// Returns the credentials once the password was received.
+    /// Empty usernames and passwords are refused.
+    pub fn respond(&mut self, input: &[u8]) -> Result<Option<Login>, SaslError> {
+        let value = decode_utf8(input)?;
+
+        if value.is_empty() {
+            return Err(SaslError::Syntax);
+        }
+
+        match self.username.take() {
+            None => {
+                self.username = Some(value);
+                Ok(None)
+            }
+            Some(username) => Ok(Some(Login { username, password: value })),
+        }
+    }
+}
+
+/// Response to a CRAM-MD5 challenge.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CramMd5 {
+    /// Username the shared secret belongs to.
+    pub username: String,
+    /// HMAC-MD5 of the challenge keyed with the shared secret.
+    pub digest: [u8; 16],
+}
+
+/// Encode a CRAM-MD5 challenge.
+///
+/// `challenge` should be a unique msg-id like string, such as
+/// `"<1896.697170952@postoffice.example.net>"`.
+pub fn encode_cram_md5_challenge(challenge: &str) -> String {
+    base64::encode(challenge)
+}
+
+/// Decode a CRAM-MD5 challenge received from a server.
+///
+/// The challenge must be a msg-id like string enclosed in angle
+/// brackets.
+/// # Examples
+/// ```
+/// use rustyknife::sasl::cram_md5_challenge;
+///
+/// assert_eq!(cram_md5_challenge(b"PDE4OTYuNjk3MTcwOTUyQHBvc3RvZmZpY2UucmVzdG9uLm1jaS5uZXQ+"),
+///            Ok("<1896.697170952@postoffice.reston.mci.net>".into()));
+/// ```
+pub fn cram_md5_challenge(input: &[u8]) -> Result<String, SaslError> {
+    let challenge = decode_utf8(input)?;
+
+    if challenge.len() > 2 && challenge.starts_with('<') && challenge.ends_with('>') {
+        Ok(challenge)
+    } else {
+        Err(SaslError::Syntax)
+    }
+}
+
+/// Decode a CRAM-MD5 response.
+///
+/// The response is the username followed by a space and the digest
+/// as 32 lowercase hexadecimal digits.
+/// # Examples
+/// ```
+/// use rustyknife::sasl::cram_md5_response;
+///
+/// let response = cram_md5_response(b"dGltIGI5MTNhNjAyYzdlZGE3YTQ5NWI0ZTZlNzMzNGQzODkw").unwrap();
+///
+/// assert_eq!(response.username, "tim");
+/// assert_eq!(response.digest[..2], [0xb9, 0x13]);
+/// ```
+pub fn cram_md5_response(input: &[u8]) -> Result<CramMd5, SaslError> {
+    let decoded = decode_utf8(input)?;
+    let (username, hex) = decoded.rsplit_once(' ').ok_or(SaslError::Syntax)?;
+
+    if username.is_empty() || hex.len() != 32 || !hex.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f')) {
+        return Err(SaslError::Syntax);
+    }
+
+    let mut digest = [0; 16];
+    for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        // Validated above.
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap();
+    }
+
+    Ok(CramMd5 { username: username.into(), digest })
+}
+
+fn base64_line(input: &[u8]) -> NomResult<&[u8]> {
+    recognize(pair(take_while(|c: u8| c.is_ascii_alphanumeric() || c == b'+' || c == b'/'),
+                   take_while_m_n(0, 2, |c| c == b'=')))(input)
+}
+
+fn sasl_mech(input: &[u8]) -> NomResult<String> {
+    map(take_while_m_n(1, 20, |c: u8| c.is_ascii_alphanumeric() || c == b'-' || c == b'_'),
+        |m| str::from_utf8(m).unwrap().to_ascii_uppercase())(input)
+}
+
+/// Parse an AUTH command.
+///
+/// Returns the uppercase mechanism name and the raw initial response,
+/// which is passed as is to the decoders of this module.
+/// # Examples
+/// ```
+/// use rustyknife::sasl::{auth_command, plain};
+///
+/// let (_, (mechanism, initial)) = auth_command(b"AUTH plain AHRpbQBzZWNyZXQ=\r\n").unwrap();
+/// assert_eq!(mechanism, "PLAIN");
+/// assert_eq!(plain(initial.unwrap()).unwrap().authcid, "tim");
+///
+/// assert_eq!(auth_command(b"AUTH LOGIN\r\n").unwrap().1, ("LOGIN".into(), None));
+/// assert_eq!(auth_command(b"AUTH PLAIN =\r\n").unwrap().1, ("PLAIN".into(), Some(&b"="[..])));
+/// ```
+pub fn auth_command(input: &[u8]) -> NomResult<(String, Option<&[u8]>)> {
+    delimited(tag_no_case("AUTH "),
+              pair(sasl_mech, opt(preceded(tag(" "), verify(base64_line, |r: &[u8]| !r.is_empty())))),
+              crlf)(input)
+}
+
+/// Decode the challenge of a `"334"` reply.
+///
+/// Returns [`None`] for any other reply, which ends the exchange:
+/// `"235"` when authentication succeeded and an error otherwise.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Legacy;
+/// use rustyknife::reply::reply;
+/// use rustyknife::sasl::challenge;
+///
+/// let (_, username) = reply::<Legacy>(b"334 VXNlcm5hbWU6\r\n").unwrap();
+/// assert_eq!(challenge(&username), Some(Ok(b"Username:".to_vec())));
+///
+/// let (_, done) = reply::<Legacy>(b"235 2.7.0 Authentication successful\r\n").unwrap();
+/// assert_eq!(challenge(&done), None);
+/// ```
+pub fn challenge(reply: &Reply) -> Option<Result<Vec<u8>, SaslError>> {
+    if reply.code != ReplyCode::AUTH_CHALLENGE {
+        return None;
+    }
+
+    Some(match &reply.lines[..] {
+        [line] if exact!(line.as_bytes(), base64_line).is_ok() => {
+            base64::decode(line).map_err(|_| SaslError::Base64)
+        }
+        _ => Err(SaslError::Base64),
+    })
+}
+
+/// A client line answering a challenge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Continuation<'a> {
+    /// The raw base64 response, possibly empty.
+    Response(&'a [u8]),
+    /// `"*"`, the client cancels the exchange.
+    Cancel,
+}
+
+impl<'a> Continuation<'a> {
+    /// The decoded response.
+    pub fn decode(&self) -> Result<Vec<u8>, SaslError> {
+        match self {
+            Continuation::Response(line) => base64::decode(line).map_err(|_| SaslError::Base64),
+            Continuation::Cancel => Err(SaslError::Cancelled),
+        }
+    }
+
+    /// The line without its CRLF, as taken by the decoders of this
+    /// module.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        match self {
+            Continuation::Response(line) => line,
+            Continuation::Cancel => b"*",
+        }
+    }
+}
+
+/// Parse a client continuation line.
+/// # Examples
+/// ```
+/// use rustyknife::sasl::{continuation, Continuation, LoginExchange};
+///
+/// let mut exchange = LoginExchange::new();
+/// let (_, line) = continuation(b"dGlt\r\n").unwrap();
+/// assert_eq!(line, Continuation::Response(b"dGlt"));
+/// assert_eq!(exchange.respond(line.as_bytes()), Ok(None));
+///
+/// assert_eq!(continuation(b"*\r\n").unwrap().1, Continuation::Cancel);
+/// assert!(continuation(b"not base64\r\n").is_err());
+/// ```
+pub fn continuation(input: &[u8]) -> NomResult<Continuation> {
+    terminated(alt((map(tag("*"), |_| Continuation::Cancel),
+                    map(base64_line, Continuation::Response))),
+               crlf)(input)
+}
@@ -0,0 +1,192 @@
+//! Anti-abuse accounting for SMTP sessions
+//!
+//! [`SessionGuard`] wraps [`command_limited`] and keeps the counters
+//! that servers use to slow down or disconnect misbehaving clients:
+//! command lines, syntax errors and unknown verbs. Each parsed line
+//! comes with a [`Verdict`] derived from the [`SessionLimits`].
+
+use std::fmt::{self, Display};
+
+use crate::limits::{DecodeError, Limit, SessionLimits, SmtpLimits};
+use crate::rfc5321::{command_limited, extension_command, Command, UTF8Policy, VERBS};
+
+/// What the server should do after replying to a command.
+///
+/// Verdicts are ordered by severity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verdict {
+    /// Nothing unusual.
+    Continue,
+    /// The client made several errors, the command should be logged.
+    Warn,
+    /// The client made many errors, replies should be delayed.
+    SlowDown,
+    /// The session should be closed with a 421 reply.
+    Drop,
+}
+
+/// Why a line was not accepted by the [`SessionGuard`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SessionError {
+    /// The input does not contain a complete line yet. Nothing was
+    /// counted.
+    Incomplete,
+    /// The line is not a valid command.
+    Syntax,
+    /// The verb is neither a base command nor an allowed extension.
+    UnknownVerb(String),
+    /// The line or one of its parts is over the [`SmtpLimits`].
+    LimitExceeded(Limit),
+}
+
+impl Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::Incomplete => write!(f, "incomplete command line"),
+            SessionError::Syntax => write!(f, "syntax error"),
+            SessionError::UnknownVerb(verb) => write!(f, "unknown command {}", verb),
+            SessionError::LimitExceeded(limit) => write!(f, "{}", DecodeError::LimitExceeded(*limit)),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+/// A command accepted by the [`SessionGuard`].
+#[derive(Debug)]
+pub enum SessionCommand<'a> {
+    /// A base SMTP command.
+    Base(Command),
+    /// An allowed extension command with its verb and raw argument,
+    /// see [`extension_command`].
+    Extension(&'a str, &'a [u8]),
+}
+
+/// Counters kept by the [`SessionGuard`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SessionCounters {
+    /// Complete command lines received.
+    pub commands: usize,
+    /// Lines that were invalid or over the [`SmtpLimits`].
+    pub syntax_errors: usize,
+    /// Lines with an unknown verb.
+    pub unknown_verbs: usize,
+}
+
+impl SessionCounters {
+    /// Syntax errors and unknown verbs combined.
+    pub fn errors(&self) -> usize {
+        self.syntax_errors + self.unknown_verbs
+    }
+}
+
+/// Result of [`SessionGuard::parse`].
+#[derive(Debug)]
+pub struct Checked<'a> {
+    /// The input following the line, or all of it when the line is
+    /// incomplete.
+    pub rem: &'a [u8],
+    /// The command or the reason it was not accepted.
+    pub command: Result<SessionCommand<'a>, SessionError>,
+    /// The verdict after counting this line.
+    pub verdict: Verdict,
+}
+
+/// SMTP command parser enforcing per-session caps.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::limits::{SessionLimits, SmtpLimits};
+/// use rustyknife::session::{SessionCommand, SessionError, SessionGuard, Verdict};
+///
+/// let limits = SessionLimits { warn_errors: 1, max_unknown_verbs: 2, ..SessionLimits::default() };
+/// let mut guard = SessionGuard::new(SmtpLimits::default(), limits).allow_verbs(&["STARTTLS"]);
+///
+/// let checked = guard.parse::<Intl>(b"STARTTLS\r\n");
+/// assert!(matches!(checked.command, Ok(SessionCommand::Extension("STARTTLS", b""))));
+/// assert_eq!(checked.verdict, Verdict::Continue);
+///
+/// let checked = guard.parse::<Intl>(b"GET / HTTP/1.1\r\n");
+/// assert_eq!(checked.command.unwrap_err(), SessionError::UnknownVerb("GET".into()));
+/// assert_eq!(checked.verdict, Verdict::Warn);
+///
+/// assert_eq!(guard.parse::<Intl>(b"CONNECT example.org:25\r\n").verdict, Verdict::Drop);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SessionGuard {
+    limits: SmtpLimits,
+    session: SessionLimits,
+    extensions: Vec<String>,
+    counters: SessionCounters,
+}
+
+impl SessionGuard {
+    /// Create a guard for a new session.
+    pub fn new(limits: SmtpLimits, session: SessionLimits) -> Self {
+        SessionGuard { limits, session, extensions: Vec::new(), counters: SessionCounters::default() }
+    }
+
+    /// Accept the extension `verbs`, such as `"STARTTLS"` or
+    /// `"AUTH"`, instead of counting them as unknown.
+    pub fn allow_verbs(mut self, verbs: &[&str]) -> Self {
+        self.extensions.extend(verbs.iter().map(|v| v.to_string()));
+        self
+    }
+
+    /// The counters of this session.
+    pub fn counters(&self) -> SessionCounters {
+        self.counters
+    }
+
+    /// The verdict for the current counters.
+    pub fn verdict(&self) -> Verdict {
+        let (c, l) = (&self.counters, &self.session);
+
+        if c.commands > l.max_commands || c.errors() >= l.max_errors || c.unknown_verbs >= l.max_unknown_verbs {
+            Verdict::Drop
+        } else if c.errors() >= l.slow_errors {
+            Verdict::SlowDown
+        } else if c.errors() >= l.warn_errors {
+            Verdict::Warn
+        } else {
+            Verdict::Continue
+        }
+    }
+
+    /// Parse the command line at the start of `input` and count it.
+    ///
+    /// Invalid lines are skipped: [`Checked::rem`] starts after them.
+    /// A line over [`SmtpLimits::max_command_line`] without a CRLF
+    /// consumes all of `input`, the caller should then discard data
+    /// up to the next CRLF.
+    pub fn parse<'a, P: UTF8Policy>(&mut self, input: &'a [u8]) -> Checked<'a> {
+        let line_end = input.windows(2).position(|w| w == b"\r\n").map(|pos| pos + 2);
+        if line_end.is_none() && input.len() <= self.limits.max_command_line {
+            return Checked { rem: input, command: Err(SessionError::Incomplete), verdict: self.verdict() };
+        }
+        let line = &input[..line_end.unwrap_or(input.len())];
+        self.counters.commands += 1;
+
+        let command = match command_limited::<P>(line, &self.limits) {
+            Ok((_, cmd)) => Ok(SessionCommand::Base(cmd)),
+            Err(DecodeError::LimitExceeded(limit)) => Err(SessionError::LimitExceeded(limit)),
+            Err(_) => match extension_command(line) {
+                Ok((_, (verb, arg))) if self.extensions.iter().any(|v| v.eq_ignore_ascii_case(verb)) => {
+                    Ok(SessionCommand::Extension(verb, arg))
+                }
+                Ok((_, (verb, _))) if !VERBS.iter().any(|v| v.eq_ignore_ascii_case(verb)) => {
+                    Err(SessionError::UnknownVerb(verb.into()))
+                }
+                _ => Err(SessionError::Syntax),
+            },
+        };
+
+        match command {
+            Err(SessionError::UnknownVerb(_)) => self.counters.unknown_verbs += 1,
+            Err(_) => self.counters.syntax_errors += 1,
+            Ok(_) => (),
+        }
+
+        Checked { rem: &input[line.len()..], command, verdict: self.verdict() }
+    }
+}
@@ -0,0 +1,146 @@
+//! [SMTP smuggling] detection helpers
+//!
+//! SMTP only ends the DATA phase on `"\r\n.\r\n"`, but some servers
+//! also accept lookalike sequences using bare LF or bare CR line
+//! endings. An attacker can abuse these differences between the
+//! outbound and inbound server to inject an extra message.
+//!
+//! These helpers find such lookalikes in message data so that they
+//! can be rejected or normalized before relaying.
+//!
+//! [SMTP smuggling]: https://www.postfix.org/smtp-smuggling.html
+
+use nom::bytes::complete::tag;
+use nom::combinator::map;
+
+use crate::transparency::EodScanner;
+use crate::util::*;
+
+/// A line ending as found in message data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `"\r\n"`
+    CrLf,
+    /// Bare `"\n"`
+    Lf,
+    /// Bare `"\r"`
+    Cr,
+}
+
+impl LineEnding {
+    fn len(self) -> usize {
+        match self {
+            LineEnding::CrLf => 2,
+            LineEnding::Lf | LineEnding::Cr => 1,
+        }
+    }
+}
+
+/// A sequence that some servers may interpret as the end of data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EodLookalike {
+    /// Offset of the sequence in the data.
+    ///
+    /// When the dot is at the very start of the data, the preceding
+    /// line ending belongs to the DATA command and this offset points
+    /// to the dot.
+    pub offset: usize,
+    /// Length of the sequence in bytes.
+    pub len: usize,
+    /// The line ending before the dot.
+    pub before: LineEnding,
+    /// The line ending after the dot.
+    pub after: LineEnding,
+}
+
+fn ending_before(data: &[u8], dot: usize) -> Option<LineEnding> {
+    match (dot.checked_sub(2).map(|i| data[i]), dot.checked_sub(1).map(|i| data[i])) {
+        (_, None) => Some(LineEnding::CrLf),
+        (Some(b'\r'), Some(b'\n')) => Some(LineEnding::CrLf),
+        (_, Some(b'\n')) => Some(LineEnding::Lf),
+        (_, Some(b'\r')) => Some(LineEnding::Cr),
+        _ => None,
+    }
+}
+
+fn ending_after(data: &[u8], dot: usize) -> Option<LineEnding> {
+    match (data.get(dot + 1), data.get(dot + 2)) {
+        (Some(b'\r'), Some(b'\n')) => Some(LineEnding::CrLf),
+        (Some(b'\r'), _) => Some(LineEnding::Cr),
+        (Some(b'\n'), _) => Some(LineEnding::Lf),
+        _ => None,
+    }
+}
+
+/// Find all end of data lookalikes in `data`.
+///
+/// `data` is the content following the DATA command. Every line
+/// holding a single dot that is not delimited by CRLF on both sides
+/// is reported, such as `"\n.\n"`, `"\r.\r"` or `"\r\n.\n"`.
+/// # Examples
+/// ```
+/// use rustyknife::smuggling::{eod_lookalikes, EodLookalike, LineEnding};
+///
+/// let found = eod_lookalikes(b"Subject: hi\r\n\r\nbody\n.\nMAIL FROM:<x@example.org>\r\n");
+///
+/// assert_eq!(found, [EodLookalike { offset: 19, len: 3,
+///                                   before: LineEnding::Lf, after: LineEnding::Lf }]);
+/// ```
+pub fn eod_lookalikes(data: &[u8]) -> Vec<EodLookalike> {
+    let mut out = Vec::new();
+
+    for (dot, _) in data.iter().enumerate().filter(|(_, c)| **c == b'.') {
+        if let (Some(before), Some(after)) = (ending_before(data, dot), ending_after(data, dot)) {
+            if before == LineEnding::CrLf && after == LineEnding::CrLf {
+                continue;
+            }
+            let offset = if dot == 0 { 0 } else { dot - before.len() };
+
+            out.push(EodLookalike { offset, len: dot + 1 + after.len() - offset, before, after });
+        }
+    }
+
+    out
+}
+
+/// Returns `true` if `data` contains any end of data lookalike.
+///
+/// See [`eod_lookalikes`].
+pub fn has_eod_lookalike(data: &[u8]) -> bool {
+    !eod_lookalikes(data).is_empty()
+}
+
+/// Match the strict end of data sequence `"\r\n.\r\n"`.
+///
+/// No other line ending combination is accepted.
+/// # Examples
+/// ```
+/// use rustyknife::smuggling::end_of_data;
+///
+/// assert!(end_of_data(b"\r\n.\r\n").is_ok());
+/// assert!(end_of_data(b"\n.\r\n").is_err());
+/// assert!(end_of_data(b"\r\n.\n").is_err());
+/// ```
+pub fn end_of_data(input: &[u8]) -> NomResult<()> {
+    map(tag("\r\n.\r\n"), |_| ())(input)
+}
+
+/// Find the offset of the first strict end of data sequence.
+///
+/// Returns the offset of the `"\r\n.\r\n"` sequence terminating the
+/// message content. A dot line at the very start of `data` is
+/// considered to be preceded by the CRLF ending the DATA command, in
+/// which case the offset points to the dot.
+///
+/// Use [`EodScanner`] to search data received in chunks.
+/// # Examples
+/// ```
+/// use rustyknife::smuggling::find_end_of_data;
+///
+/// assert_eq!(find_end_of_data(b"a\n.\nb\r\n.\r\n"), Some(5));
+/// assert_eq!(find_end_of_data(b".\r\n"), Some(0));
+/// assert_eq!(find_end_of_data(b"a\r\n.\n"), None);
+/// ```
+pub fn find_end_of_data(data: &[u8]) -> Option<usize> {
+    EodScanner::new().feed(data).map(|end| end.saturating_sub(5))
+}
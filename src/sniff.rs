@@ -0,0 +1,69 @@
+//! Charset detection for undeclared 8-bit text
+//!
+//! Header values and text parts are sometimes sent with raw 8-bit
+//! text and no charset declaration. Instead of replacing every such
+//! byte with U+FFFD, the text is checked for UTF-8 validity and then
+//! decoded with the first legacy charset of a configurable chain that
+//! yields plausible text.
+
+use std::borrow::Cow;
+
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+
+/// Heuristic decoder for text lacking a charset declaration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sniffer {
+    chain: Vec<&'static Encoding>,
+}
+
+impl Default for Sniffer {
+    /// Falls back to Windows-1252, a superset of ISO-8859-1.
+    fn default() -> Self {
+        Sniffer::new(vec![WINDOWS_1252])
+    }
+}
+
+// Control characters other than whitespace hint at the wrong charset.
+fn plausible(text: &str) -> bool {
+    !text.chars().any(|c| c.is_control() && !"\t\r\n".contains(c))
+}
+
+impl Sniffer {
+    /// Create a sniffer trying the charsets of `chain` in order when
+    /// the input is not valid UTF-8.
+    pub fn new(chain: Vec<&'static Encoding>) -> Self {
+        Sniffer { chain }
+    }
+
+    /// Decode `input`, returning the text and the charset used.
+    ///
+    /// Valid UTF-8, including plain ASCII, is returned as is. Otherwise
+    /// the first charset of the chain that decodes the input without
+    /// errors or control characters is used. If none qualifies, the
+    /// last charset of the chain is used with replacement characters.
+    /// # Examples
+    /// ```
+    /// use rustyknife::sniff::Sniffer;
+    ///
+    /// let sniffer = Sniffer::default();
+    ///
+    /// assert_eq!(sniffer.decode("Fran\u{e7}ais".as_bytes()), ("Fran\u{e7}ais".into(), encoding_rs::UTF_8));
+    /// assert_eq!(sniffer.decode(b"Fran\xe7ais \x80"), ("Fran\u{e7}ais \u{20ac}".into(), encoding_rs::WINDOWS_1252));
+    /// ```
+    pub fn decode<'a>(&self, input: &'a [u8]) -> (Cow<'a, str>, &'static Encoding) {
+        if let Ok(text) = std::str::from_utf8(input) {
+            return (text.into(), UTF_8);
+        }
+
+        for encoding in &self.chain {
+            if let Some(text) = encoding.decode_without_bom_handling_and_without_replacement(input) {
+                if plausible(&text) {
+                    return (text, encoding);
+                }
+            }
+        }
+
+        let last = self.chain.last().copied().unwrap_or(UTF_8);
+        (last.decode_without_bom_handling(input).0, last)
+    }
+}
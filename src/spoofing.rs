@@ -0,0 +1,118 @@
+//! Display name spoofing checks
+//!
+//! Mail clients often show only the display name of the sender, so a
+//! message from `"\"ceo@bank.example\" <attacker@evil.example>"` looks
+//! like it comes from `ceo@bank.example`. These helpers run the
+//! address grammar over decoded display names to find such
+//! lookalikes.
+
+use std::ops::Range;
+
+use crate::behaviour::Intl;
+use crate::rfc5322::{addr_spec, Address, Mailbox};
+use crate::types::{self, DomainPart};
+
+/// An address found inside a display name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NameAddress {
+    /// Byte range of the address in the display name.
+    pub span: Range<usize>,
+    /// The address.
+    pub address: types::Mailbox,
+    /// Whether the address differs from the actual address of the
+    /// mailbox, compared case insensitively.
+    pub mismatch: bool,
+}
+
+fn is_atext(c: char) -> bool {
+    c.is_alphanumeric() || "!#$%&'*+-/=?^_`{|}~.".contains(c)
+}
+
+/// Find the addresses contained in `name`.
+///
+/// Only addresses with a fully qualified domain or an address
+/// literal are returned, so that `"Bob@Home"` is not reported.
+/// # Examples
+/// ```
+/// use rustyknife::spoofing::addresses_in_name;
+///
+/// let found = addresses_in_name("Support (help@bank.example)");
+/// assert_eq!(found.len(), 1);
+/// assert_eq!(found[0].0, 9..26);
+/// assert_eq!(found[0].1.to_string(), "help@bank.example");
+///
+/// assert!(addresses_in_name("Bob @ Home").is_empty());
+/// ```
+pub fn addresses_in_name(name: &str) -> Vec<(Range<usize>, types::Mailbox)> {
+    let mut out = Vec::new();
+    let mut resume = 0;
+
+    for (at, _) in name.match_indices('@') {
+        if at < resume {
+            continue;
+        }
+        let start = name[..at].char_indices().rev()
+            .take_while(|(_, c)| is_atext(*c))
+            .last()
+            .map(|(i, _)| i)
+            .unwrap_or(at);
+        let start = start + name[start..at].len() - name[start..at].trim_start_matches('.').len();
+        if start == at {
+            continue;
+        }
+
+        if let Ok((rem, address)) = addr_spec::<Intl>(&name.as_bytes()[start..]) {
+            let fqdn = match address.domain_part() {
+                DomainPart::Domain(d) => d.is_fqdn(),
+                DomainPart::Address(_) => true,
+            };
+            let end = name.len() - rem.len();
+            let end = start + name[start..end].trim_end().len();
+
+            if fqdn {
+                out.push((start..end, address));
+                resume = end;
+            }
+        }
+    }
+
+    out
+}
+
+/// Check the display name of `mailbox` for embedded addresses.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::rfc5322::from;
+/// use rustyknife::spoofing::check_mailbox;
+///
+/// let (_, addrs) = from::<Intl>(b"\"ceo@bank.example\" <attacker@evil.example>").unwrap();
+/// let found = check_mailbox(&addrs[0].mailboxes()[0]);
+/// assert_eq!(found[0].address.to_string(), "ceo@bank.example");
+/// assert!(found[0].mismatch);
+///
+/// let (_, addrs) = from::<Intl>(b"\"Bob@Example.org\" <bob@example.org>").unwrap();
+/// assert!(!check_mailbox(&addrs[0].mailboxes()[0])[0].mismatch);
+/// ```
+pub fn check_mailbox(mailbox: &Mailbox) -> Vec<NameAddress> {
+    let name = match &mailbox.dname {
+        Some(name) => name,
+        None => return Vec::new(),
+    };
+
+    addresses_in_name(name).into_iter()
+        .map(|(span, address)| {
+            let mismatch = !address.eq_ignore_case(&mailbox.address);
+            NameAddress { span, address, mismatch }
+        })
+        .collect()
+}
+
+/// Check every mailbox of `addresses`, returning the mailboxes with
+/// a display name containing a different address.
+pub fn spoofed_mailboxes(addresses: &[Address]) -> Vec<(&Mailbox, Vec<NameAddress>)> {
+    addresses.iter().flat_map(Address::mailboxes)
+        .map(|m| (m, check_mailbox(m).into_iter().filter(|f| f.mismatch).collect::<Vec<_>>()))
+        .filter(|(_, found)| !found.is_empty())
+        .collect()
+}
@@ -0,0 +1,299 @@
+//! [Sender Rewriting Scheme] (SRS)
+//!
+//! Forwarders rewrite the reverse path of relayed messages into an
+//! address of their own domain so that SPF checks pass at the next
+//! hop, encoding the original sender so that bounces can be routed
+//! back. The `SRS0` form wraps an ordinary address and the `SRS1` form
+//! wraps an address that was already rewritten by another forwarder.
+//!
+//! The address syntax and the HMAC-SHA-1 hash are those of the
+//! original scheme as implemented by libsrs2 and postsrsd, so that
+//! addresses can be reversed by either given the same secret.
+//!
+//! [Sender Rewriting Scheme]: https://www.libsrs2.net/srs/srs.pdf
+
+use std::fmt::{self, Display};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::types::{Domain, DomainPart, LocalPart, Mailbox};
+
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Errors produced when reversing an SRS address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SrsError {
+    /// The address is not an SRS address.
+    NotSrs,
+    /// The address is an SRS address with malformed fields.
+    Syntax,
+    /// The hash does not match the address.
+    Hash,
+    /// The timestamp is too old or invalid.
+    Expired,
+}
+
+impl Display for SrsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SrsError::NotSrs => write!(f, "not an SRS address"),
+            SrsError::Syntax => write!(f, "malformed SRS address"),
+            SrsError::Hash => write!(f, "invalid SRS hash"),
+            SrsError::Expired => write!(f, "expired SRS timestamp"),
+        }
+    }
+}
+
+impl std::error::Error for SrsError {}
+
+/// Fields of an `SRS0` address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Srs0 {
+    /// Base64 hash authenticating the other fields.
+    pub hash: String,
+    /// Two character base32 day number.
+    pub timestamp: String,
+    /// Domain of the original sender.
+    pub domain: String,
+    /// Local part of the original sender, unquoted.
+    pub local: String,
+}
+
+/// Fields of an `SRS1` address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Srs1 {
+    /// Base64 hash authenticating the other fields.
+    pub hash: String,
+    /// Domain of the forwarder that generated the `SRS0` address.
+    pub forwarder: String,
+    /// Separator that followed `"SRS0"` in the original address.
+    pub separator: char,
+    /// Local part of the original `SRS0` address without its
+    /// `"SRS0"` prefix and separator.
+    pub srs0: String,
+}
+
+/// A parsed SRS address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SrsAddress {
+    /// An address rewritten once.
+    Srs0(Srs0),
+    /// An address rewritten more than once.
+    Srs1(Srs1),
+}
+
+impl SrsAddress {
+    /// Parse the local part of `mailbox` as an SRS address.
+    ///
+    /// The hash and timestamp are not verified, see [`Srs::reverse`].
+    /// # Examples
+    /// ```
+    /// use rustyknife::srs::SrsAddress;
+    /// use rustyknife::types::Mailbox;
+    ///
+    /// let mailbox = Mailbox::from_smtp(b"SRS0=HHH=TT=example.org=bob@forwarder.example").unwrap();
+    ///
+    /// match SrsAddress::parse(&mailbox) {
+    ///     Ok(SrsAddress::Srs0(srs)) => {
+    ///         assert_eq!(srs.domain, "example.org");
+    ///         assert_eq!(srs.local, "bob");
+    ///     }
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn parse(mailbox: &Mailbox) -> Result<Self, SrsError> {
+        let local = local_string(mailbox.local_part());
+        let (tag, rest) = (local.get(..4).ok_or(SrsError::NotSrs)?, &local[4..]);
+        let rest = match rest.chars().next() {
+            Some(sep @ '=') | Some(sep @ '+') | Some(sep @ '-') => &rest[sep.len_utf8()..],
+            _ => return Err(SrsError::NotSrs),
+        };
+
+        if tag.eq_ignore_ascii_case("SRS0") {
+            let mut fields = rest.splitn(4, '=');
+            match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                (Some(hash), Some(timestamp), Some(domain), Some(local))
+                    if !hash.is_empty() && timestamp.len() == 2 && !domain.is_empty() && !local.is_empty() => {
+                    Ok(SrsAddress::Srs0(Srs0 { hash: hash.into(), timestamp: timestamp.into(), domain: domain.into(), local: local.into() }))
+                }
+                _ => Err(SrsError::Syntax),
+            }
+        } else if tag.eq_ignore_ascii_case("SRS1") {
+            let mut fields = rest.splitn(3, '=');
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(hash), Some(forwarder), Some(srs0)) if !hash.is_empty() && !forwarder.is_empty() => {
+                    let separator = match srs0.chars().next() {
+                        Some(c) if "=+-".contains(c) => c,
+                        _ => return Err(SrsError::Syntax),
+                    };
+                    Ok(SrsAddress::Srs1(Srs1 { hash: hash.into(), forwarder: forwarder.into(), separator, srs0: srs0[1..].into() }))
+                }
+                _ => Err(SrsError::Syntax),
+            }
+        } else {
+            Err(SrsError::NotSrs)
+        }
+    }
+}
+
+fn local_string(local: &LocalPart) -> String {
+    match local {
+        LocalPart::DotAtom(a) => a.to_string(),
+        LocalPart::Quoted(q) => q.to_string(),
+    }
+}
+
+// Build a mailbox, quoting the local part only when needed.
+fn mailbox(local: String, domain: &str) -> Result<Mailbox, SrsError> {
+    let local = LocalPart::quote_if_needed(&local);
+    let domain = DomainPart::from_smtp(domain.as_bytes()).map_err(|_| SrsError::Syntax)?;
+
+    Ok(Mailbox::from_parts(local, domain))
+}
+
+// As in libsrs2, the parts are lowercased and concatenated.
+fn hmac_sha1(key: &[u8], data: &[&str]) -> [u8; 20] {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in data {
+        mac.update(part.to_ascii_lowercase().as_bytes());
+    }
+
+    mac.finalize().into_bytes().into()
+}
+
+fn day(now: SystemTime) -> u64 {
+    now.duration_since(UNIX_EPOCH).map(|d| d.as_secs() / 86400).unwrap_or(0)
+}
+
+/// SRS address rewriting with a secret key.
+/// # Examples
+/// ```
+/// use std::time::SystemTime;
+/// use rustyknife::srs::Srs;
+/// use rustyknife::types::{Domain, Mailbox};
+///
+/// let srs = Srs::new(b"secret");
+/// let sender = Mailbox::from_smtp(b"bob@example.org").unwrap();
+/// let forwarder = Domain::from_smtp(b"forwarder.example").unwrap();
+///
+/// let rewritten = srs.forward(&sender, &forwarder, SystemTime::now()).unwrap();
+/// assert!(rewritten.to_string().starts_with("SRS0="));
+/// assert!(rewritten.to_string().ends_with("=example.org=bob@forwarder.example"));
+///
+/// assert_eq!(srs.reverse(&rewritten, SystemTime::now()), Ok(sender));
+/// ```
+#[derive(Clone)]
+pub struct Srs {
+    secret: Vec<u8>,
+    /// Number of days a rewritten address stays valid.
+    pub max_age: u16,
+    /// Number of base64 characters of the hash to include.
+    pub hash_len: usize,
+}
+
+impl fmt::Debug for Srs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Srs").field("max_age", &self.max_age).field("hash_len", &self.hash_len).finish_non_exhaustive()
+    }
+}
+
+impl Srs {
+    /// Create a rewriter with the default maximum age of 21 days and
+    /// a 4 character hash.
+    pub fn new(secret: &[u8]) -> Self {
+        Srs { secret: secret.to_vec(), max_age: 21, hash_len: 4 }
+    }
+
+    fn hash(&self, data: &[&str]) -> String {
+        let mut hash = base64::encode(hmac_sha1(&self.secret, data));
+        hash.truncate(self.hash_len);
+        hash
+    }
+
+    // Hashes are compared case insensitively since some MTAs change
+    // the case of local parts, and in constant time.
+    fn check_hash(&self, hash: &str, data: &[&str]) -> Result<(), SrsError> {
+        let expected = self.hash(data);
+        let diff = expected.bytes().zip(hash.bytes())
+            .fold(0, |diff, (a, b)| diff | (a.to_ascii_lowercase() ^ b.to_ascii_lowercase()));
+
+        if hash.len() >= self.hash_len && hash.len() == expected.len() && diff == 0 {
+            Ok(())
+        } else {
+            Err(SrsError::Hash)
+        }
+    }
+
+    fn timestamp(now: SystemTime) -> String {
+        let day = day(now) % 1024;
+        [BASE32[day as usize >> 5], BASE32[day as usize & 31]].iter().map(|&c| char::from(c)).collect()
+    }
+
+    fn check_timestamp(&self, timestamp: &str, now: SystemTime) -> Result<(), SrsError> {
+        let value = |c: u8| BASE32.iter().position(|&b| b == c.to_ascii_uppercase()).ok_or(SrsError::Expired);
+        let (hi, lo) = match timestamp.as_bytes() {
+            [hi, lo] => (value(*hi)?, value(*lo)?),
+            _ => return Err(SrsError::Expired),
+        };
+        let age = (day(now) % 1024 + 1024 - (hi << 5 | lo) as u64) % 1024;
+
+        if age <= u64::from(self.max_age) {
+            Ok(())
+        } else {
+            Err(SrsError::Expired)
+        }
+    }
+
+    /// Rewrite `sender` into an address at `domain`.
+    ///
+    /// Ordinary addresses are rewritten to the `SRS0` form, `SRS0`
+    /// addresses to the `SRS1` form and `SRS1` addresses keep their
+    /// original forwarder. Fails only if the result is not a valid
+    /// address.
+    pub fn forward(&self, sender: &Mailbox, domain: &Domain, now: SystemTime) -> Result<Mailbox, SrsError> {
+        let local = match SrsAddress::parse(sender) {
+            Ok(SrsAddress::Srs0(_)) => {
+                let forwarder = sender.domain_part().to_string();
+                let local = local_string(sender.local_part());
+                let srs0 = &local[4..];
+                let hash = self.hash(&[&forwarder, srs0]);
+                format!("SRS1={}={}={}", hash, forwarder, srs0)
+            }
+            Ok(SrsAddress::Srs1(srs1)) => {
+                let srs0 = format!("{}{}", srs1.separator, srs1.srs0);
+                let hash = self.hash(&[&srs1.forwarder, &srs0]);
+                format!("SRS1={}={}={}", hash, srs1.forwarder, srs0)
+            }
+            Err(_) => {
+                let timestamp = Self::timestamp(now);
+                let original = sender.domain_part().to_string();
+                let local = local_string(sender.local_part());
+                let hash = self.hash(&[&timestamp, &original, &local]);
+                format!("SRS0={}={}={}={}", hash, timestamp, original, local)
+            }
+        };
+
+        mailbox(local, &domain.to_string())
+    }
+
+    /// Recover the address that `recipient` was rewritten from.
+    ///
+    /// `SRS0` addresses yield the original sender and `SRS1` addresses
+    /// yield the `SRS0` address at the first forwarder.
+    pub fn reverse(&self, recipient: &Mailbox, now: SystemTime) -> Result<Mailbox, SrsError> {
+        match SrsAddress::parse(recipient)? {
+            SrsAddress::Srs0(srs0) => {
+                self.check_hash(&srs0.hash, &[&srs0.timestamp, &srs0.domain, &srs0.local])?;
+                self.check_timestamp(&srs0.timestamp, now)?;
+                mailbox(srs0.local, &srs0.domain)
+            }
+            SrsAddress::Srs1(srs1) => {
+                let srs0 = format!("{}{}", srs1.separator, srs1.srs0);
+                self.check_hash(&srs1.hash, &[&srs1.forwarder, &srs0])?;
+                mailbox(format!("SRS0{}", srs0), &srs1.forwarder)
+            }
+        }
+    }
+}
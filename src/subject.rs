@@ -0,0 +1,130 @@
+//! Subject reply and forward prefix handling
+//!
+//! Mail clients prepend localized markers such as `"Re:"`, `"AW:"` or
+//! `"回复:"` to the subject of replies and forwarded messages, often
+//! stacking them. This module recognizes those prefixes so that the
+//! base subject can be compared when deduplicating or threading
+//! messages without usable references.
+
+use crate::behaviour::Intl;
+use crate::rfc5322::unstructured;
+
+// Reply and forward markers, lowercased. Ambiguous markers such as
+// "VS", a reply in Finnish but a forward in Danish, are left out.
+const REPLY_PREFIXES: &[&str] = &["re", "aw", "sv", "antw", "odp", "ynt", "rif", "réf", "回复", "回覆", "答复", "答覆"];
+const FORWARD_PREFIXES: &[&str] = &["fwd", "fw", "tr", "wg", "rv", "enc", "doorst", "转发", "轉寄", "轉發"];
+
+/// Kind of subject prefix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PrefixKind {
+    /// The message is a reply.
+    Reply,
+    /// The message is forwarded.
+    Forward,
+}
+
+/// A prefix found at the start of a subject.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubjectPrefix {
+    /// Kind of prefix.
+    pub kind: PrefixKind,
+    /// Prefix as written, including its colon.
+    pub text: String,
+    /// Reply count of the `"Re[n]:"` form.
+    pub count: Option<u32>,
+}
+
+/// A subject split into its prefixes and base subject.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NormalizedSubject {
+    /// Prefixes in the order they appear.
+    pub prefixes: Vec<SubjectPrefix>,
+    /// Subject without prefixes, with whitespace collapsed.
+    pub base: String,
+}
+
+impl NormalizedSubject {
+    /// Returns `true` if the first prefix marks a reply.
+    pub fn is_reply(&self) -> bool {
+        matches!(self.prefixes.first(), Some(p) if p.kind == PrefixKind::Reply)
+    }
+
+    /// Returns `true` if the first prefix marks a forward.
+    pub fn is_forward(&self) -> bool {
+        matches!(self.prefixes.first(), Some(p) if p.kind == PrefixKind::Forward)
+    }
+}
+
+// Split a single prefix from the start of `subject`.
+pub(crate) fn split_prefix(subject: &str) -> Option<(SubjectPrefix, &str)> {
+    let end = subject.find([':', '：'])?;
+    let colon = subject[end..].chars().next()?.len_utf8();
+    let word = subject[..end].trim_end();
+
+    let (word, count) = match word.find('[') {
+        Some(i) if word.ends_with(']') => (word[..i].trim_end(), Some(word[i + 1..word.len() - 1].parse().ok()?)),
+        Some(_) => return None,
+        None => (word, None),
+    };
+
+    let word = word.to_lowercase();
+    let kind = if REPLY_PREFIXES.contains(&word.as_str()) {
+        PrefixKind::Reply
+    } else if FORWARD_PREFIXES.contains(&word.as_str()) {
+        PrefixKind::Forward
+    } else {
+        return None;
+    };
+
+    let text = subject[..end + colon].into();
+    Some((SubjectPrefix { kind, text, count }, subject[end + colon..].trim_start()))
+}
+
+/// Split the reply and forward prefixes from a decoded `subject`.
+///
+/// Prefixes are matched case insensitively, with an optional
+/// `"[n]"` count and either an ASCII or a fullwidth colon.
+/// # Examples
+/// ```
+/// use rustyknife::subject::{split_prefixes, PrefixKind};
+///
+/// let (prefixes, base) = split_prefixes("RE : Fwd: 回复：Lunch");
+///
+/// assert_eq!(base, "Lunch");
+/// assert_eq!(prefixes.iter().map(|p| p.kind).collect::<Vec<_>>(),
+///            [PrefixKind::Reply, PrefixKind::Forward, PrefixKind::Reply]);
+/// assert_eq!(prefixes[0].text, "RE :");
+/// ```
+pub fn split_prefixes(subject: &str) -> (Vec<SubjectPrefix>, &str) {
+    let mut subject = subject.trim_start();
+    let mut prefixes = Vec::new();
+
+    while let Some((found, rest)) = split_prefix(subject) {
+        prefixes.push(found);
+        subject = rest;
+    }
+
+    (prefixes, subject.trim_end())
+}
+
+/// Normalize a raw Subject header value.
+///
+/// The value is RFC 2047 decoded, its whitespace collapsed and its
+/// prefixes split from the base subject.
+/// # Examples
+/// ```
+/// use rustyknife::subject::normalize_subject;
+///
+/// let subject = normalize_subject(b" =?utf-8?b?5Zue5aSNOg==?= AW: Rapport\r\n  annuel");
+///
+/// assert!(subject.is_reply());
+/// assert_eq!(subject.prefixes.len(), 2);
+/// assert_eq!(subject.base, "Rapport annuel");
+/// ```
+pub fn normalize_subject(value: &[u8]) -> NormalizedSubject {
+    let decoded = unstructured::<Intl>(value).map(|(_, s)| s).unwrap_or_else(|_| String::from_utf8_lossy(value).into());
+    let collapsed = decoded.split_whitespace().collect::<Vec<_>>().join(" ");
+    let (prefixes, base) = split_prefixes(&collapsed);
+
+    NormalizedSubject { base: base.into(), prefixes }
+}
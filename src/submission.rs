@@ -0,0 +1,102 @@
+//! [Message submission] (RFC 6409) command profile
+//!
+//! A message submission agent, usually listening on port 587, is
+//! stricter than a relay on port 25: clients are mail user agents
+//! that can be required to use ESMTP and to send well formed
+//! addresses. [`SubmissionProfile`] applies those rules on top of
+//! [`command`].
+//!
+//! [Message submission]: https://tools.ietf.org/html/rfc6409
+
+use std::fmt::{self, Display};
+
+use crate::rfc5321::{command, Command, ForwardPath, Path, ReversePath, UTF8Policy};
+use crate::types::DomainPart;
+
+/// Reason a command was rejected by the [`SubmissionProfile`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubmissionError {
+    /// The line is not a valid command.
+    Syntax,
+    /// HELO was used, or a transaction started before EHLO.
+    EhloRequired,
+    /// A path has a source route, such as `"<@relay.example:bob@example.org>"`.
+    SourceRoute,
+    /// The domain of a path is not fully qualified.
+    UnqualifiedDomain,
+}
+
+impl Display for SubmissionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SubmissionError::Syntax => write!(f, "syntax error"),
+            SubmissionError::EhloRequired => write!(f, "EHLO required"),
+            SubmissionError::SourceRoute => write!(f, "source routes are not allowed"),
+            SubmissionError::UnqualifiedDomain => write!(f, "domain is not fully qualified"),
+        }
+    }
+}
+
+impl std::error::Error for SubmissionError {}
+
+fn check_path(path: &Path) -> Result<(), SubmissionError> {
+    if !path.1.is_empty() {
+        return Err(SubmissionError::SourceRoute);
+    }
+
+    match path.0.domain_part() {
+        DomainPart::Domain(d) if !d.is_fqdn() => Err(SubmissionError::UnqualifiedDomain),
+        _ => Ok(()),
+    }
+}
+
+/// Command parser for a submission session.
+///
+/// Address literals and the unqualified `"<postmaster>"` are
+/// accepted, the null reverse path too.
+/// # Examples
+/// ```
+/// use rustyknife::behaviour::Intl;
+/// use rustyknife::submission::{SubmissionError, SubmissionProfile};
+///
+/// let mut session = SubmissionProfile::new();
+///
+/// assert_eq!(session.parse::<Intl>(b"MAIL FROM:<bob@example.org>\r\n").unwrap_err(), SubmissionError::EhloRequired);
+/// assert!(session.parse::<Intl>(b"EHLO laptop.example.org\r\n").is_ok());
+/// assert!(session.parse::<Intl>(b"MAIL FROM:<bob@example.org>\r\n").is_ok());
+/// assert_eq!(session.parse::<Intl>(b"RCPT TO:<alice@mailhost>\r\n").unwrap_err(), SubmissionError::UnqualifiedDomain);
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SubmissionProfile {
+    ehlo: bool,
+}
+
+impl SubmissionProfile {
+    /// Create a profile for a new session.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a valid EHLO was received.
+    pub fn ehlo_seen(&self) -> bool {
+        self.ehlo
+    }
+
+    /// Parse a command and check it against the submission rules.
+    pub fn parse<'a, P: UTF8Policy>(&mut self, input: &'a [u8]) -> Result<(&'a [u8], Command), SubmissionError> {
+        let (rem, cmd) = command::<P>(input).map_err(|_| SubmissionError::Syntax)?;
+
+        match &cmd {
+            Command::EHLO(_) => self.ehlo = true,
+            Command::HELO(_) => return Err(SubmissionError::EhloRequired),
+            Command::MAIL(..) | Command::RCPT(..) | Command::DATA if !self.ehlo => return Err(SubmissionError::EhloRequired),
+            Command::MAIL(ReversePath::Path(path), _) | Command::RCPT(ForwardPath::Path(path), _) => check_path(path)?,
+            Command::RCPT(ForwardPath::PostMaster(Some(domain)), _) if !domain.is_fqdn() => {
+                return Err(SubmissionError::UnqualifiedDomain)
+            }
+            _ => (),
+        }
+
+        Ok((rem, cmd))
+    }
+}
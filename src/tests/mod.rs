@@ -1,4 +1,35 @@
+mod test_builder;
+mod test_bulk;
+#[cfg(feature = "tokio")]
+mod test_codec;
+mod test_dsn;
+mod test_esmtp;
+mod test_events;
+mod test_fingerprint;
 mod test_headersection;
+mod test_homograph;
+mod test_lexer;
+mod test_mediatype;
+mod test_mime;
+mod test_redact;
+mod test_reply;
+mod test_rewrite;
 mod test_rfc2231;
+mod test_rfc3463;
 mod test_rfc5321;
 mod test_rfc5322;
+mod test_roundtrip;
+mod test_sasl;
+mod test_session;
+mod test_smuggling;
+mod test_sniff;
+mod test_spoofing;
+mod test_srs;
+mod test_subject;
+mod test_submission;
+mod test_thread;
+mod test_trace;
+mod test_transcript;
+mod test_transparency;
+mod test_truncate;
+mod test_useragent;
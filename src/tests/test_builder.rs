@@ -0,0 +1,329 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::behaviour::Intl;
+use crate::builder::*;
+use crate::dsn::{delivery_status, Action, DeliveryStatus, DsnError, RecipientReport};
+use crate::headersection::HeaderOrder;
+use crate::mdn::{DispositionNotification, DispositionType};
+use crate::mime::parse_message;
+use crate::rfc3461::DSNRet;
+use crate::rfc5322::{from, unstructured, Address};
+
+fn addresses(input: &str) -> Vec<Address> {
+    from::<Intl>(input.as_bytes()).unwrap().1
+}
+
+#[test]
+fn single_text_part() {
+    let raw = MessageBuilder::new().text("line 1\nline 2").build();
+    let message = parse_message(&raw);
+
+    assert_eq!(message.content_type, "text/plain");
+    assert_eq!(message.param("charset"), Some("us-ascii"));
+    assert_eq!(message.header(b"Content-Transfer-Encoding"), Some(&b" 7bit"[..]));
+    assert_eq!(message.decoded_text(), "line 1\r\nline 2");
+    assert!(message.header(b"Date").is_some());
+    assert!(message.header(b"Message-ID").unwrap().ends_with(b"@localhost>"));
+}
+
+#[test]
+fn alternative_roundtrip() {
+    let text = "Prix : 10 €\r\n".repeat(20) + &"x".repeat(1200);
+    let raw = MessageBuilder::new()
+        .from(&addresses("Zoë <zoe@example.org>"))
+        .subject("Réunion de lundi, avec un sujet assez long pour devoir être replié")
+        .text(&text)
+        .html("<p>€</p>")
+        .build();
+    let message = parse_message(&raw);
+    let bodies = message.body_parts();
+
+    assert_eq!(message.content_type, "multipart/alternative");
+    assert_eq!(bodies.text.unwrap().decoded_text(), text);
+    assert_eq!(bodies.text.unwrap().header(b"Content-Transfer-Encoding"), Some(&b" quoted-printable"[..]));
+    assert_eq!(bodies.html.unwrap().decoded_text(), "<p>€</p>");
+
+    let subject = message.header(b"Subject").unwrap();
+    assert_eq!(unstructured::<Intl>(subject).unwrap().1.trim_start(), "Réunion de lundi, avec un sujet assez long pour devoir être replié");
+    assert_eq!(from::<Intl>(message.header(b"From").unwrap()).unwrap().1, addresses("Zoë <zoe@example.org>"));
+    assert!(message.header(b"Message-ID").unwrap().ends_with(b"@example.org>"));
+    for line in raw.split(|c| *c == b'\n') { assert!(line.len() <= 77, "{}", String::from_utf8_lossy(line)); }
+}
+
+#[test]
+fn attachments_roundtrip() {
+    let binary: Vec<u8> = (0..=255).collect();
+    let raw = MessageBuilder::new()
+        .text("see attached")
+        .attach("données.bin", "application/octet-stream", &binary)
+        .attach("readme.txt", "text/plain", b"plain\r\n")
+        .build();
+    let message = parse_message(&raw);
+    let attachments: Vec<_> = message.attachments().collect();
+
+    assert_eq!(message.content_type, "multipart/mixed");
+    assert_eq!(message.parts[0].decoded_text(), "see attached");
+    assert_eq!(attachments.len(), 2);
+    assert_eq!(attachments[0].filename.as_deref(), Some("données.bin"));
+    assert_eq!(attachments[0].entity.decoded_body(), binary);
+    assert_eq!(attachments[1].entity.header(b"Content-Transfer-Encoding"), Some(&b" 7bit"[..]));
+    assert_eq!(attachments[1].entity.decoded_body(), &b"plain\r\n"[..]);
+}
+
+#[test]
+fn header_order() {
+    let build = |order| String::from_utf8(MessageBuilder::new()
+        .header("X-Custom", "1")
+        .subject("s")
+        .to(&addresses("b@example.org"))
+        .date(UNIX_EPOCH)
+        .message_id("<id@example.org>")
+        .header("x-lower", "2")
+        .subject("replaced")
+        .order(order)
+        .text("")
+        .build()).unwrap();
+
+    assert!(build(HeaderOrder::Preserve).starts_with("X-Custom: 1\r\nSubject: replaced\r\nTo: b@example.org\r\n\
+                                                      Date: Thu, 01 Jan 1970 00:00:00 +0000\r\n\
+                                                      Message-ID: <id@example.org>\r\nx-lower: 2\r\nMIME-Version: 1.0\r\n"));
+    assert!(build(HeaderOrder::Canonical).starts_with("Date: Thu, 01 Jan 1970 00:00:00 +0000\r\nTo: b@example.org\r\n\
+                                                       Message-ID: <id@example.org>\r\nSubject: replaced\r\nMIME-Version: 1.0\r\n\
+                                                       Content-Type: text/plain; charset=us-ascii\r\n"));
+}
+
+#[test]
+fn dates() {
+    assert_eq!(format_date(UNIX_EPOCH + Duration::from_secs(4_107_542_399)), "Sun, 28 Feb 2100 23:59:59 +0000");
+    assert_eq!(format_date(UNIX_EPOCH + Duration::from_secs(1_709_164_800)), "Thu, 29 Feb 2024 00:00:00 +0000");
+}
+
+#[test]
+fn reply_subjects() {
+    assert_eq!(reply_subject(""), "Re: ");
+    assert_eq!(reply_subject("  re :Lunch "), "Re: Lunch");
+    assert_eq!(reply_subject("Re[x]: Lunch"), "Re: Re[x]: Lunch");
+    assert_eq!(reply_subject("Regarding: Lunch"), "Re: Regarding: Lunch");
+    assert_eq!(forward_subject("Re: Lunch"), "Fwd: Re: Lunch");
+    assert_eq!(forward_subject("WG: Lunch"), "WG: Lunch");
+}
+
+#[test]
+fn reply_headers() {
+    let raw = MessageBuilder::new()
+        .from(&addresses("Bob <bob@example.org>"))
+        .to(&addresses("alice@example.org, BOB@example.org"))
+        .cc(&addresses("Team: carol@example.org, alice@example.org;"))
+        .subject("Déjeuner")
+        .message_id("3@example.org")
+        .references(&["<1@example.org>", "2@example.org"])
+        .text("")
+        .build();
+    let message = parse_message(&raw);
+
+    let reply = ReplyHeaders::new(&message.headers, false, &[]);
+    assert_eq!(reply.to, addresses("Bob <bob@example.org>"));
+    assert!(reply.cc.is_empty());
+    assert_eq!(reply.subject, "Re: Déjeuner");
+    assert_eq!(reply.references, ["<1@example.org>", "<2@example.org>", "<3@example.org>"]);
+
+    let reply = ReplyHeaders::new(&message.headers, true, &[]);
+    assert_eq!(reply.cc, addresses("alice@example.org, Team: carol@example.org;"));
+
+    let raw = reply.apply(MessageBuilder::new()).message_id("4@example.org").text("").build();
+    let message = parse_message(&raw);
+    assert_eq!(message.header(b"In-Reply-To"), Some(&b" <3@example.org>"[..]));
+    let reply = ReplyHeaders::new(&message.headers, true, &[]);
+    assert_eq!(reply.references.len(), 4);
+    assert_eq!(reply.subject, "Re: Déjeuner");
+}
+
+#[test]
+fn reply_without_references() {
+    let headers = [Ok((&b"In-Reply-To"[..], &b" <1@example.org> (comment)"[..])), Ok((&b"Message-ID"[..], &b" <2@example.org>"[..]))];
+    assert_eq!(ReplyHeaders::new(&headers, false, &[]).references, ["<1@example.org>", "<2@example.org>"]);
+
+    let headers = [Ok((&b"In-Reply-To"[..], &b" <1@example.org> <0@example.org>"[..]))];
+    let reply = ReplyHeaders::new(&headers, false, &[]);
+    assert!(reply.references.is_empty() && reply.in_reply_to.is_empty() && reply.to.is_empty());
+}
+
+#[test]
+fn forward_attached() {
+    let original = b"Subject: caf\xc3\xa9\r\n\r\nbody\r\n";
+    let raw = MessageBuilder::new().subject(&forward_subject("café")).attach("original.eml", "message/rfc822", original).build();
+    let message = parse_message(&raw);
+
+    assert_eq!(message.parts[0].header(b"Content-Transfer-Encoding"), Some(&b" 8bit"[..]));
+    assert_eq!(message.parts[0].body, &original[..]);
+}
+
+#[test]
+fn delivery_report() {
+    let original = b"From: alice@example.org\r\nSubject: Hi\r\n\r\nHello\r\n";
+    let status = DeliveryStatus {
+        envelope_id: Some("QQ314159".into()),
+        reporting_mta: Some(("dns".into(), "mx.example.org".into())),
+        recipients: vec![RecipientReport {
+            original_recipient: Some(("rfc822".into(), "bob@example.org".into())),
+            final_recipient: Some(("rfc822".into(), "bob@example.net".into())),
+            action: Some(Action::Failed),
+            status: Some("5.1.1".into()),
+            ..RecipientReport::default()
+        }],
+    };
+    let raw = MessageBuilder::new().delivery_status(&status).unwrap().attach("ignored.txt", "text/plain", b"x")
+        .returned_content(original, DSNRet::Full).build();
+    let message = parse_message(&raw);
+
+    assert_eq!(message.content_type, "multipart/report");
+    assert_eq!(message.parts.len(), 3);
+    assert_eq!(message.parts[0].content_type, "text/plain");
+
+    let report = delivery_status(message.parts[1].body);
+    assert_eq!(report.envelope_id.as_deref(), Some("QQ314159"));
+    assert_eq!(report.recipients[0].action, Some(Action::Failed));
+    assert_eq!(report.recipients[0].status.as_deref(), Some("5.1.1"));
+
+    assert_eq!(message.parts[2].content_type, "message/rfc822");
+    assert_eq!(message.parts[2].body, &original[..]);
+}
+
+#[test]
+fn disposition_report() {
+    let mdn = DispositionNotification {
+        reporting_ua: Some("mua.example.net; Webmail 2.1".into()),
+        original_recipient: Some(("rfc822".into(), "bob@example.net".into())),
+        final_recipient: ("rfc822".into(), "bob@example.net".into()),
+        original_message_id: Some("<1@example.org>".into()),
+        automatic_action: true,
+        sent_automatically: true,
+        disposition: DispositionType::Deleted,
+    };
+    let raw = MessageBuilder::new().text("Deleted unread.\n").disposition_notification(&mdn).unwrap()
+        .returned_content(b"Message-ID: <1@example.org>\r\n\r\nbody\r\n", DSNRet::Hdrs).build();
+    let message = parse_message(&raw);
+
+    assert_eq!(message.param("report-type"), Some("disposition-notification"));
+    assert_eq!(message.parts[0].decoded_text(), "Deleted unread.\r\n");
+    assert_eq!(message.parts[1].content_type, "message/disposition-notification");
+    assert_eq!(message.parts[1].body, &b"Reporting-UA: mua.example.net; Webmail 2.1\r\n\
+                                          Original-Recipient: rfc822; bob@example.net\r\n\
+                                          Final-Recipient: rfc822; bob@example.net\r\n\
+                                          Original-Message-ID: <1@example.org>\r\n\
+                                          Disposition: automatic-action/MDN-sent-automatically; deleted\r\n"[..]);
+    assert_eq!(message.parts[2].body, &b"Message-ID: <1@example.org>\r\n\r\n"[..]);
+
+    let forged = DispositionNotification { final_recipient: ("rfc822".into(), "bob@example.net\r\nFinal-Recipient: rfc822; evil@x".into()), ..mdn };
+    assert_eq!(MessageBuilder::new().disposition_notification(&forged).err(), Some(DsnError::InvalidValue("Final-Recipient")));
+}
+
+#[test]
+fn global_message() {
+    let original = "From: rené@example.org\r\nSubject: Café\r\n\r\nBonjour\r\n".as_bytes();
+    let status = DeliveryStatus {
+        reporting_mta: Some(("dns".into(), "mx.example.org".into())),
+        recipients: vec![RecipientReport {
+            final_recipient: Some(("utf-8".into(), "rené@example.org".into())),
+            action: Some(Action::Failed),
+            status: Some("5.1.1".into()),
+            ..RecipientReport::default()
+        }],
+        ..DeliveryStatus::default()
+    };
+    let raw = MessageBuilder::global().from(&addresses("postmaster@example.org")).subject("Non livré")
+        .delivery_status(&status).unwrap().returned_content(original, DSNRet::Hdrs).try_build().unwrap();
+    let message = parse_message(&raw);
+
+    assert_eq!(message.parts[1].content_type, "message/global-delivery-status");
+    assert_eq!(delivery_status(message.parts[1].body).recipients[0].final_recipient, status.recipients[0].final_recipient);
+    assert_eq!(message.parts[2].content_type, "message/global-headers");
+    assert_eq!(message.parts[2].body, &original[..original.len() - 9]);
+
+    assert_eq!(message.header(b"Subject"), Some(" Non livré".as_bytes()));
+}
+
+#[test]
+fn utf8_outside_global() {
+    assert_eq!(MessageBuilder::new().subject("Café").header("X-Note", "déjà").try_build(),
+               Err(BuildError::Utf8Header("X-Note".into())));
+    assert_eq!(MessageBuilder::new().to(&addresses("rené@example.org")).try_build(),
+               Err(BuildError::Utf8Header("To".into())));
+    assert!(MessageBuilder::new().subject("Café").try_build().is_ok());
+}
+
+#[test]
+fn generated_message_id() {
+    let raw = MessageBuilder::new().from(&addresses("bob@bücher.example")).try_build().unwrap();
+    assert!(parse_message(&raw).header(b"Message-ID").unwrap().ends_with(b"@xn--bcher-kva.example>"));
+
+    let raw = MessageBuilder::global().from(&addresses("bob@bücher.example")).build();
+    assert!(parse_message(&raw).header(b"Message-ID").unwrap().ends_with("@bücher.example>".as_bytes()));
+}
+
+#[test]
+fn header_injection() {
+    let payload = "x\r\nBcc: victim@example.org\nCc: other@example.org\r";
+    let builders = vec![
+        MessageBuilder::new().subject(payload),
+        MessageBuilder::global().subject(payload),
+        MessageBuilder::new().message_id(payload),
+        MessageBuilder::new().in_reply_to(&[payload]),
+        MessageBuilder::new().references(&["a@example.org", payload]),
+        MessageBuilder::new().header("X-Note", payload),
+        MessageBuilder::new().header(payload, "1"),
+    ];
+
+    for builder in builders {
+        let checked = builder.clone().try_build();
+        let raw = builder.build();
+        let message = parse_message(&raw);
+        assert!(message.headers.iter().all(|f| f.is_ok()), "{}", String::from_utf8_lossy(&raw));
+        assert_eq!(message.header(b"Bcc"), None);
+        assert_eq!(message.header(b"Cc"), None);
+
+        if let Ok(checked) = checked {
+            assert!(parse_message(&checked).header(b"Bcc").is_none());
+        }
+    }
+
+    assert_eq!(MessageBuilder::new().header("X-Note", "a\nb").try_build(), Err(BuildError::InvalidHeader("X-Note".into())));
+    assert_eq!(MessageBuilder::new().header("X-Note", "a\r\nb").try_build(), Err(BuildError::InvalidHeader("X-Note".into())));
+    assert_eq!(MessageBuilder::new().header("X-Note", "a\x00b").try_build(), Err(BuildError::InvalidHeader("X-Note".into())));
+    assert_eq!(MessageBuilder::new().header("X:Y", "1").try_build(), Err(BuildError::InvalidHeader("X:Y".into())));
+    assert!(MessageBuilder::new().header("X-Note", "a\tb\r\n\tc").try_build().is_ok());
+
+    let attached = MessageBuilder::new().text("").attach("a.txt", "text/plain\r\nX-Injected: 1", b"a");
+    assert_eq!(attached.clone().try_build(), Err(BuildError::InvalidHeader("Content-Type".into())));
+    let raw = attached.build();
+    let message = parse_message(&raw);
+    assert_eq!(message.parts.len(), 2);
+    assert!(message.parts.iter().all(|part| part.header(b"X-Injected").is_none()));
+}
+
+#[test]
+fn reply_to_hostile_subject() {
+    let raw = b"From: attacker@example.net\r\nSubject: =?utf-8?q?hi=0D=0ABcc:_victim@example.org?=\r\n\r\n";
+    let message = parse_message(raw);
+
+    let reply = ReplyHeaders::new(&message.headers, false, &[]);
+    assert_eq!(reply.subject, "Re: hi  Bcc: victim@example.org");
+
+    let out = reply.apply(MessageBuilder::new()).text("").try_build().unwrap();
+    assert_eq!(parse_message(&out).header(b"Bcc"), None);
+}
+
+#[test]
+fn reply_all_without_me() {
+    let raw = b"From: bob@example.org\r\nTo: Alice <Alice@Example.org>, carol@example.org\r\n\
+                Cc: Team: alice@example.org, dave@example.org;\r\n\r\n";
+    let message = parse_message(raw);
+    let me = addresses("alice@example.org");
+
+    let reply = ReplyHeaders::new(&message.headers, true, me[0].mailboxes());
+    assert_eq!(reply.to, addresses("bob@example.org"));
+    assert_eq!(reply.cc, addresses("carol@example.org, Team: dave@example.org;"));
+
+    let reply = ReplyHeaders::new(&message.headers, true, addresses("bob@example.org")[0].mailboxes());
+    assert!(reply.to.is_empty());
+}
@@ -0,0 +1,39 @@
+use crate::bulk::*;
+use crate::headersection::header_section;
+
+fn indicators(section: &[u8]) -> BulkIndicators {
+    let (_, headers) = header_section(section).unwrap();
+    bulk_indicators(&headers)
+}
+
+#[test]
+fn plain_message() {
+    let bulk = indicators(b"From: bob@example.org\r\nAuto-Submitted: No (person)\r\n\r\n");
+    assert_eq!(bulk.auto_submitted, Some(AutoSubmitted::No));
+    assert!(!bulk.is_bulk() && !bulk.is_automated());
+    assert_eq!(indicators(b"\r\n"), BulkIndicators::default());
+}
+
+#[test]
+fn automated() {
+    let bulk = indicators(b"auto-submitted: Auto-Replied; owner-email=\"a@example.org\"\r\n\
+                            precedence: First-Class\r\n\r\n");
+    assert_eq!(bulk.auto_submitted, Some(AutoSubmitted::AutoReplied));
+    assert_eq!(bulk.precedence, Some(Precedence::Other("first-class".into())));
+    assert!(bulk.is_automated() && !bulk.is_bulk());
+}
+
+#[test]
+fn campaigns() {
+    let bulk = indicators(b"Feedback-ID: 111:222:333:sender\r\nX-Campaign-Id: =?utf-8?q?No=C3=ABl?=\r\n\
+                            List-Unsubscribe: <http://example.org/u>\r\n\
+                            List-Unsubscribe-Post: List-Unsubscribe=One-Click\r\n\r\n");
+    assert_eq!(bulk.feedback_id.as_deref(), Some("111:222:333:sender"));
+    assert_eq!(bulk.campaign.as_deref(), Some("Noël"));
+    assert!(!bulk.one_click_unsubscribe);
+    assert!(bulk.is_bulk());
+
+    let bulk = indicators(b"List-Unsubscribe: <HTTPS://example.org/u>\r\nX-Campaign:  \r\n\r\n");
+    assert!(!bulk.one_click_unsubscribe);
+    assert_eq!(bulk.campaign, None);
+}
@@ -0,0 +1,66 @@
+use bytes::BytesMut;
+use tokio_util::codec::Decoder;
+
+use crate::behaviour::{Intl, Legacy};
+use crate::codec::*;
+use crate::reply::Reply;
+
+const REPLIES: &[u8] = b"220 mx.example.org ESMTP\r\n250-mx.example.org\r\n250-SIZE 1000\r\n250 8BITMIME\r\n550 5.1.1 No such user\r\n";
+
+#[test]
+fn byte_by_byte() {
+    let mut codec = SmtpReplyCodec::<Legacy>::new();
+    let mut buf = BytesMut::new();
+    let mut out = Vec::new();
+
+    for c in REPLIES {
+        buf.extend_from_slice(&[*c]);
+        if let Some(reply) = codec.decode(&mut buf).unwrap() {
+            out.push(reply);
+        }
+    }
+
+    assert!(!codec.is_partial());
+    assert!(codec.decode_eof(&mut buf).unwrap().is_none());
+    assert_eq!(out, [
+        Reply::new(220, vec!["mx.example.org ESMTP"]),
+        Reply::new(250, vec!["mx.example.org", "SIZE 1000", "8BITMIME"]),
+        Reply::new(550, vec!["5.1.1 No such user"]),
+    ]);
+}
+
+#[test]
+fn limits() {
+    let mut codec = SmtpReplyCodec::<Legacy>::with_limits(16, 2);
+
+    let mut buf = BytesMut::from(&b"250-0123456789\r\n"[..]);
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+    buf.extend_from_slice(b"250-0123456789012");
+    assert!(matches!(codec.decode(&mut buf), Err(ReplyCodecError::LineTooLong)));
+
+    let mut codec = SmtpReplyCodec::<Legacy>::with_limits(16, 2);
+    let mut buf = BytesMut::from(&b"250-a\r\n250-b\r\n250 c\r\n"[..]);
+    assert!(matches!(codec.decode(&mut buf), Err(ReplyCodecError::TooManyLines)));
+
+    let mut codec = SmtpReplyCodec::<Legacy>::new();
+    let mut buf = BytesMut::from(&b"250 ok\r\n250-more"[..]);
+    assert!(codec.decode_eof(&mut buf).unwrap().is_some());
+    assert!(codec.is_partial());
+    assert!(matches!(codec.decode_eof(&mut buf), Err(ReplyCodecError::Truncated)));
+}
+
+#[test]
+fn syntax() {
+    let input = "250-mx.example.org\r\n250 Hé\r\n".as_bytes();
+
+    let mut buf = BytesMut::from(input);
+    assert!(matches!(SmtpReplyCodec::<Legacy>::new().decode(&mut buf), Err(ReplyCodecError::Syntax)));
+
+    let mut buf = BytesMut::from(input);
+    assert_eq!(SmtpReplyCodec::<Intl>::new().decode(&mut buf).unwrap().unwrap().lines, ["mx.example.org", "Hé"]);
+
+    for bad in [&b"250-a\r\n251 b\r\n"[..], b"hello\r\n", b"250 ok\n"] {
+        let mut buf = BytesMut::from(bad);
+        assert!(matches!(SmtpReplyCodec::<Legacy>::new().decode(&mut buf), Err(ReplyCodecError::Syntax)));
+    }
+}
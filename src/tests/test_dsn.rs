@@ -0,0 +1,132 @@
+use crate::dsn::*;
+
+fn recipient(path: &str, orcpt: Option<(&str, &str)>) -> TransactionRecipient {
+    TransactionRecipient {
+        path: path.parse().unwrap(),
+        orcpt: orcpt.map(|(t, a)| (t.into(), a.into())),
+    }
+}
+
+fn report(original: Option<(&str, &str)>, last: Option<(&str, &str)>) -> RecipientReport {
+    RecipientReport {
+        original_recipient: original.map(|(t, a)| (t.into(), a.into())),
+        final_recipient: last.map(|(t, a)| (t.into(), a.into())),
+        ..RecipientReport::default()
+    }
+}
+
+#[test]
+fn parse_report() {
+    let status = delivery_status(b"Original-Envelope-Id: QQ314159\r\n\
+                                   Reporting-MTA: dns; mx.example.net\r\n\
+                                   Arrival-Date: Thu, 15 Oct 2026 10:00:00 +0000\r\n\
+                                   \r\n\
+                                   Final-Recipient: RFC822; bob@example.net\r\n\
+                                   Action: Failed\r\n\
+                                   Status: 5.1.1 (unknown user)\r\n\
+                                   Diagnostic-Code: smtp; 550 5.1.1 <bob@example.net>:\r\n \
+                                   Recipient address rejected\r\n\
+                                   \r\n\
+                                   \r\n\
+                                   Final-Recipient: rfc822; carol@example.net\r\n\
+                                   Action: delayed\r\n\
+                                   Status: 4.4.1\r\n");
+
+    assert_eq!(status.envelope_id.as_deref(), Some("QQ314159"));
+    assert_eq!(status.reporting_mta, Some(("dns".into(), "mx.example.net".into())));
+    assert_eq!(status.recipients.len(), 2);
+
+    let bob = &status.recipients[0];
+    assert_eq!(bob.final_recipient, Some(("rfc822".into(), "bob@example.net".into())));
+    assert_eq!(bob.original_recipient, None);
+    assert_eq!(bob.action, Some(Action::Failed));
+    assert_eq!(bob.status.as_deref(), Some("5.1.1"));
+    assert_eq!(bob.diagnostic_code, Some(("smtp".into(), "550 5.1.1 <bob@example.net>: Recipient address rejected".into())));
+
+    assert_eq!(status.recipients[1].action, Some(Action::Delayed));
+}
+
+#[test]
+fn parse_lenient() {
+    let status = delivery_status(b"Final-Recipient: rfc822;bob@example.net\nAction: bounced\nStatus: 5.0.0");
+    assert_eq!(status.reporting_mta, None);
+    assert_eq!(status.recipients, [RecipientReport {
+        final_recipient: Some(("rfc822".into(), "bob@example.net".into())),
+        status: Some("5.0.0".into()),
+        ..RecipientReport::default()
+    }]);
+
+    assert_eq!(delivery_status(b""), DeliveryStatus::default());
+}
+
+#[test]
+fn unitext() {
+    assert_eq!(unitext_decode("plain@example.org"), "plain@example.org");
+    assert_eq!(unitext_decode(r"\x{5F20}\x{4E09}@example.org"), "张三@example.org");
+    assert_eq!(unitext_decode(r"a\x{110000}@b"), r"a\x{110000}@b");
+    assert_eq!(unitext_decode(r"a\x{41"), r"a\x{41");
+}
+
+#[test]
+fn reconcile_original_recipient() {
+    let recipients = [
+        recipient("<fwd-1@example.net>", Some(("rfc822", "bob+news@example.org"))),
+        recipient("<fwd-2@example.net>", Some(("utf-8", r"ren\x{E9}@example.org"))),
+        recipient("<fwd-3@example.net>", Some(("rfc822", "carol@example.org"))),
+    ];
+    let reports = [
+        // Copied from the ORCPT parameter without decoding it.
+        report(Some(("rfc822", "bob+2Bnews@example.org")), Some(("rfc822", "x@example.net"))),
+        report(Some(("utf-8", "René@example.org")), None),
+        report(Some(("utf-8", r"CAROL@\x{65}xample.org")), None),
+        report(Some(("rfc822", "<carol@EXAMPLE.org>")), None),
+        report(Some(("rfc822", "dave@example.org")), None),
+    ];
+
+    assert_eq!(reconcile(&reports, &recipients), [Some(0), Some(1), Some(2), Some(2), None]);
+}
+
+#[test]
+fn reconcile_final_recipient() {
+    let recipients = [
+        recipient("<postmaster>", None),
+        recipient("<\"Bob\"@Example.net>", None),
+        recipient("<list@example.net>", Some(("rfc822", "erin@example.org"))),
+    ];
+    let reports = [
+        report(None, Some(("rfc822", "bob@example.net"))),
+        report(None, Some(("rfc822", "Postmaster"))),
+        report(Some(("rfc822", "unknown@example.org")), Some(("rfc822", "erin@example.org"))),
+        report(None, None),
+    ];
+
+    assert_eq!(reconcile(&reports, &recipients), [Some(1), Some(0), Some(2), None]);
+}
+
+#[test]
+fn serialize_errors() {
+    let status = DeliveryStatus {
+        envelope_id: None,
+        reporting_mta: Some(("dns".into(), "mx.example.org".into())),
+        recipients: vec![RecipientReport {
+            final_recipient: Some(("rfc822".into(), "bob@example.org".into())),
+            action: Some(Action::Failed),
+            status: Some("5.1.1".into()),
+            ..RecipientReport::default()
+        }],
+    };
+    assert!(status.to_bytes().is_ok());
+
+    let with = |f: fn(&mut DeliveryStatus)| {
+        let mut status = status.clone();
+        f(&mut status);
+        status.to_bytes()
+    };
+    assert_eq!(with(|s| s.recipients.clear()), Err(DsnError::NoRecipients));
+    assert_eq!(with(|s| s.recipients[0].final_recipient = None), Err(DsnError::MissingField("Final-Recipient")));
+    assert_eq!(with(|s| s.recipients[0].action = None), Err(DsnError::MissingField("Action")));
+    assert_eq!(with(|s| s.recipients[0].status = None), Err(DsnError::MissingField("Status")));
+    assert_eq!(with(|s| s.envelope_id = Some("id\r\nX-Injected: 1".into())), Err(DsnError::InvalidValue("Original-Envelope-Id")));
+    assert_eq!(with(|s| s.recipients[0].diagnostic_code = Some(("smtp".into(), "550\r\n\r\nbody".into()))),
+               Err(DsnError::InvalidValue("Diagnostic-Code")));
+}
@@ -0,0 +1,255 @@
+use std::convert::TryFrom;
+
+use crate::behaviour::Intl;
+use crate::esmtp::*;
+use crate::options::DuplicatePolicy;
+use crate::reply::Reply;
+use crate::rfc3461::{DSNRet, Notify};
+use crate::rfc5321::{mail_command, rcpt_command, ForwardPath, Param, ReversePath};
+
+fn mail(input: &[u8], policy: DuplicatePolicy) -> Result<EsmtpParams, ParamError> {
+    EsmtpParams::new(mail_command::<Intl>(input).unwrap().1.1, policy)
+}
+
+fn parse_mail(input: &[u8]) -> Result<MailParams, ParamError> {
+    crate::esmtp::mail_params(mail_command::<Intl>(input).unwrap().1.1)
+}
+
+fn parse_rcpt(input: &[u8]) -> Result<RcptParams, ParamError> {
+    crate::esmtp::rcpt_params(rcpt_command::<Intl>(input).unwrap().1.1)
+}
+
+// Check that each of `params` is rejected, appended to `command`.
+fn assert_invalid<T: std::fmt::Debug>(parse: fn(&[u8]) -> Result<T, ParamError>, command: &str, params: &[&str]) {
+    for param in params {
+        let input = format!("{} {}\r\n", command, param);
+        assert!(matches!(parse(input.as_bytes()), Err(ParamError::Invalid(_))), "{}", param);
+    }
+}
+
+#[test]
+fn duplicates() {
+    let input = b"MAIL FROM:<> SIZE=10 size=20\r\n";
+
+    assert_eq!(mail(input, DuplicatePolicy::Reject).unwrap_err().to_string(), "duplicate parameter size");
+    assert_eq!(mail(input, DuplicatePolicy::KeepFirst).unwrap().value("SIZE"), Some(Some("10")));
+    assert_eq!(mail(input, DuplicatePolicy::KeepLast).unwrap().value("Size"), Some(Some("20")));
+    assert_eq!(mail(input, DuplicatePolicy::KeepLast).unwrap().iter().count(), 1);
+
+    let (_, (_, params)) = rcpt_command::<Intl>(b"RCPT TO:<bob@example.org> NOTIFY=NEVER Notify=SUCCESS\r\n").unwrap();
+    assert_eq!(EsmtpParams::try_from(params).unwrap_err(), ParamError::Duplicate("Notify".parse().unwrap()));
+
+    let map = mail(b"MAIL FROM:<> SIZE=10 Size=20 BODY=7BIT\r\n", DuplicatePolicy::KeepFirst).unwrap().into_map();
+    assert_eq!(map.len(), 2);
+    assert_eq!(map[&"size".parse().unwrap()].as_deref(), Some("10"));
+}
+
+#[test]
+fn lookup() {
+    let params = mail(b"MAIL FROM:<> SMTPUTF8 X-A=b\r\n", DuplicatePolicy::Reject).unwrap();
+
+    assert_eq!(params.value("smtputf8"), Some(None));
+    assert_eq!(params.get("x-a"), Some(&Param::new("X-A", Some("b")).unwrap()));
+    assert!(!params.contains("BODY"));
+    assert_eq!(params.value("BODY"), None);
+}
+
+#[test]
+fn mail_params() {
+    let params = mail(b"MAIL FROM:<> BODY=binarymime SMTPUTF8 RET=HDRS ENVID=abc+2Bd X-A\r\n", DuplicatePolicy::Reject).unwrap();
+    let mail = params.mail_params().unwrap();
+
+    assert_eq!(mail.body, Some(BodyType::BinaryMime));
+    assert_eq!(mail.size, None);
+    assert!(mail.smtputf8);
+    assert_eq!(mail.dsn.ret, Some(DSNRet::Hdrs));
+    assert_eq!(mail.dsn.envid.as_deref(), Some("abc+d"));
+    assert_eq!(mail.other, [Param::new("X-A", None).unwrap()]);
+}
+
+#[test]
+fn mail_params_invalid() {
+    assert_invalid(parse_mail, "MAIL FROM:<>", &["BODY=9BIT", "SIZE=-1", "SIZE", "SMTPUTF8=yes", "RET=ALL"]);
+}
+
+#[test]
+fn rcpt_params() {
+    let (_, (_, params)) = rcpt_command::<Intl>(b"RCPT TO:<bob@example.org> NOTIFY=SUCCESS,DELAY ORCPT=rfc822;bob+2B1@example.org\r\n").unwrap();
+    let rcpt = EsmtpParams::new(params, DuplicatePolicy::Reject).unwrap().rcpt_params().unwrap();
+
+    assert_eq!(rcpt.notify, Some(Notify { on_success: true, on_failure: false, delay: true }));
+    assert_eq!(rcpt.orcpt, Some(("rfc822".into(), "bob+1@example.org".into())));
+    assert!(rcpt.other.is_empty());
+
+    let (_, (_, params)) = rcpt_command::<Intl>(b"RCPT TO:<bob@example.org> NOTIFY=SOMETIMES\r\n").unwrap();
+    assert!(EsmtpParams::new(params, DuplicatePolicy::Reject).unwrap().rcpt_params().is_err());
+}
+
+#[test]
+fn mail_params_auth_requiretls() {
+    let params = parse_mail(b"MAIL FROM:<> AUTH=<> REQUIRETLS SIZE=0\r\n").unwrap();
+    assert_eq!(params.auth, Some(AuthParam::Unknown));
+    assert!(params.requiretls);
+    assert_eq!(params.size, Some(0));
+    assert!(params.other.is_empty());
+
+    let params = parse_mail(b"MAIL FROM:<> auth=alice+40example.org\r\n").unwrap();
+    assert_eq!(params.auth, Some(AuthParam::Mailbox("alice@example.org".parse().unwrap())));
+    assert!(!params.requiretls);
+    assert!(params.check_requiretls(false).is_ok());
+
+    assert!(parse_mail(b"MAIL FROM:<> AUTH\r\n").is_err());
+    assert!(parse_mail(b"MAIL FROM:<> REQUIRETLS=YES\r\n").is_err());
+    assert!(matches!(parse_mail(b"MAIL FROM:<> REQUIRETLS requiretls\r\n"), Err(ParamError::Duplicate(_))));
+}
+
+#[test]
+fn rcpt_params_rrvs() {
+    let rrvs = parse_rcpt(b"RCPT TO:<bob@example.org> RRVS=2014-04-03T23:01:00.5-05:00\r\n").unwrap().rrvs.unwrap();
+    assert_eq!(rrvs, Rrvs { time: "2014-04-03T23:01:00.5-05:00".into(), action: None });
+    assert_eq!(parse_rcpt(b"RCPT TO:<bob@example.org> rrvs=2014-04-03T23:01:00Z;r\r\n").unwrap().rrvs.unwrap().action,
+               Some(RrvsAction::Reject));
+
+    assert_invalid(parse_rcpt, "RCPT TO:<bob@example.org>",
+                   &["RRVS", "RRVS=2014-04-03", "RRVS=2014-04-03T23:01:00Z;X", "ORCPT=rfc822"]);
+}
+
+#[test]
+fn size() {
+    assert_eq!(parse_mail(b"MAIL FROM:<> SIZE=18446744073709551615\r\n").unwrap().size, Some(u64::MAX));
+    assert!(parse_mail(b"MAIL FROM:<> SIZE=18446744073709551616\r\n").is_err());
+    assert!(parse_mail(b"MAIL FROM:<> SIZE=1e6\r\n").is_err());
+    assert!(!parse_mail(b"MAIL FROM:<>\r\n").unwrap().exceeds_size_limit(1));
+
+    assert_eq!(size_capability(b"SIZE 1").unwrap(), (&b""[..], Some(1)));
+    assert!(size_capability(b"SIZE x").map_or(true, |(rem, _)| !rem.is_empty()));
+    assert!(size_capability(b"SIZE 99999999999999999999").map_or(true, |(rem, _)| !rem.is_empty()));
+    assert!(size_capability(b"PIPELINING").is_err());
+}
+
+#[test]
+fn body_type() {
+    assert_eq!("8bitmime".parse::<BodyType>(), Ok(BodyType::EightBitMime));
+    assert!("8BIT".parse::<BodyType>().is_err());
+    assert_eq!(BodyType::SevenBit.to_string(), "7BIT");
+
+    for (input, data_ok) in &[(&b"MAIL FROM:<>\r\n"[..], true), (b"MAIL FROM:<> BODY=7BIT\r\n", true),
+                              (b"MAIL FROM:<> BODY=8BITMIME\r\n", true), (b"MAIL FROM:<> BODY=BINARYMIME\r\n", false)] {
+        let params = parse_mail(input).unwrap();
+        assert_eq!(params.check_body_type(false).is_ok(), *data_ok);
+        assert!(params.check_body_type(true).is_ok());
+    }
+}
+
+#[test]
+fn auth() {
+    assert_eq!(auth_mailbox(b"\"a+20b\"@example.org").unwrap().1.unwrap().to_string(), "\"a b\"@example.org");
+    assert!(auth_mailbox(b"").is_err());
+    assert!(auth_mailbox(b"bob").is_err());
+    assert!(auth_mailbox(b"bob+40").is_err());
+    assert_eq!(auth_mailbox(b"<>x").unwrap().0, b"x");
+}
+
+#[test]
+fn by() {
+    assert_eq!(parse_mail(b"MAIL FROM:<> BY=+999999999;nt\r\n").unwrap().by,
+               Some(DeliverBy { deadline: 999_999_999, mode: ByMode::Notify, trace: true }));
+    assert_eq!(parse_mail(b"MAIL FROM:<>\r\n").unwrap().by, None);
+
+    assert_eq!(deliver_by(b"0;N").unwrap().1.deadline, 0);
+    assert!(deliver_by(b"-5;R").is_err());
+
+    assert_invalid(parse_mail, "MAIL FROM:<>",
+                   &["BY=1000000000;N", "BY=10", "BY=10;", "BY=10;X", "BY=10;NTT", "BY=;N", "BY=1;R;T", "BY=BY", "BY"]);
+}
+
+#[test]
+fn hold() {
+    assert_eq!(parse_mail(b"MAIL FROM:<> HOLDFOR=999999999\r\n").unwrap().hold, Some(Hold::For(999_999_999)));
+    assert_eq!(parse_mail(b"MAIL FROM:<> HOLDUNTIL=2021-06-01T10:00:60.25+14:00\r\n").unwrap().hold,
+               Some(Hold::Until("2021-06-01T10:00:60.25+14:00".into())));
+
+    assert_invalid(parse_mail, "MAIL FROM:<>",
+                   &["HOLDFOR=1000000000", "HOLDFOR=-1", "HOLDFOR", "HOLDUNTIL=2021-13-01T10:00:00Z",
+                     "HOLDUNTIL=2021-06-00T10:00:00Z", "HOLDUNTIL=2021-06-01T24:00:00Z", "HOLDUNTIL=21-06-01T10:00:00Z",
+                     "HOLDUNTIL=2021-6-01T10:00:00Z", "HOLDFOR=1 HOLDUNTIL=2021-06-01T10:00:00Z"]);
+}
+
+#[test]
+fn priority() {
+    assert_eq!(parse_mail(b"MAIL FROM:<> MT-PRIORITY=-9\r\n").unwrap().priority, Some(-9));
+    assert_eq!(parse_mail(b"MAIL FROM:<> mt-priority=+9\r\n").unwrap().priority, Some(9));
+    assert_eq!(parse_mail(b"MAIL FROM:<> MT-PRIORITY=0\r\n").unwrap().priority, Some(0));
+    assert_eq!(parse_mail(b"MAIL FROM:<>\r\n").unwrap().priority, None);
+
+    assert_invalid(parse_mail, "MAIL FROM:<>", &["MT-PRIORITY=10", "MT-PRIORITY=-10", "MT-PRIORITY", "MT-PRIORITY=a", "MT-PRIORITY=--1"]);
+}
+
+#[test]
+fn mail_builder() {
+    let mailbox: crate::types::Mailbox = "bob+x@example.org".parse().unwrap();
+    let cmd = MailCommand::builder(ReversePath::Null)
+        .size(1)
+        .auth(Some(&mailbox))
+        .smtputf8()
+        .requiretls()
+        .by(DeliverBy { deadline: 60, mode: ByMode::Return, trace: true })
+        .hold(Hold::For(10))
+        .hold(Hold::Until("2021-06-01T10:00:00Z".into()))
+        .priority(-3)
+        .size(2)
+        .param(Param::new("X-TAG", Some("1")).unwrap())
+        .build().unwrap();
+    let wire = cmd.to_wire();
+
+    assert_eq!(wire, &b"MAIL FROM:<> AUTH=bob+2Bx@example.org SMTPUTF8 REQUIRETLS BY=60;RT \
+                        HOLDUNTIL=2021-06-01T10:00:00Z MT-PRIORITY=-3 SIZE=2 X-TAG=1\r\n"[..]);
+    let (_, (path, params)) = mail_command::<Intl>(&wire).unwrap();
+    assert_eq!(path, ReversePath::Null);
+    assert_eq!(crate::esmtp::mail_params(params).unwrap().auth, Some(AuthParam::Mailbox(mailbox)));
+
+    assert!(MailCommand::builder(ReversePath::Null).priority(10).build().is_err());
+    assert!(MailCommand::builder(ReversePath::Null).envid(&"x".repeat(101)).build().is_err());
+    assert!(MailCommand::builder(ReversePath::Null).hold(Hold::Until("tomorrow".into())).build().is_err());
+    assert!(MailCommand::builder(ReversePath::Null).hold(Hold::Until("next week".into())).build().is_err());
+    assert_eq!(MailCommand::builder(ReversePath::Null).auth(None).build().unwrap().to_wire(), b"MAIL FROM:<> AUTH=<>\r\n");
+}
+
+#[test]
+fn rcpt_builder() {
+    let cmd = RcptCommand::builder(ForwardPath::postmaster())
+        .notify(Notify { on_success: false, on_failure: false, delay: false })
+        .orcpt("a b=c@example.org")
+        .rrvs(Rrvs { time: "2014-04-03T23:01:00Z".into(), action: Some(RrvsAction::Continue) })
+        .build().unwrap();
+    let wire = cmd.to_wire();
+
+    assert_eq!(wire, &b"RCPT TO:<postmaster> NOTIFY=NEVER ORCPT=rfc822;a+20b+3Dc@example.org RRVS=2014-04-03T23:01:00Z;C\r\n"[..]);
+    let params = crate::esmtp::rcpt_params(rcpt_command::<Intl>(&wire).unwrap().1.1).unwrap();
+    assert_eq!(params.orcpt, Some(("rfc822".into(), "a b=c@example.org".into())));
+
+    assert!(RcptCommand::builder(ForwardPath::postmaster()).rrvs(Rrvs { time: "now".into(), action: None }).build().is_err());
+    assert!(RcptCommand::builder(ForwardPath::postmaster()).rrvs(Rrvs { time: "a=b\r\n".into(), action: None }).build().is_err());
+}
+
+#[test]
+fn capabilities() {
+    let ehlo = Reply::new(250, vec!["mx.example.org", "size", "AUTH=LOGIN PLAIN", "AUTH PLAIN LOGIN CRAM-MD5", "8BITMIME",
+                                    "SMTPUTF8", "dsn", "STARTTLS", "CHUNKING", "not a keyword!", "-BAD"]);
+    let caps = Capabilities::from_reply(&ehlo).unwrap();
+
+    assert_eq!(caps.size, Some(0));
+    assert_eq!(caps.auth, ["LOGIN", "PLAIN", "CRAM-MD5"]);
+    assert!(caps.eightbitmime && caps.smtputf8 && caps.dsn && caps.starttls && !caps.pipelining);
+    assert_eq!(caps.other, [("CHUNKING".to_string(), vec![]), ("not".to_string(), vec!["a".to_string(), "keyword!".to_string()])]);
+    for keyword in ["SIZE", "auth", "chunking", "DSN"] {
+        assert!(caps.has(keyword), "{}", keyword);
+    }
+    assert!(!caps.has("PIPELINING") && !caps.has("BINARYMIME"));
+
+    assert_eq!(Capabilities::from_reply(&Reply::new(250, vec!["mx.example.org"])).unwrap().other, []);
+    assert_eq!(Capabilities::from_reply(&Reply::new(250, vec!["mx.example.org", "AUTHé"])).unwrap().other, []);
+    let caps = Capabilities::from_reply(&Reply::new(250, vec!["mx.example.org", "SIZE 10MB"])).unwrap();
+    assert_eq!((caps.size, caps.malformed), (None, vec!["SIZE 10MB".to_string()]));
+    assert_eq!(Capabilities::from_reply(&Reply::new(502, vec!["5.5.1 Use HELO"])), None);
+}
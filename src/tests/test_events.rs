@@ -0,0 +1,200 @@
+use crate::events::*;
+
+const MESSAGE: &[u8] = b"From: bob@example.org\r\n\
+Content-Type: multipart/mixed;\r\n boundary=\"outer\"\r\n\
+\r\n\
+preamble\r\n\
+--outer\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+first\r\n\
+line\r\n\
+--outer\r\n\
+Content-Type: multipart/alternative; boundary=inner\r\n\
+\r\n\
+--inner\r\n\
+\r\n\
+nested\r\n\
+--outer--\r\n\
+epilogue\r\n";
+
+fn parse_in_chunks(input: &[u8], size: usize) -> Vec<Event> {
+    let mut parser = EventParser::new();
+    let mut out = Vec::new();
+
+    for chunk in input.chunks(size) {
+        parser.feed(chunk);
+        out.extend(std::iter::from_fn(|| parser.next_event()));
+    }
+    parser.finish();
+    out.extend(parser);
+
+    // Merge adjacent body chunks, their splitting depends on the feed size.
+    let mut merged: Vec<Event> = Vec::new();
+    for event in out {
+        match (merged.last_mut(), event) {
+            (Some(Event::BodyChunk(prev)), Event::BodyChunk(next)) => prev.extend(next),
+            (_, event) => merged.push(event),
+        }
+    }
+    merged
+}
+
+fn field(name: &[u8], value: &[u8]) -> Event {
+    Event::HeaderField(Ok((name.to_vec(), value.to_vec())))
+}
+
+#[test]
+fn nested_multipart() {
+    let expected = vec![
+        Event::PartBegin { depth: 0 },
+        Event::HeaderStart,
+        field(b"From", b" bob@example.org"),
+        field(b"Content-Type", b" multipart/mixed;\r\n boundary=\"outer\""),
+        Event::HeaderEnd,
+        Event::BodyChunk(b"preamble".to_vec()),
+        Event::PartBegin { depth: 1 },
+        Event::HeaderStart,
+        field(b"Content-Type", b" text/plain"),
+        Event::HeaderEnd,
+        Event::BodyChunk(b"first\r\nline".to_vec()),
+        Event::PartEnd,
+        Event::PartBegin { depth: 1 },
+        Event::HeaderStart,
+        field(b"Content-Type", b" multipart/alternative; boundary=inner"),
+        Event::HeaderEnd,
+        Event::PartBegin { depth: 2 },
+        Event::HeaderStart,
+        Event::HeaderEnd,
+        Event::BodyChunk(b"nested".to_vec()),
+        Event::PartEnd,
+        Event::PartEnd,
+        Event::BodyChunk(b"epilogue\r\n".to_vec()),
+        Event::PartEnd,
+    ];
+
+    for size in &[1, 2, 7, 64, MESSAGE.len()] {
+        assert_eq!(parse_in_chunks(MESSAGE, *size), expected, "feed size {}", size);
+    }
+}
+
+#[test]
+fn simple_message() {
+    let events = parse_in_chunks(b"Subject: hi\r\nbad header\r\n\r\nbody\r\n--notaboundary\r\n", 5);
+    assert_eq!(events, [
+        Event::PartBegin { depth: 0 },
+        Event::HeaderStart,
+        field(b"Subject", b" hi"),
+        Event::HeaderField(Err(b"bad header".to_vec())),
+        Event::HeaderEnd,
+        Event::BodyChunk(b"body\r\n--notaboundary\r\n".to_vec()),
+        Event::PartEnd,
+    ]);
+}
+
+#[test]
+fn truncated_headers() {
+    let events = parse_in_chunks(b"Subject: hi\r\nX-Last: value", 4);
+    assert_eq!(events, [
+        Event::PartBegin { depth: 0 },
+        Event::HeaderStart,
+        field(b"Subject", b" hi"),
+        field(b"X-Last", b" value"),
+        Event::HeaderEnd,
+        Event::PartEnd,
+    ]);
+}
+
+#[test]
+fn long_lines_are_streamed() {
+    let mut input = b"\r\n".to_vec();
+    input.extend(vec![b'x'; 10000]);
+
+    let mut parser = EventParser::new();
+    parser.feed(&input);
+    let chunk = parser.find_map(|e| match e { Event::BodyChunk(c) => Some(c), _ => None });
+    assert_eq!(chunk.map(|c| c.len()), Some(10000));
+}
+
+#[derive(Default)]
+struct Collect {
+    header: Vec<u8>,
+    body: Vec<u8>,
+}
+
+impl Sink for Collect {
+    fn write(&mut self, section: Section, data: &[u8]) {
+        match section {
+            Section::Header => self.header.extend_from_slice(data),
+            Section::Body => self.body.extend_from_slice(data),
+        }
+    }
+}
+
+#[test]
+fn sink() {
+    let split = MESSAGE.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let long = [&b"Subject: long\r\n\r\n"[..], &[b'x'; 10000], b"\r\ntail"].concat();
+
+    for size in [1, 3, 7, 64, MESSAGE.len()] {
+        let mut collect = Collect::default();
+        let mut parser = EventParser::with_sink(&mut collect);
+        for chunk in MESSAGE.chunks(size) {
+            parser.feed(chunk);
+        }
+        parser.finish();
+        assert!(parser.any(|e| e == Event::PartBegin { depth: 2 }));
+        assert_eq!(collect.header, &MESSAGE[..split]);
+        assert_eq!(collect.body, &MESSAGE[split..]);
+
+        let mut parser = EventParser::with_sink(Collect::default());
+        for chunk in long.chunks(size) {
+            parser.feed(chunk);
+        }
+        parser.finish();
+        let collect = parser.into_sink();
+        assert_eq!(collect.header, b"Subject: long\r\n\r\n");
+        assert_eq!(collect.body, &long[17..]);
+    }
+
+    let mut parser = EventParser::with_sink(Collect::default());
+    parser.feed(b"Subject: no body\r\nX-Other: 1");
+    parser.finish();
+    assert_eq!(parser.sink().header, b"Subject: no body\r\nX-Other: 1");
+    assert!(parser.sink().body.is_empty());
+}
+
+#[test]
+fn oversized_field() {
+    let mut input = b"Subject: ".to_vec();
+    input.extend(vec![b'x'; MAX_FIELD_LEN]);
+    input.extend_from_slice(b"\r\n folded\r\nX-Next: 1\r\n\r\nbody");
+
+    for size in [100, MAX_FIELD_LEN, input.len()] {
+        let mut parser = EventParser::new();
+        for chunk in input.chunks(size) {
+            parser.feed(chunk);
+        }
+        parser.finish();
+        let fields: Vec<_> = parser.filter_map(|e| match e { Event::HeaderField(f) => Some(f), _ => None }).collect();
+
+        assert_eq!(fields.len(), 2, "{}", size);
+        assert_eq!(fields[0].as_ref().unwrap_err().len(), MAX_FIELD_LEN);
+        assert_eq!(fields[1], Ok((b"X-Next".to_vec(), b" 1".to_vec())));
+    }
+}
+
+#[test]
+fn after_finish() {
+    let mut parser = EventParser::new();
+    parser.feed(b"Subject: hi\r\n\r\nbody");
+    assert_eq!(parser.by_ref().count(), 4);
+    parser.finish();
+    parser.feed(b"more\r\n--b\r\n\r\n");
+    parser.finish();
+
+    assert_eq!(parser.next(), None);
+    assert_eq!(parser.next_event(), Some(Event::BodyChunk(b"body".to_vec())));
+    assert_eq!(parser.next_event(), Some(Event::PartEnd));
+    assert_eq!(parser.next_event(), None);
+}
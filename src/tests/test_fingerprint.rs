@@ -0,0 +1,34 @@
+use crate::fingerprint::*;
+use crate::headersection::header_section;
+
+#[test]
+fn relaxed() {
+    assert_eq!(relaxed_header(b"X-Test ", b"\r\n a\tb  \r\n"), b"x-test:a b\r\n");
+    assert_eq!(relaxed_header(b"Empty", b""), b"empty:\r\n");
+}
+
+#[test]
+fn header_hash_selection() {
+    let (_, a) = header_section(b"To: a@example.org\r\nTo: b@example.org\r\nX-Spam: yes\r\n\r\n").unwrap();
+    let (_, b) = header_section(b"To: a@example.org\r\nTo: b@example.org\r\nX-Spam: no\r\n\r\n").unwrap();
+    let (_, c) = header_section(b"To: b@example.org\r\nTo: a@example.org\r\n\r\n").unwrap();
+
+    assert_eq!(header_hash(&a, &["to"]), header_hash(&b, &["TO"]));
+    assert_ne!(header_hash(&a, &["To", "X-Spam"]), header_hash(&b, &["To", "X-Spam"]));
+    assert_ne!(header_hash(&a, &["To"]), header_hash(&c, &["To"]));
+    assert_eq!(header_hash(&a, &["Missing"]), header_hash(&c, &[]));
+}
+
+#[test]
+fn message_fingerprint_fields() {
+    let fp = |input: &[u8]| message_fingerprint(&header_section(input).unwrap().1);
+
+    let base = fp(b"From: a@example.org\r\nSubject: Hello\r\nMessage-ID: <1@x>\r\n\r\n");
+    assert_eq!(base, fp(b"Message-ID: <1@x>\r\nSubject: Hello\r\nFrom: \"A\" <A@EXAMPLE.ORG>\r\nDate: today\r\n\r\n"));
+    assert_eq!(base, fp(b"From: \"a\"@example.org\r\nSubject: Hello\r\nMessage-ID: <1@x>\r\n\r\n"));
+    assert_ne!(base, fp(b"From: a@example.org\r\nSubject: Hello\r\nMessage-ID: <2@x>\r\n\r\n"));
+    assert_ne!(base, fp(b"From: b@example.org\r\nSubject: Hello\r\nMessage-ID: <1@x>\r\n\r\n"));
+    assert_ne!(base, fp(b"From: a@example.org\r\nSubject: Hello!\r\nMessage-ID: <1@x>\r\n\r\n"));
+    // Fields do not bleed into each other.
+    assert_ne!(fp(b"Subject: ab\r\n\r\n"), fp(b"Subject: a\r\nMessage-ID: b\r\n\r\n"));
+}
@@ -1,4 +1,5 @@
 use crate::headersection::*;
+use crate::options::ParserOptions;
 
 fn hs(i: &[u8]) -> Vec<HeaderField> {
     let (rem, parsed) = header_section(i).unwrap();
@@ -44,3 +45,30 @@ fn big_garbage() {
                         Err(b"another bad header <4F34184B.7040006@example.com>".as_ref()),
                         Ok((b"Date".as_ref(), b" Thu, 09 Feb 2012 14:02:35 -0500".as_ref()))]);
 }
+
+#[test]
+fn write_canonical() {
+    let parsed = hs(b"Content-Type: text/plain\r\nX-Spam: no\r\nSubject: s\r\nmime-version: 1.0\r\nTo: a\r\n\
+                      Resent-From: r\r\nMessage-ID: <m>\r\nReceived: 2\r\nbroken\r\nFrom: f\r\nReceived: 1\r\n\r\n");
+    assert_eq!(write_header_section(&parsed, HeaderOrder::Canonical),
+               &b"Resent-From: r\r\nReceived: 2\r\nReceived: 1\r\nFrom: f\r\nTo: a\r\nMessage-ID: <m>\r\nSubject: s\r\n\
+                  mime-version: 1.0\r\nContent-Type: text/plain\r\nX-Spam: no\r\nbroken\r\n\r\n"[..]);
+}
+
+#[test]
+fn write_preserve() {
+    let input = b"X-B: 1\r\nx-a:2\r\nSUBJECT:\r\n folded\r\n\r\n";
+    assert_eq!(write_header_section(&hs(input), HeaderOrder::Preserve), &input[..]);
+}
+
+#[test]
+fn utf8_names() {
+    let input = ["Sujet-É: a\r\nX-ü: b\r\n".as_bytes(), b"Bad\xff: c\r\n\r\n"].concat();
+    let (rem, parsed) = header_section_with(&input, &ParserOptions::default()).unwrap();
+
+    assert!(rem.is_empty());
+    assert_eq!(parsed, [Ok(("Sujet-É".as_bytes(), b" a".as_ref())),
+                        Ok(("X-ü".as_bytes(), b" b".as_ref())),
+                        Err(b"Bad\xff: c".as_ref())]);
+    assert!(header_section(&input).unwrap().1.iter().all(|f| f.is_err()));
+}
@@ -0,0 +1,48 @@
+use crate::behaviour::Intl;
+use crate::homograph::*;
+use crate::rfc5322::from;
+use crate::types::Domain;
+
+fn domain(input: &str) -> Vec<Finding> {
+    audit_domain(&Domain::from_smtp(input.as_bytes()).unwrap())
+}
+
+#[test]
+fn scripts() {
+    assert_eq!(Script::of('a'), Some(Script::Latin));
+    assert_eq!(Script::of('é'), Some(Script::Latin));
+    assert_eq!(Script::of('ж'), Some(Script::Cyrillic));
+    assert_eq!(Script::of('7'), None);
+    assert_eq!(Script::of('-'), None);
+    assert_eq!(Script::of('ท'), Some(Script::Other));
+}
+
+#[test]
+fn labels() {
+    assert!(domain("mx.example.org").is_empty());
+    assert!(domain("例え.テスト").is_empty());
+    assert!(domain("東京abc.example").is_empty());
+    assert!(domain("пример.рф").is_empty());
+
+    assert_eq!(domain("аррӏе.com"), [Finding::Confusable { label: "аррӏе".into(), skeleton: "apple".into() }]);
+    assert_eq!(domain("gοogle.com"), [
+        Finding::MixedScript { label: "gοogle".into(), scripts: vec![Script::Latin, Script::Greek] },
+        Finding::Confusable { label: "gοogle".into(), skeleton: "google".into() },
+    ]);
+    assert_eq!(domain("bаnk한.example"), [
+        Finding::MixedScript { label: "bаnk한".into(), scripts: vec![Script::Latin, Script::Cyrillic, Script::Hangul] },
+    ]);
+}
+
+#[test]
+fn display_names() {
+    let (_, addrs) = from::<Intl>("Team\u{202e}: a@example.org, \"\u{feff}Bob\" <b@xn--80ak6aa92e.com>;".as_bytes()).unwrap();
+    let findings = audit_addresses(&addrs);
+
+    assert_eq!(findings, [
+        Finding::Invisible { name: "Team\u{202e}".into(), offset: 4, c: '\u{202e}' },
+        Finding::Invisible { name: "\u{feff}Bob".into(), offset: 0, c: '\u{feff}' },
+        Finding::Confusable { label: "аррӏе".into(), skeleton: "apple".into() },
+    ]);
+    assert_eq!(findings[0].to_string(), "display name \"Team\\u{202e}\" has invisible U+202E at 4");
+}
@@ -0,0 +1,51 @@
+use crate::behaviour::{Intl, Legacy};
+use crate::lexer::*;
+
+fn kinds(input: &[u8]) -> Vec<TokenKind> {
+    tokens::<Intl>(input).map(|t| t.kind).collect()
+}
+
+#[test]
+fn spans_cover_input() {
+    let input = b"a (b (nested) c)\r\n \"q\\\"s\"; x@y";
+    let toks: Vec<_> = tokens::<Intl>(input).collect();
+    let mut pos = 0;
+
+    for tok in &toks {
+        assert_eq!(tok.span.start, pos);
+        assert_eq!(&input[tok.span.clone()], tok.raw);
+        pos = tok.span.end;
+    }
+    assert_eq!(pos, input.len());
+}
+
+#[test]
+fn fold_and_comment() {
+    let toks: Vec<_> = tokens::<Intl>(b"(b (nested) c)\r\n x").collect();
+    assert_eq!(toks[0].kind, TokenKind::Comment);
+    assert_eq!(toks[0].value, "b (nested) c");
+    assert_eq!(toks[1].kind, TokenKind::Fold);
+    assert_eq!(toks[1].value, " ");
+    assert_eq!(toks[2].kind, TokenKind::Atom);
+}
+
+#[test]
+fn quoted_string_value() {
+    let toks: Vec<_> = tokens::<Intl>(b"\"q\\\"s\"").collect();
+    assert_eq!(toks.len(), 1);
+    assert_eq!(toks[0].kind, TokenKind::QuotedString);
+    assert_eq!(toks[0].value, "q\"s");
+}
+
+#[test]
+fn invalid_bytes() {
+    assert_eq!(kinds(b"\"open"), [TokenKind::Special(b'"'), TokenKind::Atom]);
+    assert_eq!(kinds(b"a\x01b"), [TokenKind::Atom, TokenKind::Invalid(1), TokenKind::Atom]);
+}
+
+#[test]
+fn utf8_atoms() {
+    assert_eq!(kinds("été".as_bytes()), [TokenKind::Atom]);
+    assert_eq!(tokens::<Legacy>("é".as_bytes()).map(|t| t.kind).collect::<Vec<_>>(),
+               [TokenKind::Invalid(0xc3), TokenKind::Invalid(0xa9)]);
+}
@@ -0,0 +1,46 @@
+use crate::mediatype::*;
+
+fn mt(value: &str) -> MediaType {
+    MediaType::parse(value).unwrap()
+}
+
+#[test]
+fn parse() {
+    assert_eq!(mt(" Text/Plain ").essence(), TEXT_PLAIN);
+    assert_eq!((mt("text/plain").type_name(), mt("text/plain").subtype()), ("text", "plain"));
+    assert!(MediaType::parse("/plain").is_none());
+    assert!(MediaType::parse("text/").is_none());
+    assert!(MediaType::parse("text/plain/x").is_none());
+    assert_eq!(mt("text/plain").suffix(), None);
+}
+
+#[test]
+fn top_level() {
+    assert_eq!(mt("font/woff2").top_level(), Some(TopLevel::Font));
+    assert_eq!(mt("x-bogus/thing").top_level(), None);
+    assert!(TopLevel::Image.is_discrete());
+    assert!(!TopLevel::Multipart.is_discrete());
+}
+
+#[test]
+fn registered() {
+    assert!(mt(MULTIPART_ALTERNATIVE).is_registered());
+    assert!(mt("application/PDF").is_registered());
+    assert!(!mt("application/x-tar").is_registered());
+    assert!(mt("application/x-tar").is_experimental());
+    assert!(mt("x-bogus/thing").is_experimental());
+    assert!(!mt("application/vnd.rar").is_experimental());
+}
+
+#[test]
+fn classification() {
+    assert!(mt(TEXT_HTML).is_text());
+    assert!(mt(IMAGE_PNG).is_image());
+    assert!(mt("video/webm").is_audio_video());
+    assert!(mt(MESSAGE_RFC822).is_message());
+    assert!(mt(MULTIPART_MIXED).is_multipart());
+    assert!(mt("application/x-7z-compressed").is_archive());
+    assert!(!mt(APPLICATION_PDF).is_archive());
+    assert!(mt(APPLICATION_PORTABLE_EXECUTABLE).is_executable());
+    assert!(mt("text/javascript").is_text() && mt("text/javascript").is_executable());
+}
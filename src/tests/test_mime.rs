@@ -0,0 +1,344 @@
+use std::borrow::Cow;
+
+use crate::limits::{DecodeError, Limit, MimeLimits};
+use crate::mime::*;
+use crate::options::MimeOptions;
+use crate::rfc2231::{ContentDisposition, ContentTransferEncoding};
+
+#[test]
+fn single_part() {
+    let message = parse_message(b"Subject: hi\r\nContent-Type: TEXT/HTML; charset=iso-8859-1\r\n\r\n<p>caf\xe9</p>");
+
+    assert_eq!(message.content_type, "text/html");
+    assert_eq!(message.param("Charset"), Some("iso-8859-1"));
+    assert_eq!(message.header(b"subject"), Some(&b" hi"[..]));
+    assert_eq!(message.decoded_text(), "<p>caf\u{e9}</p>");
+    assert!(message.parts.is_empty());
+}
+
+#[test]
+fn defaults() {
+    let message = parse_message(b"Content-Type: bogus\r\nContent-Transfer-Encoding: ???\r\n\r\nbody");
+
+    assert_eq!(message.content_type, "text/plain");
+    assert_eq!(message.transfer_encoding(), ContentTransferEncoding::SevenBit);
+    assert_eq!(message.decoded_body(), Cow::from(&b"body"[..]));
+}
+
+#[test]
+fn multipart_split() {
+    let message = parse_message(b"Content-Type: multipart/mixed; boundary=\"x y\"\r\n\r\n\
+                                  preamble\r\n--x y\r\n\r\nfirst\r\n\r\n--x y  \r\nX-A: 1\r\n\r\nsecond\r\n--x y--\r\nepilogue\r\n--x y\r\n");
+
+    assert_eq!(message.parts.len(), 2);
+    assert_eq!(message.parts[0].body, b"first\r\n");
+    assert_eq!(message.parts[1].header(b"X-A"), Some(&b" 1"[..]));
+    assert_eq!(message.parts[1].body, b"second");
+}
+
+#[test]
+fn multipart_unterminated() {
+    let message = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\nContent-Type: text/html\r\n--b\r\n\r\nlast");
+
+    assert_eq!(message.parts.len(), 2);
+    assert_eq!(message.parts[0].content_type, "text/html");
+    assert_eq!(message.parts[0].body, b"");
+    assert_eq!(message.parts[1].body, b"last");
+
+    let message = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\n\r\nno delimiters\r\n");
+    assert!(message.parts.is_empty());
+
+    let message = parse_message(b"Content-Type: multipart/mixed\r\n\r\n--b\r\n\r\nx\r\n--b--\r\n");
+    assert!(message.parts.is_empty());
+}
+
+#[test]
+fn transfer_encodings() {
+    let qp = parse_message(b"Content-Transfer-Encoding: Quoted-Printable\r\n\r\na=3Db=\r\nc=xx =\r\n");
+    assert_eq!(qp.decoded_body(), Cow::from(&b"a=bc=xx "[..]));
+
+    let b64 = parse_message(b"Content-Transfer-Encoding: base64\r\n\r\naGVs\r\nbG8=\r\n");
+    assert_eq!(b64.decoded_body(), Cow::from(&b"hello"[..]));
+
+    let bad = parse_message(b"Content-Transfer-Encoding: base64\r\n\r\na\r\n");
+    assert_eq!(bad.decoded_body(), Cow::from(&b"a\r\n"[..]));
+}
+
+#[test]
+fn calendar() {
+    let message = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+                                  --b\r\nContent-Type: text/calendar; method=cancel\r\n\r\nBEGIN:VCALENDAR\r\n\
+                                  --b\r\nContent-Type: text/plain; method=REQUEST\r\n\r\nx\r\n--b--\r\n");
+    let calendar = message.find_calendar().unwrap();
+
+    assert_eq!(calendar.calendar_method(), Some(CalendarMethod::Cancel));
+    assert_eq!(message.parts[1].calendar_method(), None);
+    assert_eq!(CalendarMethod::from("x-custom"), CalendarMethod::Other("X-CUSTOM".into()));
+    assert!(parse_message(b"\r\nplain").find_calendar().is_none());
+}
+
+#[test]
+fn security() {
+    let message = parse_message(b"Content-Type: multipart/encrypted; boundary=b; protocol=\"application/pgp-encrypted\"\r\n\r\n\
+                                  --b\r\nContent-Type: application/pgp-encrypted\r\n\r\nVersion: 1\r\n\
+                                  --b\r\nContent-Type: application/octet-stream\r\n\r\n-----BEGIN PGP MESSAGE-----\r\n--b--\r\n");
+    match message.security() {
+        Some(Security::Encrypted { protocol, control, payload }) => {
+            assert_eq!(protocol, Protocol::Pgp);
+            assert_eq!(control.body, b"Version: 1");
+            assert_eq!(payload.content_type, "application/octet-stream");
+        },
+        other => panic!("{:?}", other),
+    }
+
+    let message = parse_message(b"Content-Type: multipart/signed; boundary=b; protocol=\"application/pkcs7-signature\"; micalg=sha-256\r\n\r\n\
+                                  --b\r\n\r\nx\r\n--b\r\nContent-Type: application/pkcs7-signature\r\n\r\ny\r\n--b--\r\n");
+    assert!(matches!(message.security(), Some(Security::Signed { protocol: Protocol::Smime, micalg: Some("sha-256"), .. })));
+
+    let message = parse_message(b"Content-Type: application/pkcs7-mime; smime-type=Enveloped-Data\r\n\r\nMIAG");
+    assert_eq!(message.security(), Some(Security::Pkcs7 { smime_type: Some(SmimeType::EnvelopedData) }));
+
+    // Missing protocol, wrong part count and unrelated types.
+    assert_eq!(parse_message(b"Content-Type: multipart/signed; boundary=b\r\n\r\n--b\r\n\r\nx\r\n--b\r\n\r\ny\r\n--b--\r\n").security(), None);
+    assert_eq!(parse_message(b"Content-Type: multipart/signed; boundary=b; protocol=\"x/y\"\r\n\r\n--b\r\n\r\nx\r\n--b--\r\n").security(), None);
+    assert_eq!(parse_message(b"\r\nplain").security(), None);
+}
+
+#[test]
+fn attached() {
+    let input = b"Content-Type: message/global\r\n\r\n\
+                  Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+                  --b\r\nContent-Type: message/rfc822\r\n\r\nSubject: inner\r\n\r\ntext\r\n\
+                  --b\r\nContent-Type: message/rfc822\r\nContent-Transfer-Encoding: base64\r\n\r\nU3ViamVjdDogeA0KDQp5\r\n--b--\r\n";
+    let outer = attached_messages(input);
+
+    assert_eq!(outer.len(), 1);
+    assert_eq!(outer[0].offset, 32);
+    assert_eq!(outer[0].message.content_type, "multipart/mixed");
+
+    let inner = attached_messages(outer[0].raw);
+    assert_eq!(inner.len(), 1);
+    assert_eq!(inner[0].raw, b"Subject: inner\r\n\r\ntext");
+    assert_eq!(&input[outer[0].offset + inner[0].offset..][..inner[0].raw.len()], inner[0].raw);
+
+    assert!(attached_messages(b"\r\nplain").is_empty());
+}
+
+#[test]
+fn limits() {
+    let nested = b"Content-Type: multipart/mixed; boundary=a\r\n\r\n\
+                   --a\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n\
+                   --b\r\n\r\none\r\n--b\r\n\r\ntwo\r\n--b--\r\n--a--\r\n";
+    let limited = |limits| parse_message_limited(nested, &limits).map(|m| m.iter().count());
+
+    assert_eq!(limited(MimeLimits::default()), Ok(4));
+    assert_eq!(limited(MimeLimits { max_depth: 2, max_parts: 3, ..MimeLimits::default() }), Ok(4));
+    assert_eq!(limited(MimeLimits { max_depth: 1, ..MimeLimits::default() }), Err(DecodeError::LimitExceeded(Limit::MimeDepth)));
+    assert_eq!(limited(MimeLimits { max_parts: 2, ..MimeLimits::default() }), Err(DecodeError::LimitExceeded(Limit::MimeParts)));
+    assert_eq!(limited(MimeLimits { max_header_len: 40, ..MimeLimits::default() }), Err(DecodeError::LimitExceeded(Limit::HeaderLen)));
+
+    // A multipart entity without parts does not count towards the depth.
+    let empty = parse_message_limited(b"Content-Type: multipart/mixed; boundary=a\r\n\r\nnone\r\n", &MimeLimits { max_depth: 0, ..MimeLimits::default() });
+    assert!(empty.is_ok());
+}
+
+#[test]
+fn default_limits() {
+    let mut nested = Vec::new();
+    for depth in 0..40 {
+        nested.extend_from_slice(format!("Content-Type: multipart/mixed; boundary=b{0}\r\n\r\n--b{0}\r\n", depth).as_bytes());
+    }
+    nested.extend_from_slice(b"\r\ninnermost\r\n");
+
+    let message = parse_message(&nested);
+    assert_eq!(message.iter().count(), 33);
+    assert!(message.iter().last().unwrap().parts.is_empty());
+    assert_eq!(parse_message_limited(&nested, &MimeLimits::default()), Err(DecodeError::LimitExceeded(Limit::MimeDepth)));
+    assert!(attached_messages(&nested).is_empty());
+}
+
+#[test]
+fn preamble_epilogue() {
+    let message = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+                                  This is a MIME message.\r\n\r\n--b\r\n\r\nx\r\n--b--\r\ntrailer\r\n");
+
+    assert_eq!(message.preamble, b"This is a MIME message.\r\n");
+    assert_eq!(message.epilogue, b"trailer\r\n");
+    assert_eq!(message.parts[0].preamble, b"");
+
+    let message = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\r\nx");
+    assert_eq!(message.preamble, b"");
+    assert_eq!(message.epilogue, b"");
+}
+
+#[test]
+fn broken_boundaries() {
+    let strict = MimeOptions::default();
+    let parse = |input, options| parse_message_with(input, &options).unwrap().parts.iter().map(|p| p.body).collect::<Vec<_>>();
+
+    let unterminated = b"Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\r\none\r\n--b\r\n\r\ntwo";
+    assert_eq!(parse(unterminated, strict), [b"one", b"two"]);
+    assert_eq!(parse(unterminated, MimeOptions { keep_unterminated_part: false, ..strict }), [b"one"]);
+
+    let indented = b"Content-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\n\r\none\r\n  --b\r\n\r\ntwo\r\n\t--b--\r\n";
+    assert_eq!(parse(indented, strict), [&b"one\r\n  --b\r\n\r\ntwo\r\n\t--b--\r\n"[..]]);
+    assert_eq!(parse(indented, MimeOptions { indented_delimiters: true, ..strict }), [b"one", b"two"]);
+
+    let case = b"Content-Type: multipart/mixed; boundary=Part\r\n\r\n--part\r\n\r\none\r\n--PART--\r\n";
+    assert!(parse(case, strict).is_empty());
+    assert_eq!(parse(case, MimeOptions { boundary_ignore_case: true, ..strict }), [b"one"]);
+}
+
+#[test]
+fn content_type_defaults() {
+    let message = parse_message(b"Content-Type: multipart/digest; boundary=b\r\n\r\n\
+                                  --b\r\n\r\nSubject: one\r\n\r\nx\r\n\
+                                  --b\r\nContent-Type: ???\r\n\r\nSubject: two\r\n\r\ny\r\n\
+                                  --b\r\nContent-Type: text/plain\r\n\r\nz\r\n--b--\r\n");
+    let types: Vec<_> = message.parts.iter().map(|p| p.content_type.as_str()).collect();
+
+    assert_eq!(types, ["message/rfc822", "message/rfc822", "text/plain"]);
+    assert!(message.parts[0].params.is_empty());
+    assert_eq!(message.parts[2].param("charset"), None);
+    assert_eq!(attached_messages(b"Content-Type: multipart/digest; boundary=b\r\n\r\n--b\r\n\r\nSubject: one\r\n\r\nx\r\n--b--\r\n").len(), 1);
+
+    let message = parse_message("Subject: no type\r\n\r\ncafé".as_bytes());
+    assert_eq!(message.content_type, "text/plain");
+    assert_eq!(message.param("charset"), Some("us-ascii"));
+    assert_eq!(message.decoded_text(), "café");
+}
+
+#[test]
+fn attachments() {
+    let message = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+                                  --b\r\nContent-Type: multipart/related; boundary=c\r\n\r\n\
+                                  --c\r\nContent-Type: text/html\r\n\r\n<img src=cid:logo>\r\n\
+                                  --c\r\nContent-Type: image/png; name=\"logo.png\"\r\nContent-ID: <logo@example.org>\r\n\
+                                  Content-Disposition: inline\r\n\r\nPNG\r\n--c--\r\n\
+                                  --b\r\nContent-Type: application/octet-stream\r\n\
+                                  Content-Disposition: attachment; filename=\"C:\\\\tmp\\\\..\\\\evil.exe\"\r\n\r\nMZ\r\n\
+                                  --b\r\nContent-Type: text/plain\r\nContent-Disposition: attachment\r\n\r\nnotes\r\n\
+                                  --b\r\nContent-Disposition: attachment; filename=\"..\"\r\n\r\n\r\n--b--\r\n");
+    let attachments: Vec<_> = message.attachments().collect();
+
+    assert_eq!(attachments.len(), 4);
+    assert_eq!(attachments[0].filename.as_deref(), Some("logo.png"));
+    assert_eq!(attachments[0].content_id.as_deref(), Some("logo@example.org"));
+    assert_eq!(attachments[0].disposition, Some(ContentDisposition::Inline));
+    assert_eq!(attachments[0].size, 3);
+    assert_eq!(attachments[1].filename.as_deref(), Some("evil.exe"));
+    assert_eq!(attachments[1].content_id, None);
+    assert_eq!(attachments[2].filename, None);
+    assert_eq!(attachments[2].entity.body, b"notes");
+    assert_eq!(attachments[3].filename, None);
+
+    assert_eq!(parse_message(b"\r\nplain").attachments().count(), 0);
+}
+
+#[test]
+fn body_selection() {
+    let message = parse_message(b"Content-Type: multipart/alternative; boundary=b\r\n\r\n\
+                                  --b\r\n\r\nfirst\r\n\
+                                  --b\r\nContent-Type: multipart/related; boundary=c; start=\"<root>\"\r\n\r\n\
+                                  --c\r\nContent-Type: text/html\r\n\r\nnot root\r\n\
+                                  --c\r\nContent-Type: text/html\r\nContent-ID: <root>\r\n\r\nroot\r\n--c--\r\n\
+                                  --b\r\nContent-Type: text/plain\r\n\r\nlast\r\n--b--\r\n");
+    let bodies = message.body_parts();
+
+    assert_eq!(bodies.text.unwrap().body, b"last");
+    assert_eq!(bodies.html.unwrap().body, b"root");
+
+    let related = parse_message(b"Content-Type: multipart/related; boundary=c\r\n\r\n\
+                                  --c\r\nContent-Type: image/png\r\n\r\nPNG\r\n\
+                                  --c\r\nContent-Type: text/html\r\n\r\nhtml\r\n--c--\r\n");
+    assert_eq!(related.body_parts(), BodyParts::default());
+
+    let forwarded = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+                                    --b\r\nContent-Type: message/rfc822\r\n\r\n\r\ninner\r\n--b--\r\n");
+    assert_eq!(forwarded.body_parts(), BodyParts::default());
+
+    let single = parse_message(b"\r\nplain");
+    assert_eq!(single.body_parts().text.unwrap().body, b"plain");
+    assert!(single.body_parts().html.is_none());
+}
+
+#[test]
+fn encoding_validation() {
+    let message = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\nContent-Transfer-Encoding: 8bit\r\n\r\n\
+                                  --b\r\nContent-Type: message/rfc822\r\nContent-Transfer-Encoding: quoted-printable\r\n\r\nx\r\n\
+                                  --b\r\nContent-Type: message/partial; id=1; number=1\r\nContent-Transfer-Encoding: 8bit\r\n\r\nx\r\n\
+                                  --b\r\nContent-Type: text/plain\r\nContent-Transfer-Encoding: base64\r\n\r\neA==\r\n\
+                                  --b\r\nContent-Type: multipart/alternative; boundary=c\r\nContent-Transfer-Encoding: x-custom\r\n\r\n\
+                                  --c\r\n\r\nx\r\n--c--\r\n--b--\r\n");
+    let violations: Vec<_> = message.encoding_violations().into_iter()
+        .map(|v| (v.entity.content_type.as_str(), v.encoding)).collect();
+
+    assert_eq!(violations, [("message/rfc822", ContentTransferEncoding::QuotedPrintable),
+                            ("message/partial", ContentTransferEncoding::EightBit),
+                            ("multipart/alternative", ContentTransferEncoding::Extended("custom".into()))]);
+    assert!(message.has_valid_encoding());
+}
+
+#[test]
+fn quoted_printable_options() {
+    let input = b"a=e9 \r\nb=+1=\r\nc=";
+    let (decoded, corrections) = decode_quoted_printable_with(input, &QpOptions::default()).unwrap();
+
+    assert_eq!(decoded, b"a\xe9\r\nb=+1c");
+    assert_eq!(corrections, QpCorrections { lowercase_hex: 1, bare_equals: 1, invalid_escape: 1, trailing_whitespace: 1 });
+
+    let strict = QpOptions::strict();
+    assert_eq!(decode_quoted_printable_with(input, &strict), Err((4, QpViolation::TrailingWhitespace)));
+    assert_eq!(decode_quoted_printable_with(input, &QpOptions { trailing_whitespace: true, ..strict }),
+               Err((1, QpViolation::LowercaseHex)));
+    assert_eq!(decode_quoted_printable_with(input, &QpOptions { trailing_whitespace: true, lowercase_hex: true, ..strict }),
+               Err((8, QpViolation::InvalidEscape)));
+    assert_eq!(decode_quoted_printable_with(b"c=", &strict), Err((1, QpViolation::BareEquals)));
+
+    let (decoded, corrections) = decode_quoted_printable_with(b"caf=C3=A9=\r\n!\r\n", &strict).unwrap();
+    assert_eq!(decoded, "café!\r\n".as_bytes());
+    assert_eq!(corrections.total(), 0);
+}
+
+#[test]
+fn base64_strict() {
+    let line = "QUJD".repeat(19);
+    let input = format!("{}\r\n{}\nQUI=\r\n", line, line);
+    assert_eq!(decode_base64_strict(input.as_bytes()).unwrap().len(), 57 * 2 + 2);
+
+    let long = format!("{}QUJD\r\n", line);
+    assert_eq!(decode_base64_strict(long.as_bytes()), Err((76, Base64Violation::LineTooLong)));
+    assert_eq!(decode_base64_strict(b"QUJD\rQUJD"), Err((4, Base64Violation::InvalidCharacter)));
+    assert_eq!(decode_base64_strict(b"QU==QUJD"), Err((4, Base64Violation::InvalidPadding)));
+    assert_eq!(decode_base64_strict(b"QUJ=="), Err((3, Base64Violation::InvalidPadding)));
+    assert_eq!(decode_base64_strict(b"QUI="), Ok(b"AB".to_vec()));
+    assert_eq!(decode_base64_strict(b"QUK="), Err((3, Base64Violation::InvalidPadding)));
+    assert_eq!(decode_base64_strict(b""), Ok(vec![]));
+
+    assert_eq!(decode_base64(long.as_bytes()).unwrap().len(), 60);
+}
+
+#[test]
+fn stats() {
+    use crate::esmtp::BodyType;
+
+    assert_eq!(body_stats(b""), BodyStats::default());
+    assert_eq!(body_stats(b"abc\r\n\r\nde\r").bare_cr, 1);
+    assert_eq!(body_stats(b"abc\r\n\r\nde\r").max_line_len, 3);
+    assert_eq!(body_stats(b"abc\r\n").required_body_type(), BodyType::SevenBit);
+    assert_eq!(body_stats("é\r\n".as_bytes()).required_body_type(), BodyType::EightBitMime);
+    assert_eq!(body_stats(&[b'a'; 999]).required_body_type(), BodyType::BinaryMime);
+    assert_eq!(body_stats(&[b'a'; 998]).required_body_type(), BodyType::SevenBit);
+    assert!(!body_stats(b"a\nb\r\n").crlf_consistent());
+
+    let message = parse_message(b"Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+                                  --b\r\nContent-Type: multipart/alternative; boundary=c\r\n\r\n\r\n--b\r\n\r\nx\n\r\n--b--\r\n");
+    let stats: Vec<_> = message.part_stats().map(|(e, s)| (e.content_type.as_str(), s.bare_lf)).collect();
+    assert_eq!(stats, [("text/plain", 1)]);
+}
+
+#[test]
+fn media_type() {
+    let message = parse_message(b"Content-Type: application/x-msdownload\r\n\r\nMZ");
+    assert!(message.media_type().unwrap().is_executable());
+}
@@ -0,0 +1,41 @@
+use crate::behaviour::Intl;
+use crate::headersection::header_section;
+use crate::redact::*;
+use crate::rfc5322::from;
+use crate::trace::received;
+
+const MESSAGE: &[u8] = b"Received: from client (client.example [IPv6:2001:db8::7])\r\n\
+\tby mx.example (Postfix) with ESMTPS id 4F3A2 for <\"bob smith\"@example.org>;\r\n\
+\tTue, 1 Jun 2021 10:00:00 +0000\r\n\
+To: Team: alice@example.org, Bob <b.s@example.org>;, carol@[192.0.2.1]\r\n\
+X-Secret: a\r\n b\r\n\
+Subject: unchanged\r\n\
+\r\n\
+bob@example.org\r\n";
+
+#[test]
+fn structure_preserved() {
+    let redaction = Redaction { headers: vec!["x-secret".into()], local_parts: true, received_ips: true };
+    let out = redaction.redact_message(MESSAGE).unwrap();
+
+    assert_eq!(out.len(), MESSAGE.len());
+    assert_eq!(String::from_utf8(out.clone()).unwrap(), "Received: from client (client.example [IPv6:0000:000::0])\r\n\
+\tby mx.example (Postfix) with ESMTPS id 4F3A2 for <\"xxxxxxxxx\"@example.org>;\r\n\
+\tTue, 1 Jun 2021 10:00:00 +0000\r\n\
+To: Team: xxxxx@example.org, Bob <x.x@example.org>;, xxxxx@[192.0.2.1]\r\n\
+X-Secret: x\r\n x\r\n\
+Subject: unchanged\r\n\
+\r\n\
+bob@example.org\r\n");
+
+    // The redacted headers still parse.
+    let (_, fields) = header_section(&out).unwrap();
+    let (_, rcvd) = received(fields[0].unwrap().1).unwrap();
+    assert_eq!(rcvd.recipient.as_deref(), Some("\"xxxxxxxxx\"@example.org"));
+    assert_eq!(from::<Intl>(fields[1].unwrap().1).unwrap().1.len(), 2);
+}
+
+#[test]
+fn nothing_selected() {
+    assert_eq!(Redaction::default().redact_message(MESSAGE).unwrap(), MESSAGE);
+}
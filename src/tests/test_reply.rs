@@ -0,0 +1,110 @@
+use crate::behaviour::{Intl, Legacy};
+use crate::reply::{replies, reply, Reply, ReplyCategory, ReplyClass, ReplyCode};
+
+#[test]
+fn single_line() {
+    assert_eq!(reply::<Legacy>(b"220 mx.example.org ESMTP\r\n").unwrap().1, Reply::new(220, vec!["mx.example.org ESMTP"]));
+    assert_eq!(reply::<Legacy>(b"354\r\n").unwrap().1, Reply::new(354, vec![""]));
+}
+
+#[test]
+fn multi_line() {
+    let (rem, parsed) = reply::<Legacy>(b"250-mx.example.org\r\n250-\r\n250 HELP\r\nrest").unwrap();
+
+    assert_eq!(rem, b"rest");
+    assert_eq!(parsed.lines, ["mx.example.org", "", "HELP"]);
+}
+
+#[test]
+fn pipelined() {
+    let input = b"250 2.1.0 OK\r\n250-2.1.5 OK\r\n250 2.1.5 more\r\n250-partial\r\n";
+    let (rem, parsed) = replies::<Legacy>(input).unwrap();
+
+    assert_eq!(parsed, [Reply::new(250, vec!["2.1.0 OK"]), Reply::new(250, vec!["2.1.5 OK", "2.1.5 more"])]);
+    assert_eq!(rem, b"250-partial\r\n");
+
+    let (rem, parsed) = replies::<Legacy>(b"").unwrap();
+    assert!(rem.is_empty() && parsed.is_empty());
+}
+
+#[test]
+fn invalid() {
+    assert!(reply::<Legacy>(b"250-a\r\n251 b\r\n").is_err());
+    assert!(reply::<Legacy>(b"250-a\r\n").is_err());
+    assert!(reply::<Legacy>(b"160 a\r\n").is_err());
+    assert!(reply::<Legacy>(b"270 a\r\n").is_err());
+    assert!(reply::<Legacy>(b"250 a\x00\r\n").is_err());
+}
+
+#[test]
+fn utf8_text() {
+    let input = "553 Adresse refus\u{e9}e\r\n".as_bytes();
+
+    assert!(reply::<Legacy>(input).is_err());
+    assert_eq!(reply::<Intl>(input).unwrap().1.lines, ["Adresse refus\u{e9}e"]);
+    assert!(reply::<Intl>(b"553 a\xff\r\n").is_err());
+}
+
+#[test]
+fn build() {
+    assert_eq!(Reply::new(221, Vec::<String>::new()).to_smtp::<Legacy>().unwrap(), b"221\r\n");
+    assert_eq!(Reply::new(250, vec!["a\tb"]).to_smtp::<Legacy>().unwrap(), b"250 a\tb\r\n");
+    assert!(Reply::new(250, vec!["a\r\n250 b"]).to_smtp::<Intl>().is_err());
+    assert!(Reply::new(600, vec!["a"]).to_smtp::<Legacy>().is_err());
+    assert!(Reply::new(25, vec!["a"]).to_smtp::<Legacy>().is_err());
+}
+
+#[test]
+fn roundtrip() {
+    let built = Reply::new(250, vec!["caf\u{e9}", "PIPELINING", "SMTPUTF8"]);
+    let bytes = built.to_smtp::<Intl>().unwrap();
+
+    assert_eq!(reply::<Intl>(&bytes).unwrap(), (&b""[..], built));
+}
+
+#[test]
+fn with_status() {
+    let status = "4.2.2".parse().unwrap();
+
+    let full = Reply::with_status(452, status, "Mailbox full\n\nTry later");
+    assert_eq!(full.lines, ["4.2.2 Mailbox full", "4.2.2", "4.2.2 Try later"]);
+    assert_eq!(full.enhanced_code(), Some(status));
+
+    let word = "x".repeat(1000);
+    let wrapped = Reply::with_status(452, status, &format!("a {}", word));
+    assert_eq!(wrapped.lines.len(), 3);
+    assert_eq!(wrapped.lines[0], "4.2.2 a");
+    assert_eq!(wrapped.lines[1..].iter().map(|l| l.len() - 6).sum::<usize>(), 1000);
+
+    let bytes = wrapped.to_smtp::<Legacy>().unwrap();
+    assert!(bytes.split(|c| *c == b'\n').all(|l| l.len() < 512));
+    assert_eq!(reply::<Legacy>(&bytes).unwrap().1, wrapped);
+
+    let accents = Reply::with_status(250, "2.0.0".parse().unwrap(), &"é".repeat(300));
+    assert!(accents.to_smtp::<Intl>().is_ok());
+}
+
+#[test]
+fn line_limit() {
+    assert!(Reply::new(250, vec!["x".repeat(506)]).to_smtp::<Legacy>().is_ok());
+    assert!(Reply::new(250, vec!["x".repeat(507)]).to_smtp::<Legacy>().is_err());
+}
+
+#[test]
+fn reply_code() {
+    assert!(ReplyCode::OK.is_positive_completion());
+    assert!(ReplyCode::START_MAIL_INPUT.is_positive_intermediate());
+    assert!(ReplyCode::SERVICE_NOT_AVAILABLE.is_transient());
+    assert!(ReplyCode::MAILBOX_UNAVAILABLE.is_permanent());
+    assert_eq!(ReplyCode::SYNTAX_ERROR.category(), Some(ReplyCategory::Syntax));
+    assert_eq!(ReplyCode::HELP.category(), Some(ReplyCategory::Information));
+    assert_eq!(ReplyCode::AUTH_CHALLENGE.category(), Some(ReplyCategory::Unspecified));
+    assert_eq!(ReplyCode::EXCEEDED_STORAGE.class(), Some(ReplyClass::PermanentNegative));
+
+    for invalid in [199, 260, 600] {
+        assert_eq!(ReplyCode(invalid).class(), None);
+        assert!(Reply::new(invalid, vec!["x"]).to_smtp::<Legacy>().is_err());
+    }
+    assert!(reply::<Legacy>(b"260 x\r\n").is_err());
+    assert_eq!(reply::<Legacy>(b"555 x\r\n").unwrap().1.code, ReplyCode::PARAMETERS_NOT_RECOGNIZED);
+}
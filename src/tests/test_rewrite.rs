@@ -0,0 +1,78 @@
+use crate::behaviour::Intl;
+use crate::headersection::header_section;
+use crate::rewrite::*;
+use crate::rfc5322::Mailbox;
+use crate::types::{DomainPart, Mailbox as SMTPMailbox};
+
+const HEADERS: &[u8] = b"From: Bob <bob@old.example>\r\n\
+To: Team: a@old.example,\r\n b@other.example;, c@other.example\r\n\
+Cc: c@other.example (unchanged)\r\n\
+resent-from: =?utf-8?q?Ren=C3=A9?= <rene@old.example>\r\n\
+Subject: bob@old.example\r\n\
+Reply-To: <broken\r\n\
+\r\n";
+
+fn migrate(_: &[u8], mbox: &Mailbox) -> Option<Mailbox> {
+    if mbox.address.domain_part().to_string() != "old.example" {
+        return None;
+    }
+    let domain = DomainPart::from_smtp(b"new.example").unwrap();
+    Some(Mailbox { dname: mbox.dname.clone(), address: SMTPMailbox::from_parts(mbox.address.local_part().clone(), domain) })
+}
+
+#[test]
+fn domain_migration() {
+    let (_, headers) = header_section(HEADERS).unwrap();
+    let fields = rewrite_addresses::<Intl, _>(&headers, migrate);
+    let out: Vec<u8> = fields.iter().flat_map(|f| f.to_bytes()).collect();
+
+    assert_eq!(String::from_utf8(out).unwrap(), "From: Bob <bob@new.example>\r\n\
+To: Team: a@new.example, b@other.example;, c@other.example\r\n\
+Cc: c@other.example (unchanged)\r\n\
+resent-from: =?utf-8?q?Ren=C3=A9?= <rene@new.example>\r\n\
+Subject: bob@old.example\r\n\
+Reply-To: <broken\r\n");
+}
+
+#[test]
+fn untouched_when_unchanged() {
+    let (_, headers) = header_section(HEADERS).unwrap();
+    let mut seen = Vec::new();
+    let fields = rewrite_addresses::<Intl, _>(&headers, |name, mbox| {
+        seen.push((String::from_utf8_lossy(name).into_owned(), mbox.address.to_string()));
+        Some(mbox.clone())
+    });
+
+    assert!(fields.iter().zip(&headers).all(|(f, h)| *f == RewrittenField::Unchanged(*h)));
+    assert_eq!(seen.len(), 6);
+    assert_eq!(seen[1], ("To".into(), "a@old.example".into()));
+}
+
+#[test]
+fn change_set() {
+    let (_, headers) = header_section(HEADERS).unwrap();
+    let fields = rewrite_addresses::<Intl, _>(&headers, migrate);
+
+    let mut expected = HeaderChangeSet::new();
+    expected.change("From", 1, "Bob <bob@new.example>")
+        .change("To", 1, "Team: a@new.example, b@other.example;, c@other.example")
+        .change("resent-from", 1, "=?utf-8?q?Ren=C3=A9?= <rene@new.example>");
+    assert_eq!(HeaderChangeSet::from_rewritten(&fields), expected);
+
+    let (_, headers) = header_section(b"Cc: x@old.example\r\nbroken\r\nCC: y@other.example\r\ncc: z@old.example\r\n\r\n").unwrap();
+    let fields = rewrite_addresses::<Intl, _>(&headers, migrate);
+    let set = HeaderChangeSet::from_rewritten(&fields);
+    assert_eq!(set.changes.iter().map(|c| match c {
+        HeaderChange::Change { index, .. } => *index,
+        _ => 0,
+    }).collect::<Vec<_>>(), [1, 3]);
+
+    let mut edits = HeaderChangeSet::new();
+    assert!(edits.is_empty());
+    edits.insert(0, "X-Spam", " yes").delete("Received", 2).add("X-Seen", "1");
+    assert_eq!(edits.changes, [
+        HeaderChange::Insert { index: 0, name: "X-Spam".into(), value: "yes".into() },
+        HeaderChange::Delete { name: "Received".into(), index: 2 },
+        HeaderChange::Add { name: "X-Seen".into(), value: "1".into() },
+    ]);
+}
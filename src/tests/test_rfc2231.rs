@@ -1,3 +1,5 @@
+use crate::limits::*;
+use crate::options::{DuplicatePolicy, ParserOptions};
 use crate::rfc2231::*;
 use crate::rfc2231::{ContentTransferEncoding as CTE, ContentDisposition as CD};
 
@@ -174,3 +176,117 @@ fn attfnbrokentokenutf() {
     let (rem, _) = content_disposition(b"attachment; filename=foo-\xC3\xA4.html").unwrap();
     assert_eq!(rem.len(), 0);
 }
+
+#[test]
+fn limited_continuations() {
+    let input = b" attachment; filename*0=\"a\"; filename*1=\"b\"; filename*2=\"c\"";
+    let (_, (_, params)) = content_disposition_limited(input, &DecoderLimits::default()).unwrap();
+    assert_eq!(params, [("filename".into(), "abc".into())]);
+
+    let limits = DecoderLimits { max_continuations: 2, ..Default::default() };
+    assert_eq!(content_disposition_limited(input, &limits), Err(DecodeError::LimitExceeded(Limit::Continuations)));
+
+    let mut input = b"attachment".to_vec();
+    (0..100_000).for_each(|i| input.extend(format!("; filename*{}=a", i).bytes()));
+    assert_eq!(content_disposition_limited(&input, &DecoderLimits::default()), Err(DecodeError::LimitExceeded(Limit::Continuations)));
+}
+
+#[test]
+fn limited_decoded_len() {
+    let limits = DecoderLimits { max_decoded_len: 10, ..Default::default() };
+    assert!(content_type_limited(b"text/plain; a=b", &limits).is_ok());
+    assert_eq!(content_type_limited(b"text/plain; name*=utf-8''%41%42%43%44%45%46%47%48%49", &limits),
+               Err(DecodeError::LimitExceeded(Limit::DecodedLen)));
+}
+
+#[test]
+fn options_duplicates() {
+    let input = b"attachment; filename=a.txt; FILENAME=b.txt; filename*=utf-8''c.txt";
+    let first = ParserOptions { duplicate_params: DuplicatePolicy::KeepFirst, ..Default::default() };
+    let last = ParserOptions { duplicate_params: DuplicatePolicy::KeepLast, ..Default::default() };
+    let reject = ParserOptions { duplicate_params: DuplicatePolicy::Reject, ..Default::default() };
+
+    // The extended form is not a duplicate and wins over the regular form.
+    assert_eq!(content_disposition_with(input, &first).unwrap().1 .1, [("filename".into(), "c.txt".into())]);
+    assert_eq!(content_disposition_with(b"inline; a=1; A=2", &first).unwrap().1 .1, [("a".into(), "1".into())]);
+    assert_eq!(content_disposition_with(b"inline; a=1; A=2", &last).unwrap().1 .1, [("a".into(), "2".into())]);
+    assert_eq!(content_disposition_with(input, &reject), Err(DecodeError::DuplicateParameter("filename".into())));
+}
+
+#[test]
+fn options_charset_fallback() {
+    let options = ParserOptions { charset_fallback: encoding_rs::WINDOWS_1252, ..Default::default() };
+    let (_, (_, params)) = content_type_with(b"text/plain; name*=x-bogus''%E9t%E9", &options).unwrap();
+    assert_eq!(params, [("name".into(), "\u{e9}t\u{e9}".into())]);
+
+    let (_, (_, params)) = content_type(b"text/plain; name*=x-bogus''%C3%A9").unwrap();
+    assert_eq!(params, [("name".into(), "\u{e9}".into())]);
+}
+
+#[test]
+fn options_quoted_string_rfc2047() {
+    let input = b"text/plain; name=\"=?utf-8?q?caf=C3=A9?=.txt\"";
+    let decode = ParserOptions { quoted_string_rfc2047: true, ..Default::default() };
+    let raw = ParserOptions { quoted_string_rfc2047: false, ..Default::default() };
+
+    assert_eq!(content_type_with(input, &decode).unwrap().1 .1, [("name".into(), "caf\u{e9}.txt".into())]);
+    assert_eq!(content_type_with(input, &raw).unwrap().1 .1, [("name".into(), "=?utf-8?q?caf=C3=A9?=.txt".into())]);
+}
+
+#[test]
+fn encode_continuations() {
+    let encoded = encode_parameter("filename", "été quarterly report.pdf", 28);
+    assert_eq!(encoded, ";\r\n filename*0*=utf-8''%C3%A9t;\r\n filename*1*=%C3%A9%20quarter;\r\n filename*2=\"ly report.pdf\"");
+
+    for value in ["été quarterly report.pdf", "plain but rather long \"quoted\" value", "ünïcödé", ""] {
+        for max_len in [1, 12, 20, 40, 200] {
+            let encoded = encode_parameter("filename", value, max_len);
+            let input = format!("attachment{}", encoded);
+            let (rem, (_, params)) = content_disposition(input.as_bytes()).unwrap();
+            assert_eq!(rem.len(), 0);
+            assert_eq!(params, [("filename".into(), value.into())], "{}", encoded);
+        }
+    }
+}
+
+#[test]
+fn options_rfc2047() {
+    let input = b"attachment; filename=\"=?utf-8?b?w6l0w6k=?=.txt\"; name*0=\"=?utf-8?q?a=C3=A9?=\"; name*1=b";
+    let decode = ParserOptions { quoted_string_rfc2047: true, ..Default::default() };
+    let verbatim = ParserOptions { quoted_string_rfc2047: false, ..Default::default() };
+
+    let mut params = content_disposition_with(input, &decode).unwrap().1 .1;
+    params.sort();
+    assert_eq!(params, [("filename".into(), "été.txt".into()), ("name".into(), "aéb".into())]);
+
+    let mut params = content_disposition_with(input, &verbatim).unwrap().1 .1;
+    params.sort();
+    assert_eq!(params, [("filename".into(), "=?utf-8?b?w6l0w6k=?=.txt".into()), ("name".into(), "=?utf-8?q?a=C3=A9?=b".into())]);
+}
+
+#[test]
+fn options_rfc2047_precedence() {
+    let decode = ParserOptions { quoted_string_rfc2047: true, ..Default::default() };
+    let filename = |input: &[u8]| content_disposition_with(input, &decode).unwrap().1 .1;
+
+    assert_eq!(filename(b"attachment; filename*=utf-8''b.txt; filename=\"=?utf-8?q?a.txt?=\""), [("filename".into(), "b.txt".into())]);
+    assert_eq!(filename(b"attachment; filename=\"=?utf-8?q?a.txt?=\"; filename*=utf-8''b.txt"), [("filename".into(), "b.txt".into())]);
+    assert_eq!(filename(b"attachment; filename*=utf-8''b.txt; filename*0=c; filename*1=.txt"), [("filename".into(), "c.txt".into())]);
+}
+
+#[test]
+fn detailed_parameters() {
+    let input = b"attachment; a=1; b*=utf-8''%C3%A9; c*=x-bogus''%E9; d*0*=utf-8''%C3; d*1=x; e*=utf-8''%C3%A9; e=2";
+    let (_, (disp, mut params)) = content_disposition_detailed(input, &ParserOptions::default()).unwrap();
+    params.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let summary: Vec<_> = params.iter().map(|p| (p.name.as_str(), p.value.as_str(), p.source, p.charset.as_deref(), p.decode_error)).collect();
+    assert_eq!(disp, CD::Attachment);
+    assert_eq!(summary, [
+        ("a", "1", ParameterSource::Regular, None, false),
+        ("b", "é", ParameterSource::Extended, Some("utf-8"), false),
+        ("c", "\u{FFFD}", ParameterSource::Extended, Some("x-bogus"), true),
+        ("d", "\u{FFFD}x", ParameterSource::Continuations, Some("utf-8"), true),
+        ("e", "é", ParameterSource::Extended, Some("utf-8"), false),
+    ]);
+}
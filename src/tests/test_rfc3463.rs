@@ -0,0 +1,39 @@
+use std::convert::TryFrom;
+
+use crate::behaviour::Legacy;
+use crate::dsn::delivery_status;
+use crate::reply::reply;
+use crate::rfc3463::*;
+
+#[test]
+fn parse() {
+    assert_eq!("2.0.0".parse(), Ok(EnhancedStatusCode::new(2, 0, 0).unwrap()));
+    assert_eq!("4.999.10".parse(), Ok(EnhancedStatusCode::new(4, 999, 10).unwrap()));
+    for bad in ["1.0.0", "5.1", "5.1000.1", "5.00.1", "5.1.1 ", "5..1"] {
+        assert!(bad.parse::<EnhancedStatusCode>().is_err(), "{}", bad);
+    }
+    assert!(EnhancedStatusCode::try_from(&b"5.7.26"[..]).unwrap().is_permanent());
+    assert_eq!(EnhancedStatusCode::new(3, 0, 0), None);
+    assert_eq!(EnhancedStatusCode::new(5, 1000, 0), None);
+}
+
+#[test]
+fn categories() {
+    let code = EnhancedStatusCode::new(5, 1, 1).unwrap();
+    assert_eq!(code.class, StatusClass::PermanentFailure);
+    assert_eq!(code.category(), Some(StatusSubject::Addressing));
+    assert!(!code.is_success() && !code.is_transient());
+    assert_eq!(EnhancedStatusCode::new(2, 8, 0).unwrap().category(), None);
+}
+
+#[test]
+fn sources() {
+    let (_, parsed) = reply::<Legacy>(b"451-4.3.0 Try again\r\n451 4.3.0 later\r\n").unwrap();
+    assert_eq!(parsed.enhanced_code(), EnhancedStatusCode::new(4, 3, 0));
+
+    let (_, parsed) = reply::<Legacy>(b"250 OK\r\n").unwrap();
+    assert_eq!(parsed.enhanced_code(), None);
+
+    let status = delivery_status(b"Reporting-MTA: dns; mx.example.org\n\nFinal-Recipient: rfc822; bob@example.org\nAction: failed\nStatus: 5.2.2\n");
+    assert_eq!(status.recipients[0].status_code().unwrap().category(), Some(StatusSubject::Mailbox));
+}
@@ -1,8 +1,10 @@
+use std::collections::HashSet;
 use std::str::FromStr;
 use std::net::{IpAddr, Ipv4Addr};
 
 use crate::behaviour::*;
 use crate::rfc5321::*;
+use crate::options::AddressOptions;
 use crate::types::*;
 
 fn dp<T: Into<String>>(value: T) -> DomainPart {
@@ -41,6 +43,20 @@ fn esmtp_param() {
     assert_eq!(params, [Param::new("ORCPT", Some("rfc822;mrbob+AD@example.org")).unwrap()]);
 }
 
+#[test]
+fn keyword_case() {
+    let lower: Keyword = "mt-priority".parse().unwrap();
+    let upper: Keyword = "MT-Priority".parse().unwrap();
+
+    assert_eq!(lower, upper);
+    assert_eq!(lower, Keyword::MT_PRIORITY);
+    assert_ne!(lower, Keyword::SIZE);
+    let set: HashSet<_> = std::iter::once(lower).chain(Some(upper)).collect();
+    assert_eq!(set.len(), 1);
+    assert!(set.contains(&Keyword::MT_PRIORITY));
+    assert_eq!(Param::new("size", Some("1")).unwrap(), Param::new("SIZE", Some("1")).unwrap());
+}
+
 #[test]
 fn address_literal_domain() {
     let (_, (path, params)) = rcpt_command::<Intl>(b"RCPT TO:<bob@[127.0.0.1]>\r\n").unwrap();
@@ -100,3 +116,382 @@ fn normal_quoted_lp() {
     lp.smtp_try_unquote();
     assert_eq!(lp, LocalPart::Quoted(QuotedString("a b".into())));
 }
+
+#[test]
+fn idna_rendering() {
+    let mbox = Mailbox::from_smtp("bob@xn--bcher-kva.example".as_bytes()).unwrap();
+    assert_eq!(mbox.to_unicode_string(), "bob@bücher.example");
+    assert_eq!(mbox.to_ascii_string(), Ok("bob@xn--bcher-kva.example".into()));
+
+    let literal = DomainPart::from_smtp(b"[IPv6:2001:db8::1]").unwrap();
+    assert_eq!(literal.to_ascii_string(), Ok("[IPv6:2001:db8::1]".into()));
+    assert_eq!(literal.to_unicode_string(), "[IPv6:2001:db8::1]");
+}
+
+#[test]
+fn canonical_reverse_path() {
+    let (_, (path, _)) = mail_command::<Intl>(b"MAIL FROM:<@relay.example:\"a.b\"@Sub.EXAMPLE.com>\r\n").unwrap();
+    let canonical = path.canonicalize();
+    assert_eq!(canonical, ReversePath::Path(Path(Mailbox(DotAtom("a.b".into()).into(), dp("sub.example.com")), vec![])));
+    assert_eq!(canonical.to_string(), "<a.b@sub.example.com>");
+
+    assert_eq!(ReversePath::Null.canonicalize(), ReversePath::Null);
+}
+
+#[test]
+fn canonical_keeps_needed_quotes() {
+    let path = ForwardPath::from_str("<\"a b\"@example.org>").unwrap();
+    assert_eq!(path.canonicalize().to_string(), "<\"a b\"@example.org>");
+
+    let path = ForwardPath::from_str("<bob@[X400:Value]>").unwrap();
+    assert_eq!(path.canonicalize().to_string(), "<bob@[x400:Value]>");
+}
+
+#[test]
+fn postmaster_paths() {
+    let domain = Domain::from_smtp(b"example.org").unwrap();
+
+    assert_eq!(ForwardPath::postmaster().to_string(), "<postmaster>");
+    assert_eq!(ForwardPath::postmaster_at(domain.clone()).to_string(), "<postmaster@example.org>");
+
+    let (_, (path, _)) = rcpt_command::<Intl>(b"RCPT TO:<pOsTmAsTeR@example.org>\r\n").unwrap();
+    assert_eq!(path, ForwardPath::postmaster_at(domain));
+    assert_eq!(path.to_string(), "<postmaster@example.org>");
+
+    let (_, (path, _)) = rcpt_command::<Intl>(b"RCPT TO:<\"Postmaster\"@example.org>\r\n").unwrap();
+    assert!(matches!(path, ForwardPath::Path(_)));
+    assert!(path.is_postmaster());
+
+    let (_, (path, _)) = rcpt_command::<Intl>(b"RCPT TO:<\"post master\"@example.org>\r\n").unwrap();
+    assert!(!path.is_postmaster());
+}
+
+#[test]
+fn lmtp_commands() {
+    use crate::lmtp::{command, lhlo_command, Command};
+
+    assert_eq!(lhlo_command::<Intl>(b"lhlo [192.0.2.1]\r\n").unwrap().1, DomainPart::from_smtp(b"[192.0.2.1]").unwrap());
+    assert!(matches!(command::<Intl>(b"RCPT TO:<bob@example.org>\r\n"), Ok((_, Command::RCPT(_, _)))));
+    assert!(matches!(command::<Intl>(b"QUIT\r\n"), Ok((_, Command::QUIT))));
+    assert!(command::<Intl>(b"HELO client.example\r\n").is_err());
+    assert!(command::<Intl>(b"EHLO client.example\r\n").is_err());
+}
+
+#[test]
+fn atrn() {
+    assert_eq!(atrn_command::<Intl>(b"atrn example.org,example.net\r\n").unwrap().1,
+               vec![Domain("example.org".into()), Domain("example.net".into())]);
+    assert_eq!(atrn_command::<Intl>(b"ATRN\r\n").unwrap().1, vec![]);
+    assert!(atrn_command::<Intl>(b"ATRN example.org,\r\n").is_err());
+    assert!(atrn_command::<Intl>(b"ATRN [192.0.2.1]\r\n").is_err());
+    assert!(matches!(command::<Intl>(b"ATRN example.org\r\n"), Ok((_, Command::ATRN(_)))));
+}
+
+#[test]
+fn raw_arguments() {
+    let (_, (raw, decoded)) = vrfy_command_raw::<Intl>(b"VRFY \"Smith, \\Fred\"\r\n").unwrap();
+    assert_eq!(raw, b"\"Smith, \\Fred\"");
+    assert_eq!(&*decoded, "Smith, Fred");
+
+    let (_, (raw, decoded)) = expn_command_raw::<Intl>(b"EXPN staff\r\n").unwrap();
+    assert_eq!((raw, &*decoded), (&b"staff"[..], "staff"));
+
+    assert_eq!(help_command_raw::<Intl>(b"HELP\r\n").unwrap().1, None);
+    assert_eq!(help_command_raw::<Intl>(b"HELP MAIL\r\n").unwrap().1.unwrap().0, b"MAIL");
+    assert_eq!(noop_command_raw::<Intl>(b"NOOP\r\n").unwrap().1, None);
+}
+
+#[test]
+fn bdat_size_overflow() {
+    assert_eq!(bdat_command(b"BDAT 18446744073709551615 LAST\r\n").unwrap().1, (u64::MAX, true));
+    assert!(bdat_command(b"BDAT 18446744073709551616\r\n").is_err());
+}
+
+#[test]
+fn bdat_receiver() {
+    let mut receiver = BdatReceiver::new();
+
+    assert_eq!(receiver.command(3, false), Ok(BdatStatus::Pending(3)));
+    assert_eq!(receiver.receive(b"ab"), Ok(BdatStatus::Pending(1)));
+    assert_eq!(receiver.command(2, true), Err(BdatError::Underrun(1)));
+    assert_eq!(receiver.receive(b"cde"), Err(BdatError::Overrun(2)));
+    assert_eq!(receiver.receive(b"c"), Ok(BdatStatus::ChunkComplete));
+    assert_eq!(receiver.finish(), Err(BdatError::MissingLast));
+    assert_eq!(receiver.command(0, true), Ok(BdatStatus::MessageComplete(3)));
+    assert_eq!(receiver.finish(), Ok(3));
+    assert_eq!(receiver.command(1, false), Err(BdatError::AfterLast));
+    assert_eq!(receiver.receive(b"x"), Err(BdatError::Overrun(1)));
+
+    receiver.reset();
+    assert_eq!(receiver.received(), 0);
+    assert_eq!(receiver.split(b"abc"), (&b""[..], &b"abc"[..]));
+    receiver.command(u64::MAX, true).unwrap();
+    assert_eq!(receiver.split(b"abc"), (&b"abc"[..], &b""[..]));
+    assert_eq!(receiver.receive(b"abc"), Ok(BdatStatus::Pending(u64::MAX - 3)));
+}
+
+#[test]
+fn streaming_commands() {
+    let input = b"MAIL FROM:<bob@example.org> SIZE=100\r\nRCPT TO:<alice@example.org>\r\n";
+
+    for end in 0..38 {
+        assert!(matches!(streaming(command::<Intl>)(&input[..end]), Err(nom::Err::Incomplete(_))), "{}", end);
+    }
+    let (rem, _) = streaming(mail_command::<Intl>)(&input[..45]).unwrap();
+    assert_eq!(rem, b"RCPT TO");
+    assert!(matches!(streaming(rcpt_command::<Intl>)(rem), Err(nom::Err::Incomplete(_))));
+
+    assert!(matches!(streaming(crate::lmtp::command::<Intl>)(b"LHLO client.example\r\n"), Ok((b"", crate::lmtp::Command::LHLO(_)))));
+    assert!(matches!(streaming(command::<Intl>)(b"MAIL FROM:<>\n\r\n"), Err(nom::Err::Error(_))));
+}
+
+#[test]
+fn pipelined_commands() {
+    let input = b"RSET\r\nMAIL FROM:<> SIZE=1\r\nRCPT TO:<a@example.org>\r\nRCPT TO:<b@exa";
+    let (rem, cmds) = commands::<Intl>(input).unwrap();
+    assert_eq!(cmds.len(), 3);
+    assert!(matches!(cmds[2], Command::RCPT(_, _)));
+    assert_eq!(rem, b"RCPT TO:<b@exa");
+
+    let (rem, cmds) = commands::<Intl>(b"MAIL FROM:<>\r\nBOGUS\r\nRSET\r\n").unwrap();
+    assert_eq!(cmds.len(), 1);
+    assert_eq!(rem, b"BOGUS\r\nRSET\r\n");
+
+    let (rem, cmds) = commands::<Intl>(b"EHLO client.example\r\nMAIL FROM:<>\r\n").unwrap();
+    assert_eq!(cmds.len(), 1);
+    assert_eq!(rem, b"MAIL FROM:<>\r\n");
+
+    let (rem, cmds) = commands::<Intl>(b"ATRN example.org\r\n220 mx.example.org\r\n").unwrap();
+    assert!(matches!(cmds[..], [Command::ATRN(_)]));
+    assert_eq!(rem, b"220 mx.example.org\r\n");
+
+    assert!(commands::<Intl>(b"").unwrap().1.is_empty());
+}
+
+#[test]
+fn command_to_wire() {
+    for input in &[&b"EHLO [192.0.2.1]\r\n"[..], b"HELO mx.example.org\r\n", b"MAIL FROM:<>\r\n",
+                   b"MAIL FROM:<\"a b\"@example.org> BODY=8BITMIME SMTPUTF8\r\n", b"RCPT TO:<postmaster>\r\n",
+                   b"RCPT TO:<bob@example.org> NOTIFY=SUCCESS,FAILURE\r\n", b"DATA\r\n", b"RSET\r\n", b"NOOP\r\n",
+                   b"NOOP x\r\n", b"QUIT\r\n", b"VRFY bob\r\n", b"EXPN \"list \\\"a\\\"\"\r\n", b"HELP\r\n",
+                   b"HELP MAIL\r\n", b"ATRN\r\n", b"ATRN a.example,b.example\r\n"] {
+        let (_, cmd) = command::<Intl>(input).unwrap();
+        assert_eq!(cmd.to_wire(), *input, "{}", String::from_utf8_lossy(input));
+    }
+
+    let path = Path::from_str("<@a.example,@b.example:bob@example.org>").unwrap();
+    assert_eq!(path.to_string(), "<@a.example,@b.example:bob@example.org>");
+    assert_eq!(Path::from_str("<bob@example.org>").unwrap().to_string(), "<bob@example.org>");
+}
+
+#[test]
+fn routed_paths() {
+    for input in &[&b"MAIL FROM:<@a.example:bob@example.org>\r\n"[..], b"RCPT TO:<@a.example,@b.example:bob@example.org>\r\n"] {
+        let (_, cmd) = command::<Intl>(input).unwrap();
+        assert_eq!(cmd.to_wire(), *input, "{}", String::from_utf8_lossy(input));
+    }
+
+    let mailbox = Mailbox::from_str("bob@example.org").unwrap();
+    let path = Path::with_route(vec![Domain::from_smtp(b"a.example").unwrap()], mailbox.clone());
+    assert_eq!(ReversePath::from(path.clone()).to_string(), "<@a.example:bob@example.org>");
+    assert_eq!(ForwardPath::from(path).canonicalize().to_string(), "<bob@example.org>");
+    assert_eq!(ReversePath::from(Path::new(mailbox)).to_string(), "<bob@example.org>");
+}
+
+#[test]
+fn lenient_commands() {
+    let parse = |input: &[u8]| lenient(command::<Intl>)(input).map(|(rem, cmd)| (rem.to_vec(), cmd.to_string()));
+
+    assert_eq!(parse(b"MAIL FROM: <bob@example.org>\r\n").unwrap().1, "MAIL FROM:<bob@example.org>");
+    assert_eq!(parse(b"mail from:bob@example.org BODY=8BITMIME \n").unwrap().1, "MAIL FROM:<bob@example.org> BODY=8BITMIME");
+    assert_eq!(parse(b"RCPT TO:  alice@example.org\nQUIT\n").unwrap(), (b"QUIT\n".to_vec(), "RCPT TO:<alice@example.org>".into()));
+    assert_eq!(parse(b"MAIL FROM:<>\n").unwrap().1, "MAIL FROM:<>");
+    assert_eq!(parse(b"QUIT\n").unwrap().1, "QUIT");
+
+    assert!(matches!(parse(b"MAIL FROM: bob@exa"), Err(nom::Err::Incomplete(_))));
+    assert!(matches!(parse(b"MAIL FROM:\n"), Err(nom::Err::Error(_))));
+    assert!(matches!(parse(b"MAIL FROM: bob\n"), Err(nom::Err::Error(_))));
+    assert!(matches!(parse(b"QUIT\r\r\n"), Err(nom::Err::Error(_))));
+}
+
+#[test]
+fn spanned_commands() {
+    let input = b"RCPT TO:<postmaster> NOTIFY=NEVER  ORCPT=rfc822;a@example.org\r\nQUIT\r\n";
+    let (rem, cmd) = rcpt_command_spanned::<Intl>(input).unwrap();
+
+    assert_eq!(rem, b"QUIT\r\n");
+    assert_eq!(&input[cmd.verb.clone()], b"RCPT TO:");
+    assert_eq!(cmd.path.value, ForwardPath::postmaster());
+    assert_eq!(&input[cmd.path.span.clone()], b"<postmaster>");
+    let params: Vec<_> = cmd.params.iter().map(|p| &input[p.span.clone()]).collect();
+    assert_eq!(params, [&b"NOTIFY=NEVER"[..], b"ORCPT=rfc822;a@example.org"]);
+    assert_eq!(cmd.into_parts().1.len(), 2);
+
+    let (_, cmd) = mail_command_spanned::<Intl>(b"mail from:<>\r\n").unwrap();
+    assert_eq!(cmd.path.span, 10..12);
+    assert!(cmd.params.is_empty());
+    assert!(mail_command_spanned::<Intl>(b"MAIL FROM:<> \r\n").is_err());
+}
+
+#[test]
+fn size_limits() {
+    use crate::limits::{DecodeError, Limit, SmtpLimits};
+
+    let limits = SmtpLimits::default();
+    let check = |input: String| command_limited::<Intl>(input.as_bytes(), &limits).map(|_| ());
+    let label = "a".repeat(63);
+    let long_domain = format!("{0}.{0}.{0}.{0}.com", label);
+
+    assert_eq!(check(format!("MAIL FROM:<{}@example.org>\r\n", "a".repeat(64))), Ok(()));
+    assert_eq!(check(format!("MAIL FROM:<\"{}\"@example.org>\r\n", "a".repeat(63))), Err(DecodeError::LimitExceeded(Limit::LocalPart)));
+    assert_eq!(check(format!("EHLO {}\r\n", long_domain)), Err(DecodeError::LimitExceeded(Limit::Domain)));
+    assert_eq!(check(format!("RCPT TO:<postmaster@{}>\r\n", long_domain)), Err(DecodeError::LimitExceeded(Limit::Domain)));
+    assert_eq!(check(format!("RCPT TO:<{}@{}.{}.{}.{}.org>\r\n", "a".repeat(64), label, label, label, "b".repeat(40))),
+               Err(DecodeError::LimitExceeded(Limit::Path)));
+    assert_eq!(check(format!("NOOP {}\r\n", "a".repeat(506))), Err(DecodeError::LimitExceeded(Limit::CommandLine)));
+    assert_eq!(check(format!("NOOP {}", "a".repeat(600))), Err(DecodeError::LimitExceeded(Limit::CommandLine)));
+    assert_eq!(check(format!("NOOP {}\r\n", "a".repeat(505))), Ok(()));
+    assert!(matches!(check("BOGUS\r\n".into()), Err(DecodeError::Syntax(_))));
+}
+
+#[test]
+fn helo_policy() {
+    let check = |input: &[u8]| validate_helo(&DomainPart::from_smtp(input).unwrap());
+
+    assert_eq!(check(b"mx.example.org"), Ok(()));
+    assert_eq!(check(b"xn--bcher-kva.example"), Ok(()));
+    assert_eq!(check(b"[IPv6:2001:db8::1]"), Ok(()));
+    assert_eq!(check(b"[x400:cn=bob]"), Err(HeloViolation::UnknownLiteral));
+    assert_eq!(check(b"10.0.0.1"), Err(HeloViolation::BareAddress));
+    assert_eq!(check(b"10.0.0"), Err(HeloViolation::NumericTld));
+    assert_eq!(check(b"mail"), Err(HeloViolation::SingleLabel));
+    assert_eq!(validate_helo(&dp("mail.")), Err(HeloViolation::SingleLabel));
+    assert_eq!(check(b"mail").unwrap_err().to_string(), "hostname is not fully qualified");
+
+    let (_, helo) = helo_command::<Intl>(b"HELO mail\r\n").unwrap();
+    assert!(!helo.is_fqdn());
+    assert!(!DomainPart::from(helo).is_address_literal());
+}
+
+#[test]
+fn extension_commands() {
+    let (rem, (verb, arg)) = extension_command(b"xclient ADDR=192.0.2.1 NAME=[UNAVAILABLE]\r\nQUIT\r\n").unwrap();
+    assert_eq!((verb, arg, rem), ("xclient", &b"ADDR=192.0.2.1 NAME=[UNAVAILABLE]"[..], &b"QUIT\r\n"[..]));
+
+    assert_eq!(extension_command(b"MAIL FROM:<>\r\n").unwrap().1, ("MAIL", &b"FROM:<>"[..]));
+    assert_eq!(extension_command(b"XFOO \r\n").unwrap().1, ("XFOO", &b""[..]));
+    assert_eq!(extension_command(b"XFOO  a\xff\r\n").unwrap().1, ("XFOO", &b" a\xff"[..]));
+    assert!(extension_command(b"XFOO a\rb\r\n").is_err());
+    assert!(extension_command(b"XFOO a\n").is_err());
+    assert!(extension_command(b"1FOO\r\n").is_err());
+    assert!(extension_command(b" XFOO\r\n").is_err());
+}
+
+#[test]
+fn address_diagnostics() {
+    for input in [&b"bob@example.org"[..], b"\"b\\\"ob\"@[IPv6:::1]"].iter() {
+        assert_eq!(validate_address_detailed::<Intl>(input), Ok(()));
+    }
+
+    let cases: &[(&[u8], usize, AddressRule)] = &[
+        (b"", 0, AddressRule::Empty),
+        (b".bob@example.org", 0, AddressRule::LocalPart),
+        (b"bob.@example.org", 3, AddressRule::LocalPart),
+        (b"b ob@example.org", 1, AddressRule::LocalPart),
+        (b"bob", 3, AddressRule::MissingAt),
+        (b"\"bob\"example.org", 5, AddressRule::MissingAt),
+        (b"\"bob", 4, AddressRule::QuotedString),
+        (b"\"b\x01ob\"@example.org", 2, AddressRule::QuotedString),
+        (b"bob@", 4, AddressRule::Domain),
+        (b"bob@-example.org", 4, AddressRule::Domain),
+        (b"bob@example.org>", 15, AddressRule::Domain),
+        (b"bob@[192.0.2.1", 14, AddressRule::AddressLiteral),
+        (b"bob@[192.0.2.1]x", 15, AddressRule::AddressLiteral),
+        (b"bob@[]", 5, AddressRule::AddressLiteral),
+    ];
+
+    for (input, offset, rule) in cases {
+        let err = validate_address_detailed::<Intl>(input).unwrap_err();
+        assert_eq!((err.offset, err.rule), (*offset, *rule), "{}", String::from_utf8_lossy(input));
+        assert!(!validate_address::<Intl>(input));
+    }
+    assert_eq!(validate_address_detailed::<Legacy>("bøb@example.org".as_bytes()).unwrap_err().offset, 1);
+}
+
+#[test]
+fn address_options() {
+    let default = AddressOptions::default();
+    let strict = AddressOptions::strict();
+    let rule = |input: &[u8], options: &AddressOptions| validate_address_with(input, options).map_err(|e| (e.offset, e.rule));
+
+    for input in [&b"\"a@b\"@[192.0.2.1]"[..], b"bob@localhost", "rené@bücher.example".as_bytes()].iter() {
+        assert_eq!(rule(input, &default), Ok(()));
+    }
+
+    assert_eq!(rule(b"\"a@b\"@example.org", &strict), Err((0, AddressRule::QuotedLocalPartForbidden)));
+    assert_eq!(rule(b"a@[192.0.2.1]", &strict), Err((2, AddressRule::AddressLiteralForbidden)));
+    assert_eq!(rule(b"a@[192.0.2.1]", &AddressOptions { require_dot_in_domain: true, ..default }), Ok(()));
+    assert_eq!(rule(b"a@localhost", &strict), Err((2, AddressRule::UnqualifiedDomain)));
+    assert_eq!(rule(b"a@b..c", &strict), Err((3, AddressRule::Domain)));
+    assert_eq!(rule("rené@example.org".as_bytes(), &AddressOptions { allow_utf8: false, ..default }),
+               Err((3, AddressRule::LocalPart)));
+
+    let local = "a".repeat(65);
+    assert_eq!(rule(format!("{}@example.org", local).as_bytes(), &strict), Err((64, AddressRule::LocalPartTooLong)));
+    assert_eq!(rule(format!("{}@example.org", &local[1..]).as_bytes(), &strict), Ok(()));
+
+    let domain = format!("{}.", "a".repeat(60)).repeat(4);
+    let address = format!("{}@{}", &local[..10], &domain[..200]);
+    assert_eq!(rule(address.as_bytes(), &strict), Ok(()));
+    let address = format!("{}@{}", &local[..60], &domain[..200]);
+    assert_eq!(rule(address.as_bytes(), &strict), Err((254, AddressRule::TooLong)));
+    let options = AddressOptions { max_domain_len: 100, ..strict };
+    assert_eq!(rule(address.as_bytes(), &options), Err((161, AddressRule::DomainTooLong)));
+}
+
+#[test]
+fn probe() {
+    let helo = dp("mx.bücher.example");
+    let probe = rcpt_probe(&helo, &Mailbox::from_smtp(b"\"Bob\"@[192.0.2.1]").unwrap());
+    let wire: Vec<_> = probe.commands().iter().flat_map(|c| c.to_wire()).collect();
+
+    assert_eq!(wire, b"EHLO mx.xn--bcher-kva.example\r\nMAIL FROM:<>\r\nRCPT TO:<Bob@[192.0.2.1]>\r\n");
+    assert!(!probe.smtputf8);
+
+    let probe = rcpt_probe(&helo, &Mailbox::from_smtp("\"ren\u{e9} smith\"@example.org".as_bytes()).unwrap());
+    assert_eq!(probe.rcpt.to_string(), "RCPT TO:<\"ren\u{e9} smith\"@example.org>");
+    assert!(probe.smtputf8);
+}
+
+#[test]
+fn classified_errors() {
+    let cases: &[(&[u8], CommandError, u16, &str)] = &[
+        (b"BOGUS\r\n", CommandError::UnknownVerb, 500, "5.5.1"),
+        (b"\r\n", CommandError::UnknownVerb, 500, "5.5.1"),
+        (b"MAILFROM:<bob@example.org>\r\n", CommandError::UnknownVerb, 500, "5.5.1"),
+        (b"QUIT\n", CommandError::BadSyntax("QUIT".into()), 501, "5.5.2"),
+        (b"helo\r\n", CommandError::BadSyntax("HELO".into()), 501, "5.5.2"),
+        (b"MAIL FROM:<bob@>\r\n", CommandError::BadSyntax("MAIL".into()), 501, "5.1.7"),
+        (b"MAIL FROM:<bob@example.org>x\r\n", CommandError::BadSyntax("MAIL".into()), 501, "5.1.7"),
+        (b"RCPT TO:<bob@example.org> =1\r\n", CommandError::BadParameter("RCPT".into()), 555, "5.5.4"),
+        (b"RCPT TO:<postmaster> NOTIFY=\r\n", CommandError::BadParameter("RCPT".into()), 555, "5.5.4"),
+    ];
+
+    for (input, error, code, status) in cases {
+        let err = match command_classified::<Intl>(input) {
+            Err(nom::Err::Error(err)) => err,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(&err, error, "{}", String::from_utf8_lossy(input));
+        assert_eq!(err.reply_code(), *code);
+        assert_eq!(err.status().to_string(), *status);
+    }
+
+    let err = command_classified::<Legacy>("MAIL FROM:<bob@example.org> ENVID=é\r\n".as_bytes()).unwrap_err();
+    assert_eq!(err, nom::Err::Error(CommandError::BadParameter("MAIL".into())));
+    assert!(command_classified::<Intl>("MAIL FROM:<bob@example.org> ENVID=é\r\n".as_bytes()).is_ok());
+
+    for partial in [&b""[..], b"QUIT", b"MAIL FROM:<bob@example.org>\r", b"BOGUS"] {
+        assert!(matches!(command_classified::<Intl>(partial), Err(nom::Err::Incomplete(_))));
+    }
+}
@@ -1,5 +1,14 @@
+use std::time::{Duration, UNIX_EPOCH};
+
 use crate::behaviour::{Intl, Legacy};
-use crate::rfc5322::{Address, Group, Mailbox, from, reply_to, sender, unstructured};
+use crate::headersection::header_section;
+use crate::limits::{DecodeError, DecoderLimits, Limit};
+use crate::rfc5322::{Address, Group, Mailbox, from, mailbox_with_route, reply_to, sender, unstructured, unstructured_limited};
+use crate::options::ParserOptions;
+use crate::rfc5322::parse_address_headers;
+use crate::rfc5322::{from_with, reply_to_with, sender_with, unstructured_with};
+use crate::rfc5322::{DeliveryHeader, delivered_to, delivery_addresses, envelope_to, x_original_to};
+use crate::rfc5322::{date_time, in_reply_to, message_id, references};
 use crate::types::{Mailbox as SMTPMailbox, *};
 
 fn dp<T: Into<String>>(value: T) -> DomainPart {
@@ -128,3 +137,233 @@ fn invalid_latin1() {
     assert_eq!(rem.len(), 0);
     assert_eq!(parsed, "\u{fffd}");
 }
+
+#[test]
+fn delivered_to_bare() {
+    let (rem, parsed) = delivered_to::<Intl>(b" bob@example.org\r\n").unwrap();
+    assert_eq!(rem.len(), 0);
+    assert_eq!(parsed, SMTPMailbox(DotAtom("bob".into()).into(), dp("example.org")));
+}
+
+#[test]
+fn x_original_to_angle() {
+    let (rem, parsed) = x_original_to::<Intl>(b" <alias@example.org>").unwrap();
+    assert_eq!(rem.len(), 0);
+    assert_eq!(parsed, SMTPMailbox(DotAtom("alias".into()).into(), dp("example.org")));
+}
+
+#[test]
+fn envelope_to_multi() {
+    let (rem, parsed) = envelope_to::<Intl>(b" a@example.org, <b@example.org>\r\n").unwrap();
+    assert_eq!(rem.len(), 0);
+    assert_eq!(parsed, [SMTPMailbox(DotAtom("a".into()).into(), dp("example.org")),
+                        SMTPMailbox(DotAtom("b".into()).into(), dp("example.org"))]);
+}
+
+#[test]
+fn delivery_addresses_section() {
+    let (_, headers) = header_section(b"delivered-to: a@example.org\r\nSubject: hi\r\nEnvelope-To: b@example.org, c@example.org\r\nDelivered-To: not an address\r\n\r\n").unwrap();
+    let found = delivery_addresses::<Intl>(&headers);
+    assert_eq!(found, [
+        (DeliveryHeader::DeliveredTo, SMTPMailbox(DotAtom("a".into()).into(), dp("example.org"))),
+        (DeliveryHeader::EnvelopeTo, SMTPMailbox(DotAtom("b".into()).into(), dp("example.org"))),
+        (DeliveryHeader::EnvelopeTo, SMTPMailbox(DotAtom("c".into()).into(), dp("example.org"))),
+    ]);
+}
+
+#[test]
+fn limited_subject_matches() {
+    let input = b" Re: =?utf-8?q?caf=C3=A9?=  =?utf-8?q?_cr=C3=A8me?= \xc3\xa9t\xc3\xa9 ";
+    let (rem, expected) = unstructured::<Intl>(input).unwrap();
+    assert_eq!(rem.len(), 0);
+
+    let (rem, parsed) = unstructured_limited::<Intl>(input, &DecoderLimits::default()).unwrap();
+    assert_eq!(rem.len(), 0);
+    assert_eq!(parsed, expected);
+}
+
+#[test]
+fn limited_subject_too_long() {
+    let limits = DecoderLimits { max_decoded_len: 8, ..Default::default() };
+    assert_eq!(unstructured_limited::<Intl>(b"short", &limits).unwrap().1, "short");
+    assert_eq!(unstructured_limited::<Intl>(b"=?utf-8?b?dGhpcyBpcyB0b28gbG9uZw==?=", &limits),
+               Err(DecodeError::LimitExceeded(Limit::DecodedLen)));
+}
+
+#[test]
+fn batch_strict() {
+    let inputs: &[&[u8]] = &[b"a@example.org, b@example.org\r\n", b"", b"Group: c@example.org;"];
+    let parsed = parse_address_headers::<Intl>(inputs, false);
+    assert_eq!(parsed.len(), 3);
+    assert_eq!(parsed[0].as_ref().unwrap().len(), 2);
+    assert!(parsed[1].is_err());
+    assert!(matches!(parsed[2].as_ref().unwrap()[0], Address::Group(_)));
+}
+
+#[test]
+fn batch_recover() {
+    let inputs: &[&[u8]] = &[b"\"Doe, John\" <jdoe@example.org>, <<bad>>, x@, (a, b) c@example.org", b"garbage"];
+    let parsed = parse_address_headers::<Intl>(inputs, true);
+    let recovered = parsed[0].as_ref().unwrap();
+    assert_eq!(recovered.len(), 2);
+    assert_eq!(recovered[0], Address::Mailbox(Mailbox { dname: Some("Doe, John".into()),
+                                                        address: SMTPMailbox(DotAtom("jdoe".into()).into(), dp("example.org")) }));
+    assert_eq!(recovered[1], Address::Mailbox(Mailbox { dname: None,
+                                                        address: SMTPMailbox(DotAtom("c".into()).into(), dp("example.org")) }));
+    assert!(parsed[1].is_err());
+}
+
+#[test]
+fn options_utf8_policy() {
+    let intl = ParserOptions::default();
+    let legacy = ParserOptions { allow_utf8: false, ..Default::default() };
+    let input = "Ren\u{e9} <ren\u{e9}@example.org>".as_bytes();
+
+    assert!(from_with(input, &intl).is_ok());
+    assert!(matches!(from_with(input, &legacy), Err(DecodeError::Syntax(_))));
+    assert_eq!(unstructured_with(b"caf\xc3\xa9", &intl).unwrap().1, "caf\u{e9}");
+}
+
+#[test]
+fn options_comment_depth() {
+    let options = ParserOptions { max_comment_depth: 1, ..Default::default() };
+    assert!(sender_with(b"bob@example.org (one) (two)", &options).is_ok());
+    assert!(sender_with(b"\"((\" <bob@example.org>", &options).is_ok());
+    assert_eq!(reply_to_with(b"bob@example.org ((deep))", &options),
+               Err(DecodeError::LimitExceeded(Limit::CommentDepth)));
+}
+
+#[test]
+fn options_limits() {
+    let limits = DecoderLimits { max_encoded_words: 1, max_decoded_len: 8, ..Default::default() };
+    let options = ParserOptions { limits, ..Default::default() };
+
+    assert!(from_with(b"=?utf-8?b?Ym9i?= <bob@example.org>", &options).is_ok());
+    assert_eq!(from_with(b"=?utf-8?q?a?= =?utf-8?q?b?= <bob@example.org>", &options),
+               Err(DecodeError::LimitExceeded(Limit::EncodedWords)));
+    assert_eq!(sender_with(b"Bob Example <bob@example.org>", &options),
+               Err(DecodeError::LimitExceeded(Limit::DecodedLen)));
+}
+
+#[test]
+fn options_recover_addresses() {
+    let options = ParserOptions { recover_addresses: true, ..Default::default() };
+    let input = b"bob@example.org, invalid, Alice <alice@example.org>";
+
+    assert!(from_with(input, &ParserOptions::default()).unwrap().0.starts_with(b","));
+    let (rem, list) = reply_to_with(input, &options).unwrap();
+    assert!(rem.is_empty());
+    assert_eq!(list.len(), 2);
+    assert!(matches!(from_with(b"invalid", &options), Err(DecodeError::Syntax(_))));
+}
+
+#[test]
+fn options_quoted_string_rfc2047() {
+    let input = b"\"=?utf-8?q?caf=C3=A9?=\" <a@example.org>";
+    let name = |options: &ParserOptions| match sender_with(input, options).unwrap().1 {
+        Address::Mailbox(m) => m.dname.unwrap(),
+        _ => unreachable!(),
+    };
+
+    assert_eq!(name(&ParserOptions { quoted_string_rfc2047: true, ..Default::default() }), "caf\u{e9}");
+    assert_eq!(name(&ParserOptions { quoted_string_rfc2047: false, ..Default::default() }), "=?utf-8?q?caf=C3=A9?=");
+}
+
+#[test]
+fn sniffed_subject() {
+    let sniffer = crate::sniff::Sniffer::default();
+    let (rem, (text, charset)) = crate::rfc5322::unstructured_sniffed(b"Gr\xfc\xdfe aus M\xfcnchen\r\n", &sniffer).unwrap();
+
+    assert_eq!(rem, b"\r\n");
+    assert_eq!(text, "Gr\u{fc}\u{df}e aus M\u{fc}nchen");
+    assert_eq!(charset, encoding_rs::WINDOWS_1252);
+}
+
+#[test]
+fn encode_contexts() {
+    use crate::rfc2047::{encode, EncodeContext};
+    use crate::rfc5322::comment;
+
+    let text = "Doe, J\u{f6}rg (R&D) \"x\" a=b?_\\";
+
+    let phrase = encode(text, EncodeContext::Phrase);
+    let input = format!("{} <jorg@example.org>", phrase);
+    assert_eq!(parse_single(from::<Intl>, input.as_bytes()).dname.unwrap(), text);
+
+    let unstructured_text = encode(text, EncodeContext::Text);
+    assert_eq!(unstructured::<Intl>(unstructured_text.as_bytes()).unwrap(), (&b""[..], text.into()));
+
+    let comment_text = format!("({})", encode(text, EncodeContext::Comment));
+    assert_eq!(comment::<Intl>(comment_text.as_bytes()).unwrap().0, b"");
+    assert!(!comment_text[1..comment_text.len()-1].contains(&['(', ')', '\\'][..]));
+}
+
+#[test]
+fn obs_route() {
+    let (rem, (route, parsed)) = mailbox_with_route::<Intl>(b"Bob <@hop1.example,@[192.0.2.1]:bob@example.org>\r\n").unwrap();
+    assert_eq!(rem, b"\r\n");
+    assert_eq!(parsed.address, SMTPMailbox(DotAtom("bob".into()).into(), dp("example.org")));
+    assert_eq!(route, [dp("hop1.example"), DomainPart::Address(AddressLiteral::IP("192.0.2.1".parse().unwrap()))]);
+    assert_eq!(parsed.to_string(), "Bob <bob@example.org>");
+
+    // obs-domain-list tolerates leading and empty list elements.
+    let (_, (route, _)) = mailbox_with_route::<Intl>(b"<,, @a.example,,@b.example : bob@example.org>").unwrap();
+    assert_eq!(route, [dp("a.example"), dp("b.example")]);
+
+    assert!(mailbox_with_route::<Intl>(b"<bob@example.org>").unwrap().1.0.is_empty());
+    assert!(mailbox_with_route::<Intl>(b"bob@example.org").unwrap().1.0.is_empty());
+    assert_eq!(parse_single(from::<Intl>, b"Bob <@hop1.example:bob@example.org>").to_string(), "Bob <bob@example.org>");
+    assert!(from::<Intl>(b"<hop1.example:bob@example.org>").map_or(true, |(rem, _)| !rem.is_empty()));
+}
+
+#[test]
+fn merge_lists() {
+    use crate::rfc5322::{merge_address_lists, AddressList};
+
+    let (_, to) = from::<Intl>(b"a@example.org, \"A\" <A@EXAMPLE.org>, Empty:;").unwrap();
+    let (_, cc) = from::<Intl>(b"Dup: a@example.org;, \"b\"@[192.0.2.1], b@[192.0.2.1]").unwrap();
+
+    let merged = merge_address_lists(&[&to, &cc], false);
+    assert_eq!(AddressList(&merged).to_string(), "a@example.org, Empty:;, Dup:;, \"b\"@[192.0.2.1]");
+
+    let merged = merge_address_lists(&[&to, &cc], true);
+    assert_eq!(AddressList(&merged).to_string(), "a@example.org, \"b\"@[192.0.2.1]");
+
+    assert!(merge_address_lists(&[], true).is_empty());
+}
+
+#[test]
+fn message_ids() {
+    assert_eq!(message_id::<Intl>(b" <a.b@[1.2.3.4]>").unwrap().1, "a.b@[1.2.3.4]");
+    assert_eq!(message_id::<Intl>(b" < \"a b\" @ example.org >\r\n").unwrap().1, "\"a b\"@example.org");
+    assert!(message_id::<Intl>(b" a@example.org").is_err());
+    assert_eq!(message_id::<Intl>(" <1.café@bücher.example>".as_bytes()).unwrap().1, "1.café@bücher.example");
+    assert!(message_id::<Legacy>(" <1.café@bücher.example>".as_bytes()).is_err());
+    assert_eq!(references::<Intl>(b"<1@x><2@x>").unwrap().1, ["1@x", "2@x"]);
+    assert!(references::<Intl>(b"").is_err());
+    assert_eq!(in_reply_to::<Intl>(b" message from \"bob\"").unwrap().1, Vec::<String>::new());
+}
+
+#[test]
+fn dates() {
+    let secs = |input: &[u8]| date_time(input).unwrap().1.duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+    assert_eq!(secs(b"Thu, 01 Jan 1970 00:00:00 +0000"), 0);
+    assert_eq!(secs(b"Tue, 29 Feb 2000 00:00:00 +0000"), 951_782_400);
+    assert_eq!(secs(b" 1 Jan 1970 00:00 -0130"), 5400);
+    assert_eq!(secs(b"1 jan 70 (comment) 00:00:00 (another) gmt"), 0);
+    assert_eq!(secs(b"1 Jan 070 00:00:00 Z"), 0);
+    assert_eq!(secs(b"Sat, 31 Dec 2016 23:59:60 +0000"), 1_483_228_799);
+    assert_eq!(date_time(b"1 Jan 1970 00:00:00 +0000 (UTC)\r\nrest").unwrap().0, b"rest");
+    assert_eq!(date_time(b"31 Dec 1969 23:59:59 +0000").unwrap().1, UNIX_EPOCH - Duration::from_secs(1));
+
+    let date = |input: &[u8]| date_time(input).unwrap().1;
+    assert_eq!(date(b"1 Jan 049 00:00:00 +0000"), date(b"1 Jan 1949 00:00:00 +0000"));
+    assert_eq!(date(b"1 Jan 49 00:00:00 +0000"), date(b"1 Jan 2049 00:00:00 +0000"));
+    assert_eq!(date(b"1 Jan 0049 00:00:00 +0000") + Duration::from_secs(59_958_144_000), date(b"1 Jan 1949 00:00:00 +0000"));
+
+    for bad in [&b"29 Feb 2100 00:00:00 +0000"[..], b"1 Foo 2000 00:00:00 +0000", b"1 Jan 2000 24:00:00 +0000",
+                b"1 Jan 2000 00:00:00 +0060", b"1 Jan 2000 00:00:00 CEST", b"1 Jan 2000 00:00:00 J", b"1 Jan 2000"] {
+        assert!(date_time(bad).is_err(), "{}", String::from_utf8_lossy(bad));
+    }
+}
@@ -0,0 +1,178 @@
+// Exercise the `serialize(parse(x)) == normalize(x)` invariant and
+// check that the normalized form parses back to the same value.
+
+use std::fmt::{Debug, Display};
+
+use crate::behaviour::Intl;
+use crate::rfc2231::{content_disposition, content_type, encode_parameter_list};
+use crate::rfc5321::{mail_command, rcpt_command, Params};
+use crate::rfc5322::{from, AddressList};
+use crate::xforward::xforward_params;
+
+fn roundtrip<T, F>(parse: F, corpus: &[(&[u8], &str)])
+    where F: Fn(&[u8]) -> T,
+          T: Display + Debug + PartialEq,
+{
+    for (input, normalized) in corpus {
+        let parsed = parse(input);
+        assert_eq!(parsed.to_string(), *normalized, "serializing {:?}", String::from_utf8_lossy(input));
+        assert_eq!(parse(normalized.as_bytes()), parsed, "reparsing {:?}", normalized);
+    }
+}
+
+// Wrap an owned address list to display it.
+#[derive(Debug, PartialEq)]
+struct Addresses(Vec<crate::rfc5322::Address>);
+
+impl Display for Addresses {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", AddressList(&self.0))
+    }
+}
+
+#[test]
+fn imf_addresses() {
+    roundtrip(|i| Addresses(from::<Intl>(i).unwrap().1), &[
+        (b"John Doe <jdoe@machine.example>\r\n", "John Doe <jdoe@machine.example>"),
+        (b"\"Mary Smith: Personal Account\" <smith@home.example>", "\"Mary Smith: Personal Account\" <smith@home.example>"),
+        (b"\"Joe Q. Public\" <john.q.public@example.com>", "\"Joe Q. Public\" <john.q.public@example.com>"),
+        (b"Pete(A nice \\) chap) <pete(his account)@silly.test(his host)>", "Pete <pete@silly.test>"),
+        (b"  A Group(Some people)\r\n    :Chris Jones <c@(Chris's host.)public.example>,\r\n        joe@example.org,\r\n John <jdoe@one.test> (my dear friend); (the end of the group)\r\n",
+         "A Group: Chris Jones <c@public.example>, joe@example.org, John <jdoe@one.test>;"),
+        (b"Undisclosed recipients:;", "Undisclosed recipients:;"),
+        (b"=?utf-8?q?caf=C3=A9?= <a@example.org>, b@[192.0.2.1]", "café <a@example.org>, b@[192.0.2.1]"),
+        (b"\"bob\\\"s\"   \"\" <\"odd local\"@example.org>", "\"bob\\\"s\" <\"odd local\"@example.org>"),
+        (b"\"\" <a@example.org>", "\"\" <a@example.org>"),
+        ("Ren\u{e9}  Dupont <ren\u{e9}@example.org>".as_bytes(), "Ren\u{e9} Dupont <ren\u{e9}@example.org>"),
+    ]);
+}
+
+#[derive(Debug, PartialEq)]
+struct Envelope(String, Vec<crate::rfc5321::Param>);
+
+impl Display for Envelope {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.1.is_empty() {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "{} {}", self.0, Params(&self.1))
+        }
+    }
+}
+
+#[test]
+fn smtp_mail() {
+    roundtrip(|i| {
+        let mut line = b"MAIL FROM:".to_vec();
+        line.extend_from_slice(i);
+        line.extend_from_slice(b"\r\n");
+        let (_, (path, params)) = mail_command::<Intl>(&line).unwrap();
+        Envelope(path.to_string(), params)
+    }, &[
+        (b"<>", "<>"),
+        (b"<bob@example.org> BODY=8BITMIME  SIZE=1000", "<bob@example.org> BODY=8BITMIME SIZE=1000"),
+        (b"<\"a\\\\b\"@[IPv6:2001:db8:0::1]> SMTPUTF8", "<\"a\\\\b\"@[IPv6:2001:db8::1]> SMTPUTF8"),
+    ]);
+}
+
+#[test]
+fn smtp_rcpt() {
+    roundtrip(|i| {
+        let mut line = b"RCPT TO:".to_vec();
+        line.extend_from_slice(i);
+        line.extend_from_slice(b"\r\n");
+        let (_, (path, params)) = rcpt_command::<Intl>(&line).unwrap();
+        Envelope(path.to_string(), params)
+    }, &[
+        (b"<postmaster>", "<postmaster>"),
+        (b"<Postmaster@example.org>", "<postmaster@example.org>"),
+        (b"<bob@example.org> NOTIFY=SUCCESS,FAILURE ORCPT=rfc822;bob+2B@example.org", "<bob@example.org> NOTIFY=SUCCESS,FAILURE ORCPT=rfc822;bob+2B@example.org"),
+    ]);
+}
+
+#[derive(Debug, PartialEq)]
+struct Mime(String, Vec<(String, String)>);
+
+impl Display for Mime {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}", self.0, encode_parameter_list(&self.1))
+    }
+}
+
+#[test]
+fn mime_content_type() {
+    roundtrip(|i| {
+        let (_, (mtype, mut params)) = content_type(i).unwrap();
+        params.sort();
+        Mime(mtype, params)
+    }, &[
+        (b" Text/Plain; charset=\"us-ascii\"", "text/plain; charset=us-ascii"),
+        (b"multipart/mixed;\r\n boundary=\"----=_Part_0\"", "multipart/mixed; boundary=\"----=_Part_0\""),
+        (b"application/pdf; name*=iso-8859-1''%E9t%E9.pdf", "application/pdf; name*=utf-8''%C3%A9t%C3%A9.pdf"),
+    ]);
+}
+
+#[test]
+fn mime_content_disposition() {
+    roundtrip(|i| {
+        let (_, (disp, params)) = content_disposition(i).unwrap();
+        Mime(disp.to_string(), params)
+    }, &[
+        (b"ATTACHMENT; filename*0*=utf-8''%C3%A9; filename*1=\"t.txt\"", "attachment; filename*=utf-8''%C3%A9t.txt"),
+        (b"inline", "inline"),
+    ]);
+}
+
+#[derive(Debug)]
+struct XForward(Vec<crate::xforward::Param>);
+
+impl PartialEq for XForward {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len() && self.0.iter().zip(&other.0).all(|(a, b)| a.0 == b.0 && a.1 == b.1)
+    }
+}
+
+impl Display for XForward {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let params: Vec<_> = self.0.iter().map(|p| p.to_string()).collect();
+        write!(f, "{}", params.join(" "))
+    }
+}
+
+#[test]
+fn xforward() {
+    roundtrip(|i| XForward(xforward_params(i).unwrap().1), &[
+        (b"addr=192.0.2.1  name=[unavailable]", "ADDR=192.0.2.1 NAME=[UNAVAILABLE]"),
+        (b"HELO=mail+2Bexample PROTO=ESMTP", "HELO=mail+2Bexample PROTO=ESMTP"),
+    ]);
+}
+
+#[test]
+fn imf_addresses_ascii() {
+    let corpus: &[(&str, &str)] = &[
+        ("Caf\u{e9} Cr\u{e8}me: a@example.org, \"D. Smith\" <d@example.org>;",
+         "=?utf-8?q?Caf=C3=A9_Cr=C3=A8me?=: a@example.org, \"D. Smith\" <d@example.org>;"),
+        ("\u{e9}quipe:;", "=?utf-8?q?=C3=A9quipe?=:;"),
+        ("Bob <bob@b\u{fc}cher.example>", "Bob <bob@xn--bcher-kva.example>"),
+    ];
+
+    for (input, ascii) in corpus {
+        let parsed = from::<Intl>(input.as_bytes()).unwrap().1;
+        let rendered = AddressList(&parsed).to_ascii_string().unwrap();
+        assert_eq!(rendered, *ascii);
+
+        // Only the domain changes form when reparsed.
+        let reparsed = from::<Intl>(rendered.as_bytes()).unwrap().1;
+        assert_eq!(AddressList(&reparsed).to_ascii_string().unwrap(), rendered);
+    }
+}
+
+#[test]
+fn long_display_name_ascii() {
+    let name = "\u{e9}".repeat(40);
+    let parsed = from::<Intl>(format!("{} <a@example.org>", name).as_bytes()).unwrap().1;
+    let rendered = AddressList(&parsed).to_ascii_string().unwrap();
+
+    assert!(rendered.split(' ').all(|word| word.len() <= 75));
+    assert_eq!(from::<Intl>(rendered.as_bytes()).unwrap().1, parsed);
+}
@@ -0,0 +1,97 @@
+use crate::behaviour::Legacy;
+use crate::reply::{reply, Reply, ReplyCode};
+use crate::sasl::*;
+
+#[test]
+fn plain_identities() {
+    let response = encode_response(b"admin\0tim\0p\xc3\xa4ss");
+    assert_eq!(plain(response.as_bytes()), Ok(Plain {
+        authzid: Some("admin".into()),
+        authcid: "tim".into(),
+        password: "päss".into(),
+    }));
+
+    assert_eq!(plain(encode_response(b"\0\0pass").as_bytes()), Err(SaslError::Syntax));
+    assert_eq!(plain(encode_response(b"a\0b\0c\0d").as_bytes()), Err(SaslError::Syntax));
+    assert_eq!(plain(encode_response(b"tim\0pass").as_bytes()), Err(SaslError::Syntax));
+    assert_eq!(plain(encode_response(b"\0tim\0\xff").as_bytes()), Err(SaslError::Utf8));
+    assert_eq!(plain(b"="), Err(SaslError::Syntax));
+    assert_eq!(plain(b"*"), Err(SaslError::Cancelled));
+}
+
+#[test]
+fn login_exchange() {
+    let mut exchange = LoginExchange::new();
+    assert_eq!(exchange.challenge(), LOGIN_USERNAME_CHALLENGE);
+    assert_eq!(base64::decode(exchange.challenge()).unwrap(), b"Username:");
+
+    assert_eq!(exchange.respond(b"dGlt"), Ok(None));
+    assert_eq!(base64::decode(exchange.challenge()).unwrap(), b"Password:");
+    assert_eq!(exchange.respond(b"=").unwrap_err(), SaslError::Syntax);
+    assert_eq!(exchange.respond(b"c2VjcmV0").unwrap().unwrap().username, "tim");
+    assert_eq!(exchange.challenge(), LOGIN_USERNAME_CHALLENGE);
+
+    assert_eq!(LoginExchange::with_initial_response(b"!!"), Err(SaslError::Base64));
+}
+
+#[test]
+fn cram_md5() {
+    let challenge = encode_cram_md5_challenge("<1.2@example.org>");
+    assert_eq!(cram_md5_challenge(challenge.as_bytes()), Ok("<1.2@example.org>".into()));
+    assert_eq!(cram_md5_challenge(encode_response(b"1.2@example.org").as_bytes()), Err(SaslError::Syntax));
+
+    let response = encode_response(b"first last 00112233445566778899aabbccddeeff");
+    let response = cram_md5_response(response.as_bytes()).unwrap();
+    assert_eq!(response.username, "first last");
+    assert_eq!(response.digest[15], 0xff);
+
+    for invalid in [&b"tim 0011"[..], b" 00112233445566778899aabbccddeeff", b"tim 00112233445566778899AABBCCDDEEFF"].iter() {
+        assert_eq!(cram_md5_response(encode_response(invalid).as_bytes()), Err(SaslError::Syntax));
+    }
+}
+
+#[test]
+fn login_dialogue() {
+    let client = b"AUTH LOGIN\r\ndGlt\r\nc2VjcmV0\r\n";
+
+    let (mut input, (mechanism, initial)) = auth_command(client).unwrap();
+    assert_eq!((mechanism.as_str(), initial), ("LOGIN", None));
+
+    let mut exchange = LoginExchange::new();
+    let mut client_seen = Vec::new();
+    let login = loop {
+        // The server challenges, the client decodes it.
+        let sent = Reply::new(ReplyCode::AUTH_CHALLENGE, vec![exchange.challenge()]);
+        let (_, received) = reply::<Legacy>(&sent.to_smtp::<Legacy>().unwrap()).unwrap();
+        client_seen.push(challenge(&received).unwrap().unwrap());
+
+        let (rem, line) = continuation(input).unwrap();
+        input = rem;
+        if let Some(login) = exchange.respond(line.as_bytes()).unwrap() {
+            break login;
+        }
+    };
+
+    assert!(input.is_empty());
+    assert_eq!(client_seen, [b"Username:".to_vec(), b"Password:".to_vec()]);
+    assert_eq!(login, Login { username: "tim".into(), password: "secret".into() });
+}
+
+#[test]
+fn exchange_lines() {
+    assert_eq!(auth_command(b"auth cram-md5\r\n").unwrap().1, ("CRAM-MD5".into(), None));
+    assert!(auth_command(b"AUTH PLAIN \r\n").is_err());
+    assert!(auth_command(b"AUTH PLAIN AH=RpbQ\r\n").is_err());
+    assert!(auth_command(b"AUTH X-A-MECHANISM-NAME-TOO-LONG\r\n").is_err());
+
+    assert_eq!(continuation(b"\r\n").unwrap().1, Continuation::Response(b""));
+    assert_eq!(continuation(b"\r\n").unwrap().1.decode(), Ok(vec![]));
+    assert_eq!(continuation(b"*\r\n").unwrap().1.decode(), Err(SaslError::Cancelled));
+    assert_eq!(plain(continuation(b"*\r\n").unwrap().1.as_bytes()), Err(SaslError::Cancelled));
+    assert!(continuation(b"**\r\n").is_err());
+
+    assert_eq!(challenge(&Reply::new(334, vec![""])), Some(Ok(vec![])));
+    assert_eq!(challenge(&Reply::new(334, vec!["Go ahead"])), Some(Err(SaslError::Base64)));
+    assert_eq!(challenge(&Reply::new(334, vec!["aGk=", "aGk="])), Some(Err(SaslError::Base64)));
+    assert_eq!(challenge(&Reply::new(535, vec!["5.7.8 Authentication credentials invalid"])), None);
+}
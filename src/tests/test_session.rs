@@ -0,0 +1,54 @@
+use crate::behaviour::Intl;
+use crate::limits::{Limit, SessionLimits, SmtpLimits};
+use crate::rfc5321::Command;
+use crate::session::*;
+
+fn guard(session: SessionLimits) -> SessionGuard {
+    SessionGuard::new(SmtpLimits::default(), session)
+}
+
+#[test]
+fn pipelined() {
+    let mut guard = guard(SessionLimits::default());
+    let input = b"MAIL FROM:<>\r\nRCPT TO:<bob@example.org\r\nRCPT TO:<bob@example.org>\r\nDATA";
+
+    let checked = guard.parse::<Intl>(input);
+    assert!(matches!(checked.command, Ok(SessionCommand::Base(Command::MAIL(..)))));
+
+    let checked = guard.parse::<Intl>(checked.rem);
+    assert_eq!(checked.command.unwrap_err(), SessionError::Syntax);
+
+    let checked = guard.parse::<Intl>(checked.rem);
+    assert!(matches!(checked.command, Ok(SessionCommand::Base(Command::RCPT(..)))));
+
+    let checked = guard.parse::<Intl>(checked.rem);
+    assert_eq!(checked.command.unwrap_err(), SessionError::Incomplete);
+    assert_eq!(checked.rem, b"DATA");
+
+    assert_eq!(guard.counters(), SessionCounters { commands: 3, syntax_errors: 1, unknown_verbs: 0 });
+    assert_eq!(guard.verdict(), Verdict::Continue);
+}
+
+#[test]
+fn escalation() {
+    let mut guard = guard(SessionLimits { warn_errors: 1, slow_errors: 2, max_errors: 3, ..SessionLimits::default() });
+    let verdicts: Vec<_> = (0..4).map(|_| guard.parse::<Intl>(b"HELO\r\n").verdict).collect();
+
+    assert_eq!(verdicts, [Verdict::Warn, Verdict::SlowDown, Verdict::Drop, Verdict::Drop]);
+    assert_eq!(guard.counters().syntax_errors, 4);
+}
+
+#[test]
+fn command_caps() {
+    let mut guard = guard(SessionLimits { max_commands: 2, ..SessionLimits::default() });
+    assert_eq!(guard.parse::<Intl>(b"NOOP\r\n").verdict, Verdict::Continue);
+    assert_eq!(guard.parse::<Intl>(b"NOOP\r\n").verdict, Verdict::Continue);
+    assert_eq!(guard.parse::<Intl>(b"NOOP\r\n").verdict, Verdict::Drop);
+
+    let mut guard = self::guard(SessionLimits::default());
+    let long = vec![b'a'; 600];
+    let checked = guard.parse::<Intl>(&long);
+    assert_eq!(checked.command.unwrap_err(), SessionError::LimitExceeded(Limit::CommandLine));
+    assert!(checked.rem.is_empty());
+    assert_eq!(guard.counters().syntax_errors, 1);
+}
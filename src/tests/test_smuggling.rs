@@ -0,0 +1,40 @@
+use crate::smuggling::*;
+
+fn kinds(data: &[u8]) -> Vec<(LineEnding, LineEnding)> {
+    eod_lookalikes(data).into_iter().map(|l| (l.before, l.after)).collect()
+}
+
+#[test]
+fn proper_eod_is_not_lookalike() {
+    assert_eq!(kinds(b"line\r\n.\r\n"), []);
+    assert_eq!(kinds(b"a.b\r\n..\r\n"), []);
+}
+
+#[test]
+fn all_lookalikes() {
+    assert_eq!(kinds(b"a\n.\nb"), [(LineEnding::Lf, LineEnding::Lf)]);
+    assert_eq!(kinds(b"a\r.\rb"), [(LineEnding::Cr, LineEnding::Cr)]);
+    assert_eq!(kinds(b"a\r\n.\nb"), [(LineEnding::CrLf, LineEnding::Lf)]);
+    assert_eq!(kinds(b"a\n.\r\nb"), [(LineEnding::Lf, LineEnding::CrLf)]);
+    assert_eq!(kinds(b"a\r\n.\rb"), [(LineEnding::CrLf, LineEnding::Cr)]);
+}
+
+#[test]
+fn lookalike_at_start() {
+    assert_eq!(eod_lookalikes(b".\nrest"), [EodLookalike { offset: 0, len: 2, before: LineEnding::CrLf, after: LineEnding::Lf }]);
+}
+
+#[test]
+fn lookalike_span() {
+    let data = b"x\r\n.\ny";
+    let found = eod_lookalikes(data);
+    assert_eq!(found.len(), 1);
+    assert_eq!(&data[found[0].offset..found[0].offset+found[0].len], b"\r\n.\n");
+    assert!(has_eod_lookalike(data));
+}
+
+#[test]
+fn strict_eod_skips_lookalikes() {
+    let data = b"a\n.\nb\r.\rc\r\n.\r\n";
+    assert_eq!(find_end_of_data(data), Some(9));
+}
@@ -0,0 +1,24 @@
+use encoding_rs::{ISO_8859_2, KOI8_R, UTF_8, WINDOWS_1250, WINDOWS_1252};
+
+use crate::sniff::*;
+
+#[test]
+fn utf8_first() {
+    assert_eq!(Sniffer::default().decode(b"plain"), ("plain".into(), UTF_8));
+    assert_eq!(Sniffer::default().decode("Gr\u{fc}\u{df}e".as_bytes()).1, UTF_8);
+}
+
+#[test]
+fn chain_order() {
+    let sniffer = Sniffer::new(vec![KOI8_R, WINDOWS_1252]);
+    assert_eq!(sniffer.decode(b"\xf0\xd2\xc9\xd7\xc5\xd4"), ("\u{41f}\u{440}\u{438}\u{432}\u{435}\u{442}".into(), KOI8_R));
+
+    // 0x8d is a control character in Windows-1252.
+    let sniffer = Sniffer::new(vec![WINDOWS_1252, WINDOWS_1250, ISO_8859_2]);
+    assert_eq!(sniffer.decode(b"\x8d\xe1"), ("\u{164}\u{e1}".into(), WINDOWS_1250));
+}
+
+#[test]
+fn last_resort() {
+    assert_eq!(Sniffer::new(vec![]).decode(b"a\xffb"), ("a\u{fffd}b".into(), UTF_8));
+}
@@ -0,0 +1,33 @@
+use crate::behaviour::Intl;
+use crate::rfc5322::from;
+use crate::spoofing::*;
+
+fn found(name: &str) -> Vec<(&str, String)> {
+    addresses_in_name(name).into_iter().map(|(span, a)| (&name[span], a.to_string())).collect()
+}
+
+#[test]
+fn names() {
+    assert_eq!(found("ceo@bank.example via Portal <ops@[192.0.2.1]>"),
+               [("ceo@bank.example", "ceo@bank.example".into()), ("ops@[192.0.2.1]", "ops@[192.0.2.1]".into())]);
+    assert_eq!(found("...jean.dupont@société.example."), [("jean.dupont@société.example", "jean.dupont@société.example".into())]);
+    assert_eq!(found("Säkerhet: ärende@bank.example"), [("ärende@bank.example", "ärende@bank.example".into())]);
+    assert!(found("@bank.example").is_empty());
+    assert!(found("admin@localhost").is_empty());
+    assert!(found("Bob").is_empty());
+}
+
+#[test]
+fn mailboxes() {
+    let (_, addrs) = from::<Intl>(b"\"PayPal <service@paypal.example>\" <x@evil.example>, \
+                                    =?utf-8?q?ceo=40bank=2Eexample?= <ceo@bank.example>, Alice <alice@example.org>").unwrap();
+    let spoofed = spoofed_mailboxes(&addrs);
+
+    assert_eq!(spoofed.len(), 1);
+    assert_eq!(spoofed[0].0.address.to_string(), "x@evil.example");
+    assert_eq!(spoofed[0].1, [NameAddress { span: 8..30, address: "service@paypal.example".parse().unwrap(), mismatch: true }]);
+
+    let second = check_mailbox(&addrs[1].mailboxes()[0]);
+    assert_eq!(second.len(), 1);
+    assert!(!second[0].mismatch);
+}
@@ -0,0 +1,80 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::srs::*;
+use crate::types::{Domain, Mailbox};
+
+fn mb(s: &str) -> Mailbox {
+    Mailbox::from_smtp(s.as_bytes()).unwrap()
+}
+
+fn days(n: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(n * 86400 + 3600)
+}
+
+#[test]
+fn srs0_roundtrip() {
+    let srs = Srs::new(b"secret");
+    let domain = Domain::from_smtp(b"fwd.example").unwrap();
+
+    for sender in ["bob@example.org", "\"bob smith\"@example.org", "a=b@[192.0.2.1]"].iter() {
+        let rewritten = srs.forward(&mb(sender), &domain, days(1023)).unwrap();
+        assert_eq!(srs.reverse(&rewritten, days(1023)), Ok(mb(sender)));
+        // Timestamps wrap around after 1024 days.
+        assert_eq!(srs.reverse(&rewritten, days(1023 + 21)), Ok(mb(sender)));
+        assert_eq!(srs.reverse(&rewritten, days(1023 + 22)), Err(SrsError::Expired));
+    }
+
+    let rewritten = srs.forward(&mb("bob@example.org"), &domain, days(33)).unwrap();
+    let local = rewritten.local_part().to_string();
+    assert_eq!(local, "SRS0=JWPO=BB=example.org=bob");
+    assert_eq!(srs.reverse(&mb(&format!("{}@x.example", local.to_lowercase())), days(33)), Ok(mb("bob@example.org")));
+    assert_eq!(Srs::new(b"other").reverse(&rewritten, days(33)), Err(SrsError::Hash));
+
+    let forged = local.replace("example.org=bob", "example.org=eve");
+    assert_eq!(srs.reverse(&mb(&format!("{}@x.example", forged)), days(33)), Err(SrsError::Hash));
+}
+
+#[test]
+fn libsrs2_hashes() {
+    // Hashes computed as libsrs2 and postsrsd do.
+    let srs = Srs::new(b"secret");
+    assert_eq!(srs.reverse(&mb("SRS0=JWPO=BB=example.org=bob@fwd.example"), days(33)), Ok(mb("bob@example.org")));
+
+    let srs1 = Srs::new(b"other").forward(&mb("SRS0=JWPO=BB=example.org=bob@fwd.example"),
+                                          &Domain::from_smtp(b"b.example").unwrap(), days(33)).unwrap();
+    assert_eq!(srs1, mb("SRS1=vo+r=fwd.example==JWPO=BB=example.org=bob@b.example"));
+}
+
+#[test]
+fn srs1_chain() {
+    let (first, second) = (Srs::new(b"one"), Srs::new(b"two"));
+    let now = SystemTime::now();
+
+    let srs0 = first.forward(&mb("bob@example.org"), &Domain::from_smtp(b"a.example").unwrap(), now).unwrap();
+    let srs1 = second.forward(&srs0, &Domain::from_smtp(b"b.example").unwrap(), now).unwrap();
+    let srs1_again = second.forward(&srs1, &Domain::from_smtp(b"c.example").unwrap(), now).unwrap();
+
+    assert!(srs1.to_string().starts_with("SRS1="));
+    assert_eq!(srs1.local_part(), srs1_again.local_part());
+    match SrsAddress::parse(&srs1) {
+        Ok(SrsAddress::Srs1(fields)) => {
+            assert_eq!(fields.forwarder, "a.example");
+            assert_eq!(fields.separator, '=');
+        }
+        other => panic!("{:?}", other),
+    }
+
+    assert_eq!(second.reverse(&srs1_again, now), Ok(srs0.clone()));
+    assert_eq!(first.reverse(&srs0, now), Ok(mb("bob@example.org")));
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!(SrsAddress::parse(&mb("bob@example.org")), Err(SrsError::NotSrs));
+    assert_eq!(SrsAddress::parse(&mb("SRS0x@example.org")), Err(SrsError::NotSrs));
+    assert_eq!(SrsAddress::parse(&mb("SRS0=h=TTT=d=l@example.org")), Err(SrsError::Syntax));
+    assert_eq!(SrsAddress::parse(&mb("SRS1=h=f=x@example.org")), Err(SrsError::Syntax));
+    assert!(SrsAddress::parse(&mb("srs0+h=TT=d=l@example.org")).is_ok());
+    assert_eq!(Srs::new(b"k").reverse(&mb("bob@example.org"), SystemTime::now()), Err(SrsError::NotSrs));
+    assert!(!format!("{:?}", Srs::new(b"hidden")).contains("hidden"));
+}
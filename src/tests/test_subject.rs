@@ -0,0 +1,35 @@
+use crate::subject::*;
+
+#[test]
+fn prefixes() {
+    let (prefixes, base) = split_prefixes(" Re[2]: WG: SV:Ynt: Odp：  Meeting: notes ");
+    assert_eq!(base, "Meeting: notes");
+    assert_eq!(prefixes.len(), 5);
+    assert_eq!(prefixes[0], SubjectPrefix { kind: PrefixKind::Reply, text: "Re[2]:".into(), count: Some(2) });
+    assert_eq!(prefixes[1].kind, PrefixKind::Forward);
+    assert_eq!(prefixes[4].text, "Odp：");
+
+    assert_eq!(split_prefixes("转发: 答复: x").0.iter().map(|p| p.kind).collect::<Vec<_>>(), [PrefixKind::Forward, PrefixKind::Reply]);
+    assert_eq!(split_prefixes("RÉF: x").0.len(), 1);
+}
+
+#[test]
+fn not_prefixes() {
+    for subject in ["Re[a]: x", "Re [2: x", "Reply: x", "Re", "Re x: y", ": x"].iter() {
+        assert!(split_prefixes(subject).0.is_empty(), "{}", subject);
+    }
+    assert_eq!(split_prefixes("Re: "), (vec![SubjectPrefix { kind: PrefixKind::Reply, text: "Re:".into(), count: None }], ""));
+}
+
+#[test]
+fn normalize() {
+    let subject = normalize_subject(b" =?iso-8859-1?q?Fwd=3A_caf=E9?=\r\n\tmenu");
+    assert!(subject.is_forward() && !subject.is_reply());
+    assert_eq!(subject.base, "café menu");
+
+    let subject = normalize_subject(b" \xe5\x9b\x9e\xe5\xa4\x8d: x");
+    assert!(subject.is_reply());
+    assert_eq!(subject.base, "x");
+
+    assert_eq!(normalize_subject(b"Re: x").base, normalize_subject(b"x").base);
+}
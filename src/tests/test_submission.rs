@@ -0,0 +1,42 @@
+use crate::behaviour::Intl;
+use crate::rfc5321::Command;
+use crate::submission::*;
+
+fn after_ehlo() -> SubmissionProfile {
+    let mut session = SubmissionProfile::new();
+    session.parse::<Intl>(b"EHLO client.example.org\r\n").unwrap();
+    session
+}
+
+#[test]
+fn ehlo_required() {
+    let mut session = SubmissionProfile::new();
+
+    assert_eq!(session.parse::<Intl>(b"HELO client.example.org\r\n").unwrap_err(), SubmissionError::EhloRequired);
+    assert_eq!(session.parse::<Intl>(b"RCPT TO:<bob@example.org>\r\n").unwrap_err(), SubmissionError::EhloRequired);
+    assert_eq!(session.parse::<Intl>(b"DATA\r\n").unwrap_err(), SubmissionError::EhloRequired);
+    assert!(matches!(session.parse::<Intl>(b"NOOP\r\n"), Ok((_, Command::NOOP(None)))));
+    assert!(!session.ehlo_seen());
+
+    // A client without a proper hostname is still allowed to submit.
+    assert!(session.parse::<Intl>(b"EHLO [192.0.2.1]\r\n").is_ok());
+    assert!(session.ehlo_seen());
+    assert_eq!(session.parse::<Intl>(b"HELO client.example.org\r\n").unwrap_err(), SubmissionError::EhloRequired);
+    assert!(session.parse::<Intl>(b"RSET\r\n").is_ok());
+    assert!(session.parse::<Intl>(b"MAIL FROM:<>\r\n").is_ok());
+}
+
+#[test]
+fn paths() {
+    let mut session = after_ehlo();
+
+    assert_eq!(session.parse::<Intl>(b"MAIL FROM:<@relay.example.org:bob@example.org>\r\n").unwrap_err(),
+               SubmissionError::SourceRoute);
+    assert_eq!(session.parse::<Intl>(b"MAIL FROM:<bob@localhost>\r\n").unwrap_err(), SubmissionError::UnqualifiedDomain);
+    assert_eq!(session.parse::<Intl>(b"RCPT TO:<Postmaster@mailhost>\r\n").unwrap_err(), SubmissionError::UnqualifiedDomain);
+    assert_eq!(session.parse::<Intl>(b"RCPT TO:<bob@example.org\r\n").unwrap_err(), SubmissionError::Syntax);
+
+    for ok in [&b"RCPT TO:<postmaster>\r\n"[..], b"RCPT TO:<bob@[192.0.2.1]>\r\n", b"RCPT TO:<bob@mail.example.org>\r\n"] {
+        assert!(session.parse::<Intl>(ok).is_ok());
+    }
+}
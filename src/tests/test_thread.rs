@@ -0,0 +1,107 @@
+use crate::headersection::header_section;
+use crate::thread::*;
+
+fn message(id: &str, references: &[&str], subject: &str) -> ThreadMessage {
+    ThreadMessage {
+        message_id: Some(id.into()),
+        references: references.iter().map(|r| r.to_string()).collect(),
+        subject: subject.trim_start_matches("Re: ").into(),
+        is_reply: subject.starts_with("Re: "),
+    }
+}
+
+// Render each thread as the message indexes with their depth.
+fn shape(threads: &Threads) -> Vec<Vec<(Option<usize>, usize)>> {
+    threads.roots.iter()
+        .map(|&r| threads.walk(r).map(|(n, d)| (threads.nodes[n].message, d)).collect())
+        .collect()
+}
+
+#[test]
+fn references_chain() {
+    let messages = [
+        message("d", &["a", "b", "c"], ""),
+        message("a", &[], ""),
+        message("c", &["a", "x"], ""),
+        message("e", &["a"], ""),
+    ];
+    // c's own references override the guessed b parent, b and x are
+    // pruned as empty containers.
+    assert_eq!(shape(&thread(&messages, false)), [vec![(Some(1), 0), (Some(2), 1), (Some(0), 2), (Some(3), 1)]]);
+}
+
+#[test]
+fn empty_root_kept() {
+    let messages = [message("b", &["a"], ""), message("c", &["a"], ""), message("d", &["z"], "")];
+    assert_eq!(shape(&thread(&messages, false)), [
+        vec![(None, 0), (Some(0), 1), (Some(1), 1)],
+        vec![(Some(2), 0)],
+    ]);
+}
+
+#[test]
+fn loops_and_duplicates() {
+    let messages = [
+        message("a", &["b"], ""),
+        message("b", &["a"], ""),
+        message("a", &[], ""),
+        message("c", &["c"], ""),
+    ];
+    let threads = thread(&messages, false);
+    // The second link would close a loop and is ignored.
+    assert_eq!(threads.nodes.len(), 4);
+    assert_eq!(shape(&threads), [
+        vec![(Some(1), 0), (Some(0), 1)],
+        vec![(Some(2), 0)],
+        vec![(Some(3), 0)],
+    ]);
+}
+
+#[test]
+fn subjects() {
+    let messages = [
+        message("a", &[], "Re: Lunch"),
+        message("b", &[], "Lunch"),
+        message("c", &[], "Dinner"),
+        message("d", &[], "Dinner"),
+        message("e", &["lost"], "Re: Dinner"),
+        message("f", &[], ""),
+        message("g", &[], ""),
+    ];
+    assert_eq!(shape(&thread(&messages, true)), [
+        vec![(Some(1), 0), (Some(0), 1)],
+        vec![(None, 0), (Some(2), 1), (Some(3), 1), (Some(4), 1)],
+        vec![(Some(5), 0)],
+        vec![(Some(6), 0)],
+    ]);
+    assert_eq!(thread(&messages, false).roots.len(), 7);
+}
+
+#[test]
+fn deep_thread() {
+    let ids: Vec<String> = (0..100_000).map(|i| i.to_string()).collect();
+    let messages: Vec<_> = ids.iter().enumerate()
+        .map(|(i, id)| ThreadMessage {
+            message_id: Some(id.clone()),
+            references: ids[i.saturating_sub(1)..i].to_vec(),
+            ..ThreadMessage::default()
+        })
+        .collect();
+    let threads = thread(&messages, true);
+    assert_eq!(threads.walk(threads.roots[0]).last(), Some((99_999, 99_999)));
+}
+
+#[test]
+fn from_headers() {
+    let (_, headers) = header_section(b"message-id: <a@example.org>\r\n\
+                                        In-Reply-To: \"x\" <c@example.org> <d@example.org>\r\n\
+                                        References: <b@example.org> (x) <c@example.org>\r\n\
+                                        Subject: =?utf-8?q?AW=3A_Caf=C3=A9?=\r\n\r\n").unwrap();
+    let message = ThreadMessage::from_headers(&headers);
+    assert_eq!(message.message_id.as_deref(), Some("a@example.org"));
+    assert_eq!(message.references, ["b@example.org", "c@example.org"]);
+    assert_eq!(message.subject, "Café");
+    assert!(message.is_reply);
+
+    assert_eq!(ThreadMessage::from_headers(&[]), ThreadMessage::default());
+}
@@ -0,0 +1,114 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::trace::*;
+
+#[test]
+fn postfix() {
+    let (rem, rcvd) = received(b" from mail.example.org (mail.example.org [192.0.2.1])\r\n\
+        \t(using TLSv1.3 with cipher TLS_AES_256_GCM_SHA384 (256/256 bits))\r\n\
+        \tby mx.example.com (Postfix) with ESMTPSA id 4F3A2C0012\r\n\
+        \tfor <bob@example.com>; Tue,  1 Jun 2021 10:00:00 +0000 (UTC)\r\n").unwrap();
+
+    assert_eq!(rem.len(), 0);
+    assert_eq!(rcvd.from, Some(ExtendedDomain { name: "mail.example.org".into(), info: Some("mail.example.org [192.0.2.1]".into()) }));
+    assert_eq!(rcvd.by, Some(ExtendedDomain { name: "mx.example.com".into(), info: Some("Postfix".into()) }));
+    assert_eq!(rcvd.with, Some(WithProtocol::Esmtpsa));
+    assert_eq!(rcvd.id.as_deref(), Some("4F3A2C0012"));
+    assert_eq!(rcvd.recipient.as_deref(), Some("bob@example.com"));
+    assert_eq!(rcvd.date, "Tue,  1 Jun 2021 10:00:00 +0000 (UTC)");
+}
+
+#[test]
+fn exim() {
+    let (_, rcvd) = received(b"from [192.0.2.7] (helo=client)\r\n\tby mx.example.com with esmtps (TLS1.3) tls TLS_AES_256_GCM_SHA384\r\n\t(Exim 4.94) (envelope-from <a@example.org>) id 1lnX2c-0001; Tue, 01 Jun 2021 10:00:00 +0000").unwrap();
+
+    assert_eq!(rcvd.from.unwrap().name, "[192.0.2.7]");
+    assert_eq!(rcvd.with, Some(WithProtocol::Esmtps));
+    assert_eq!(rcvd.additional, [("tls".into(), "TLS_AES_256_GCM_SHA384".into())]);
+    assert_eq!(rcvd.id.as_deref(), Some("1lnX2c-0001"));
+}
+
+#[test]
+fn comment_only() {
+    let (_, rcvd) = received(b"(qmail 1234 invoked by uid 89); 1 Jun 2021 10:00:00 -0000").unwrap();
+    assert_eq!(rcvd, Received { date: "1 Jun 2021 10:00:00 -0000".into(), ..Default::default() });
+}
+
+#[test]
+fn missing_date() {
+    assert!(received(b"from a by b").is_err());
+}
+
+#[test]
+fn with_protocols() {
+    let classify = |p: &str| {
+        let p = WithProtocol::from(p);
+        (p.is_authenticated(), p.is_encrypted())
+    };
+
+    assert_eq!(classify("smtp"), (false, false));
+    assert_eq!(classify("ESMTPA"), (true, false));
+    assert_eq!(classify("esmtps"), (false, true));
+    assert_eq!(classify("UTF8LMTPSA"), (true, true));
+    assert_eq!(classify("HTTP"), (false, false));
+    assert_eq!(WithProtocol::from("utf8smtps").to_string(), "UTF8SMTPS");
+    assert_eq!(WithProtocol::from("local").to_string(), "local");
+}
+
+fn client_ip(input: &str) -> Option<IpAddr> {
+    received(input.as_bytes()).unwrap().1.client_ip()
+}
+
+#[test]
+fn client_ips() {
+    let ip = |s: &str| Some(s.parse::<IpAddr>().unwrap());
+
+    assert_eq!(client_ip("from [192.0.2.9] (rdns.example [198.51.100.2]) by mx; x"), ip("198.51.100.2"));
+    assert_eq!(client_ip("from [192.0.2.9] by mx; x"), ip("192.0.2.9"));
+    assert_eq!(client_ip("from [IPv6:2001:db8::1] by mx; x"), ip("2001:db8::1"));
+    assert_eq!(client_ip("from helo (HELO helo) (198.51.100.3) by mx; x"), None);
+    assert_eq!(client_ip("from helo (helo=x.example 198.51.100.4) by mx; x"), ip("198.51.100.4"));
+    assert_eq!(client_ip("from helo (unknown) by mx; x"), None);
+    assert_eq!(client_ip("by mx; x"), None);
+    assert_eq!(client_ip("from helo (rdns.example [IPv6é]) by mx; x"), None);
+    assert_eq!(client_ip("from [IPvé] by mx; x"), None);
+}
+
+#[test]
+fn ip_scopes() {
+    for (ip, scope) in [("10.0.0.1", IpScope::Private), ("172.31.255.255", IpScope::Private), ("172.32.0.1", IpScope::Public),
+                        ("100.64.0.1", IpScope::Private), ("100.128.0.1", IpScope::Public), ("169.254.1.1", IpScope::LinkLocal),
+                        ("0.0.0.0", IpScope::Reserved), ("224.0.0.1", IpScope::Reserved), ("::1", IpScope::Loopback),
+                        ("fe80::1", IpScope::LinkLocal), ("fc00::1", IpScope::Private), ("::", IpScope::Reserved),
+                        ("2001:db8::1", IpScope::Public), ("::ffff:10.0.0.1", IpScope::Private)] {
+        assert_eq!(IpScope::of(ip.parse().unwrap()), scope, "{}", ip);
+    }
+    assert!(IpScope::Loopback.is_internal() && !IpScope::Reserved.is_internal());
+
+    let chain: Vec<_> = ["from a ([127.0.0.1]) by b; x", "from c ([0.0.0.0]) by d; x", "from e by f; x"]
+        .iter().map(|h| received(h.as_bytes()).unwrap().1).collect();
+    assert_eq!(first_external_ip(&chain), None);
+}
+
+#[test]
+fn skew() {
+    let chain: Vec<_> = [
+        &b"from b by c; Tue, 1 Jun 2021 10:05:00 +0000"[..],
+        b"from a by b; Tue, 1 Jun 2021 06:00:00 -0400",
+        b"from x by a; not a date",
+    ].iter().map(|h| received(h).unwrap().1).collect();
+    let hour = Duration::from_secs(3600);
+
+    let skew = date_skew(b"Tue, 1 Jun 2021 11:00:00 +0000", &chain).unwrap();
+    assert_eq!(skew.received, chain[1].timestamp().unwrap());
+    assert_eq!(skew.skew, -3600);
+    assert!(skew.is_future(Duration::from_secs(900)) && !skew.is_future(hour));
+    assert!(!skew.is_ancient(Duration::from_secs(0)));
+
+    let skew = date_skew(b"Mon, 1 Jun 2020 10:00:00 +0000", &chain).unwrap();
+    assert!(skew.is_ancient(hour * 24 * 30));
+
+    assert_eq!(date_skew(b"yesterday", &chain), None);
+    assert_eq!(date_skew(b"Tue, 1 Jun 2021 11:00:00 +0000", &chain[2..]), None);
+}
@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use crate::behaviour::Intl;
+use crate::reply::Reply;
+use crate::rfc5321::Command;
+use crate::submission::SubmissionProfile;
+use crate::transcript::*;
+
+fn entry(ms: u64, event: Event) -> Entry {
+    Entry { elapsed: Duration::from_millis(ms), event }
+}
+
+fn captured() -> Transcript {
+    Transcript { entries: vec![
+        entry(0, Event::Reply(Reply::new(220, vec!["mx.example.org ESMTP"]))),
+        entry(10, Event::Command(b"EHLO client.example.org\r\n".to_vec())),
+        entry(11, Event::Reply(Reply::new(250, vec!["mx.example.org", "PIPELINING"]))),
+        entry(20, Event::Command(b"MAIL FROM:<bob@example.org>\r\n".to_vec())),
+        entry(20, Event::Command(b"RCPT TO:<alice@mailhost>\r\n".to_vec())),
+        entry(21, Event::Reply(Reply::new(250, vec!["OK"]))),
+        entry(21, Event::Reply(Reply::new(550, vec!["domain is not fully qualified"]))),
+        entry(30, Event::Command(b"QUIT\r\n".to_vec())),
+        entry(31, Event::Reply(Reply::new(221, vec!["Bye"]))),
+    ]}
+}
+
+// Answers each command on its own, without holding back pipelined replies.
+fn server() -> impl FnMut(&[u8]) -> Vec<Reply> {
+    let mut profile = SubmissionProfile::new();
+
+    move |line| vec![match profile.parse::<Intl>(line) {
+        Ok((_, Command::EHLO(_))) => Reply::new(250, vec!["mx.example.org", "PIPELINING"]),
+        Ok((_, Command::QUIT)) => Reply::new(221, vec!["Bye"]),
+        Ok(_) => Reply::new(250, vec!["OK"]),
+        Err(e) => Reply::new(550, vec![e.to_string()]),
+    }]
+}
+
+fn greeting() -> Reply {
+    Reply::new(220, vec!["mx.example.org ESMTP"])
+}
+
+#[test]
+fn replay() {
+    let transcript = captured();
+    assert_eq!(transcript.commands().count(), 4);
+    assert!(transcript.replay(&greeting(), server()).is_empty());
+
+    let diverged = transcript.replay(&Reply::new(554, vec!["No service"]), server());
+    assert_eq!(diverged, [Divergence { entry: 0, expected: vec![greeting()], actual: vec![Reply::new(554, vec!["No service"])] }]);
+}
+
+#[test]
+fn replay_pipelined() {
+    // Holds back the replies to MAIL and RCPT until the next command.
+    let mut inner = server();
+    let mut held = Vec::new();
+    let pipelining = |line: &[u8]| {
+        held.extend(inner(line));
+        if line.starts_with(b"MAIL") { vec![] } else { std::mem::take(&mut held) }
+    };
+    assert!(captured().replay(&greeting(), pipelining).is_empty());
+
+    // The reply to RCPT is missing, so the reply to QUIT is taken as its.
+    let mut inner = server();
+    let dropping = |line: &[u8]| if line.starts_with(b"RCPT") { vec![] } else { inner(line) };
+    assert_eq!(captured().replay(&greeting(), dropping), [
+        Divergence {
+            entry: 4,
+            expected: vec![Reply::new(550, vec!["domain is not fully qualified"])],
+            actual: vec![Reply::new(221, vec!["Bye"])],
+        },
+        Divergence { entry: 7, expected: vec![Reply::new(221, vec!["Bye"])], actual: vec![] },
+    ]);
+}
+
+#[test]
+fn recorder() {
+    let mut recorder = Recorder::new();
+    recorder.command(b"NOOP\r\n");
+    recorder.reply(&Reply::new(250, vec!["OK"]));
+    let transcript = recorder.finish();
+
+    assert_eq!(transcript.entries.len(), 2);
+    assert!(transcript.entries[0].elapsed <= transcript.entries[1].elapsed);
+    assert_eq!(transcript.entries[1].event, Event::Reply(Reply::new(250, vec!["OK"])));
+    assert_eq!(transcript.replay(&greeting(), server()), [Divergence { entry: 0, expected: vec![], actual: vec![greeting()] }]);
+}
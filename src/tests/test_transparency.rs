@@ -0,0 +1,48 @@
+use crate::transparency::*;
+
+#[test]
+fn stuff_roundtrip() {
+    let data = b".\r\n..\r\nx.\r\n.y\r\n";
+    assert_eq!(dot_stuff(data), b"..\r\n...\r\nx.\r\n..y\r\n");
+    assert_eq!(dot_unstuff(&dot_stuff(data)), data);
+    assert_eq!(dot_stuff(b""), b"");
+}
+
+#[test]
+fn bare_line_endings_ignored() {
+    assert_eq!(dot_stuff(b"a\n.b\r.c"), b"a\n.b\r.c");
+    assert_eq!(dot_unstuff(b"a\n..b"), b"a\n..b");
+}
+
+#[test]
+fn scanner_split_everywhere() {
+    let data = b"a\r\n..\r\nb\r\n.\r\nNEXT";
+    for split in 0..data.len() {
+        let mut scanner = EodScanner::new();
+        let (first, second) = data.split_at(split);
+        let end = match scanner.feed(first) {
+            Some(end) => end,
+            None => split + scanner.feed(second).unwrap(),
+        };
+        assert_eq!(&data[end..], b"NEXT", "split at {}", split);
+        assert!(scanner.is_done());
+    }
+}
+
+#[test]
+fn scanner_empty_message() {
+    let mut scanner = EodScanner::new();
+    assert_eq!(scanner.feed(b".\r\n"), Some(3));
+    assert_eq!(scanner.feed(b"\r\n.\r\n"), None);
+
+    scanner.reset();
+    assert_eq!(scanner.feed(b"\n.\n\r\r\n.\r\n"), Some(9));
+}
+
+#[test]
+fn scanner_matches_to_data() {
+    let data = to_data(b".\r\nline\n.\nmore");
+    let mut scanner = EodScanner::default();
+    assert_eq!(scanner.feed(&data), Some(data.len()));
+    assert_eq!(dot_unstuff(&data[..data.len()-3]), b".\r\nline\n.\nmore\r\n");
+}
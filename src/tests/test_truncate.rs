@@ -0,0 +1,90 @@
+use crate::mime::parse_message;
+use crate::truncate::*;
+
+const NESTED: &[u8] = b"From: bob@example.org\r\n\
+Content-Type: multipart/mixed; boundary=outer\r\n\
+\r\n\
+preamble\r\n\
+--outer\r\n\
+Content-Type: multipart/alternative; boundary=inner\r\n\
+\r\n\
+--inner\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+first line\r\n\
+second line\r\n\
+--inner\r\n\
+Content-Type: text/html\r\n\
+\r\n\
+<p>html</p>\r\n\
+--inner--\r\n\
+--outer\r\n\
+Content-Type: application/octet-stream\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\r\n\
+AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\r\n\
+--outer--\r\n";
+
+#[test]
+fn every_length() {
+    let header_len = NESTED.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let original = parse_message(NESTED);
+
+    for max_len in 0..NESTED.len() {
+        let out = truncate_message(NESTED, max_len);
+        let note = format!("{}: {}\r\n", TRUNCATED_HEADER, NESTED.len());
+        assert!(out.starts_with(note.as_bytes()));
+        assert!(out.len() <= max_len.max(note.len() + header_len), "{}", max_len);
+
+        let message = parse_message(&out);
+        assert_eq!(message.headers[1..], original.headers[..], "{}", max_len);
+        assert!(message.epilogue.is_empty());
+        for (part, orig) in message.iter().zip(original.iter()).skip(1) {
+            assert_eq!(part.headers, orig.headers, "{}", max_len);
+            if !part.is_multipart() {
+                // Parts are cut after a complete line.
+                assert!(part.body.is_empty() || part.body == orig.body || orig.body[part.body.len()..].starts_with(b"\r\n"), "{}", max_len);
+            }
+        }
+    }
+
+    assert_eq!(truncate_message(NESTED, NESTED.len()), NESTED);
+}
+
+#[test]
+fn cuts() {
+    let out = truncate_message(NESTED, 260);
+    assert_eq!(String::from_utf8_lossy(&out), "X-Truncated: 521\r\n\
+From: bob@example.org\r\n\
+Content-Type: multipart/mixed; boundary=outer\r\n\
+\r\n\
+preamble\r\n\
+--outer\r\n\
+Content-Type: multipart/alternative; boundary=inner\r\n\
+\r\n\
+--inner\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+first line\r\n\
+second line\r\n\
+--inner--\r\n\
+--outer--\r\n");
+
+    let short = truncate_message(NESTED, 10);
+    assert_eq!(short.len(), 18 + 72);
+    assert!(parse_message(&short).parts.is_empty());
+}
+
+#[test]
+fn deeply_nested() {
+    let mut nested = Vec::new();
+    for depth in 0..40 {
+        nested.extend_from_slice(format!("Content-Type: multipart/mixed; boundary=b{0}\r\n\r\n--b{0}\r\n", depth).as_bytes());
+    }
+    nested.extend_from_slice(b"\r\nline 1\r\nline 2\r\n");
+
+    let out = truncate_message(&nested, nested.len() - 1);
+    assert!(out.len() < nested.len() + 200);
+    assert_eq!(parse_message(&out).iter().count(), 33);
+}
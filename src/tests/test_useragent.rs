@@ -0,0 +1,40 @@
+use crate::useragent::*;
+
+fn product(name: &str, version: Option<&str>) -> Token {
+    Token::Product(Product { name: name.into(), version: version.map(Into::into) })
+}
+
+#[test]
+fn product_tokens() {
+    let ua = user_agent(b" Mozilla/5.0 (X11; Linux x86_64; rv:102.0)\r\n Gecko/20100101 Thunderbird/102.4.2");
+    assert_eq!(ua.tokens, [
+        product("Mozilla", Some("5.0")),
+        Token::Comment("X11; Linux x86_64; rv:102.0".into()),
+        product("Gecko", Some("20100101")),
+        product("Thunderbird", Some("102.4.2")),
+    ]);
+    assert_eq!(ua.products().nth(2).unwrap().to_string(), "Thunderbird/102.4.2");
+}
+
+#[test]
+fn free_text() {
+    assert_eq!(user_agent(b" Microsoft Outlook 16.0").tokens, [product("Microsoft Outlook", Some("16.0"))]);
+    assert_eq!(user_agent(b"Apple Mail (2.3654.120.0.1.13)").tokens, [
+        product("Apple Mail", None),
+        Token::Comment("2.3654.120.0.1.13".into()),
+    ]);
+    assert_eq!(user_agent(b"1.0 foo/ bar").tokens, [product("1.0 foo", None), product("bar", None)]);
+    assert_eq!(user_agent(b"Mutt v2 x").normalized(), "mutt/2 x");
+}
+
+#[test]
+fn comments() {
+    let ua = user_agent(br" a (nested (c\)omment)) (unbalanced");
+    assert_eq!(ua.tokens, [
+        product("a", None),
+        Token::Comment("nested (c)omment)".into()),
+        product("(unbalanced", None),
+    ]);
+    assert_eq!(user_agent(b" \r\n ").tokens, []);
+    assert_eq!(user_agent(b"\xffMailer/1").products().next().unwrap().name, "\u{fffd}Mailer");
+}
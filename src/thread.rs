@@ -0,0 +1,355 @@
+//! Message threading
+//!
+//! Groups messages into conversations with the [threading algorithm]
+//! used by most mail clients. Messages are linked through their
+//! Message-ID, References and In-Reply-To headers, and optionally
+//! grouped by subject when those references were lost.
+//!
+//! Threads are returned as a flat [`Threads`] forest indexing the
+//! input messages, so that deep conversations need no recursion.
+//!
+//! [threading algorithm]: https://www.jwz.org/doc/threading.html
+
+use std::collections::HashMap;
+
+use crate::behaviour::Intl;
+use crate::headersection::HeaderField;
+use crate::rfc5322::{in_reply_to, message_id, references};
+use crate::subject::normalize_subject;
+
+/// Threading information of a single message.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThreadMessage {
+    /// Message identifier, without angle brackets.
+    pub message_id: Option<String>,
+    /// Identifiers of the ancestors, oldest first.
+    pub references: Vec<String>,
+    /// Subject without its reply and forward prefixes.
+    pub subject: String,
+    /// Whether the subject starts with a reply prefix.
+    pub is_reply: bool,
+}
+
+impl ThreadMessage {
+    /// Extract the threading information from a header section.
+    ///
+    /// The first identifier of In-Reply-To is appended to References
+    /// when missing from it.
+    /// # Examples
+    /// ```
+    /// use rustyknife::headersection::header_section;
+    /// use rustyknife::thread::ThreadMessage;
+    ///
+    /// let (_, headers) = header_section(b"Message-ID: <3@example.org>\r\n\
+    ///                                     References: <1@example.org>\r\n\
+    ///                                     In-Reply-To: <2@example.org>\r\n\
+    ///                                     Subject: Re: Lunch\r\n\r\n").unwrap();
+    /// let message = ThreadMessage::from_headers(&headers);
+    ///
+    /// assert_eq!(message.message_id.as_deref(), Some("3@example.org"));
+    /// assert_eq!(message.references, ["1@example.org", "2@example.org"]);
+    /// assert_eq!(message.subject, "Lunch");
+    /// assert!(message.is_reply);
+    /// ```
+    pub fn from_headers(headers: &[HeaderField]) -> Self {
+        let get = |name: &str| headers.iter().filter_map(|h| h.as_ref().ok())
+            .find(|(n, _)| n.eq_ignore_ascii_case(name.as_bytes()))
+            .map(|(_, v)| *v);
+
+        let message_id = get("Message-ID").and_then(|v| message_id::<Intl>(v).ok()).map(|(_, id)| id);
+        let mut references = get("References").and_then(|v| references::<Intl>(v).ok()).map(|(_, ids)| ids).unwrap_or_default();
+        let parent = get("In-Reply-To").and_then(|v| in_reply_to::<Intl>(v).ok()).and_then(|(_, ids)| ids.into_iter().next());
+
+        if let Some(parent) = parent {
+            if !references.contains(&parent) {
+                references.push(parent);
+            }
+        }
+
+        let subject = normalize_subject(get("Subject").unwrap_or_default());
+
+        ThreadMessage { message_id, references, is_reply: subject.is_reply(), subject: subject.base }
+    }
+}
+
+/// A node of a [`Threads`] forest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThreadNode {
+    /// Index of the message in the input.
+    ///
+    /// `None` for a placeholder standing for a message that was
+    /// referenced but not provided, or grouping messages that share a
+    /// subject.
+    pub message: Option<usize>,
+    /// Indexes of the children in [`Threads::nodes`].
+    pub children: Vec<usize>,
+}
+
+/// A forest of threads.
+///
+/// Roots and children are ordered by the position of their first
+/// message in the input.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Threads {
+    /// All the nodes of the forest.
+    pub nodes: Vec<ThreadNode>,
+    /// Indexes of the thread roots in [`nodes`](Self::nodes).
+    pub roots: Vec<usize>,
+}
+
+impl Threads {
+    /// Iterate depth first over the thread starting at node `root`.
+    ///
+    /// Yields each node index along with its depth below `root`.
+    pub fn walk(&self, root: usize) -> impl Iterator<Item=(usize, usize)> + '_ {
+        let mut stack = vec![(root, 0)];
+
+        std::iter::from_fn(move || {
+            let (node, depth) = stack.pop()?;
+            stack.extend(self.nodes[node].children.iter().rev().map(|&c| (c, depth + 1)));
+            Some((node, depth))
+        })
+    }
+}
+
+#[derive(Default)]
+struct Container {
+    message: Option<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+#[derive(Default)]
+struct Arena {
+    nodes: Vec<Container>,
+}
+
+impl Arena {
+    fn add(&mut self, message: Option<usize>) -> usize {
+        self.nodes.push(Container { message, ..Container::default() });
+        self.nodes.len() - 1
+    }
+
+    // Whether `ancestor` is `node` or one of its ancestors.
+    fn is_ancestor(&self, ancestor: usize, node: usize) -> bool {
+        let mut cur = Some(node);
+
+        while let Some(c) = cur {
+            if c == ancestor {
+                return true;
+            }
+            cur = self.nodes[c].parent;
+        }
+
+        false
+    }
+
+    fn unlink(&mut self, child: usize) {
+        if let Some(parent) = self.nodes[child].parent.take() {
+            self.nodes[parent].children.retain(|&c| c != child);
+        }
+    }
+
+    fn link(&mut self, parent: usize, child: usize) {
+        self.unlink(child);
+        self.nodes[child].parent = Some(parent);
+        self.nodes[parent].children.push(child);
+    }
+
+    // Containers reachable from `roots`, children before their parent.
+    fn post_order(&self, roots: &[usize]) -> Vec<usize> {
+        let mut out = Vec::new();
+        let mut stack: Vec<usize> = roots.to_vec();
+
+        while let Some(c) = stack.pop() {
+            out.push(c);
+            stack.extend(&self.nodes[c].children);
+        }
+
+        out.reverse();
+        out
+    }
+}
+
+/// Thread `messages`.
+///
+/// With `group_by_subject`, threads whose root has the same base
+/// subject are merged, replies being placed below the original
+/// message.
+/// # Examples
+/// ```
+/// use rustyknife::thread::{thread, ThreadMessage};
+///
+/// let message = |id: &str, references: &[&str]| ThreadMessage {
+///     message_id: Some(id.into()),
+///     references: references.iter().map(|r| r.to_string()).collect(),
+///     ..ThreadMessage::default()
+/// };
+/// let messages = [message("c", &["a", "b"]), message("b", &["a"]), message("d", &[])];
+/// let threads = thread(&messages, false);
+///
+/// let first: Vec<_> = threads.walk(threads.roots[0])
+///     .map(|(node, depth)| (threads.nodes[node].message, depth))
+///     .collect();
+/// assert_eq!(first, [(Some(1), 0), (Some(0), 1)]);
+/// assert_eq!(threads.nodes[threads.roots[1]].message, Some(2));
+/// ```
+pub fn thread(messages: &[ThreadMessage], group_by_subject: bool) -> Threads {
+    let mut arena = Arena::default();
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+
+    for (i, message) in messages.iter().enumerate() {
+        let this = match message.message_id.as_deref() {
+            Some(id) => match ids.get(id) {
+                Some(&c) if arena.nodes[c].message.is_none() => c,
+                // Duplicate identifiers get their own unlinked container.
+                Some(_) => arena.add(None),
+                None => {
+                    let c = arena.add(None);
+                    ids.insert(id, c);
+                    c
+                }
+            },
+            None => arena.add(None),
+        };
+        arena.nodes[this].message = Some(i);
+
+        let mut prev = None;
+        for id in &message.references {
+            let c = match ids.get(id.as_str()) {
+                Some(&c) => c,
+                None => {
+                    let c = arena.add(None);
+                    ids.insert(id.as_str(), c);
+                    c
+                }
+            };
+            if let Some(p) = prev {
+                if arena.nodes[c].parent.is_none() && !arena.is_ancestor(c, p) {
+                    arena.link(p, c);
+                }
+            }
+            prev = Some(c);
+        }
+
+        // The message itself is authoritative over links guessed from
+        // the references of other messages.
+        match prev {
+            Some(p) if !arena.is_ancestor(this, p) => arena.link(p, this),
+            Some(_) => (),
+            None => arena.unlink(this),
+        }
+    }
+
+    let roots: Vec<usize> = (0..arena.nodes.len()).filter(|&c| arena.nodes[c].parent.is_none()).collect();
+
+    // Replace empty containers by their children, except at the root
+    // where that would split a thread.
+    for c in arena.post_order(&roots) {
+        let children = std::mem::take(&mut arena.nodes[c].children);
+        arena.nodes[c].children = children.into_iter().flat_map(|child| match arena.nodes[child].message {
+            Some(_) => vec![child],
+            None => arena.nodes[child].children.clone(),
+        }).collect();
+    }
+    let mut roots: Vec<usize> = roots.into_iter().flat_map(|r| match arena.nodes[r].message {
+        None if arena.nodes[r].children.len() < 2 => arena.nodes[r].children.clone(),
+        _ => vec![r],
+    }).collect();
+
+    if group_by_subject {
+        roots = group_subjects(&mut arena, roots, messages);
+    }
+
+    output(&arena, &roots)
+}
+
+fn group_subjects(arena: &mut Arena, roots: Vec<usize>, messages: &[ThreadMessage]) -> Vec<usize> {
+    // Empty roots always have at least two non-empty children.
+    let message = |arena: &Arena, r: usize| &messages[arena.nodes[r].message.unwrap_or_else(|| {
+        arena.nodes[arena.nodes[r].children[0]].message.unwrap()
+    })];
+
+    // Prefer an empty container, then a message that is not a reply.
+    let mut table: HashMap<&str, usize> = HashMap::new();
+    for &r in &roots {
+        let m = message(arena, r);
+        if m.subject.is_empty() {
+            continue;
+        }
+        let better = |old: usize| {
+            match (arena.nodes[r].message, arena.nodes[old].message) {
+                (None, Some(_)) => true,
+                (Some(_), Some(_)) => message(arena, old).is_reply && !m.is_reply,
+                _ => false,
+            }
+        };
+        match table.get(m.subject.as_str()) {
+            Some(&old) if !better(old) => (),
+            _ => { table.insert(&m.subject, r); }
+        }
+    }
+
+    let mut out = Vec::with_capacity(roots.len());
+    let mut replaced: HashMap<usize, usize> = HashMap::new();
+
+    for r in roots {
+        let m = message(arena, r);
+        let target = match table.get(m.subject.as_str()) {
+            Some(&t) if t != r => t,
+            _ => {
+                out.push(r);
+                continue;
+            }
+        };
+        let t = *replaced.get(&target).unwrap_or(&target);
+
+        match (arena.nodes[t].message, arena.nodes[r].message) {
+            (None, None) => {
+                let children = std::mem::take(&mut arena.nodes[r].children);
+                arena.nodes[t].children.extend(children);
+            }
+            (None, Some(_)) => arena.nodes[t].children.push(r),
+            (Some(_), _) if m.is_reply && !message(arena, t).is_reply => arena.nodes[t].children.push(r),
+            _ => {
+                let group = arena.add(None);
+                arena.nodes[group].children = vec![t, r];
+                replaced.insert(target, group);
+            }
+        }
+    }
+
+    out.into_iter().map(|r| *replaced.get(&r).unwrap_or(&r)).collect()
+}
+
+// Renumber the containers reachable from `roots` and order siblings
+// by their first message.
+fn output(arena: &Arena, roots: &[usize]) -> Threads {
+    let mut first = vec![usize::MAX; arena.nodes.len()];
+    for c in arena.post_order(roots) {
+        let node = &arena.nodes[c];
+        first[c] = node.children.iter().map(|&child| first[child]).chain(node.message).min().unwrap_or(usize::MAX);
+    }
+
+    let sorted = |list: &[usize]| {
+        let mut list = list.to_vec();
+        list.sort_by_key(|&c| first[c]);
+        list
+    };
+
+    let mut threads = Threads::default();
+    let mut index = vec![0; arena.nodes.len()];
+    let mut stack: Vec<(usize, Option<usize>)> = sorted(roots).into_iter().rev().map(|r| (r, None)).collect();
+
+    while let Some((c, parent)) = stack.pop() {
+        index[c] = threads.nodes.len();
+        threads.nodes.push(ThreadNode { message: arena.nodes[c].message, children: Vec::new() });
+        match parent {
+            Some(p) => threads.nodes[index[p]].children.push(index[c]),
+            None => threads.roots.push(index[c]),
+        }
+        stack.extend(sorted(&arena.nodes[c].children).into_iter().rev().map(|child| (child, Some(c))));
+    }
+
+    threads
+}
@@ -0,0 +1,407 @@
+//! Parser for the [`"Received:"`] trace header
+//!
+//! The header is parsed leniently, since implementations differ in
+//! the clauses they emit and in what they put in comments. Clauses
+//! are keyword and value pairs, the values are kept as text.
+//!
+//! [`"Received:"`]: https://tools.ietf.org/html/rfc5321#section-4.4
+
+use std::fmt::{self, Display};
+use std::net::IpAddr;
+use std::str;
+use std::time::{Duration, SystemTime};
+
+use nom::bytes::complete::{tag, take_while1};
+use nom::combinator::{opt, recognize};
+use nom::sequence::{pair, preceded};
+
+use crate::behaviour::Intl;
+use crate::rfc5234::crlf;
+use crate::rfc5322::{comment, date_time, ofws, unstructured};
+use crate::util::*;
+
+/// Protocol from the `with` clause, as registered in the IANA [Mail
+/// Transmission Types] registry.
+///
+/// [Mail Transmission Types]: https://www.iana.org/assignments/mail-parameters/mail-parameters.xhtml#mail-parameters-5
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(missing_docs)] // Variants are named after the registered values.
+pub enum WithProtocol {
+    Smtp,
+    Esmtp,
+    Esmtpa,
+    Esmtps,
+    Esmtpsa,
+    Lmtp,
+    Lmtpa,
+    Lmtps,
+    Lmtpsa,
+    Utf8Smtp,
+    Utf8Smtpa,
+    Utf8Smtps,
+    Utf8Smtpsa,
+    Utf8Lmtp,
+    Utf8Lmtpa,
+    Utf8Lmtps,
+    Utf8Lmtpsa,
+    /// Any other value, as it appears in the header.
+    Other(String),
+}
+
+use self::WithProtocol as WP;
+
+const PROTOCOLS: &[(&str, WithProtocol)] = &[
+    ("SMTP", WP::Smtp), ("ESMTP", WP::Esmtp), ("ESMTPA", WP::Esmtpa),
+    ("ESMTPS", WP::Esmtps), ("ESMTPSA", WP::Esmtpsa),
+    ("LMTP", WP::Lmtp), ("LMTPA", WP::Lmtpa), ("LMTPS", WP::Lmtps), ("LMTPSA", WP::Lmtpsa),
+    ("UTF8SMTP", WP::Utf8Smtp), ("UTF8SMTPA", WP::Utf8Smtpa),
+    ("UTF8SMTPS", WP::Utf8Smtps), ("UTF8SMTPSA", WP::Utf8Smtpsa),
+    ("UTF8LMTP", WP::Utf8Lmtp), ("UTF8LMTPA", WP::Utf8Lmtpa),
+    ("UTF8LMTPS", WP::Utf8Lmtps), ("UTF8LMTPSA", WP::Utf8Lmtpsa),
+];
+
+impl WithProtocol {
+    fn registered_name(&self) -> Option<&'static str> {
+        PROTOCOLS.iter().find(|(_, p)| p == self).map(|(name, _)| *name)
+    }
+
+    /// The client authenticated, with SMTP AUTH or LMTP AUTH.
+    pub fn is_authenticated(&self) -> bool {
+        matches!(self.registered_name(), Some(name) if name.ends_with('A'))
+    }
+
+    /// The transfer was protected with STARTTLS or implicit TLS.
+    pub fn is_encrypted(&self) -> bool {
+        matches!(self.registered_name(), Some(name) if name.ends_with('S') || name.ends_with("SA"))
+    }
+}
+
+impl From<&str> for WithProtocol {
+    /// Registered values are matched case insensitively.
+    fn from(value: &str) -> Self {
+        PROTOCOLS.iter().find(|(name, _)| name.eq_ignore_ascii_case(value))
+            .map_or_else(|| WP::Other(value.into()), |(_, p)| p.clone())
+    }
+}
+
+impl Display for WithProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WP::Other(value) => write!(f, "{}", value),
+            _ => write!(f, "{}", self.registered_name().unwrap()),
+        }
+    }
+}
+
+/// Host from the `from` or `by` clause.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExtendedDomain {
+    /// The name as given, usually a domain or an address literal. In
+    /// the `from` clause, this is the client's HELO argument.
+    pub name: String,
+    /// The comment following the name, without its parentheses. In
+    /// the `from` clause, this usually holds the reverse DNS name and
+    /// IP address of the client.
+    pub info: Option<String>,
+}
+
+/// Parsed `"Received:"` header.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Received {
+    /// The `from` clause.
+    pub from: Option<ExtendedDomain>,
+    /// The `by` clause.
+    pub by: Option<ExtendedDomain>,
+    /// The `via` clause.
+    pub via: Option<String>,
+    /// The `with` clause.
+    pub with: Option<WithProtocol>,
+    /// The `id` clause.
+    pub id: Option<String>,
+    /// The `for` clause, with angle brackets removed.
+    pub recipient: Option<String>,
+    /// Any other clauses, with keywords in lowercase.
+    pub additional: Vec<(String, String)>,
+    /// The date and time following the `";"`, unfolded.
+    pub date: String,
+}
+
+fn keyword(input: &[u8]) -> NomResult<&[u8]> {
+    take_while1(|c: u8| c.is_ascii_alphanumeric() || c == b'-')(input)
+}
+
+fn clause_value(input: &[u8]) -> NomResult<&[u8]> {
+    take_while1(|c| !b" \t\r\n(;".contains(&c))(input)
+}
+
+fn skip_comments(mut input: &[u8]) -> NomResult<()> {
+    loop {
+        let (rem, _) = ofws(input)?;
+        match comment::<Intl>(rem) {
+            Ok((rem, _)) => input = rem,
+            Err(_) => return Ok((rem, ())),
+        }
+    }
+}
+
+fn extended_domain(input: &[u8]) -> NomResult<ExtendedDomain> {
+    let (rem, name) = clause_value(input)?;
+    let (rem, info) = opt(preceded(ofws, recognize(comment::<Intl>)))(rem)?;
+
+    Ok((rem, ExtendedDomain {
+        name: String::from_utf8_lossy(name).into(),
+        info: info.map(|i| String::from_utf8_lossy(&i[1..i.len()-1]).into()),
+    }))
+}
+
+/// Parse the content of a `"Received:"` header.
+///
+/// # Examples
+/// ```
+/// use rustyknife::trace::{received, WithProtocol};
+///
+/// let (_, rcvd) = received(b"from mail.example.org (mail.example.org [192.0.2.1])\r
+///     by mx.example.com (Postfix) with ESMTPS id 4F3A2; Tue, 1 Jun 2021 10:00:00 +0000\r\n").unwrap();
+///
+/// assert_eq!(rcvd.from.unwrap().info.unwrap(), "mail.example.org [192.0.2.1]");
+/// assert_eq!(rcvd.by.unwrap().name, "mx.example.com");
+/// assert_eq!(rcvd.with, Some(WithProtocol::Esmtps));
+/// assert!(rcvd.with.unwrap().is_encrypted());
+/// assert_eq!(rcvd.date, "Tue, 1 Jun 2021 10:00:00 +0000");
+/// ```
+pub fn received(input: &[u8]) -> NomResult<Received> {
+    let mut out = Received::default();
+    let (mut rem, _) = skip_comments(input)?;
+
+    while !rem.starts_with(b";") {
+        let (r, kw) = keyword(rem)?;
+        let (r, _) = ofws(r)?;
+        let kw = str::from_utf8(kw).unwrap().to_ascii_lowercase();
+
+        let r = match kw.as_str() {
+            "from" | "by" => {
+                let (r, ed) = extended_domain(r)?;
+                if kw == "from" { out.from = Some(ed) } else { out.by = Some(ed) }
+                r
+            }
+            _ => {
+                let (r, value) = clause_value(r)?;
+                let value = String::from_utf8_lossy(value).into_owned();
+                match kw.as_str() {
+                    "via" => out.via = Some(value),
+                    "with" => out.with = Some(value.as_str().into()),
+                    "id" => out.id = Some(value),
+                    "for" => out.recipient = Some(value.trim_start_matches('<').trim_end_matches('>').into()),
+                    _ => out.additional.push((kw, value)),
+                }
+                r
+            }
+        };
+        rem = skip_comments(r)?.0;
+    }
+
+    let (rem, (date, _)) = preceded(tag(";"), pair(unstructured::<Intl>, opt(crlf)))(rem)?;
+    out.date = date.trim().into();
+
+    Ok((rem, out))
+}
+
+/// Routing scope of an IP address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpScope {
+    /// `127.0.0.0/8` and `::1`.
+    Loopback,
+    /// Private ranges: `10.0.0.0/8`, `172.16.0.0/12`,
+    /// `192.168.0.0/16`, the `100.64.0.0/10` shared address space and
+    /// IPv6 unique local addresses `fc00::/7`.
+    Private,
+    /// `169.254.0.0/16` and `fe80::/10`.
+    LinkLocal,
+    /// Unspecified, multicast and other addresses that cannot be the
+    /// source of a connection.
+    Reserved,
+    /// Any other address.
+    Public,
+}
+
+impl IpScope {
+    /// Classify `ip`. IPv4-mapped IPv6 addresses are classified as
+    /// the IPv4 address.
+    /// # Examples
+    /// ```
+    /// use rustyknife::trace::IpScope;
+    ///
+    /// assert_eq!(IpScope::of("172.20.1.1".parse().unwrap()), IpScope::Private);
+    /// assert_eq!(IpScope::of("fd00::1".parse().unwrap()), IpScope::Private);
+    /// assert_eq!(IpScope::of("::ffff:127.0.0.1".parse().unwrap()), IpScope::Loopback);
+    /// assert_eq!(IpScope::of("198.51.100.7".parse().unwrap()), IpScope::Public);
+    /// ```
+    pub fn of(ip: IpAddr) -> IpScope {
+        match ip {
+            IpAddr::V4(ip) => {
+                let o = ip.octets();
+                if ip.is_loopback() {
+                    IpScope::Loopback
+                } else if ip.is_private() || (o[0] == 100 && (o[1] & 0xc0) == 64) {
+                    IpScope::Private
+                } else if ip.is_link_local() {
+                    IpScope::LinkLocal
+                } else if o[0] == 0 || o[0] >= 224 {
+                    IpScope::Reserved
+                } else {
+                    IpScope::Public
+                }
+            }
+            IpAddr::V6(ip) => {
+                if let Some(v4) = ip.to_ipv4_mapped() {
+                    return IpScope::of(IpAddr::V4(v4));
+                }
+                let first = ip.segments()[0];
+                if ip.is_loopback() {
+                    IpScope::Loopback
+                } else if (first & 0xfe00) == 0xfc00 {
+                    IpScope::Private
+                } else if (first & 0xffc0) == 0xfe80 {
+                    IpScope::LinkLocal
+                } else if ip.is_unspecified() || ip.is_multicast() {
+                    IpScope::Reserved
+                } else {
+                    IpScope::Public
+                }
+            }
+        }
+    }
+
+    /// Whether the address belongs to the local site: loopback,
+    /// private or link local.
+    pub fn is_internal(self) -> bool {
+        matches!(self, IpScope::Loopback | IpScope::Private | IpScope::LinkLocal)
+    }
+}
+
+fn parse_ip(text: &str) -> Option<IpAddr> {
+    let text = match text.get(..5) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("IPv6:") => &text[5..],
+        _ => text,
+    };
+    text.parse().ok()
+}
+
+impl Received {
+    /// The IP address of the client that connected to the receiving
+    /// host.
+    ///
+    /// Bracketed addresses in the TCP information of the `from`
+    /// clause are preferred, as added by the receiving host, then any
+    /// bare address in it. An address literal used as the HELO name
+    /// is the last resort.
+    /// # Examples
+    /// ```
+    /// use rustyknife::trace::received;
+    ///
+    /// let (_, rcvd) = received(b"from helo.example (rdns.example [IPv6:2001:db8::25]) by mx.example.org; \
+    ///                            Tue, 1 Jun 2021 10:00:00 +0000").unwrap();
+    /// assert_eq!(rcvd.client_ip(), Some("2001:db8::25".parse().unwrap()));
+    /// ```
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        let from = self.from.as_ref()?;
+
+        if let Some(info) = &from.info {
+            let bracketed = info.split('[').skip(1).filter_map(|s| s.split(']').next()).find_map(parse_ip);
+            let bare = || info.split(|c: char| c.is_whitespace() || "()[]=,;".contains(c)).find_map(parse_ip);
+            if let Some(ip) = bracketed.or_else(bare) {
+                return Some(ip);
+            }
+        }
+
+        from.name.strip_prefix('[').and_then(|n| n.strip_suffix(']')).and_then(parse_ip)
+    }
+}
+
+/// Find the first hop from outside the local site.
+///
+/// `chain` holds the `"Received:"` headers in the order they appear
+/// in the message, most recent first. Hops with an internal client
+/// address are skipped and the first public client address is
+/// returned with the index of its header. Hops without an address
+/// are skipped as well.
+/// # Examples
+/// ```
+/// use rustyknife::trace::{first_external_ip, received};
+///
+/// let chain: Vec<_> = [
+///     &b"from filter.local (localhost [127.0.0.1]) by mx.example.org; Tue, 1 Jun 2021 10:00:02 +0000"[..],
+///     b"from gw.example.org (gw.example.org [10.1.2.3]) by filter.local; Tue, 1 Jun 2021 10:00:01 +0000",
+///     b"from mail.sender.example (mail.sender.example [203.0.113.5]) by gw.example.org; Tue, 1 Jun 2021 10:00:00 +0000",
+///     b"from laptop (laptop [192.168.1.20]) by mail.sender.example; Tue, 1 Jun 2021 09:59:59 +0000",
+/// ].iter().map(|h| received(h).unwrap().1).collect();
+///
+/// assert_eq!(first_external_ip(&chain), Some((2, "203.0.113.5".parse().unwrap())));
+/// ```
+pub fn first_external_ip(chain: &[Received]) -> Option<(usize, IpAddr)> {
+    chain.iter().enumerate()
+        .filter_map(|(i, rcvd)| rcvd.client_ip().map(|ip| (i, ip)))
+        .find(|(_, ip)| IpScope::of(*ip) == IpScope::Public)
+}
+
+/// Comparison of the `"Date:"` header with the earliest
+/// `"Received:"` timestamp, see [`date_skew`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateSkew {
+    /// The `"Date:"` header.
+    pub date: SystemTime,
+    /// The earliest timestamp of the `"Received:"` chain.
+    pub received: SystemTime,
+    /// Seconds from the date to the reception, negative when the
+    /// message is dated after it was received.
+    pub skew: i64,
+}
+
+impl DateSkew {
+    /// Whether the message is dated after its reception, by more than
+    /// `tolerance`.
+    pub fn is_future(&self, tolerance: Duration) -> bool {
+        self.skew < 0 && self.skew.unsigned_abs() > tolerance.as_secs()
+    }
+
+    /// Whether the message was received more than `max_age` after its
+    /// date.
+    pub fn is_ancient(&self, max_age: Duration) -> bool {
+        self.skew > 0 && self.skew.unsigned_abs() > max_age.as_secs()
+    }
+}
+
+impl Received {
+    /// The date of this header, converted to UTC.
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        date_time(self.date.as_bytes()).ok().map(|(_, time)| time)
+    }
+}
+
+/// Compare the content of the `"Date:"` header with the earliest
+/// timestamp of `chain`.
+///
+/// Headers of the chain with an invalid date are ignored. Returns
+/// `None` when the date is invalid or no timestamp was found.
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use rustyknife::trace::{date_skew, received};
+///
+/// let (_, rcvd) = received(b"from a.example by b.example; Tue, 1 Jun 2021 10:00:00 +0000").unwrap();
+/// let skew = date_skew(b" Tue, 1 Jun 2021 11:30:00 +0200", &[rcvd]).unwrap();
+///
+/// assert_eq!(skew.skew, 1800);
+/// assert!(!skew.is_future(Duration::from_secs(900)));
+/// assert!(skew.is_ancient(Duration::from_secs(900)));
+/// ```
+pub fn date_skew(date: &[u8], chain: &[Received]) -> Option<DateSkew> {
+    let (_, date) = date_time(date).ok()?;
+    let received = chain.iter().filter_map(Received::timestamp).min()?;
+    let skew = match received.duration_since(date) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+
+    Some(DateSkew { date, received, skew })
+}
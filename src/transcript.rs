@@ -0,0 +1,170 @@
+//! SMTP session transcripts
+//!
+//! A [`Recorder`] captures the command lines received and the
+//! replies sent during a session, with the time elapsed since the
+//! start of the session. The resulting [`Transcript`] can be stored,
+//! with the `serde` feature, and later replayed against a server
+//! implementation to check that its behaviour did not change.
+
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use crate::reply::Reply;
+
+/// Something that happened during the session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Event {
+    /// A command line sent by the client, including the CRLF.
+    Command(Vec<u8>),
+    /// A reply sent by the server.
+    Reply(Reply),
+}
+
+/// An [`Event`] and when it happened.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Entry {
+    /// Time since the start of the session.
+    pub elapsed: Duration,
+    /// The command or reply.
+    pub event: Event,
+}
+
+/// The events of a session, in order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Transcript {
+    /// All the recorded entries.
+    pub entries: Vec<Entry>,
+}
+
+/// A difference found by [`Transcript::replay`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the command in the transcript entries.
+    pub entry: usize,
+    /// The recorded replies to the command.
+    pub expected: Vec<Reply>,
+    /// The replies of the server under test.
+    pub actual: Vec<Reply>,
+}
+
+/// Captures a [`Transcript`] as the session goes.
+#[derive(Clone, Debug)]
+pub struct Recorder {
+    start: Instant,
+    transcript: Transcript,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recorder {
+    /// Start recording, timestamps are relative to now.
+    pub fn new() -> Self {
+        Recorder { start: Instant::now(), transcript: Transcript::default() }
+    }
+
+    /// Record a command line received from the client.
+    pub fn command(&mut self, line: &[u8]) {
+        self.push(Event::Command(line.to_vec()));
+    }
+
+    /// Record a reply sent to the client.
+    pub fn reply(&mut self, reply: &Reply) {
+        self.push(Event::Reply(reply.clone()));
+    }
+
+    fn push(&mut self, event: Event) {
+        self.transcript.entries.push(Entry { elapsed: self.start.elapsed(), event });
+    }
+
+    /// Stop recording.
+    pub fn finish(self) -> Transcript {
+        self.transcript
+    }
+}
+
+impl Transcript {
+    /// The recorded command lines.
+    pub fn commands(&self) -> impl Iterator<Item=&[u8]> {
+        self.entries.iter().filter_map(|e| match &e.event {
+            Event::Command(line) => Some(line.as_slice()),
+            _ => None,
+        })
+    }
+
+    /// Compare `greeting` to the recorded one, then feed the recorded
+    /// commands to `server` and compare its replies.
+    ///
+    /// `server` is called with each command line and returns the
+    /// replies it sends at that point, possibly none when the command
+    /// is pipelined. As on the wire, each reply, recorded or actual,
+    /// answers the oldest command still waiting for one. Replies
+    /// beyond that are attributed to the last command. A divergence in
+    /// the greeting is reported with the index of the first entry.
+    /// # Examples
+    /// ```
+    /// use rustyknife::reply::Reply;
+    /// use rustyknife::transcript::Recorder;
+    ///
+    /// let mut recorder = Recorder::new();
+    /// recorder.reply(&Reply::new(220, vec!["mx.example.org"]));
+    /// recorder.command(b"NOOP\r\n");
+    /// recorder.reply(&Reply::new(250, vec!["OK"]));
+    /// recorder.command(b"QUIT\r\n");
+    /// recorder.reply(&Reply::new(221, vec!["Bye"]));
+    /// let transcript = recorder.finish();
+    ///
+    /// let diverged = transcript.replay(&Reply::new(220, vec!["mx.example.org"]), |line| match line {
+    ///     b"QUIT\r\n" => vec![Reply::new(221, vec!["Bye"])],
+    ///     _ => vec![Reply::new(502, vec!["Not implemented"])],
+    /// });
+    /// assert_eq!(diverged.len(), 1);
+    /// assert_eq!(diverged[0].entry, 1);
+    /// assert_eq!(diverged[0].actual[0].code, 502);
+    /// ```
+    pub fn replay<F>(&self, greeting: &Reply, mut server: F) -> Vec<Divergence>
+        where F: FnMut(&[u8]) -> Vec<Reply>
+    {
+        let mut recorded_greeting = Vec::new();
+        // Command entry indices with their recorded and actual replies.
+        let mut commands: Vec<(usize, Vec<Reply>, Vec<Reply>)> = Vec::new();
+        let (mut expected_next, mut actual_next) = (0, 0);
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            match &entry.event {
+                Event::Reply(reply) if commands.is_empty() => recorded_greeting.push(reply.clone()),
+                Event::Reply(reply) => {
+                    let target = expected_next.min(commands.len() - 1);
+                    commands[target].1.push(reply.clone());
+                    expected_next += 1;
+                }
+                Event::Command(line) => {
+                    commands.push((i, Vec::new(), Vec::new()));
+                    for reply in server(line) {
+                        let target = actual_next.min(commands.len() - 1);
+                        commands[target].2.push(reply);
+                        actual_next += 1;
+                    }
+                }
+            }
+        }
+
+        let mut diverged = Vec::new();
+        if recorded_greeting != [greeting.clone()] {
+            diverged.push(Divergence { entry: 0, expected: recorded_greeting, actual: vec![greeting.clone()] });
+        }
+        diverged.extend(commands.into_iter()
+            .filter(|(_, expected, actual)| expected != actual)
+            .map(|(entry, expected, actual)| Divergence { entry, expected, actual }));
+
+        diverged
+    }
+}
@@ -0,0 +1,167 @@
+//! SMTP DATA [transparency] (dot-stuffing)
+//!
+//! During the DATA phase a line holding a single dot ends the message
+//! content. Clients therefore double the dot at the start of every
+//! line before sending, and servers remove it again on reception.
+//!
+//! Lines are only delimited by CRLF, in line with the strict end of
+//! data sequence used in [`crate::smuggling`]. Data containing bare
+//! CR or LF should be normalized or rejected beforehand.
+//!
+//! [transparency]: https://tools.ietf.org/html/rfc5321#section-4.5.2
+
+/// The end of data sequence, as sent after CRLF terminated content.
+const EOD: &[u8] = b"\r\n.\r\n";
+
+/// Iterate over the lines of `data`, each including its CRLF.
+fn lines(data: &[u8]) -> impl Iterator<Item=&[u8]> {
+    let mut rest = data;
+
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest.windows(2).position(|w| w == b"\r\n").map(|i| i + 2).unwrap_or_else(|| rest.len());
+        let (line, tail) = rest.split_at(end);
+        rest = tail;
+        Some(line)
+    })
+}
+
+/// Double the leading dot of every line in `data`.
+///
+/// The start of `data` is considered the start of a line.
+/// # Examples
+/// ```
+/// use rustyknife::transparency::dot_stuff;
+///
+/// assert_eq!(dot_stuff(b".hidden\r\nline\r\n.\r\n"), b"..hidden\r\nline\r\n..\r\n");
+/// assert_eq!(dot_stuff(b"a.b\n.c"), b"a.b\n.c");
+/// ```
+pub fn dot_stuff(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+
+    for line in lines(data) {
+        if line.starts_with(b".") {
+            out.push(b'.');
+        }
+        out.extend_from_slice(line);
+    }
+
+    out
+}
+
+/// Remove the leading dot of every line in `data`.
+///
+/// `data` is the message content without the terminating `".\r\n"`.
+/// # Examples
+/// ```
+/// use rustyknife::transparency::dot_unstuff;
+///
+/// assert_eq!(dot_unstuff(b"..hidden\r\nline\r\n..\r\n"), b".hidden\r\nline\r\n.\r\n");
+/// ```
+pub fn dot_unstuff(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+
+    for line in lines(data) {
+        out.extend_from_slice(line.strip_prefix(b".").unwrap_or(line));
+    }
+
+    out
+}
+
+/// Dot-stuff `data` and append the end of data sequence.
+///
+/// A CRLF is added first if `data` does not already end with one,
+/// producing the exact bytes to send after the DATA command.
+/// # Examples
+/// ```
+/// use rustyknife::transparency::to_data;
+///
+/// assert_eq!(to_data(b".a\r\nb"), b"..a\r\nb\r\n.\r\n");
+/// assert_eq!(to_data(b"a\r\n"), b"a\r\n.\r\n");
+/// assert_eq!(to_data(b""), b".\r\n");
+/// ```
+pub fn to_data(data: &[u8]) -> Vec<u8> {
+    let mut out = dot_stuff(data);
+
+    if out.is_empty() || out.ends_with(b"\r\n") {
+        out.extend_from_slice(&EOD[2..]);
+    } else {
+        out.extend_from_slice(EOD);
+    }
+
+    out
+}
+
+/// Incremental end of data scanner.
+///
+/// Finds the `"\r\n.\r\n"` sequence in data received in arbitrary
+/// chunks, including when the sequence is split across chunks. The
+/// scanner starts at the beginning of a line, just after the CRLF
+/// ending the DATA command.
+/// # Examples
+/// ```
+/// use rustyknife::transparency::EodScanner;
+///
+/// let mut scanner = EodScanner::new();
+///
+/// assert_eq!(scanner.feed(b"line\r\n..dot\r"), None);
+/// assert_eq!(scanner.feed(b"\n."), None);
+/// assert_eq!(scanner.feed(b"\r\nMAIL FROM:<>\r\n"), Some(2));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EodScanner {
+    matched: usize,
+}
+
+impl EodScanner {
+    /// Create a scanner positioned at the start of the message content.
+    pub fn new() -> Self {
+        EodScanner { matched: 2 }
+    }
+
+    /// Scan the next `chunk` of data.
+    ///
+    /// Returns the offset in `chunk` just past the end of data
+    /// sequence, where the next command starts. The message content
+    /// ends three bytes before the end of data sequence ends, the
+    /// final CRLF belonging to the last line.
+    ///
+    /// The scanner must be [reset](Self::reset) before reuse.
+    pub fn feed(&mut self, chunk: &[u8]) -> Option<usize> {
+        for (i, c) in chunk.iter().enumerate() {
+            if self.matched == EOD.len() {
+                return None;
+            }
+            self.matched = if *c == EOD[self.matched] {
+                self.matched + 1
+            } else if *c == b'\r' {
+                1
+            } else {
+                0
+            };
+            if self.matched == EOD.len() {
+                return Some(i + 1);
+            }
+        }
+
+        None
+    }
+
+    /// Returns `true` once the end of data sequence was found.
+    pub fn is_done(&self) -> bool {
+        self.matched == EOD.len()
+    }
+
+    /// Position the scanner at the start of a new message content.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Default for EodScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,141 @@
+//! Bounded copies of messages
+//!
+//! Logging and quarantine systems often keep only the start of large
+//! messages. Cutting at an arbitrary byte breaks the header section
+//! and leaves multipart bodies without their close delimiter, so the
+//! copy no longer parses like the original. [`truncate_message`]
+//! cuts at a safe place instead.
+
+use std::borrow::Cow;
+
+use crate::mime::{parse_message, Entity};
+
+/// Name of the header field added to truncated messages. Its value
+/// is the original size in bytes.
+pub const TRUNCATED_HEADER: &str = "X-Truncated";
+
+fn offset(input: &[u8], part: &[u8]) -> usize {
+    part.as_ptr() as usize - input.as_ptr() as usize
+}
+
+// Start of the header section of a body part, or None when it has no
+// header fields.
+fn headers_start(input: &[u8], entity: &Entity) -> Option<usize> {
+    entity.headers.first().map(|field| match field {
+        Ok((name, _)) => offset(input, name),
+        Err(raw) => offset(input, raw),
+    })
+}
+
+// Move `limit` back to a line boundary outside of any header section.
+fn cut_point(input: &[u8], message: &Entity, limit: usize) -> usize {
+    let header_end = offset(input, message.body);
+    let mut cut = limit.min(input.len()).max(header_end);
+
+    if let Some(nl) = input[header_end..cut].iter().rposition(|c| *c == b'\n') {
+        cut = header_end + nl + 1;
+    }
+
+    for part in message.iter().skip(1) {
+        let body = offset(input, part.body);
+        if let Some(start) = headers_start(input, part) {
+            if (start..body).contains(&cut) {
+                // Also drop the delimiter line ending at `start`.
+                cut = input[..start.saturating_sub(1)].iter().rposition(|c| *c == b'\n').map_or(start, |nl| nl + 1);
+            }
+        }
+    }
+
+    cut
+}
+
+// Close delimiters of the multipart entities left open by cutting at
+// `cut`, innermost first, each on its own line.
+fn closers(input: &[u8], message: &Entity, cut: usize) -> Vec<u8> {
+    let mut open: Vec<&Entity> = message.iter()
+        .filter(|e| e.is_multipart() && !e.parts.is_empty())
+        .filter(|e| {
+            let body = offset(input, e.body);
+            // A missing close delimiter leaves the epilogue at the start of the body.
+            let end = match offset(input, e.epilogue) {
+                end if end == body => body + e.body.len() + 1,
+                end => end,
+            };
+            (body..end).contains(&cut)
+        })
+        .collect();
+    open.reverse();
+
+    let mut out = Vec::new();
+    if !open.is_empty() && cut > 0 && input[cut - 1] != b'\n' {
+        out.extend_from_slice(b"\r\n");
+    }
+    for entity in open {
+        out.extend_from_slice(b"--");
+        out.extend_from_slice(entity.param("boundary").unwrap_or_default().as_bytes());
+        out.extend_from_slice(b"--\r\n");
+    }
+    out
+}
+
+/// Truncate `input` to at most `max_len` bytes, keeping the result
+/// parseable.
+///
+/// Messages that fit are returned as is. Otherwise:
+/// - The cut is made at the end of a line and never inside the header
+///   section of the message or of a body part. A part whose header
+///   section does not fit is dropped along with its delimiter line.
+/// - Every multipart left open is closed with its close delimiter.
+/// - A [`TRUNCATED_HEADER`] field with the original size is prepended.
+///
+/// The header section of the message is always kept, so the result is
+/// longer than `max_len` when the header section alone does not fit.
+/// Encapsulated messages are not looked into.
+/// # Examples
+/// ```
+/// use rustyknife::mime::parse_message;
+/// use rustyknife::truncate::truncate_message;
+///
+/// let input = b"Subject: report\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n\
+///               --b\r\n\r\nSee the attached log.\r\n\
+///               --b\r\nContent-Type: text/plain\r\n\r\nline 1\r\nline 2\r\nline 3\r\n--b--\r\n";
+/// let out = truncate_message(input, 120);
+///
+/// assert_eq!(&out[..], &b"X-Truncated: 156\r\n\
+///                          Subject: report\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n\
+///                          --b\r\n\r\nSee the attached log.\r\n\
+///                          --b--\r\n"[..]);
+/// assert_eq!(parse_message(&out).parts.len(), 1);
+///
+/// assert_eq!(truncate_message(input, 200), &input[..]);
+/// ```
+pub fn truncate_message(input: &[u8], max_len: usize) -> Cow<[u8]> {
+    if input.len() <= max_len {
+        return input.into();
+    }
+
+    let note = format!("{}: {}\r\n", TRUNCATED_HEADER, input.len());
+    let message = parse_message(input);
+    let header_end = offset(input, message.body);
+    let budget = max_len.saturating_sub(note.len());
+
+    let mut limit = budget;
+    let (cut, tail) = loop {
+        let cut = cut_point(input, &message, limit);
+        let tail = closers(input, &message, cut);
+        if cut <= header_end {
+            break (header_end, Vec::new());
+        }
+        if cut + tail.len() <= budget {
+            break (cut, tail);
+        }
+        limit = (cut - 1).min(budget.saturating_sub(tail.len()));
+    };
+
+    let kept = &input[..cut];
+    let mut out = Vec::with_capacity(note.len() + kept.len() + tail.len());
+    out.extend_from_slice(note.as_bytes());
+    out.extend_from_slice(kept);
+    out.extend_from_slice(&tail);
+    out.into()
+}
@@ -11,7 +11,7 @@
 //! from valid values.
 use std::fmt::{self, Display};
 
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv4Addr};
 
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
@@ -23,11 +23,71 @@ use crate::util::*;
 
 /// A domain name such as used by DNS.
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Domain(pub(crate) String);
 string_newtype!(Domain);
 impl Domain {
     nom_from_smtp!(smtp::domain::<Intl>);
     nom_from_imf!(imf::_domain::<Intl>);
+
+    /// Render this domain with internationalized labels converted to
+    /// punycode, as required on the wire without SMTPUTF8.
+    ///
+    /// Fails if a label cannot be converted.
+    /// # Examples
+    /// ```
+    /// use rustyknife::types::Domain;
+    ///
+    /// let domain = Domain::from_smtp("bücher.example".as_bytes()).unwrap();
+    /// assert_eq!(domain.to_ascii_string(), Ok("xn--bcher-kva.example".into()));
+    /// ```
+    #[allow(clippy::result_unit_err)]
+    pub fn to_ascii_string(&self) -> Result<String, ()> {
+        idna::domain_to_ascii(&self.0).map_err(|_| ())
+    }
+
+    /// Render this domain with punycode labels converted to Unicode,
+    /// for display purposes.
+    ///
+    /// Labels that fail to convert are left untouched.
+    /// # Examples
+    /// ```
+    /// use rustyknife::types::Domain;
+    ///
+    /// let domain = Domain::from_smtp(b"xn--bcher-kva.example").unwrap();
+    /// assert_eq!(domain.to_unicode_string(), "bücher.example");
+    /// ```
+    pub fn to_unicode_string(&self) -> String {
+        match idna::domain_to_unicode(&self.0) {
+            (out, Ok(())) => out,
+            (_, Err(_)) => self.0.clone(),
+        }
+    }
+
+    /// Whether this domain looks like a fully qualified domain name.
+    ///
+    /// It must have at least two labels and a top level label that is
+    /// not purely numeric. No DNS lookup is done.
+    /// # Examples
+    /// ```
+    /// use rustyknife::types::Domain;
+    ///
+    /// assert!(Domain::from_smtp(b"mx.example.org").unwrap().is_fqdn());
+    /// assert!(!Domain::from_smtp(b"localhost").unwrap().is_fqdn());
+    /// assert!(!Domain::from_smtp(b"192.0.2.1").unwrap().is_fqdn());
+    /// ```
+    pub fn is_fqdn(&self) -> bool {
+        match self.0.trim_end_matches('.').rsplit_once('.') {
+            Some((_, tld)) => !tld.bytes().all(|c| c.is_ascii_digit()),
+            None => false,
+        }
+    }
+
+    /// Whether this domain is a bare IPv4 address, such as a client
+    /// sending `"EHLO 192.0.2.1"` instead of `"EHLO [192.0.2.1]"`.
+    pub fn is_bare_address(&self) -> bool {
+        self.0.parse::<Ipv4Addr>().is_ok()
+    }
 }
 
 /// The local part of an address preceding the `"@"` in an email address.
@@ -55,6 +115,38 @@ impl LocalPart {
             LocalPart::DotAtom(_) => (),
         }
     }
+
+    /// Return this local part unquoted if its contents fit the
+    /// dot-atom grammar, so that equivalent addresses compare equal.
+    /// # Examples
+    /// ```
+    /// use rustyknife::types::LocalPart;
+    ///
+    /// let local = LocalPart::from_smtp(b"\"bob\"").unwrap();
+    /// assert_eq!(local.canonicalize(), LocalPart::from_smtp(b"bob").unwrap());
+    ///
+    /// let local = LocalPart::from_smtp(b"\"bob smith\"").unwrap();
+    /// assert_eq!(local.canonicalize(), local);
+    /// ```
+    pub fn canonicalize(&self) -> LocalPart {
+        let mut out = self.clone();
+        out.smtp_try_unquote();
+        out
+    }
+
+    /// Build a local part from its unquoted `value`, quoting it only
+    /// if it does not fit the dot-atom grammar.
+    /// # Examples
+    /// ```
+    /// use rustyknife::types::LocalPart;
+    ///
+    /// assert_eq!(LocalPart::quote_if_needed("bob.smith").to_string(), "bob.smith");
+    /// assert_eq!(LocalPart::quote_if_needed("bob smith").to_string(), "\"bob smith\"");
+    /// assert_eq!(LocalPart::quote_if_needed("bob..smith").to_string(), "\"bob..smith\"");
+    /// ```
+    pub fn quote_if_needed(value: &str) -> LocalPart {
+        LocalPart::Quoted(QuotedString(value.into())).canonicalize()
+    }
 }
 
 impl From<QuotedString> for LocalPart {
@@ -144,6 +236,39 @@ pub enum DomainPart {
 impl DomainPart {
     nom_from_smtp!(smtp::_domain_part::<Intl>);
     nom_from_imf!(imf::domain::<Intl>);
+
+    /// Render this domain part using punycode for domain names.
+    ///
+    /// Address literals are rendered unchanged. See [`Domain::to_ascii_string`].
+    #[allow(clippy::result_unit_err)]
+    pub fn to_ascii_string(&self) -> Result<String, ()> {
+        match self {
+            DomainPart::Domain(d) => d.to_ascii_string(),
+            DomainPart::Address(a) => Ok(a.to_string()),
+        }
+    }
+
+    /// Render this domain part using Unicode for domain names.
+    ///
+    /// Address literals are rendered unchanged. See [`Domain::to_unicode_string`].
+    pub fn to_unicode_string(&self) -> String {
+        match self {
+            DomainPart::Domain(d) => d.to_unicode_string(),
+            DomainPart::Address(a) => a.to_string(),
+        }
+    }
+
+    /// Whether this is a domain name that looks fully qualified.
+    ///
+    /// See [`Domain::is_fqdn`]. Address literals are not.
+    pub fn is_fqdn(&self) -> bool {
+        matches!(self, DomainPart::Domain(d) if d.is_fqdn())
+    }
+
+    /// Whether this is an address literal such as `"[192.0.2.1]"`.
+    pub fn is_address_literal(&self) -> bool {
+        matches!(self, DomainPart::Address(_))
+    }
 }
 
 impl From<Domain> for DomainPart {
@@ -276,6 +401,59 @@ impl Mailbox {
         self.0.smtp_try_unquote()
     }
 
+    /// Render this address with the domain converted to punycode.
+    ///
+    /// Fails if the domain cannot be converted or if the local part
+    /// contains non-ASCII characters, since there is no ASCII form for
+    /// those.
+    /// # Examples
+    /// ```
+    /// use rustyknife::types::Mailbox;
+    ///
+    /// let mbox = Mailbox::from_smtp("bob@bücher.example".as_bytes()).unwrap();
+    /// assert_eq!(mbox.to_ascii_string(), Ok("bob@xn--bcher-kva.example".into()));
+    ///
+    /// let mbox = Mailbox::from_smtp("rené@example.org".as_bytes()).unwrap();
+    /// assert_eq!(mbox.to_ascii_string(), Err(()));
+    /// ```
+    #[allow(clippy::result_unit_err)]
+    pub fn to_ascii_string(&self) -> Result<String, ()> {
+        let local = self.0.to_string();
+
+        if !local.is_ascii() {
+            return Err(());
+        }
+        Ok(format!("{}@{}", local, self.1.to_ascii_string()?))
+    }
+
+    /// Render this address with the domain converted to Unicode.
+    pub fn to_unicode_string(&self) -> String {
+        format!("{}@{}", self.0, self.1.to_unicode_string())
+    }
+
+    // Lowercased canonical form, see `eq_ignore_case`.
+    pub(crate) fn caseless_key(&self) -> String {
+        smtp::canonical_mailbox(self).to_unicode_string().to_lowercase()
+    }
+
+    /// Compare two addresses ignoring case, needless quoting of the
+    /// local part and the form of the domain.
+    ///
+    /// Local parts are case sensitive according to RFC 5321, but
+    /// almost every system treats them case insensitively.
+    /// # Examples
+    /// ```
+    /// use rustyknife::types::Mailbox;
+    ///
+    /// let a = Mailbox::from_smtp(b"\"Bob\"@xn--bcher-kva.example").unwrap();
+    /// let b = Mailbox::from_smtp("bob@B\u{fc}cher.example".as_bytes()).unwrap();
+    ///
+    /// assert!(a.eq_ignore_case(&b));
+    /// ```
+    pub fn eq_ignore_case(&self, other: &Mailbox) -> bool {
+        self.caseless_key() == other.caseless_key()
+    }
+
     nom_from_smtp!(smtp::mailbox::<Intl>);
     nom_from_imf!(imf::addr_spec::<Intl>);
 }
@@ -0,0 +1,224 @@
+//! Mail user agent identification headers
+//!
+//! Parses the `"User-Agent:"`, `"X-Mailer:"` and `"X-MimeOLE:"`
+//! headers into product tokens and comments, following the
+//! [User-Agent] syntax. Values written as free text, such as
+//! `"Microsoft Outlook 16.0"`, are accepted too: a word starting with
+//! a digit is taken as the version of the words before it.
+//!
+//! [User-Agent]: https://tools.ietf.org/html/rfc7231#section-5.5.3
+
+use std::fmt::{self, Display};
+
+use crate::behaviour::Intl;
+use crate::headersection::HeaderField;
+use crate::rfc5322::{comment, CommentContent};
+
+/// A product name with its optional version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Product {
+    /// Product name, multiple words are separated by a single space.
+    pub name: String,
+    /// Version, without any leading `"v"`.
+    pub version: Option<String>,
+}
+
+impl Product {
+    /// Returns the first component of the version.
+    /// # Examples
+    /// ```
+    /// use rustyknife::useragent::Product;
+    ///
+    /// let product = Product { name: "Thunderbird".into(), version: Some("102.4.2".into()) };
+    /// assert_eq!(product.major_version(), Some("102"));
+    /// ```
+    pub fn major_version(&self) -> Option<&str> {
+        self.version.as_deref().and_then(|v| v.split(|c: char| !c.is_ascii_alphanumeric()).next())
+    }
+}
+
+impl Display for Product {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{}/{}", self.name, version),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// A component of a user agent value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Token {
+    /// A product name and version.
+    Product(Product),
+    /// A comment without its parentheses.
+    Comment(String),
+}
+
+/// A parsed user agent value.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UserAgent {
+    /// Products and comments in order of appearance.
+    pub tokens: Vec<Token>,
+}
+
+impl UserAgent {
+    /// Iterate over the products.
+    pub fn products(&self) -> impl Iterator<Item=&Product> {
+        self.tokens.iter().filter_map(|t| match t {
+            Token::Product(p) => Some(p),
+            Token::Comment(_) => None,
+        })
+    }
+
+    /// Iterate over the comments.
+    pub fn comments(&self) -> impl Iterator<Item=&str> {
+        self.tokens.iter().filter_map(|t| match t {
+            Token::Comment(c) => Some(c.as_str()),
+            Token::Product(_) => None,
+        })
+    }
+
+    /// A normalized form for grouping similar agents.
+    ///
+    /// Products are lowercased and reduced to their major version,
+    /// comments are dropped.
+    /// # Examples
+    /// ```
+    /// use rustyknife::useragent::user_agent;
+    ///
+    /// let ua = user_agent(b" Mozilla/5.0 (Windows NT 10.0; Win64; x64) Thunderbird/102.4.2");
+    /// assert_eq!(ua.normalized(), "mozilla/5 thunderbird/102");
+    /// ```
+    pub fn normalized(&self) -> String {
+        self.products().map(|p| match p.major_version() {
+            Some(major) => format!("{}/{}", p.name.to_lowercase(), major),
+            None => p.name.to_lowercase(),
+        }).collect::<Vec<_>>().join(" ")
+    }
+}
+
+fn render_comment(content: &[CommentContent], out: &mut String) {
+    for c in content {
+        match c {
+            CommentContent::Text(text) => out.push_str(text),
+            CommentContent::QP(c) => out.push(*c),
+            CommentContent::Comment(inner) => {
+                out.push('(');
+                render_comment(inner, out);
+                out.push(')');
+            }
+        }
+    }
+}
+
+fn is_version(word: &str) -> bool {
+    let digits = word.strip_prefix(['v', 'V']).unwrap_or(word);
+    digits.starts_with(|c: char| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_alphanumeric() || ".-_+".contains(c))
+}
+
+/// Parse a `"User-Agent:"`, `"X-Mailer:"` or `"X-MimeOLE:"` value.
+///
+/// Parsing never fails, unbalanced parentheses are kept as text.
+/// # Examples
+/// ```
+/// use rustyknife::useragent::{user_agent, Product, Token};
+///
+/// let ua = user_agent(b" Produced By Microsoft MimeOLE V6.00.2900.2180\r\n");
+/// assert_eq!(ua.tokens, [Token::Product(Product {
+///     name: "Produced By Microsoft MimeOLE".into(),
+///     version: Some("6.00.2900.2180".into()),
+/// })]);
+///
+/// let ua = user_agent(b" iPhone Mail (20B101)");
+/// assert_eq!(ua.products().next().unwrap().name, "iPhone Mail");
+/// assert_eq!(ua.comments().collect::<Vec<_>>(), ["20B101"]);
+/// ```
+pub fn user_agent(input: &[u8]) -> UserAgent {
+    let mut out = UserAgent::default();
+    let mut name: Vec<String> = Vec::new();
+    let mut rem = input;
+
+    let flush = |name: &mut Vec<String>, version: Option<String>, out: &mut UserAgent| {
+        if !name.is_empty() {
+            out.tokens.push(Token::Product(Product { name: name.join(" "), version }));
+            name.clear();
+        }
+    };
+
+    loop {
+        rem = &rem[rem.iter().take_while(|c| b" \t\r\n".contains(c)).count()..];
+        if rem.is_empty() {
+            break;
+        }
+
+        if let Ok((r, content)) = comment::<Intl>(rem) {
+            flush(&mut name, None, &mut out);
+            let mut text = String::new();
+            render_comment(&content, &mut text);
+            out.tokens.push(Token::Comment(text.trim().into()));
+            rem = r;
+            continue;
+        }
+
+        let len = rem.iter().skip(1).take_while(|c| !b" \t\r\n(".contains(c)).count() + 1;
+        let word = String::from_utf8_lossy(&rem[..len]).into_owned();
+        rem = &rem[len..];
+
+        match word.split_once('/') {
+            Some((product, version)) if !product.is_empty() => {
+                name.push(product.into());
+                flush(&mut name, Some(version.into()).filter(|v: &String| !v.is_empty()), &mut out);
+            }
+            _ if !name.is_empty() && is_version(&word) => {
+                let version = word.trim_start_matches(['v', 'V']);
+                flush(&mut name, Some(version.into()), &mut out);
+            }
+            _ => name.push(word),
+        }
+    }
+    flush(&mut name, None, &mut out);
+
+    out
+}
+
+/// Header identifying the user agent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AgentHeader {
+    /// `"User-Agent:"`
+    UserAgent,
+    /// `"X-Mailer:"`
+    XMailer,
+    /// `"X-MimeOLE:"`
+    XMimeOle,
+}
+
+/// Collect the user agent headers of a header section.
+///
+/// Header names are matched case-insensitively.
+/// # Examples
+/// ```
+/// use rustyknife::headersection::header_section;
+/// use rustyknife::useragent::{agent_headers, AgentHeader};
+///
+/// let (_, headers) = header_section(b"X-Mailer: PHPMailer 6.1.4 (https://github.com/PHPMailer/PHPMailer)\r\n\r\n").unwrap();
+/// let found = agent_headers(&headers);
+///
+/// assert_eq!(found[0].0, AgentHeader::XMailer);
+/// assert_eq!(found[0].1.normalized(), "phpmailer/6");
+/// ```
+pub fn agent_headers(headers: &[HeaderField]) -> Vec<(AgentHeader, UserAgent)> {
+    headers.iter().filter_map(|h| h.as_ref().ok()).filter_map(|(name, value)| {
+        let header = if name.eq_ignore_ascii_case(b"User-Agent") {
+            AgentHeader::UserAgent
+        } else if name.eq_ignore_ascii_case(b"X-Mailer") {
+            AgentHeader::XMailer
+        } else if name.eq_ignore_ascii_case(b"X-MimeOLE") {
+            AgentHeader::XMimeOle
+        } else {
+            return None;
+        };
+        Some((header, user_agent(value)))
+    }).collect()
+}
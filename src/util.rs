@@ -1,7 +1,9 @@
 use nom::IResult;
-use nom::bytes::complete::take;
-use nom::combinator::{map, recognize, verify};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, tag_no_case, take, take_while1, take_while_m_n};
+use nom::combinator::{map, map_opt, opt, recognize, verify};
 use nom::multi::{fold_many0, fold_many1};
+use nom::sequence::{pair, preceded, tuple};
 // Change this to something else that implements ParseError to get a
 // different error type out of nom.
 pub(crate) type NomError<'a> = ();
@@ -125,3 +127,54 @@ pub(crate) fn take1_filter<F>(pred: F) -> impl Fn(&[u8]) -> NomResult<u8>
         verify(map(take(1usize), |c: &[u8]| c[0]), |c| pred(*c))(input)
     }
 }
+
+// Decimal number of 1 to `max_digits` digits, no greater than `max`.
+pub(crate) fn bounded_u64(max_digits: usize, max: u64) -> impl Fn(&[u8]) -> NomResult<u64> {
+    move |input| {
+        verify(map_opt(take_while_m_n(1, max_digits, |c: u8| c.is_ascii_digit()), |digits: &[u8]| {
+            digits.iter().try_fold(0u64, |acc, c| acc.checked_mul(10)?.checked_add(u64::from(c - b'0')))
+        }), |n| *n <= max)(input)
+    }
+}
+
+// Number of seconds of 1 to `max_digits` digits with an optional sign.
+pub(crate) fn signed_seconds(max_digits: usize) -> impl Fn(&[u8]) -> NomResult<i64> {
+    move |input| {
+        map(pair(opt(alt((tag("-"), tag("+")))), bounded_u64(max_digits, i64::MAX as u64)),
+            |(sign, n)| if sign == Some(&b"-"[..]) { -(n as i64) } else { n as i64 })(input)
+    }
+}
+
+fn two_digits(min: u8, max: u8) -> impl Fn(&[u8]) -> NomResult<u8> {
+    move |input| {
+        verify(map(take_while_m_n(2, 2, |c: u8| c.is_ascii_digit()), |d: &[u8]| (d[0] - b'0') * 10 + d[1] - b'0'),
+               |n| (min..=max).contains(n))(input)
+    }
+}
+
+// RFC 3339 date-time, with range checks on each field.
+pub(crate) fn rfc3339_date_time(input: &[u8]) -> NomResult<&[u8]> {
+    recognize(tuple((
+        take_while_m_n(4, 4, |c: u8| c.is_ascii_digit()), tag("-"), two_digits(1, 12), tag("-"), two_digits(1, 31),
+        tag_no_case("T"),
+        two_digits(0, 23), tag(":"), two_digits(0, 59), tag(":"), two_digits(0, 60),
+        opt(preceded(tag("."), take_while1(|c: u8| c.is_ascii_digit()))),
+        alt((tag_no_case("Z"), recognize(tuple((alt((tag("+"), tag("-"))), two_digits(0, 23), tag(":"), two_digits(0, 59)))))),
+    )))(input)
+}
+
+// Convert bare CR and LF line endings to CRLF.
+pub(crate) fn normalize_crlf(text: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    let mut iter = text.iter().copied().peekable();
+
+    while let Some(c) = iter.next() {
+        match c {
+            b'\r' if iter.peek() == Some(&b'\n') => (),
+            b'\r' | b'\n' => out.extend_from_slice(b"\r\n"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
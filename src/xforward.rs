@@ -2,6 +2,8 @@
 //!
 //! [XFORWARD]: http://www.postfix.org/XFORWARD_README.html
 
+use std::fmt::{self, Display};
+
 use charset::decode_ascii;
 
 use nom::branch::alt;
@@ -11,7 +13,7 @@ use nom::multi::{many1};
 use nom::sequence::{delimited, preceded, separated_pair};
 
 use crate::rfc5234::{crlf, wsp};
-use crate::rfc3461::xtext;
+use crate::rfc3461::{xtext, xtext_encode};
 use crate::util::*;
 
 /// XFORWARD parameter name and value.
@@ -20,6 +22,15 @@ use crate::util::*;
 #[derive(Clone, Debug)]
 pub struct Param(pub &'static str, pub Option<String>);
 
+impl Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.1 {
+            Some(value) => write!(f, "{}={}", self.0.to_uppercase(), xtext_encode(value.as_bytes())),
+            None => write!(f, "{}=[UNAVAILABLE]", self.0.to_uppercase()),
+        }
+    }
+}
+
 fn command_name(input: &[u8]) -> NomResult<&'static str> {
     alt((map(tag_no_case("addr"), |_| "addr"),
          map(tag_no_case("helo"), |_| "helo"),